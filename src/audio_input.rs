@@ -140,6 +140,7 @@ impl AudioCapture {
                             timestamp_us: clock.now_us(),
                             samples,
                             sample_rate,
+                            channels: Vec::new(),
                         });
                         if tx.send(event).is_err() {
                             return; // Receiver dropped (calibration finished)
@@ -0,0 +1,318 @@
+//! Live audio input from a raw PCM byte stream (stdin or a named pipe), so
+//! arecord, sox, JACK tools or any other program can feed the pipeline
+//! without the cpal-based `calibration` feature.
+//!
+//! Samples are s16, s32 or f32 little-endian at any channel count and rate.
+//! They come out as ordinary `AudioChunk`s, the same as `WavPlayer`'s, so
+//! the coordinator's string detection and bar fusion run unchanged; used
+//! with the simulator, the piped audio replaces the synthesized strings.
+
+use crate::types::*;
+use crate::wav_player::{downmix, split_channels, CHUNK_SIZE};
+use crossbeam_channel::Sender;
+use log::{error, info, warn};
+use std::fmt;
+use std::io::{ErrorKind, Read};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+/// A source this far behind real time has stalled (paused producer, xrun);
+/// timestamps restart from the wall clock.
+const MAX_LAG_US: u64 = 200_000;
+
+/// Interleaved little-endian sample encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PcmFormat {
+    #[default]
+    S16,
+    S32,
+    F32,
+}
+
+impl PcmFormat {
+    pub fn bytes(self) -> usize {
+        match self {
+            Self::S16 => 2,
+            Self::S32 | Self::F32 => 4,
+        }
+    }
+
+    /// One sample from exactly `bytes()` bytes, normalized to -1.0–1.0.
+    fn decode(self, b: &[u8]) -> f32 {
+        match self {
+            Self::S16 => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+            Self::S32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648.0,
+            Self::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }
+    }
+}
+
+/// Parses `s16`, `s32` or `f32` (an `le` suffix, as arecord and sox spell
+/// them, is accepted).
+impl FromStr for PcmFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s
+            .to_ascii_lowercase()
+            .trim_end_matches("le")
+            .trim_end_matches('_')
+        {
+            "s16" => Ok(Self::S16),
+            "s32" => Ok(Self::S32),
+            "f32" => Ok(Self::F32),
+            _ => Err(format!("unknown PCM format '{}' (s16, s32, f32)", s)),
+        }
+    }
+}
+
+impl fmt::Display for PcmFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::S16 => write!(f, "s16"),
+            Self::S32 => write!(f, "s32"),
+            Self::F32 => write!(f, "f32"),
+        }
+    }
+}
+
+/// Streams raw interleaved PCM from stdin or a named pipe as AudioChunk
+/// events, for audio from arecord, sox, JACK tools or another process:
+///
+/// `arecord -f S16_LE -r 48000 -c 2 | steel-capture --audio-pipe - --audio-pipe-channels 2`
+///
+/// Channels are mixed down to mono like `WavPlayer`, and kept for
+/// per-string detection with a hexaphonic pickup. Chunk timestamps
+/// count samples from when audio started arriving, so they stay evenly
+/// spaced however the producer buffers; a source that runs ahead of real
+/// time (a file through `sox`) is paced, one that stalls is resynced.
+pub struct AudioPipe {
+    reader: Box<dyn Read + Send>,
+    tx: Sender<InputEvent>,
+    clock: SessionClock,
+    format: PcmFormat,
+    sample_rate: u32,
+    channels: usize,
+}
+
+impl AudioPipe {
+    /// Mono s16 at 48 kHz unless configured otherwise.
+    pub fn new(
+        reader: impl Read + Send + 'static,
+        tx: Sender<InputEvent>,
+        clock: SessionClock,
+    ) -> Self {
+        Self {
+            reader: Box::new(reader),
+            tx,
+            clock,
+            format: PcmFormat::S16,
+            sample_rate: 48000,
+            channels: 1,
+        }
+    }
+
+    pub fn with_format(mut self, format: PcmFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate.max(1);
+        self
+    }
+
+    pub fn with_channels(mut self, channels: usize) -> Self {
+        self.channels = channels.max(1);
+        self
+    }
+
+    /// Read until end of stream or until the coordinator shuts down.
+    pub fn run(mut self) {
+        info!(
+            "Audio pipe: {} {} Hz {} ch",
+            self.format, self.sample_rate, self.channels
+        );
+        if self.sample_rate != 48000 {
+            warn!(
+                "Audio pipe rate is {} Hz; string detector expects 48000 Hz. \
+                 Goertzel thresholds may be off.",
+                self.sample_rate
+            );
+        }
+
+        let frame_bytes = self.format.bytes() * self.channels;
+        let chunk_bytes = CHUNK_SIZE * frame_bytes;
+        let mut pending: Vec<u8> = Vec::with_capacity(chunk_bytes * 2);
+        let mut buf = [0u8; 8192];
+        let mut pacer = Pacer::new(self.sample_rate);
+
+        loop {
+            let ended = match self.reader.read(&mut buf) {
+                Ok(0) => true,
+                Ok(n) => {
+                    pending.extend_from_slice(&buf[..n]);
+                    false
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    error!("Audio pipe read failed: {}", e);
+                    true
+                }
+            };
+
+            // Whole chunks, plus the trailing whole frames at end of stream
+            let usable = if ended {
+                pending.len() - pending.len() % frame_bytes
+            } else {
+                pending.len() - pending.len() % chunk_bytes
+            };
+            for bytes in pending[..usable].chunks(chunk_bytes) {
+                let interleaved: Vec<f32> = bytes
+                    .chunks_exact(self.format.bytes())
+                    .map(|b| self.format.decode(b))
+                    .collect();
+                let channels = split_channels(&interleaved, self.channels);
+                let samples = downmix(interleaved, self.channels);
+                let timestamp_us = pacer.timestamp(samples.len(), &self.clock);
+                let event = InputEvent::Audio(AudioChunk {
+                    timestamp_us,
+                    samples,
+                    sample_rate: self.sample_rate,
+                    channels,
+                });
+                if self.tx.send(event).is_err() {
+                    return;
+                }
+            }
+            pending.drain(..usable);
+            if ended {
+                break;
+            }
+        }
+        info!("Audio pipe ended after {:.1}s", pacer.total_secs());
+    }
+}
+
+/// Sample-counting timestamps anchored to the session clock.
+struct Pacer {
+    sample_rate: u32,
+    /// Session time of the first sample since the last (re)anchor
+    anchor_us: Option<u64>,
+    since_anchor: u64,
+    total: u64,
+}
+
+impl Pacer {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            anchor_us: None,
+            since_anchor: 0,
+            total: 0,
+        }
+    }
+
+    fn duration_us(&self, samples: u64) -> u64 {
+        samples * 1_000_000 / self.sample_rate as u64
+    }
+
+    /// Timestamp of the first sample of a chunk of `len` samples that has
+    /// just been read; sleeps first if the source is ahead of real time.
+    fn timestamp(&mut self, len: usize, clock: &SessionClock) -> u64 {
+        let chunk_us = self.duration_us(len as u64);
+        let now = clock.now_us();
+        // The chunk's last sample arrived just now
+        let anchor = *self
+            .anchor_us
+            .get_or_insert_with(|| now.saturating_sub(chunk_us));
+        let mut ts = anchor + self.duration_us(self.since_anchor);
+        let due = ts + chunk_us;
+        if due > now {
+            thread::sleep(Duration::from_micros(due - now));
+        } else if now - due > MAX_LAG_US {
+            warn!(
+                "Audio pipe fell {} ms behind; resyncing timestamps",
+                (now - due) / 1000
+            );
+            ts = now.saturating_sub(chunk_us);
+            self.anchor_us = Some(ts);
+            self.since_anchor = 0;
+        }
+        self.since_anchor += len as u64;
+        self.total += len as u64;
+        ts
+    }
+
+    fn total_secs(&self) -> f64 {
+        self.total as f64 / self.sample_rate as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+    use std::io::Cursor;
+
+    fn chunks(bytes: Vec<u8>, pipe: impl FnOnce(AudioPipe) -> AudioPipe) -> Vec<AudioChunk> {
+        let (tx, rx) = unbounded();
+        pipe(AudioPipe::new(Cursor::new(bytes), tx, SessionClock::new())).run();
+        rx.try_iter()
+            .map(|e| match e {
+                InputEvent::Audio(chunk) => chunk,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_formats_and_downmix() {
+        assert_eq!("S16_LE".parse::<PcmFormat>().unwrap(), PcmFormat::S16);
+        assert_eq!("f32le".parse::<PcmFormat>().unwrap(), PcmFormat::F32);
+        assert!("u8".parse::<PcmFormat>().is_err());
+
+        // Stereo s16: left at half scale, right silent
+        let bytes: Vec<u8> = (0..10).flat_map(|_| [0x00, 0x40, 0x00, 0x00]).collect();
+        let got = chunks(bytes, |p| p.with_channels(2));
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].samples, vec![0.25; 10]);
+
+        let bytes: Vec<u8> = [i32::MIN, 0, i32::MAX / 2]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let got = chunks(bytes, |p| p.with_format(PcmFormat::S32));
+        assert_eq!(got[0].samples[0], -1.0);
+        assert!((got[0].samples[2] - 0.5).abs() < 1e-6);
+
+        let bytes: Vec<u8> = [0.5f32, -0.25]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let got = chunks(bytes, |p| {
+            p.with_format(PcmFormat::F32).with_sample_rate(8000)
+        });
+        assert_eq!(got[0].samples, vec![0.5, -0.25]);
+        assert_eq!(got[0].sample_rate, 8000);
+    }
+
+    #[test]
+    fn test_file_speed_source_is_paced_with_even_timestamps() {
+        // 0.2 s of mono s16 at 48 kHz, available instantly, plus a stray byte
+        let mut bytes = vec![0u8; 9600 * 2];
+        bytes.push(0x7F);
+        let start = std::time::Instant::now();
+        let got = chunks(bytes, |p| p);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        assert_eq!(got.iter().map(|c| c.samples.len()).sum::<usize>(), 9600);
+        assert_eq!(got.len(), 10);
+        let step = CHUNK_SIZE as u64 * 1_000_000 / 48000;
+        for pair in got.windows(2) {
+            let gap = pair[1].timestamp_us - pair[0].timestamp_us;
+            assert!(gap.abs_diff(step) <= 1, "gap {}", gap);
+        }
+    }
+}
@@ -4,6 +4,11 @@ use crate::dsp::{compute_rms, goertzel_magnitude};
 use crate::types::*;
 use log::trace;
 
+/// How long a held audio estimate stays valid without a fresh analysis.
+/// Several analysis intervals, so the gaps between large audio chunks
+/// (1024 samples from a WAV file or pipe) don't drop the hold.
+const AUDIO_HOLD_US: u64 = 150_000;
+
 /// Infers bar position by fusing two sources:
 ///
 /// 1. **Hall sensor array** (primary): 4 SS49E sensors along the rail give
@@ -15,7 +20,8 @@ use log::trace;
 ///
 /// Fusion strategy:
 ///   - Sensor only (silence): use sensor position, moderate confidence
-///   - Audio only (sensor fail): use audio, lower confidence
+///   - Audio only (sensor fail): use audio, lower confidence. The last audio
+///     estimate is held between analyses so the position doesn't flicker.
 ///   - Both available: weighted average biased toward audio (finer resolution),
 ///     with sensor providing the neighborhood to search
 ///
@@ -39,6 +45,10 @@ pub struct BarInference {
     sample_rate: u32,
     /// Hall sensor bar position estimator
     bar_sensor: BarSensor,
+    /// Most recent audio estimate, held until the next analysis
+    last_audio: Option<(f32, f32)>,
+    /// Sensor timestamp of the analysis that produced `last_audio`
+    last_audio_us: u64,
 }
 
 impl BarInference {
@@ -55,6 +65,8 @@ impl BarInference {
             analysis_interval: 2048, // run analysis every ~42ms
             sample_rate: 48000,
             bar_sensor: BarSensor::new(),
+            last_audio: None,
+            last_audio_us: 0,
         }
     }
 
@@ -84,7 +96,15 @@ impl BarInference {
         let sensor_est = self.bar_sensor.estimate(&sensor.bar_sensors);
 
         // ── 2. Audio estimate (only when enough audio buffered) ───────
+        let analyzed = self.ready();
         let audio_est = self.infer_audio(sensor, engine);
+        if analyzed {
+            self.last_audio = audio_est;
+            self.last_audio_us = sensor.timestamp_us;
+        } else if sensor.timestamp_us.saturating_sub(self.last_audio_us) > AUDIO_HOLD_US {
+            // Audio stopped arriving: release the held estimate
+            self.last_audio = None;
+        }
 
         // ── 3. Fuse ──────────────────────────────────────────────────
         match (sensor_est, audio_est) {
@@ -138,6 +158,16 @@ impl BarInference {
                 }
             }
 
+            // No sensor, between audio analyses: hold the last audio estimate
+            (None, None) if self.last_audio.is_some() => {
+                let (a_pos, a_conf) = self.last_audio.unwrap();
+                BarState {
+                    position: self.last_position.or(Some(a_pos)),
+                    confidence: a_conf * 0.7,
+                    source: BarSource::Audio,
+                }
+            }
+
             // Nothing
             (None, None) => {
                 self.last_position = None;
@@ -213,6 +243,16 @@ impl BarInference {
 
         Some((refined, confidence))
     }
+
+    /// Forget the bar position, buffered audio and sensor smoothing.
+    pub fn reset(&mut self) {
+        self.last_position = None;
+        self.audio_buf.clear();
+        self.samples_since_analysis = 0;
+        self.last_audio = None;
+        self.last_audio_us = 0;
+        self.bar_sensor.reset();
+    }
 }
 
 impl Default for BarInference {
@@ -279,6 +319,7 @@ mod tests {
             timestamp_us: 0,
             samples: samples.to_vec(),
             sample_rate: sr,
+            channels: Vec::new(),
         };
         inf.push_audio(&chunk);
         // Force the analysis window to be satisfied
@@ -391,4 +432,33 @@ mod tests {
         let r = inf.infer(&sensor, &engine);
         assert!(r.position.is_none());
     }
+
+    #[test]
+    fn test_audio_only_held_between_analyses() {
+        use crate::copedant::buddy_emmons_e9;
+        let engine = CopedantEngine::new(buddy_emmons_e9());
+        let mut inf = BarInference::new();
+        // Dead hall array, strings 3-6 and 8 sounding at fret 5
+        let sensor = SensorFrame::at_rest(0);
+        let open = engine.effective_open_pitches(&sensor);
+        let freqs: Vec<f64> = [2, 3, 4, 5, 7]
+            .iter()
+            .map(|&si| midi_to_hz(open[si] + 5.0))
+            .collect();
+        let samples = multi_sine(&freqs, 0.2, 48000, 100);
+        let r = feed_and_infer(&mut inf, &samples, 48000, &sensor, &engine);
+        assert_eq!(r.source, BarSource::Audio);
+        // Following ticks: no new analysis yet (audio arrives in large
+        // chunks), the position must not drop out
+        for t in [1_000, 40_000, AUDIO_HOLD_US] {
+            let r = inf.infer(&SensorFrame::at_rest(t), &engine);
+            assert_eq!(r.source, BarSource::Audio, "t={}", t);
+            let p = r.position.expect("held audio position");
+            assert!((p - 5.0).abs() < 0.5, "pos={:.2}, want ~5.0", p);
+        }
+        // Audio stops arriving: the hold expires
+        let r = inf.infer(&SensorFrame::at_rest(AUDIO_HOLD_US + 1_000), &engine);
+        assert!(r.position.is_none());
+        assert_eq!(r.source, BarSource::None);
+    }
 }
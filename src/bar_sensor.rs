@@ -53,6 +53,8 @@ impl BarSensor {
     /// interpolate between it and the second-strongest neighbor. This
     /// avoids centroid edge bias at frets 0 and 15+.
     pub fn estimate(&mut self, readings: &[f32; 4]) -> Option<(f32, f32)> {
+        let (readings, repaired) = self.repair_dead_sensor(readings);
+        let readings = &readings;
         let total: f32 = readings.iter().sum();
 
         // Bar not present
@@ -115,7 +117,10 @@ impl BarSensor {
 
         // Confidence based on how peaked the distribution is.
         let peakedness = peak_val / total; // 0.25 (uniform) to 1.0 (one sensor)
-        let confidence = ((peakedness - 0.25) / 0.75).clamp(0.3, 1.0);
+        let mut confidence = ((peakedness - 0.25) / 0.75).clamp(0.3, 1.0);
+        if repaired {
+            confidence *= 0.5;
+        }
 
         // Apply smoothing
         let smoothed = match self.last_position {
@@ -136,6 +141,25 @@ impl BarSensor {
         Some((smoothed, confidence))
     }
 
+    /// A single magnet can't leave an interior sensor near zero while both
+    /// of its neighbors see it: that sensor is dead (open circuit), and the
+    /// magnet is sitting on it. Substitute a saturated reading so the
+    /// interpolation lands there instead of on a neighbor.
+    fn repair_dead_sensor(&self, readings: &[f32; 4]) -> ([f32; 4], bool) {
+        let mut out = *readings;
+        for i in 1..3 {
+            if readings[i] < self.presence_threshold * 0.5
+                && readings[i - 1] > self.presence_threshold
+                && readings[i + 1] > self.presence_threshold
+            {
+                trace!("bar_sensor: s{} implausibly low, treating as dead", i);
+                out[i] = 1.0;
+                return (out, true);
+            }
+        }
+        (out, false)
+    }
+
     /// Reset state (e.g., on session restart)
     pub fn reset(&mut self) {
         self.last_position = None;
@@ -283,4 +307,14 @@ mod tests {
             pos
         );
     }
+
+    #[test]
+    fn test_dead_sensor_under_bar() {
+        let mut sensor = BarSensor::new();
+        let mut readings = simulate_bar_readings(5.0);
+        readings[1] = 0.0; // open circuit on the fret-5 sensor
+        let (pos, conf) = sensor.estimate(&readings).unwrap();
+        assert!((pos - 5.0).abs() < 1.0, "pos={:.2}, want ~5.0", pos);
+        assert!(conf < 0.5, "repaired reading should lower confidence");
+    }
 }
@@ -0,0 +1,401 @@
+//! Ground-truth accuracy benchmark.
+//!
+//! Plays a simulator sequence offline through the coordinator with audio
+//! string detection on, then scores the output against what the simulator
+//! actually played: attack precision/recall/F1 within a timing tolerance,
+//! onset latency, bar position error in cents, and how often each
+//! `BarSource` was used. The report serializes to JSON so algorithm changes
+//! can be compared run to run.
+
+use crate::coordinator::Coordinator;
+use crate::impairments::Impairments;
+use crate::sensor_faults::{FaultKind, SensorChannel};
+use crate::simulator::{GroundTruth, ImprovParams, Simulator};
+use crate::string_synth::AudioModel;
+use crate::tab_import::Tab;
+use crate::types::*;
+use crossbeam_channel::{bounded, unbounded};
+use serde::Serialize;
+use std::thread;
+
+/// What to play and how to score it.
+#[derive(Debug, Clone)]
+pub struct BenchmarkConfig {
+    /// Simulator demo sequence ("basic", "e9", "improv", "faults", "tab")
+    pub demo: String,
+    pub sensor_rate_hz: u32,
+    pub audio_model: AudioModel,
+    pub impairments: Impairments,
+    pub sensor_faults: Vec<(SensorChannel, FaultKind)>,
+    /// Parameters for the "improv" demo
+    pub improv: ImprovParams,
+    /// Tab for the "tab" demo
+    pub tab: Option<Tab>,
+    /// A detected attack matches a true one if within ± this many ms
+    pub tolerance_ms: u32,
+    /// Per-string (onset, release) thresholds from calibration, if any
+    pub string_thresholds: Option<([f64; 10], [f64; 10])>,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            demo: "basic".to_string(),
+            sensor_rate_hz: 1000,
+            audio_model: AudioModel::Sine,
+            impairments: Impairments::default(),
+            sensor_faults: Vec::new(),
+            improv: ImprovParams::default(),
+            tab: None,
+            tolerance_ms: 100,
+            string_thresholds: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub demo: String,
+    pub audio_model: String,
+    /// Generator parameters, when the demo was "improv" — enough to replay it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub improv: Option<ImprovParams>,
+    pub duration_s: f64,
+    pub frames: usize,
+    pub attacks: AttackScore,
+    /// Detected minus true onset time, over matched attacks
+    pub onset_latency_ms: Option<Distribution>,
+    pub bar: BarScore,
+    pub bar_source: BarSourceCounts,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AttackScore {
+    pub tolerance_ms: u32,
+    pub truth: usize,
+    pub detected: usize,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BarScore {
+    /// Frames where both truth and output have a bar position
+    pub frames_scored: usize,
+    /// |output − truth| in cents (1 fret = 100 cents)
+    pub abs_error_cents: Option<Distribution>,
+    /// Bar present in truth, missing from output
+    pub missed_frames: usize,
+    /// Bar lifted in truth, present in output
+    pub phantom_frames: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BarSourceCounts {
+    pub none: usize,
+    pub sensor: usize,
+    pub audio: usize,
+    pub fused: usize,
+}
+
+/// Summary statistics of a sample.
+#[derive(Debug, Clone, Serialize)]
+pub struct Distribution {
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    /// Non-finite samples are left out; `None` if nothing is left.
+    fn from_samples(mut values: Vec<f64>) -> Option<Self> {
+        values.retain(|v| v.is_finite());
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+        Some(Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            median: at(0.5),
+            p95: at(0.95),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+/// Play `config.demo` offline through the full pipeline and score it.
+pub fn run(config: &BenchmarkConfig, copedant: Copedant) -> BenchmarkReport {
+    let (input_tx, input_rx) = bounded::<InputEvent>(4096);
+    let (frame_tx, frame_rx) = unbounded::<CaptureFrame>();
+    let (truth_tx, truth_rx) = unbounded::<GroundTruth>();
+
+    let sim_config = config.clone();
+    let sim_cop = copedant.clone();
+    let sim = thread::Builder::new()
+        .name("bench-simulator".into())
+        .spawn(move || {
+            let mut sim = Simulator::new(
+                SessionClock::new(),
+                input_tx,
+                sim_cop,
+                sim_config.sensor_rate_hz,
+            )
+            .with_offline()
+            .with_ground_truth(truth_tx)
+            .with_audio_model(sim_config.audio_model)
+            .with_impairments(&sim_config.impairments)
+            .with_improv(sim_config.improv.clone());
+            if let Some(tab) = &sim_config.tab {
+                sim = sim.with_tab(tab.clone());
+            }
+            for (channel, fault) in &sim_config.sensor_faults {
+                sim = sim.with_sensor_fault(*channel, *fault);
+            }
+            sim.play(&sim_config.demo);
+        })
+        .unwrap();
+
+    let thresholds = config.string_thresholds;
+    let coord = thread::Builder::new()
+        .name("bench-coordinator".into())
+        .spawn(move || {
+            let mut coord = Coordinator::new(input_rx, vec![frame_tx], None, copedant)
+                .with_audio_detection(true);
+            if let Some((onset, release)) = thresholds {
+                coord = coord.with_string_thresholds(onset, release);
+            }
+            coord.run();
+        })
+        .unwrap();
+
+    let frames: Vec<CaptureFrame> = frame_rx.iter().collect();
+    let _ = sim.join();
+    let _ = coord.join();
+    let truth: Vec<GroundTruth> = truth_rx.iter().collect();
+
+    let mut report = score(&truth, &frames, config.tolerance_ms);
+    report.demo = config.demo.clone();
+    report.audio_model = format!("{:?}", config.audio_model).to_lowercase();
+    if config.demo == "improv" {
+        report.improv = Some(config.improv.clone());
+    }
+    report
+}
+
+/// Score pipeline output against ground truth. Frames and truth are paired
+/// by timestamp; unpaired entries are ignored.
+pub fn score(truth: &[GroundTruth], frames: &[CaptureFrame], tolerance_ms: u32) -> BenchmarkReport {
+    let tolerance_us = tolerance_ms as u64 * 1000;
+    let truth_at: std::collections::HashMap<u64, &GroundTruth> =
+        truth.iter().map(|t| (t.timestamp_us, t)).collect();
+
+    // ── Attacks: per-string greedy matching in time order ────────────
+    let mut tp = 0;
+    let mut detected = 0;
+    let mut truth_count = 0;
+    let mut latencies = Vec::new();
+    for si in 0..10 {
+        let true_times: Vec<u64> = truth
+            .iter()
+            .filter(|t| t.attacks[si])
+            .map(|t| t.timestamp_us)
+            .collect();
+        let found_times: Vec<u64> = frames
+            .iter()
+            .filter(|f| f.attacks[si])
+            .map(|f| f.timestamp_us)
+            .collect();
+        truth_count += true_times.len();
+        detected += found_times.len();
+
+        let mut matched = vec![false; true_times.len()];
+        for &found in &found_times {
+            let hit = true_times
+                .iter()
+                .enumerate()
+                .find(|&(k, &t)| !matched[k] && found.abs_diff(t) <= tolerance_us);
+            if let Some((k, &t)) = hit {
+                matched[k] = true;
+                tp += 1;
+                latencies.push((found as f64 - t as f64) / 1000.0);
+            }
+        }
+    }
+    let precision = ratio(tp, detected);
+    let recall = ratio(tp, truth_count);
+    let f1 = if precision + recall > 0.0 {
+        2.0 * precision * recall / (precision + recall)
+    } else {
+        0.0
+    };
+
+    // ── Bar position and source ─────────────────────────────────────
+    let mut errors = Vec::new();
+    let mut missed_frames = 0;
+    let mut phantom_frames = 0;
+    let mut sources = BarSourceCounts::default();
+    let mut scored = 0;
+    for frame in frames {
+        let Some(t) = truth_at.get(&frame.timestamp_us) else {
+            continue;
+        };
+        scored += 1;
+        match frame.bar_source {
+            BarSource::None => sources.none += 1,
+            BarSource::Sensor => sources.sensor += 1,
+            BarSource::Audio => sources.audio += 1,
+            BarSource::Fused => sources.fused += 1,
+        }
+        match (t.bar_fret, frame.bar_position) {
+            (Some(true_fret), Some(pos)) => {
+                errors.push(((pos - true_fret) as f64 * 100.0).abs());
+            }
+            (Some(_), None) => missed_frames += 1,
+            (None, Some(_)) => phantom_frames += 1,
+            (None, None) => {}
+        }
+    }
+
+    let duration_s = match (truth.first(), truth.last()) {
+        (Some(a), Some(b)) => (b.timestamp_us - a.timestamp_us) as f64 / 1_000_000.0,
+        _ => 0.0,
+    };
+
+    BenchmarkReport {
+        demo: String::new(),
+        audio_model: String::new(),
+        improv: None,
+        duration_s,
+        frames: scored,
+        attacks: AttackScore {
+            tolerance_ms,
+            truth: truth_count,
+            detected,
+            true_positives: tp,
+            false_positives: detected - tp,
+            false_negatives: truth_count - tp,
+            precision,
+            recall,
+            f1,
+        },
+        onset_latency_ms: Distribution::from_samples(latencies),
+        bar: BarScore {
+            frames_scored: errors.len(),
+            abs_error_cents: Distribution::from_samples(errors),
+            missed_frames,
+            phantom_frames,
+        },
+        bar_source: sources,
+    }
+}
+
+fn ratio(num: usize, den: usize) -> f64 {
+    if den == 0 {
+        0.0
+    } else {
+        num as f64 / den as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn truth(ts_ms: u64, bar: Option<f32>, attack: Option<usize>) -> GroundTruth {
+        let mut attacks = [false; 10];
+        if let Some(si) = attack {
+            attacks[si] = true;
+        }
+        GroundTruth {
+            timestamp_us: ts_ms * 1000,
+            pedals: [0.0; 3],
+            knee_levers: [0.0; 5],
+            volume: 0.8,
+            bar_fret: bar,
+            string_active: [false; 10],
+            attacks,
+        }
+    }
+
+    fn frame(
+        ts_ms: u64,
+        bar: Option<f32>,
+        source: BarSource,
+        attack: Option<usize>,
+    ) -> CaptureFrame {
+        let mut attacks = [false; 10];
+        if let Some(si) = attack {
+            attacks[si] = true;
+        }
+        CaptureFrame {
+            volume: 0.8,
+            bar_position: bar,
+            bar_confidence: 0.5,
+            bar_source: source,
+            attacks,
+            ..CaptureFrame::at_rest(ts_ms * 1000)
+        }
+    }
+
+    #[test]
+    fn test_attack_matching_with_tolerance() {
+        let t = vec![
+            truth(0, Some(3.0), Some(2)),
+            truth(100, Some(3.0), Some(3)),
+            truth(500, Some(3.0), Some(2)),
+        ];
+        let f = vec![
+            frame(40, Some(3.0), BarSource::Fused, Some(2)), // 40 ms late: hit
+            frame(300, Some(3.0), BarSource::Fused, Some(3)), // 200 ms late: miss
+            frame(520, Some(3.0), BarSource::Fused, Some(2)), // hit
+        ];
+        let r = score(&t, &f, 100);
+        assert_eq!(r.attacks.true_positives, 2);
+        assert_eq!(r.attacks.false_positives, 1);
+        assert_eq!(r.attacks.false_negatives, 1);
+        assert!((r.attacks.precision - 2.0 / 3.0).abs() < 1e-9);
+        assert!((r.attacks.f1 - 2.0 / 3.0).abs() < 1e-9);
+        let lat = r.onset_latency_ms.unwrap();
+        assert!((lat.mean - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bar_error_and_sources() {
+        let t = vec![
+            truth(0, Some(5.0), None),
+            truth(1, Some(5.0), None),
+            truth(2, Some(5.0), None),
+            truth(3, None, None),
+        ];
+        let f = vec![
+            frame(0, Some(5.1), BarSource::Sensor, None),
+            frame(1, Some(4.8), BarSource::Fused, None),
+            frame(2, None, BarSource::None, None),
+            frame(3, Some(5.0), BarSource::Audio, None),
+        ];
+        let r = score(&t, &f, 100);
+        assert_eq!(r.bar.frames_scored, 2);
+        assert_eq!(r.bar.missed_frames, 1);
+        assert_eq!(r.bar.phantom_frames, 1);
+        let err = r.bar.abs_error_cents.unwrap();
+        assert!((err.mean - 15.0).abs() < 0.01, "mean {}", err.mean);
+        assert!((err.max - 20.0).abs() < 0.01);
+        assert_eq!(r.bar_source.sensor, 1);
+        assert_eq!(r.bar_source.fused, 1);
+        assert_eq!(r.bar_source.audio, 1);
+        assert_eq!(r.bar_source.none, 1);
+    }
+
+    #[test]
+    fn test_distribution_skips_non_finite() {
+        let d = Distribution::from_samples(vec![3.0, f64::NAN, 1.0, f64::INFINITY, 2.0]).unwrap();
+        assert_eq!((d.mean, d.median, d.max), (2.0, 2.0, 3.0));
+        assert!(Distribution::from_samples(vec![f64::NAN]).is_none());
+    }
+}
@@ -148,7 +148,7 @@ impl Calibrator {
                         audio_buf.drain(..ANALYSIS_WINDOW);
                     }
                 }
-                Ok(InputEvent::Sensor(_)) => {
+                Ok(_) => {
                     // Ignore — calibration is audio-only
                 }
                 Err(_) => {
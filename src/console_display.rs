@@ -14,11 +14,9 @@ impl ConsoleDisplay {
     }
 
     pub fn run(&self) {
-        let skip = if self.update_hz == 0 {
-            50
-        } else {
-            (1000 / self.update_hz).max(1) as u64
-        };
+        let skip = 1000u32
+            .checked_div(self.update_hz)
+            .map_or(50, |s| s.max(1) as u64);
         let mut count: u64 = 0;
         let mut stdout = io::stdout();
 
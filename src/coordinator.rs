@@ -1,9 +1,9 @@
 use crate::bar_inference::BarInference;
 use crate::copedant::CopedantEngine;
-use crate::string_detector::StringDetector;
+use crate::string_detector::{StringChannels, StringDetector};
 use crate::types::*;
 use crossbeam_channel::{Receiver, Sender};
-use log::{debug, info, trace};
+use log::{debug, info, trace, warn};
 
 /// The coordinator receives InputEvents (sensor frames and audio chunks),
 /// runs bar inference, string detection, and copedant computation, and
@@ -28,6 +28,8 @@ pub struct Coordinator {
     engine: CopedantEngine,
     inference: BarInference,
     string_detector: StringDetector,
+    /// Remote commands (`Reset` is acted on here)
+    control_rx: Option<Receiver<ControlCommand>>,
     /// If true, use audio-based string detection instead of sensor.string_active.
     /// Defaults to false (simulator ground truth). Set true for hardware mode.
     pub use_audio_detection: bool,
@@ -47,6 +49,7 @@ impl Coordinator {
             engine: CopedantEngine::new(copedant),
             inference: BarInference::new(),
             string_detector: StringDetector::new(),
+            control_rx: None,
             use_audio_detection: false,
         }
     }
@@ -63,6 +66,18 @@ impl Coordinator {
         self
     }
 
+    /// Detect strings per channel from a hexaphonic pickup.
+    pub fn with_string_channels(mut self, channels: StringChannels) -> Self {
+        self.string_detector = self.string_detector.with_string_channels(channels);
+        self
+    }
+
+    /// Accept remote commands; `Reset` clears detector and bar state.
+    pub fn with_control(mut self, rx: Receiver<ControlCommand>) -> Self {
+        self.control_rx = Some(rx);
+        self
+    }
+
     pub fn run(&mut self) {
         info!(
             "Coordinator running (audio string detection: {})",
@@ -74,15 +89,22 @@ impl Coordinator {
         );
 
         let mut prev_active = [false; 10];
-        let mut prev_pedal_engaged = [false; 3];
-        let mut prev_lever_engaged = [false; 5];
+        let mut articulation = ArticulationTracker::new();
         let mut frame_count: u64 = 0;
 
-        // Which strings are affected by each pedal/lever (from copedant)
-        let pedal_strings = pedal_string_map();
-        let lever_strings = lever_string_map();
-
         for event in self.input_rx.iter() {
+            let reset = self.control_rx.as_ref().is_some_and(|rx| {
+                rx.try_iter()
+                    .fold(false, |reset, cmd| reset | (cmd == ControlCommand::Reset))
+            });
+            if reset {
+                info!("Coordinator: resetting detector and bar state");
+                self.string_detector.reset();
+                self.inference.reset();
+                articulation = ArticulationTracker::new();
+                prev_active = [false; 10];
+            }
+
             match event {
                 InputEvent::Sensor(sensor) => {
                     let bar_state = self.inference.infer(&sensor, &self.engine);
@@ -110,6 +132,7 @@ impl Coordinator {
                     //   1. String pick: inactive → active
                     //   2. Pedal state change: crosses 0.5 threshold while string active
                     //   3. Lever state change: crosses 0.5 threshold while string active
+                    // Crossings use a hysteresis band so a noisy pot can't chatter.
                     let mut attacks = if self.use_audio_detection {
                         // Audio detector already provides attacks
                         audio_attacks
@@ -124,41 +147,11 @@ impl Coordinator {
                         a
                     };
 
-                    // 2. Pedal state changes (applies in both modes)
-                    let pedal_engaged: [bool; 3] = [
-                        sensor.pedals[0] > 0.5,
-                        sensor.pedals[1] > 0.5,
-                        sensor.pedals[2] > 0.5,
-                    ];
-                    for j in 0..3 {
-                        if pedal_engaged[j] != prev_pedal_engaged[j] {
-                            for i in 0..10 {
-                                if string_active[i] && pedal_strings[j][i] {
-                                    attacks[i] = true;
-                                }
-                            }
-                        }
+                    // 2–3. Pedal/lever state changes (applies in both modes)
+                    let rearticulated = articulation.update(&sensor, &string_active);
+                    for (attack, again) in attacks.iter_mut().zip(rearticulated) {
+                        *attack |= again;
                     }
-                    prev_pedal_engaged = pedal_engaged;
-
-                    // 3. Lever state changes
-                    let lever_engaged: [bool; 5] = [
-                        sensor.knee_levers[0] > 0.5,
-                        sensor.knee_levers[1] > 0.5,
-                        sensor.knee_levers[2] > 0.5,
-                        sensor.knee_levers[3] > 0.5,
-                        sensor.knee_levers[4] > 0.5,
-                    ];
-                    for j in 0..5 {
-                        if lever_engaged[j] != prev_lever_engaged[j] {
-                            for i in 0..10 {
-                                if string_active[i] && lever_strings[j][i] {
-                                    attacks[i] = true;
-                                }
-                            }
-                        }
-                    }
-                    prev_lever_engaged = lever_engaged;
                     prev_active = string_active;
 
                     let frame = CaptureFrame {
@@ -195,6 +188,12 @@ impl Coordinator {
                     self.inference.push_audio(&chunk);
                     self.string_detector.push_audio(&chunk);
                 }
+
+                InputEvent::Status(status) => match status {
+                    DeviceStatus::Reconnecting { .. } => debug!("Input: {}", status),
+                    DeviceStatus::Disconnected { .. } => warn!("Input: {}", status),
+                    DeviceStatus::Connected { .. } => info!("Input: {}", status),
+                },
             }
         }
 
@@ -202,6 +201,93 @@ impl Coordinator {
     }
 }
 
+/// Tracks pedal and lever engagement across frames and reports which
+/// sounding strings are re-articulated when one crosses its threshold.
+/// Shared with the simulator so ground-truth attacks follow the same rule.
+pub struct ArticulationTracker {
+    pedal_engaged: [bool; 3],
+    lever_engaged: [bool; 5],
+    pedal_strings: [[bool; 10]; 3],
+    lever_strings: [[bool; 10]; 5],
+}
+
+impl ArticulationTracker {
+    pub fn new() -> Self {
+        Self {
+            pedal_engaged: [false; 3],
+            lever_engaged: [false; 5],
+            pedal_strings: pedal_string_map(),
+            lever_strings: lever_string_map(),
+        }
+    }
+
+    /// Feed one frame; returns the active strings affected by a pedal or
+    /// lever that changed state since the previous frame.
+    pub fn update(&mut self, sensor: &SensorFrame, string_active: &[bool; 10]) -> [bool; 10] {
+        let mut attacks = [false; 10];
+
+        let pedal_engaged: [bool; 3] =
+            std::array::from_fn(|j| engaged(sensor.pedals[j], self.pedal_engaged[j]));
+        mark_changes(
+            &pedal_engaged,
+            &self.pedal_engaged,
+            &self.pedal_strings,
+            string_active,
+            &mut attacks,
+        );
+        self.pedal_engaged = pedal_engaged;
+
+        let lever_engaged: [bool; 5] =
+            std::array::from_fn(|j| engaged(sensor.knee_levers[j], self.lever_engaged[j]));
+        mark_changes(
+            &lever_engaged,
+            &self.lever_engaged,
+            &self.lever_strings,
+            string_active,
+            &mut attacks,
+        );
+        self.lever_engaged = lever_engaged;
+
+        attacks
+    }
+}
+
+impl Default for ArticulationTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// For each control whose engaged state changed, mark the active strings
+/// it affects.
+fn mark_changes(
+    now: &[bool],
+    before: &[bool],
+    affected: &[[bool; 10]],
+    string_active: &[bool; 10],
+    attacks: &mut [bool; 10],
+) {
+    for ((n, b), strings) in now.iter().zip(before).zip(affected) {
+        if n != b {
+            for ((attack, &active), &hit) in attacks.iter_mut().zip(string_active).zip(strings) {
+                *attack |= active && hit;
+            }
+        }
+    }
+}
+
+/// Half-width of the engage/release band around 0.5 for pedals and levers.
+const ENGAGE_HYSTERESIS: f32 = 0.1;
+
+/// Engaged state with hysteresis: engage above 0.6, release below 0.4.
+fn engaged(value: f32, was_engaged: bool) -> bool {
+    if was_engaged {
+        value > 0.5 - ENGAGE_HYSTERESIS
+    } else {
+        value > 0.5 + ENGAGE_HYSTERESIS
+    }
+}
+
 /// Maps each pedal to the strings it affects (from Geoff Derby E9 copedant).
 fn pedal_string_map() -> [[bool; 10]; 3] {
     [
@@ -0,0 +1,383 @@
+//! CSV export for analysis tools: one row per frame and one named column
+//! per value, flattened with the session header's `channels` table, so
+//! pandas or R can read a session without knowing the JSONL layout.
+//!
+//! ```text
+//! timestamp_us,pedal_A,pedal_B,pedal_C,lever_LKL,...,string10_amplitude
+//! 1000,0.0,0.25,0.0,0.0,...,0.0
+//! ```
+//!
+//! Array channels get a column per element: `pedal_A` and `lever_LKL` by
+//! the copedant's pedal and lever names, `bar_sensor0`–`bar_sensor3`, and
+//! `string3_hz`, `string3_active`, `string3_attack`, `string3_amplitude`
+//! per string. Other channels keep their name (`volume`, `bar_position`).
+//! Booleans are written as 1/0, and a lifted bar as an empty cell.
+//!
+//! Frames are read and written one at a time, so a session of any size
+//! exports in constant memory. `with_rate` keeps one frame per period and
+//! carries attacks over from the frames it skips; `with_range` stops
+//! reading at the end of its window.
+
+use crate::jsonl_reader::{SessionHeader, SessionReader};
+use crate::types::{LEVER_NAMES, PEDAL_NAMES};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+/// One CSV column: a channel key and, for arrays, the element index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvColumn {
+    pub name: String,
+    key: String,
+    index: Option<usize>,
+}
+
+/// Every column a session's channels table describes, in table order.
+pub fn session_columns(header: &SessionHeader) -> Result<Vec<CsvColumn>, String> {
+    if header.channels.is_empty() {
+        return Err("session header has no channels table".into());
+    }
+    let copedant_names = |kind: &str, defaults: &[&str]| -> Vec<String> {
+        let names = header.raw["copedant"][kind].as_array();
+        (0..defaults.len().max(names.map_or(0, Vec::len)))
+            .map(|i| {
+                names
+                    .and_then(|n| n.get(i)?["name"].as_str())
+                    .or(defaults.get(i).copied())
+                    .map_or_else(|| i.to_string(), str::to_string)
+            })
+            .collect()
+    };
+    let pedals = copedant_names("pedals", &PEDAL_NAMES);
+    let levers = copedant_names("levers", &LEVER_NAMES);
+
+    let mut columns = Vec::new();
+    for channel in &header.channels {
+        let key = channel["key"].as_str().ok_or("channel without a key")?;
+        let name = channel["name"].as_str().unwrap_or(key);
+        let Some(count) = channel["count"].as_u64() else {
+            columns.push(CsvColumn {
+                name: name.to_string(),
+                key: key.to_string(),
+                index: None,
+            });
+            continue;
+        };
+        for i in 0..count as usize {
+            let label = |names: &[String]| names.get(i).cloned().unwrap_or(i.to_string());
+            let name = match key {
+                "p" => format!("pedal_{}", label(&pedals)),
+                "kl" => format!("lever_{}", label(&levers)),
+                "bs" => format!("bar_sensor{}", i),
+                "hz" => format!("string{}_hz", i + 1),
+                "sa" => format!("string{}_active", i + 1),
+                "at" => format!("string{}_attack", i + 1),
+                "am" => format!("string{}_amplitude", i + 1),
+                _ => format!("{}{}", name, i),
+            };
+            columns.push(CsvColumn {
+                name,
+                key: key.to_string(),
+                index: Some(i),
+            });
+        }
+    }
+    Ok(columns)
+}
+
+/// What a `write` produced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvSummary {
+    pub rows: u64,
+    /// Malformed lines passed over
+    pub skipped: usize,
+}
+
+/// Streams a session to CSV.
+pub struct CsvExport {
+    period_us: Option<u64>,
+    start_us: u64,
+    end_us: Option<u64>,
+    patterns: Vec<String>,
+}
+
+impl CsvExport {
+    /// Every frame, every column.
+    pub fn new() -> Self {
+        Self {
+            period_us: None,
+            start_us: 0,
+            end_us: None,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Downsample to at most `hz` rows per second (0 keeps every frame).
+    pub fn with_rate(mut self, hz: f64) -> Self {
+        self.period_us = (hz > 0.0).then(|| (1e6 / hz).round().max(1.0) as u64);
+        self
+    }
+
+    /// Only frames from `start_s` up to `end_s`, in seconds of session time
+    /// (the `timestamp_us` column).
+    pub fn with_range(mut self, start_s: f64, end_s: Option<f64>) -> Self {
+        self.start_us = (start_s.max(0.0) * 1e6) as u64;
+        self.end_us = end_s.map(|s| (s.max(0.0) * 1e6) as u64);
+        self
+    }
+
+    /// Only these columns, in this order. A `*` matches any run of
+    /// characters: `pedal_*`, `string3_*`, `*_hz`.
+    pub fn with_columns(mut self, patterns: Vec<String>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// The columns `write` will produce for a session.
+    pub fn columns(&self, header: &SessionHeader) -> Result<Vec<CsvColumn>, String> {
+        let all = session_columns(header)?;
+        if self.patterns.is_empty() {
+            return Ok(all);
+        }
+        let mut chosen: Vec<CsvColumn> = Vec::new();
+        for pattern in &self.patterns {
+            let matches: Vec<&CsvColumn> = all.iter().filter(|c| glob(pattern, &c.name)).collect();
+            if matches.is_empty() {
+                let names: Vec<&str> = all.iter().map(|c| c.name.as_str()).collect();
+                return Err(format!(
+                    "no column matches '{}' (columns: {})",
+                    pattern,
+                    names.join(", ")
+                ));
+            }
+            for column in matches {
+                if !chosen.contains(column) {
+                    chosen.push(column.clone());
+                }
+            }
+        }
+        Ok(chosen)
+    }
+
+    /// Write a header row, then a row per kept frame.
+    pub fn write<R: BufRead>(
+        &self,
+        reader: &mut SessionReader<R>,
+        mut out: impl Write,
+    ) -> Result<CsvSummary, String> {
+        let columns = self.columns(&reader.header)?;
+        let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+        let io = |e: std::io::Error| e.to_string();
+        writeln!(out, "{}", names.join(",")).map_err(io)?;
+
+        let mut summary = CsvSummary {
+            rows: 0,
+            skipped: 0,
+        };
+        let mut next_due: Option<u64> = None;
+        // Attacks seen since the last row, when downsampling
+        let mut attacks = vec![false; columns.len()];
+        let mut row = String::new();
+        while let Some(frame) = reader.next_value() {
+            let Some((frame, t)) = frame.ok().and_then(|f| f["t"].as_u64().map(|t| (f, t))) else {
+                summary.skipped += 1;
+                continue;
+            };
+            if t < self.start_us {
+                continue;
+            }
+            if self.end_us.is_some_and(|end| t >= end) {
+                break;
+            }
+            if let Some(period) = self.period_us {
+                for (seen, column) in attacks.iter_mut().zip(&columns) {
+                    *seen |= column.key == "at" && column.value(&frame) == &Value::Bool(true);
+                }
+                if next_due.is_some_and(|due| t < due) {
+                    continue;
+                }
+                let due = next_due.unwrap_or(t);
+                next_due = Some(due + period * ((t - due) / period + 1));
+            }
+
+            row.clear();
+            for (i, column) in columns.iter().enumerate() {
+                if i > 0 {
+                    row.push(',');
+                }
+                match column.value(&frame) {
+                    _ if std::mem::take(&mut attacks[i]) => row.push('1'),
+                    value => push_cell(&mut row, value),
+                }
+            }
+            row.push('\n');
+            out.write_all(row.as_bytes()).map_err(io)?;
+            summary.rows += 1;
+        }
+        out.flush().map_err(io)?;
+        Ok(summary)
+    }
+}
+
+impl Default for CsvExport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsvColumn {
+    fn value<'a>(&self, frame: &'a Value) -> &'a Value {
+        let value = &frame[self.key.as_str()];
+        match self.index {
+            Some(i) => &value[i],
+            None => value,
+        }
+    }
+}
+
+fn push_cell(row: &mut String, value: &Value) {
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => row.push(if *b { '1' } else { '0' }),
+        Value::Number(n) => row.push_str(&n.to_string()),
+        Value::String(s) => push_text(row, s),
+        other => push_text(row, &other.to_string()),
+    }
+}
+
+/// Text, quoted if it would break the row.
+fn push_text(row: &mut String, text: &str) {
+    if text.contains([',', '"', '\n', '\r']) {
+        row.push('"');
+        row.push_str(&text.replace('"', "\"\""));
+        row.push('"');
+    } else {
+        row.push_str(text);
+    }
+}
+
+/// Whole-name match where `*` stands for any run of characters.
+fn glob(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((head, rest)) => {
+            let Some(tail) = name.strip_prefix(head) else {
+                return false;
+            };
+            tail.char_indices()
+                .map(|(i, _)| i)
+                .chain([tail.len()])
+                .any(|i| glob(rest, &tail[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::copedant::geoff_derby_e9;
+    use crate::data_logger::build_jsonl_header;
+    use crate::types::{BarSource, CompactFrame};
+    use std::io::Cursor;
+
+    fn frame(t_ms: u64, bar: Option<f32>, attack: bool) -> CompactFrame {
+        let mut at = [false; 10];
+        at[2] = attack;
+        CompactFrame {
+            t: t_ms * 1000,
+            p: [0.0, 0.25, 0.0],
+            kl: [0.0; 5],
+            v: 0.5,
+            bs: [0.0; 4],
+            bp: bar,
+            bc: 0.9,
+            bx: BarSource::Sensor,
+            hz: [440.0; 10],
+            sa: [true; 10],
+            at,
+            am: [0.0; 10],
+        }
+    }
+
+    fn session(frames: &[CompactFrame]) -> SessionReader<Cursor<String>> {
+        let mut jsonl = build_jsonl_header(&geoff_derby_e9()).to_string() + "\n";
+        for f in frames {
+            jsonl += &serde_json::to_string(f).unwrap();
+            jsonl.push('\n');
+        }
+        SessionReader::open(Cursor::new(jsonl)).unwrap()
+    }
+
+    #[test]
+    fn test_column_names_and_selection() {
+        let reader = session(&[]);
+        let all = session_columns(&reader.header).unwrap();
+        let names: Vec<&str> = all.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names.len(), 1 + 3 + 5 + 1 + 4 + 3 + 4 * 10);
+        assert_eq!(
+            &names[..5],
+            ["timestamp_us", "pedal_A", "pedal_B", "pedal_C", "lever_LKL"]
+        );
+        assert!(names.contains(&"bar_sensor3"));
+        assert!(names.contains(&"string10_attack"));
+
+        let picked = CsvExport::new()
+            .with_columns(vec!["string3_*".into(), "volume".into(), "*_hz".into()])
+            .columns(&reader.header)
+            .unwrap();
+        let picked: Vec<&str> = picked.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            &picked[..5],
+            [
+                "string3_hz",
+                "string3_active",
+                "string3_attack",
+                "string3_amplitude",
+                "volume"
+            ]
+        );
+        // string3_hz isn't repeated by *_hz
+        assert_eq!(picked.len(), 5 + 9);
+
+        let err = CsvExport::new()
+            .with_columns(vec!["pedal_D".into()])
+            .columns(&reader.header)
+            .unwrap_err();
+        assert!(err.starts_with("no column matches 'pedal_D' (columns: timestamp_us, pedal_A"));
+    }
+
+    #[test]
+    fn test_stream_with_range_rate_and_latched_attacks() {
+        let frames: Vec<CompactFrame> = (0..10)
+            .map(|i| frame(i * 10, (i != 4).then_some(5.0), i == 3))
+            .collect();
+        let mut reader = session(&frames);
+        let export = CsvExport::new()
+            .with_columns(vec![
+                "timestamp_us".into(),
+                "pedal_B".into(),
+                "bar_position".into(),
+                "string3_attack".into(),
+                "bar_source".into(),
+            ])
+            .with_range(0.02, Some(0.09))
+            .with_rate(50.0);
+        let mut out = Vec::new();
+        let summary = export.write(&mut reader, &mut out).unwrap();
+        // 20–80 ms every 20 ms; the attack at 30 ms lands on the 40 ms row
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "timestamp_us,pedal_B,bar_position,string3_attack,bar_source\n\
+             20000,0.25,5.0,0,Sensor\n\
+             40000,0.25,,1,Sensor\n\
+             60000,0.25,5.0,0,Sensor\n\
+             80000,0.25,5.0,0,Sensor\n"
+        );
+        assert_eq!(
+            summary,
+            CsvSummary {
+                rows: 4,
+                skipped: 0
+            }
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::types::{AudioChunk, CaptureFrame, CompactFrame, Copedant};
+use crate::types::{AudioChunk, CaptureFrame, CompactFrame, ControlCommand, Copedant};
 use crossbeam_channel::Receiver;
 use log::{error, info};
 use serde_json::json;
@@ -50,6 +50,10 @@ pub struct DataLogger {
     audio_rx: Receiver<AudioChunk>,
     session_dir: PathBuf,
     copedant: Copedant,
+    /// Remote commands (`Record` and `Marker` are acted on here)
+    control_rx: Option<Receiver<ControlCommand>>,
+    /// Frames and audio are written only while recording
+    recording: bool,
 }
 
 impl DataLogger {
@@ -71,11 +75,31 @@ impl DataLogger {
             audio_rx,
             session_dir,
             copedant,
+            control_rx: None,
+            recording: true,
         }
     }
 
+    /// Accept remote commands: `Record` pauses and resumes writing frames
+    /// and audio, `Marker` is appended to `markers.jsonl`.
+    pub fn with_control(mut self, rx: Receiver<ControlCommand>) -> Self {
+        self.control_rx = Some(rx);
+        self
+    }
+
+    /// Whether to write from the start (default) or wait for a
+    /// `Record(true)` command.
+    pub fn with_recording(mut self, recording: bool) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    pub fn session_dir(&self) -> &Path {
+        &self.session_dir
+    }
+
     /// Run the logger. Blocks the calling thread.
-    pub fn run(&self) {
+    pub fn run(mut self) {
         info!("Data logger → {:?}", self.session_dir);
 
         // Write manifest
@@ -98,9 +122,22 @@ impl DataLogger {
 
         let mut frame_count: u64 = 0;
 
+        // Record/marker events, timestamped by the frame they apply from
+        let mut markers_writer = self.control_rx.as_ref().map(|_| {
+            let path = self.session_dir.join("markers.jsonl");
+            BufWriter::new(File::create(&path).expect("create markers file"))
+        });
+        let mut marker_count: u64 = 0;
+        if !self.recording {
+            info!("Logging paused until record start");
+        }
+
         loop {
             // Non-blocking drain of audio chunks
             while let Ok(chunk) = self.audio_rx.try_recv() {
+                if !self.recording {
+                    continue;
+                }
                 audio_sample_rate = chunk.sample_rate;
                 for &s in &chunk.samples {
                     let bytes = s.to_le_bytes();
@@ -112,6 +149,35 @@ impl DataLogger {
             // Blocking receive of capture frames
             match self.rx.recv() {
                 Ok(frame) => {
+                    let t = frame.timestamp_us;
+                    // Commands sent before this frame take effect from it
+                    let commands: Vec<ControlCommand> = self
+                        .control_rx
+                        .as_ref()
+                        .map(|rx| rx.try_iter().collect())
+                        .unwrap_or_default();
+                    for cmd in commands {
+                        let event = match cmd {
+                            ControlCommand::Record(on) if on != self.recording => {
+                                self.recording = on;
+                                info!("Logging {}", if on { "started" } else { "stopped" });
+                                json!({"t": t, "record": if on { "start" } else { "stop" }})
+                            }
+                            ControlCommand::Marker(label) => {
+                                info!("Marker {:?} at {} us", label, t);
+                                marker_count += 1;
+                                json!({"t": t, "marker": label})
+                            }
+                            _ => continue,
+                        };
+                        if let Some(w) = markers_writer.as_mut() {
+                            let _ = writeln!(w, "{}", event);
+                            let _ = w.flush();
+                        }
+                    }
+                    if !self.recording {
+                        continue;
+                    }
                     let compact = CompactFrame::from(&frame);
                     let line = serde_json::to_string(&compact).unwrap();
                     let _ = writeln!(frames_writer, "{}", line);
@@ -139,6 +205,7 @@ impl DataLogger {
             "total_frames": frame_count,
             "total_audio_samples": audio_sample_count,
             "audio_sample_rate": audio_sample_rate,
+            "markers": marker_count,
         });
         fs::write(&stats_path, serde_json::to_string_pretty(&stats).unwrap())
             .unwrap_or_else(|e| error!("Failed to write stats: {}", e));
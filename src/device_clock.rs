@@ -0,0 +1,333 @@
+//! Device-to-host clock mapping for Teensy timestamps.
+//!
+//! Every frame carries the Teensy's `micros()` — a u32 that wraps every
+//! ~71.6 minutes — taken when the ADCs were sampled. The host receive time
+//! of the same frame includes USB scheduling jitter of a millisecond or
+//! more. `DeviceClock` unwraps the device counter and fits
+//! `host = offset + (1 + drift) × device` over the last ~10 s (exponentially
+//! weighted least squares), so frames are stamped with evenly spaced device
+//! time expressed on the host clock. Gaps in the device timestamps reveal
+//! dropped frames; a lasting change of step size (a new sample rate) is
+//! learned as the new frame period.
+
+use serde::Serialize;
+
+/// Forgetting factor per frame (~10 s memory at 1 kHz)
+const FORGET: f64 = 0.9999;
+/// Below this much device-time spread the fit is offset-only
+const MIN_FIT_SPREAD_US: f64 = 100_000.0;
+/// Crystal drift beyond this is not physical — clamp the estimate (±1000 ppm)
+const MAX_DRIFT: f64 = 1e-3;
+/// Host time this far from the prediction means the device reset or the
+/// link stalled: start a new fit
+const RESYNC_US: f64 = 100_000.0;
+/// A device-time step over this many nominal periods counts as a gap
+const GAP_FACTOR: f64 = 1.5;
+/// This many consecutive same-sized steps away from the nominal period
+/// mean the device rate changed (`SetRate`, calibration stream): the period
+/// is re-learned and the steps aren't counted as gaps
+const RELEARN_STEPS: u32 = 8;
+
+/// Timing diagnostics.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ClockStats {
+    pub frames: u64,
+    /// Frames missing according to device timestamps
+    pub dropped_frames: u64,
+    /// Separate runs of missing frames
+    pub gaps: u64,
+    /// Fit restarts (device reset, long stall)
+    pub resyncs: u64,
+    /// Device clock rate relative to the host, in ppm (positive = device fast)
+    pub drift_ppm: f64,
+    /// RMS of host receive time around the fitted mapping
+    pub jitter_us: f64,
+    /// Nominal device frame period
+    pub period_us: f64,
+}
+
+/// A run of same-sized steps away from the nominal period, and what it
+/// has added to the gap counts so far.
+struct OffPeriod {
+    delta: f64,
+    steps: u32,
+    gaps: u64,
+    dropped: u64,
+}
+
+#[derive(Default)]
+pub struct DeviceClock {
+    last_raw: Option<u32>,
+    wraps: u64,
+    /// (device_us, host_us) the fit is relative to
+    anchor: Option<(u64, u64)>,
+    // Weighted sums of (d, h) relative to the anchor
+    sw: f64,
+    sd: f64,
+    sh: f64,
+    sdd: f64,
+    sdh: f64,
+    last_device: Option<u64>,
+    off_period: Option<OffPeriod>,
+    last_out: u64,
+    residual_sq: f64,
+    stats: ClockStats,
+}
+
+impl DeviceClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> ClockStats {
+        self.stats
+    }
+
+    /// Map a raw device timestamp, received at `host_us`, to host time.
+    /// Output is monotonic.
+    pub fn map(&mut self, raw: u32, host_us: u64) -> u64 {
+        self.stats.frames += 1;
+        let device = match self.unwrap(raw) {
+            Some(device) => device,
+            None => {
+                // Counter went backwards: the device restarted
+                self.stats.resyncs += 1;
+                self.wraps = 0;
+                self.last_raw = Some(raw);
+                self.last_device = None;
+                self.off_period = None;
+                self.reset_fit(None);
+                raw as u64
+            }
+        };
+        self.track_gaps(device);
+        self.fit(device, host_us)
+    }
+
+    /// Extend the u32 counter to 64 bits. `None` if it stepped backwards
+    /// without wrapping.
+    fn unwrap(&mut self, raw: u32) -> Option<u64> {
+        if let Some(last) = self.last_raw {
+            if raw < last {
+                if last - raw > u32::MAX / 2 {
+                    self.wraps += 1;
+                } else {
+                    return None;
+                }
+            }
+        }
+        self.last_raw = Some(raw);
+        Some((self.wraps << 32) | raw as u64)
+    }
+
+    fn track_gaps(&mut self, device: u64) {
+        if let Some(last) = self.last_device {
+            let delta = device.saturating_sub(last) as f64;
+            let period = self.stats.period_us;
+            if period <= 0.0 {
+                self.stats.period_us = delta;
+            } else if delta > GAP_FACTOR * period || delta * GAP_FACTOR < period {
+                self.step_off_period(delta, period);
+            } else {
+                self.off_period = None;
+                self.stats.period_us = 0.99 * period + 0.01 * delta;
+            }
+        }
+        self.last_device = Some(device);
+    }
+
+    /// A longer step is a gap of dropped frames — unless it keeps
+    /// repeating at the same size, in which case the device rate changed.
+    fn step_off_period(&mut self, delta: f64, period: f64) {
+        let (gaps, dropped) = if delta > GAP_FACTOR * period {
+            (1, ((delta / period).round() as u64).saturating_sub(1))
+        } else {
+            (0, 0)
+        };
+        self.stats.gaps += gaps;
+        self.stats.dropped_frames += dropped;
+
+        let run = match self.off_period.take() {
+            Some(run) if (delta - run.delta).abs() <= 0.1 * run.delta => OffPeriod {
+                steps: run.steps + 1,
+                gaps: run.gaps + gaps,
+                dropped: run.dropped + dropped,
+                ..run
+            },
+            _ => OffPeriod {
+                delta,
+                steps: 1,
+                gaps,
+                dropped,
+            },
+        };
+        if run.steps >= RELEARN_STEPS {
+            self.stats.gaps -= run.gaps;
+            self.stats.dropped_frames -= run.dropped;
+            self.stats.period_us = run.delta;
+        } else {
+            self.off_period = Some(run);
+        }
+    }
+
+    fn fit(&mut self, device: u64, host_us: u64) -> u64 {
+        let (ad, ah) = *self.anchor.get_or_insert((device, host_us));
+        let d = device as f64 - ad as f64;
+        let h = host_us as f64 - ah as f64;
+
+        if self.sw > 0.0 && (h - self.predict(d)).abs() > RESYNC_US {
+            self.stats.resyncs += 1;
+            self.reset_fit(Some((device, host_us)));
+            return self.fit(device, host_us);
+        }
+
+        self.sw = self.sw * FORGET + 1.0;
+        self.sd = self.sd * FORGET + d;
+        self.sh = self.sh * FORGET + h;
+        self.sdd = self.sdd * FORGET + d * d;
+        self.sdh = self.sdh * FORGET + d * h;
+
+        let predicted = self.predict(d);
+        let residual = h - predicted;
+        self.residual_sq = 0.999 * self.residual_sq + 0.001 * residual * residual;
+        self.stats.jitter_us = self.residual_sq.sqrt();
+
+        let out = (ah as f64 + predicted).round().max(0.0) as u64;
+        self.last_out = self.last_out.max(out);
+        self.last_out
+    }
+
+    /// Fitted host offset (relative to the anchor) at device offset `d`.
+    fn predict(&mut self, d: f64) -> f64 {
+        let mean_d = self.sd / self.sw;
+        let mean_h = self.sh / self.sw;
+        let var = self.sdd / self.sw - mean_d * mean_d;
+        let slope = if var > MIN_FIT_SPREAD_US * MIN_FIT_SPREAD_US {
+            let cov = self.sdh / self.sw - mean_d * mean_h;
+            (cov / var).clamp(1.0 - MAX_DRIFT, 1.0 + MAX_DRIFT)
+        } else {
+            1.0
+        };
+        // host = (1 + drift) × device, so a fast device has slope < 1
+        self.stats.drift_ppm = (1.0 / slope - 1.0) * 1e6;
+        mean_h + slope * (d - mean_d)
+    }
+
+    fn reset_fit(&mut self, anchor: Option<(u64, u64)>) {
+        self.anchor = anchor;
+        self.sw = 0.0;
+        self.sd = 0.0;
+        self.sh = 0.0;
+        self.sdd = 0.0;
+        self.sdh = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::Rng;
+
+    #[test]
+    fn test_unwraps_u32_counter() {
+        let mut clock = DeviceClock::new();
+        let start = u32::MAX - 2500;
+        let mut outs = Vec::new();
+        for k in 0..6u32 {
+            let raw = start.wrapping_add(k * 1000);
+            outs.push(clock.map(raw, 1_000_000 + k as u64 * 1000));
+        }
+        for pair in outs.windows(2) {
+            assert_eq!(pair[1] - pair[0], 1000);
+        }
+        assert_eq!(clock.stats().resyncs, 0);
+        assert_eq!(clock.stats().dropped_frames, 0);
+    }
+
+    #[test]
+    fn test_drift_and_jitter_removed() {
+        // Device crystal 50 ppm fast; USB delivers 0–2 ms late
+        let mut rng = Rng::new(3);
+        let mut clock = DeviceClock::new();
+        let mut worst = 0.0f64;
+        for k in 0..30_000u64 {
+            let true_host = 5_000_000.0 + k as f64 * 1000.0;
+            let device = (k as f64 * 1000.0 * (1.0 + 50e-6)) as u32;
+            let received = true_host + 1000.0 + 1000.0 * rng.bipolar() as f64;
+            let out = clock.map(device, received as u64) as f64;
+            if k > 20_000 {
+                // Offset is the mean latency; what matters is even spacing
+                worst = worst.max((out - true_host - 1000.0).abs());
+            }
+        }
+        let stats = clock.stats();
+        assert!(
+            (stats.drift_ppm - 50.0).abs() < 10.0,
+            "drift {}",
+            stats.drift_ppm
+        );
+        assert!(worst < 150.0, "corrected error {} µs", worst);
+        assert!(stats.jitter_us > 400.0, "input jitter {}", stats.jitter_us);
+    }
+
+    #[test]
+    fn test_counts_dropped_frames() {
+        let mut clock = DeviceClock::new();
+        let mut t = 0u32;
+        for k in 0..100 {
+            // Lose 5 frames after the 50th, and 1 after the 80th
+            t += match k {
+                51 => 6000,
+                81 => 2000,
+                _ => 1000,
+            };
+            clock.map(t, t as u64 + 500);
+        }
+        let stats = clock.stats();
+        assert_eq!(stats.gaps, 2);
+        assert_eq!(stats.dropped_frames, 6);
+        assert!((stats.period_us - 1000.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_relearns_period_after_rate_change() {
+        let mut clock = DeviceClock::new();
+        let mut t = 0u32;
+        let mut step = |clock: &mut DeviceClock, dt: u32| {
+            t += dt;
+            clock.map(t, t as u64 + 500);
+        };
+        for _ in 0..100 {
+            step(&mut clock, 1000);
+        }
+        // SetRate(100): 10 ms frames from now on, one of them lost
+        for k in 0..50 {
+            step(&mut clock, if k == 30 { 20_000 } else { 10_000 });
+        }
+        let stats = clock.stats();
+        assert_eq!((stats.gaps, stats.dropped_frames), (1, 1));
+        assert!((stats.period_us - 10_000.0).abs() < 10.0);
+        // Back up to 2 kHz: faster frames are learned the same way
+        for k in 0..50 {
+            step(&mut clock, if k == 30 { 1000 } else { 500 });
+        }
+        let stats = clock.stats();
+        assert_eq!((stats.gaps, stats.dropped_frames), (2, 2));
+        assert!((stats.period_us - 500.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_device_reset_resyncs() {
+        let mut clock = DeviceClock::new();
+        let mut last = 0;
+        for k in 0..100u64 {
+            last = clock.map(10_000_000 + k as u32 * 1000, 2_000_000 + k * 1000);
+        }
+        // Device reboots: counter restarts near zero
+        let after = clock.map(500, 2_100_000);
+        assert_eq!(clock.stats().resyncs, 1);
+        assert!(after >= last);
+        let next = clock.map(1500, 2_101_000);
+        assert_eq!(next - after, 1000);
+    }
+}
@@ -0,0 +1,370 @@
+//! Signal impairments for robustness testing.
+//!
+//! Real rigs are noisy: mains hum, hiss, amp buzz, clipped preamps, and
+//! unpicked strings ringing in sympathy. `Impairments` layers these onto
+//! clean simulator or WAV audio so we can measure how `StringDetector` and
+//! `BarInference` degrade, and catch regressions.
+//!
+//! Levels are linear amplitudes relative to full scale (1.0), except noise,
+//! which is set by SNR against a running estimate of the signal level.
+
+use crate::simulator::Rng;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+/// Spectral color of the broadband noise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseColor {
+    /// Flat spectrum (hiss)
+    White,
+    /// −3 dB/octave (1/f), closer to typical analog noise
+    Pink,
+}
+
+impl FromStr for NoiseColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "white" => Ok(Self::White),
+            "pink" => Ok(Self::Pink),
+            other => Err(format!(
+                "unknown noise color '{}' (expected: white, pink)",
+                other
+            )),
+        }
+    }
+}
+
+/// Mains hum: fundamental plus decaying odd/even harmonics.
+#[derive(Debug, Clone)]
+pub struct Hum {
+    /// Mains frequency (50 or 60 Hz)
+    pub freq_hz: f64,
+    /// Peak amplitude of the fundamental
+    pub level: f32,
+    /// Number of harmonics including the fundamental
+    pub harmonics: usize,
+}
+
+/// Broadband noise at a target signal-to-noise ratio.
+#[derive(Debug, Clone)]
+pub struct Noise {
+    pub color: NoiseColor,
+    /// Signal-to-noise ratio in dB (signal = running RMS of the clean audio)
+    pub snr_db: f32,
+}
+
+/// Every impairment is optional; `Impairments::default()` is a no-op.
+#[derive(Debug, Clone, Default)]
+pub struct Impairments {
+    pub hum: Option<Hum>,
+    pub noise: Option<Noise>,
+    /// Amp buzz level: a rectified-mains sawtooth at twice the hum frequency
+    /// (60 Hz mains if no hum is configured), rich in upper harmonics.
+    pub buzz: Option<f32>,
+    /// Hard-clip threshold (e.g. 0.5 clips at ±0.5)
+    pub clip: Option<f32>,
+    /// Sympathetic resonance level, relative to the sounding strings' level
+    pub sympathetic: Option<f32>,
+}
+
+/// Running state (phases, noise filters, level tracker) for an `Impairments`
+/// configuration. One per audio stream.
+pub struct ImpairmentChain {
+    config: Impairments,
+    sample_rate: u32,
+    sample_counter: u64,
+    rng: Rng,
+    /// Paul Kellet's pink filter state
+    pink: [f32; 7],
+    /// Running RMS of the clean signal while it is sounding
+    signal_rms: f32,
+    /// Per-string sympathetic oscillator phase
+    sym_phase: [f64; 10],
+}
+
+/// Signal level assumed before any audio has been measured (~−20 dBFS).
+const NOMINAL_SIGNAL_RMS: f32 = 0.1;
+
+impl Impairments {
+    /// True if at least one impairment is configured.
+    pub fn is_active(&self) -> bool {
+        self.hum.is_some()
+            || self.noise.is_some()
+            || self.buzz.is_some()
+            || self.clip.is_some()
+            || self.sympathetic.is_some()
+    }
+
+    pub fn with_hum(mut self, freq_hz: f64, level: f32) -> Self {
+        self.hum = Some(Hum {
+            freq_hz,
+            level,
+            harmonics: 5,
+        });
+        self
+    }
+
+    pub fn with_noise(mut self, color: NoiseColor, snr_db: f32) -> Self {
+        self.noise = Some(Noise { color, snr_db });
+        self
+    }
+
+    pub fn with_buzz(mut self, level: f32) -> Self {
+        self.buzz = Some(level);
+        self
+    }
+
+    pub fn with_clip(mut self, threshold: f32) -> Self {
+        self.clip = Some(threshold);
+        self
+    }
+
+    pub fn with_sympathetic(mut self, level: f32) -> Self {
+        self.sympathetic = Some(level);
+        self
+    }
+
+    /// Build the stateful processor for a stream at `sample_rate`.
+    pub fn chain(&self, sample_rate: u32) -> ImpairmentChain {
+        ImpairmentChain {
+            config: self.clone(),
+            sample_rate,
+            sample_counter: 0,
+            rng: Rng::new(0x0a15e_u64),
+            pink: [0.0; 7],
+            signal_rms: NOMINAL_SIGNAL_RMS,
+            sym_phase: [0.0; 10],
+        }
+    }
+}
+
+impl ImpairmentChain {
+    pub fn is_active(&self) -> bool {
+        self.config.is_active()
+    }
+
+    /// Apply the signal-independent impairments (hum, noise, buzz, clip).
+    /// Use this when string pitches are unknown (e.g. WAV playback).
+    pub fn process(&mut self, samples: &mut [f32]) {
+        self.process_inner(samples, None);
+    }
+
+    /// Apply all impairments, including sympathetic resonance, which needs
+    /// the current per-string pitches and which strings were picked.
+    pub fn process_with_strings(
+        &mut self,
+        samples: &mut [f32],
+        pitches_hz: &[f64; 10],
+        active: &[bool; 10],
+    ) {
+        self.process_inner(samples, Some((pitches_hz, active)));
+    }
+
+    fn process_inner(&mut self, samples: &mut [f32], strings: Option<(&[f64; 10], &[bool; 10])>) {
+        let sr = self.sample_rate as f64;
+        self.track_signal_level(samples);
+
+        if let (Some(level), Some((pitches, active))) = (self.config.sympathetic, strings) {
+            self.add_sympathetic(samples, level, pitches, active);
+        }
+
+        let noise_rms = self
+            .config
+            .noise
+            .as_ref()
+            .map(|n| self.signal_rms / 10f32.powf(n.snr_db / 20.0));
+        let mains = self.config.hum.as_ref().map_or(60.0, |h| h.freq_hz);
+
+        for (j, sample) in samples.iter_mut().enumerate() {
+            let t = (self.sample_counter + j as u64) as f64 / sr;
+            let mut v = *sample;
+
+            if let Some(hum) = &self.config.hum {
+                for k in 1..=hum.harmonics {
+                    let amp = hum.level / (k * k) as f32;
+                    v += amp * (2.0 * PI * hum.freq_hz * k as f64 * t).sin() as f32;
+                }
+            }
+
+            if let Some(level) = self.config.buzz {
+                // Full-wave rectified mains → sawtooth-like at 2× mains
+                let phase = (2.0 * mains * t).fract() as f32;
+                v += level * (2.0 * phase - 1.0);
+            }
+
+            if let (Some(rms), Some(noise)) = (noise_rms, &self.config.noise) {
+                let white = self.rng.bipolar();
+                let n = match noise.color {
+                    NoiseColor::White => white,
+                    NoiseColor::Pink => self.pink_sample(white),
+                };
+                // Uniform [-1, 1) has RMS 1/√3
+                v += rms * n * 3f32.sqrt();
+            }
+
+            if let Some(threshold) = self.config.clip {
+                v = v.clamp(-threshold, threshold);
+            }
+
+            *sample = v;
+        }
+
+        self.sample_counter += samples.len() as u64;
+    }
+
+    /// Update the running signal RMS from the clean input. Silent chunks are
+    /// ignored so the noise floor doesn't collapse between phrases.
+    fn track_signal_level(&mut self, samples: &[f32]) {
+        let rms = crate::dsp::compute_rms(samples);
+        if rms > 0.003 {
+            // ~1 s time constant
+            let alpha = (samples.len() as f32 / self.sample_rate as f32).min(1.0);
+            self.signal_rms += alpha * (rms - self.signal_rms);
+        }
+    }
+
+    /// Unpicked strings whose pitch is a simple ratio (up to 4:1) of a
+    /// sounding string pick up energy from it and ring quietly.
+    fn add_sympathetic(
+        &mut self,
+        samples: &mut [f32],
+        level: f32,
+        pitches: &[f64; 10],
+        active: &[bool; 10],
+    ) {
+        let sr = self.sample_rate as f64;
+        for i in 0..10 {
+            if active[i] {
+                continue;
+            }
+            let mut coupling = 0.0f32;
+            for j in (0..10).filter(|&j| active[j]) {
+                for m in 1..=4 {
+                    for k in 1..=4 {
+                        let cents =
+                            1200.0 * (pitches[i] * k as f64 / (pitches[j] * m as f64)).log2();
+                        if cents.abs() < 15.0 {
+                            coupling = coupling.max(1.0 / m.max(k) as f32);
+                        }
+                    }
+                }
+            }
+            if coupling == 0.0 {
+                continue;
+            }
+            let amp = level * coupling * self.signal_rms * 2f32.sqrt();
+            let inc = 2.0 * PI * pitches[i] / sr;
+            for sample in samples.iter_mut() {
+                self.sym_phase[i] += inc;
+                *sample += amp * self.sym_phase[i].sin() as f32;
+            }
+            self.sym_phase[i] %= 2.0 * PI;
+        }
+    }
+
+    /// Paul Kellet's refined pink noise filter (white in, −3 dB/octave out).
+    fn pink_sample(&mut self, white: f32) -> f32 {
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let out = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+        // Kellet's filter has ~+10 dB gain over its white input
+        out * 0.3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_helpers::sine_wave;
+    use crate::dsp::{compute_rms, goertzel_magnitude};
+
+    #[test]
+    fn test_default_is_noop() {
+        let imp = Impairments::default();
+        assert!(!imp.is_active());
+        let mut samples = sine_wave(440.0, 0.5, 48000, 50);
+        let clean = samples.clone();
+        imp.chain(48000).process(&mut samples);
+        assert_eq!(samples, clean);
+    }
+
+    #[test]
+    fn test_hum_has_mains_harmonics() {
+        let mut samples = vec![0.0f32; 48000 / 2];
+        Impairments::default()
+            .with_hum(60.0, 0.05)
+            .chain(48000)
+            .process(&mut samples);
+        let n = samples.len();
+        let fund = goertzel_magnitude(&samples, 60.0, 48000.0, n);
+        let third = goertzel_magnitude(&samples, 180.0, 48000.0, n);
+        let off = goertzel_magnitude(&samples, 97.0, 48000.0, n);
+        assert!(fund > third && third > off * 10.0);
+    }
+
+    #[test]
+    fn test_noise_hits_target_snr() {
+        let signal = sine_wave(440.0, 0.1 * 2f64.sqrt(), 48000, 500); // RMS 0.1
+        let mut noisy = signal.clone();
+        Impairments::default()
+            .with_noise(NoiseColor::White, 20.0)
+            .chain(48000)
+            .process(&mut noisy);
+        let noise: Vec<f32> = noisy.iter().zip(&signal).map(|(a, b)| a - b).collect();
+        let snr = 20.0 * (compute_rms(&signal) / compute_rms(&noise)).log10();
+        assert!((snr - 20.0).abs() < 1.5, "SNR {:.1} dB, expected 20", snr);
+    }
+
+    #[test]
+    fn test_pink_noise_tilts_low() {
+        let mut samples = vec![0.0f32; 48000];
+        Impairments::default()
+            .with_noise(NoiseColor::Pink, 0.0)
+            .chain(48000)
+            .process(&mut samples);
+        let n = samples.len();
+        let low: f64 = (100..110)
+            .map(|f| goertzel_magnitude(&samples, f as f64, 48000.0, n))
+            .sum();
+        let high: f64 = (8000..8010)
+            .map(|f| goertzel_magnitude(&samples, f as f64, 48000.0, n))
+            .sum();
+        assert!(low > high * 3.0, "pink: low {:.1} high {:.1}", low, high);
+    }
+
+    #[test]
+    fn test_clip_bounds_output() {
+        let mut samples = sine_wave(440.0, 0.9, 48000, 20);
+        Impairments::default()
+            .with_clip(0.3)
+            .chain(48000)
+            .process(&mut samples);
+        assert!(samples.iter().all(|s| s.abs() <= 0.3));
+    }
+
+    #[test]
+    fn test_sympathetic_rings_octave_string() {
+        let mut pitches = [1000.0f64; 10];
+        pitches[0] = 220.0; // picked
+        pitches[1] = 440.0; // octave above: should ring
+        pitches[2] = 311.0; // unrelated: should stay silent
+        let mut active = [false; 10];
+        active[0] = true;
+
+        let mut samples = sine_wave(220.0, 0.3, 48000, 200);
+        let mut chain = Impairments::default().with_sympathetic(0.2).chain(48000);
+        chain.process_with_strings(&mut samples, &pitches, &active);
+        let n = samples.len();
+        let octave = goertzel_magnitude(&samples, 440.0, 48000.0, n);
+        let unrelated = goertzel_magnitude(&samples, 311.0, 48000.0, n);
+        assert!(octave > unrelated * 10.0);
+    }
+}
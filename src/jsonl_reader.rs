@@ -16,6 +16,16 @@ pub struct SessionHeader {
     pub raw: serde_json::Value,
 }
 
+impl SessionHeader {
+    /// Open string tuning (MIDI notes, string 1 first) from the embedded
+    /// copedant, if the header has all ten.
+    pub fn open_strings(&self) -> Option<[f64; 10]> {
+        let midi = self.raw["copedant"]["open_strings_midi"].as_array()?;
+        let midi: Vec<f64> = midi.iter().filter_map(|m| m.as_f64()).collect();
+        midi.try_into().ok()
+    }
+}
+
 /// Line-by-line JSONL session reader.
 pub struct SessionReader<R: BufRead> {
     reader: R,
@@ -67,21 +77,33 @@ impl<R: BufRead> SessionReader<R> {
 
     /// Read the next frame. Returns `None` at EOF, `Err` for unparseable lines.
     pub fn next_frame(&mut self) -> Option<Result<CaptureFrame, String>> {
-        self.line_buf.clear();
-        match self.reader.read_line(&mut self.line_buf) {
-            Ok(0) => None, // EOF
-            Ok(_) => {
-                let trimmed = self.line_buf.trim();
-                if trimmed.is_empty() {
-                    return self.next_frame(); // skip blank lines
-                }
-                Some(
-                    serde_json::from_str::<CompactFrame>(trimmed)
-                        .map(CaptureFrame::from)
-                        .map_err(|e| format!("parse frame: {}", e)),
-                )
+        Some(self.next_line()?.and_then(|line| {
+            serde_json::from_str::<CompactFrame>(line)
+                .map(CaptureFrame::from)
+                .map_err(|e| format!("parse frame: {}", e))
+        }))
+    }
+
+    /// Read the next frame as raw JSON, for readers that go by the header's
+    /// `channels` table rather than `CompactFrame`.
+    pub fn next_value(&mut self) -> Option<Result<serde_json::Value, String>> {
+        Some(
+            self.next_line()?.and_then(|line| {
+                serde_json::from_str(line).map_err(|e| format!("parse frame: {}", e))
+            }),
+        )
+    }
+
+    /// The next non-blank line, trimmed.
+    fn next_line(&mut self) -> Option<Result<&str, String>> {
+        loop {
+            self.line_buf.clear();
+            match self.reader.read_line(&mut self.line_buf) {
+                Ok(0) => return None,                          // EOF
+                Ok(_) if self.line_buf.trim().is_empty() => {} // skip blank lines
+                Ok(_) => return Some(Ok(self.line_buf.trim())),
+                Err(e) => return Some(Err(format!("read line: {}", e))),
             }
-            Err(e) => Some(Err(format!("read line: {}", e))),
         }
     }
 
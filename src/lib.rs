@@ -1,16 +1,37 @@
+pub mod audio_pipe;
 pub mod bar_inference;
 pub mod bar_sensor;
+pub mod benchmark;
 pub mod calibration;
 pub mod console_display;
 pub mod coordinator;
 pub mod copedant;
+pub mod csv_export;
 pub mod data_logger;
+pub mod device_clock;
 pub mod dsp;
+pub mod impairments;
 pub mod jsonl_reader;
+pub mod midi_input;
+pub mod mpe_output;
+pub mod musicxml_export;
+pub mod note_events;
+pub mod osc_control;
 pub mod osc_sender;
+pub mod sensor_calibrator;
+pub mod sensor_curve;
+pub mod sensor_faults;
+pub mod serial_protocol;
+pub mod serial_reader;
 pub mod simulator;
+pub mod smf_export;
 pub mod string_detector;
+pub mod string_synth;
+pub mod tab_import;
+pub mod teensy_emulator;
+pub mod text_tab;
 pub mod types;
+pub mod udp_input;
 pub mod wav_player;
 pub mod ws_server;
 
@@ -20,8 +41,5 @@ pub mod audio_input;
 #[cfg(feature = "calibration")]
 pub mod calibrator;
 
-#[cfg(feature = "hardware")]
-pub mod serial_reader;
-
 #[cfg(feature = "gui")]
 pub mod webview_app;
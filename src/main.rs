@@ -1,3 +1,5 @@
+use steel_capture::audio_pipe::{AudioPipe, PcmFormat};
+use steel_capture::benchmark;
 use steel_capture::calibration::Calibration;
 #[cfg(feature = "calibration")]
 use steel_capture::calibrator::Calibrator;
@@ -6,22 +8,41 @@ use steel_capture::coordinator;
 use steel_capture::copedant::geoff_derby_e9;
 #[cfg(feature = "calibration")]
 use steel_capture::copedant::CopedantEngine;
+use steel_capture::csv_export::CsvExport;
 use steel_capture::data_logger;
-use steel_capture::osc_sender;
+use steel_capture::impairments::{Impairments, NoiseColor};
+use steel_capture::jsonl_reader::SessionReader;
+use steel_capture::midi_input::{self, parse_midi_map_spec, MidiSource, MidiTarget};
+use steel_capture::mpe_output::{MpeEncoder, MpeOutput};
+use steel_capture::musicxml_export::MusicXmlExport;
+use steel_capture::note_events::note_events;
+use steel_capture::osc_control;
+use steel_capture::osc_sender::{self, OscTarget};
+#[cfg(feature = "hardware")]
+use steel_capture::sensor_curve::CurveFit;
+use steel_capture::sensor_faults::{parse_fault_spec, FaultKind, SensorChannel};
+use steel_capture::serial_protocol::{ChannelMap, ChannelRole, ProtocolVersion};
 #[cfg(feature = "hardware")]
 use steel_capture::serial_reader;
+#[cfg(feature = "hardware")]
+use steel_capture::serial_reader::DeviceCommand;
 use steel_capture::simulator;
+use steel_capture::smf_export::SmfExport;
+use steel_capture::string_detector::StringChannels;
+use steel_capture::string_synth::AudioModel;
+use steel_capture::tab_import::Tab;
+use steel_capture::teensy_emulator;
+use steel_capture::text_tab::{self, TabOutput, TabRenderer};
 use steel_capture::types::*;
+use steel_capture::udp_input;
 use steel_capture::wav_player;
 #[cfg(feature = "gui")]
 use steel_capture::webview_app;
 use steel_capture::ws_server;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use crossbeam_channel::{bounded, unbounded};
-#[cfg(any(feature = "calibration", not(feature = "hardware")))]
-use log::error;
-use log::info;
+use log::{error, info, warn};
 use std::path::PathBuf;
 use std::thread;
 
@@ -29,6 +50,9 @@ use std::thread;
 #[command(name = "steel-capture")]
 #[command(about = "Pedal steel guitar expression capture system")]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Run in simulator mode (no hardware required).
     /// Use --simulate false for hardware mode.
     #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true")]
@@ -38,18 +62,116 @@ struct Cli {
     #[arg(long, default_value = "/dev/ttyACM0")]
     port: String,
 
-    /// OSC target address
+    /// Receive sensor data over UDP on this address (e.g. 0.0.0.0:9100)
+    /// instead of the simulator or serial port: Teensy binary frames or
+    /// OSC on the /steel/... addresses. Enables audio string detection.
+    #[arg(long)]
+    udp_input: Option<String>,
+
+    /// Read a raw MIDI byte stream (file, FIFO, or - for stdin) from
+    /// expression pedals and foot controllers instead of the simulator or
+    /// serial port. Frames go out at --sensor-rate.
+    #[arg(long, conflicts_with = "udp_input")]
+    midi_input: Option<PathBuf>,
+
+    /// Map a MIDI source to a sensor, as TARGET=SOURCE (repeatable;
+    /// replaces the default map). Targets: pedal:A-C,
+    /// lever:LKL/LKR/LKV/RKL/RKR, volume, bar, bar:0-3, string:1-10.
+    /// Sources: ccN, bend, noteN. Default: volume=cc7, pedals cc20-22,
+    /// levers cc23-27, bar=cc28, strings note60-69 (bar=bend is opt-in:
+    /// a bend wheel springs back to centre).
+    #[arg(long, value_parser = parse_midi_map_spec)]
+    midi_map: Vec<(MidiTarget, MidiSource)>,
+
+    /// While the Teensy is disconnected: hold (keep the last sensor state)
+    /// or zero (release everything, volume off). The reader keeps retrying
+    /// the port either way.
+    #[cfg(feature = "hardware")]
+    #[arg(long, default_value = "hold")]
+    disconnect_policy: serial_reader::DisconnectPolicy,
+
+    /// Set the Teensy's frame rate (Hz, 10–4000) on every connect.
+    #[cfg(feature = "hardware")]
+    #[arg(long)]
+    device_rate: Option<u16>,
+
+    /// Set the Teensy's ADC hardware averaging (1, 4, 8, 16 or 32) on
+    /// every connect.
+    #[cfg(feature = "hardware")]
+    #[arg(long)]
+    device_averaging: Option<u8>,
+
+    /// Set the Teensy's ADC resolution (8, 10 or 12 bits) on every connect.
+    /// Values stay on the 12-bit scale, so calibration still applies.
+    #[cfg(feature = "hardware")]
+    #[arg(long)]
+    device_resolution: Option<u8>,
+
+    /// Blink the Teensy's LED fast for this many seconds after connecting.
+    #[cfg(feature = "hardware")]
+    #[arg(long)]
+    identify: Option<u8>,
+
+    /// OSC target HOST:PORT, optionally with an address prefix replacing
+    /// /steel (127.0.0.1:57120/sc/steel). Repeatable.
     #[arg(long, default_value = "127.0.0.1:9000")]
-    osc_target: String,
+    osc_target: Vec<OscTarget>,
+
+    /// OSC bundles per second (0 = every 1 kHz frame)
+    #[arg(long, default_value_t = 100)]
+    osc_rate: u32,
+
+    /// Smallest change in a 0–1 value (and bar position) resent over OSC
+    #[arg(long, default_value_t = 0.002)]
+    osc_deadband: f32,
+
+    /// Smallest pitch change resent over OSC, in cents
+    #[arg(long, default_value_t = 1.0)]
+    osc_pitch_deadband: f32,
 
     /// Enable OSC output
     #[arg(long)]
     osc: bool,
 
+    /// Write an MPE MIDI stream (one channel per string) to a raw MIDI
+    /// device, FIFO or file, e.g. /dev/snd/midiC1D0 for snd-virmidi
+    #[arg(long)]
+    mpe_out: Option<PathBuf>,
+
+    /// MPE member-channel pitch-bend range in semitones
+    #[arg(long, default_value_t = 48)]
+    mpe_bend_range: u8,
+
+    /// MPE pitch-bend/pressure updates per second (0 = every frame)
+    #[arg(long, default_value_t = 500)]
+    mpe_rate: u32,
+
+    /// Write text tab as it is played to this file, or - for stdout
+    #[arg(long)]
+    tab_out: Option<PathBuf>,
+
+    /// Length of one --tab-out column in milliseconds
+    #[arg(long, default_value_t = 125)]
+    tab_column_ms: u32,
+
+    /// --tab-out columns per line
+    #[arg(long, default_value_t = 32)]
+    tab_width: usize,
+
     /// Enable data logging
     #[arg(long)]
     log_data: bool,
 
+    /// With --log-data and --osc-control, don't write until a
+    /// /steel/cmd/record start arrives
+    #[arg(long)]
+    log_wait: bool,
+
+    /// Listen for OSC control commands (/steel/cmd/record, marker, demo,
+    /// reset) on this UDP address, e.g. 0.0.0.0:9001
+    #[arg(long)]
+    osc_control: Option<String>,
+
     /// Output directory for logged sessions
     #[arg(long, default_value = "./sessions")]
     output_dir: PathBuf,
@@ -82,10 +204,105 @@ struct Cli {
     #[arg(long, default_value_t = 1000)]
     sensor_rate: u32,
 
-    /// Simulator demo sequence: "basic" (default), "e9" (90s scripted tour), or "improv" (algorithmic)
+    /// Simulator demo sequence: "basic" (default), "e9" (90s scripted tour),
+    /// "improv" (algorithmic), or "faults" (sensor channels failing mid-phrase).
+    /// --tab plays a tab file instead.
     #[arg(long, default_value = "basic")]
     demo: String,
 
+    /// Simulator audio model: "sine" (pure tones, default) or "steel"
+    /// (plucked-string model with harmonics, pick transients, decay and
+    /// pickup comb filtering — much closer to a real guitar).
+    #[arg(long, default_value = "sine")]
+    audio_model: AudioModel,
+
+    /// Inject mains hum at this frequency (50 or 60 Hz) with harmonics.
+    /// Applies to simulator and --audio-file audio.
+    #[arg(long)]
+    hum: Option<f64>,
+
+    /// Peak amplitude of the hum fundamental (full scale = 1.0).
+    #[arg(long, default_value_t = 0.02)]
+    hum_level: f32,
+
+    /// Inject broadband noise at this signal-to-noise ratio (dB).
+    #[arg(long)]
+    noise_snr: Option<f32>,
+
+    /// Noise color for --noise-snr: "white" (default) or "pink".
+    #[arg(long, default_value = "white")]
+    noise_color: NoiseColor,
+
+    /// Inject amp buzz (rectified-mains sawtooth) at this amplitude.
+    #[arg(long)]
+    buzz: Option<f32>,
+
+    /// Hard-clip the audio at this level (e.g. 0.3).
+    #[arg(long)]
+    clip: Option<f32>,
+
+    /// Sympathetic resonance of unpicked strings, relative to the picked
+    /// strings' level (simulator only).
+    #[arg(long)]
+    sympathetic: Option<f32>,
+
+    /// Simulate a sensor channel fault from the start, as CHANNEL=FAULT.
+    /// Channels: pedal:A-C, lever:LKL/LKR/LKV/RKL/RKR, volume, bar:0-3.
+    /// Faults: dead, railed, drift:PER_SEC, noise:AMPLITUDE, dropout:PER_SEC:MS.
+    /// Repeatable, e.g. --sensor-fault bar:1=dead --sensor-fault pedal:A=noise:0.05
+    #[arg(long, value_parser = parse_fault_spec)]
+    sensor_fault: Vec<(SensorChannel, FaultKind)>,
+
+    /// Random seed for --demo improv (decimal or 0x-prefixed hex).
+    /// The same seed and options always give the same performance.
+    #[arg(long, value_parser = parse_seed, default_value = "0xc0ffee")]
+    improv_seed: u64,
+
+    /// Length of the improv performance, in beats.
+    #[arg(long, default_value_t = 200)]
+    improv_beats: u32,
+
+    /// Improv tempo (BPM, 10-400).
+    #[arg(long, default_value_t = 72.0)]
+    improv_tempo: f32,
+
+    /// Transpose the improv up this many frets (0 = key of E, 3 = G, ...).
+    #[arg(long, default_value_t = 0.0)]
+    improv_key_fret: f32,
+
+    /// Improv balance of pedal/lever moves at the current fret (1.0)
+    /// versus bar slides to other frets (0.0).
+    #[arg(long, default_value_t = 0.5)]
+    improv_pedal_density: f32,
+
+    /// Improv bar vibrato rate (Hz).
+    #[arg(long, default_value_t = 5.2)]
+    improv_vibrato_hz: f32,
+
+    /// Restrict the improv to these chord labels, comma-separated
+    /// (e.g. E,A,B7). Default: the whole E9 vocabulary.
+    #[arg(long, value_delimiter = ',')]
+    improv_chords: Vec<String>,
+
+    /// Play this plain-text steel tab through the simulator instead of a
+    /// demo (the `export tab` format, plus `h` hammer-ons).
+    #[arg(long)]
+    tab: Option<PathBuf>,
+
+    /// Tempo for --tab (BPM).
+    #[arg(long, default_value_t = 120.0)]
+    tab_tempo: f32,
+
+    /// Note value of one --tab column: 4 = quarters, 8, 16 (default), 32.
+    #[arg(long, default_value_t = 16)]
+    tab_grid: u32,
+
+    /// Write the simulator's ground truth (true pedal/lever/bar/string state
+    /// per tick) to this JSONL file. The header line records the demo and
+    /// improv parameters, so the run can be reproduced.
+    #[arg(long)]
+    ground_truth_out: Option<PathBuf>,
+
     /// Use audio-based string detection instead of simulator ground truth.
     /// Automatically enabled in hardware mode. Use with simulator to test
     /// the string detector against synthetic audio.
@@ -108,6 +325,31 @@ struct Cli {
     #[arg(long)]
     audio_file: Option<PathBuf>,
 
+    /// Read live audio as raw interleaved PCM from a named pipe, or - for
+    /// stdin (arecord, sox, JACK tools). Replaces simulator audio and
+    /// enables audio-based string detection.
+    #[arg(long, conflicts_with = "audio_file")]
+    audio_pipe: Option<PathBuf>,
+
+    /// --audio-pipe sample format: s16, s32 or f32 (little-endian)
+    #[arg(long, default_value = "s16")]
+    audio_pipe_format: PcmFormat,
+
+    /// --audio-pipe sample rate in Hz
+    #[arg(long, default_value_t = 48000)]
+    audio_pipe_rate: u32,
+
+    /// --audio-pipe channel count (mixed down to mono; kept per channel
+    /// for --string-channels)
+    #[arg(long, default_value_t = 1)]
+    audio_pipe_channels: usize,
+
+    /// Hexaphonic pickup channel for each string, 1-based, strings 1–10 in
+    /// order; - leaves a string on mono detection (e.g. 1,2,3,4,5,6,-,-,-,-).
+    /// Applies to multichannel --audio-file and --audio-pipe input.
+    #[arg(long)]
+    string_channels: Option<StringChannels>,
+
     /// Run interactive per-string calibration and write calibration.json.
     /// Requires: --features calibration (or --features audio for WAV-only).
     #[cfg(feature = "calibration")]
@@ -118,6 +360,638 @@ struct Cli {
     /// Loaded automatically at startup if present; written by --calibrate.
     #[arg(long, default_value = "calibration.json")]
     calibration_file: PathBuf,
+
+    /// Run the guided pedal/lever/volume/bar sensor calibration against
+    /// --port and write --sensor-calibration-file.
+    /// Requires: --features hardware.
+    #[cfg(feature = "hardware")]
+    #[arg(long)]
+    calibrate_sensors: bool,
+
+    /// Positions captured per pedal/lever/volume channel during
+    /// --calibrate-sensors, counting rest and full travel. Above 2, the
+    /// intermediate captures fit a linearization curve.
+    #[cfg(feature = "hardware")]
+    #[arg(long, default_value_t = 2)]
+    sensor_cal_points: usize,
+
+    /// Linearization curve fitted from the intermediate captures: table
+    /// (through every point) or polyN (least-squares, degree N).
+    #[cfg(feature = "hardware")]
+    #[arg(long, default_value = "table")]
+    sensor_curve: CurveFit,
+
+    /// Path to the sensor (ADC) calibration JSON file.
+    /// Loaded automatically in hardware mode if present; written by
+    /// --calibrate-sensors.
+    #[arg(long, default_value = "sensor_calibration.json")]
+    sensor_calibration_file: PathBuf,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Play a simulator sequence offline with audio string detection on and
+    /// score the output against ground truth. Uses --demo, --audio-model,
+    /// --sensor-rate, the impairment options and --sensor-fault.
+    Benchmark {
+        /// Write the JSON report here.
+        #[arg(long, default_value = "benchmark.json")]
+        out: PathBuf,
+
+        /// Attack timing tolerance: a detected attack within ± this many ms
+        /// of a true one counts as a hit.
+        #[arg(long, default_value_t = 100)]
+        tolerance_ms: u32,
+    },
+
+    /// Emulate a Teensy: stream the simulator's sensor frames in the
+    /// firmware's binary format to a pseudo-terminal (printed at startup),
+    /// for `--simulate false --port <PTY>` in another process. Uses --demo,
+    /// --sensor-rate, --sensor-fault and the improv options.
+    Emulate {
+        /// Write to this file, FIFO or tty instead of creating a pty.
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Probability per frame of a burst of garbage bytes.
+        #[arg(long, default_value_t = 0.0)]
+        garbage: f32,
+
+        /// Probability per frame of a CRC error.
+        #[arg(long, default_value_t = 0.0)]
+        crc_errors: f32,
+
+        /// Probability per frame of a truncated frame.
+        #[arg(long, default_value_t = 0.0)]
+        partial_frames: f32,
+
+        /// Wire protocol: v1 (fixed 34-byte frames) or v2 (versioned,
+        /// variable channel count, channel descriptors).
+        #[arg(long, default_value = "v2")]
+        protocol: ProtocolVersion,
+
+        /// v2 only: append this many channels with no host role (e.g. a
+        /// pick sensor), to exercise variable channel counts.
+        #[arg(long, default_value_t = 0)]
+        extra_channels: u8,
+    },
+
+    /// Convert a recorded JSONL session to another format.
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExportFormat {
+    /// Type 1 Standard MIDI File: a track per string (notes from attacks
+    /// and releases, pitch bend from bar and pedal movement) and CC tracks
+    /// for the volume pedal, pedals and levers.
+    Midi {
+        /// JSONL session to read.
+        input: PathBuf,
+
+        /// Output file (default: the input with a .mid extension).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// MPE layout: strings on member channels 2–11 with the zone
+        /// configuration and controls on channel 1.
+        #[arg(long)]
+        mpe: bool,
+
+        /// Pitch-bend range in semitones, written to each string channel.
+        #[arg(long, default_value_t = 48)]
+        bend_range: u8,
+
+        /// Tempo for the bar grid (the performance keeps its real timing).
+        #[arg(long, default_value_t = 120.0)]
+        tempo: f64,
+    },
+
+    /// MusicXML score: a notation staff and a 10-line tab staff with
+    /// string/fret per note, pedal and lever markings, and slides for bar
+    /// moves, quantized to a grid at a tempo.
+    Musicxml {
+        /// JSONL session to read.
+        input: PathBuf,
+
+        /// Output file (default: the input with a .musicxml extension).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Tempo the performance is quantized against.
+        #[arg(long, default_value_t = 120.0)]
+        tempo: f64,
+
+        /// Quantization grid as a note value: 4, 8, 16 or 32.
+        #[arg(long, default_value_t = 16)]
+        grid: u32,
+    },
+
+    /// Plain-text steel tab: ten string lines, fret numbers with pedal and
+    /// lever suffixes (10A, 5B), slides as /12 and \8.
+    Tab {
+        /// JSONL session to read.
+        input: PathBuf,
+
+        /// Output file, or - for stdout (default: the input with a .txt
+        /// extension).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Length of one tab column in milliseconds.
+        #[arg(long, default_value_t = 125)]
+        column_ms: u32,
+
+        /// Columns per line.
+        #[arg(long, default_value_t = 32)]
+        width: usize,
+    },
+
+    /// CSV for analysis tools: a row per frame and a named column per
+    /// value (pedal_A, string3_hz, ...), streamed so any session size works.
+    Csv {
+        /// JSONL session to read.
+        input: PathBuf,
+
+        /// Output file, or - for stdout (default: the input with a .csv
+        /// extension).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Downsample to at most this many rows per second; attacks from
+        /// skipped frames are kept.
+        #[arg(long)]
+        rate: Option<f64>,
+
+        /// Start of the time range, in seconds of session time.
+        #[arg(long, default_value_t = 0.0)]
+        start: f64,
+
+        /// End of the time range, in seconds of session time.
+        #[arg(long)]
+        end: Option<f64>,
+
+        /// Columns to write, comma-separated, in order; * matches any run
+        /// of characters (e.g. timestamp_us,pedal_*,string3_*). Default: all.
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+    },
+}
+
+/// Link settings for `emulate`.
+struct EmulatedLink {
+    protocol: ProtocolVersion,
+    extra_channels: u8,
+    garbage: f32,
+    crc_errors: f32,
+    partial_frames: f32,
+}
+
+fn run_emulator(
+    cli: &Cli,
+    copedant: Copedant,
+    output: Option<&std::path::Path>,
+    link: EmulatedLink,
+    tab: Option<Tab>,
+) {
+    let (tx, rx) = bounded::<InputEvent>(1024);
+    let mut sim = simulator::Simulator::new(SessionClock::new(), tx, copedant, cli.sensor_rate)
+        .with_suppress_audio()
+        .with_improv(improv_from_cli(cli));
+    if let Some(tab) = tab {
+        sim = sim.with_tab(tab);
+    }
+    for (channel, fault) in cli.sensor_fault.iter() {
+        sim = sim.with_sensor_fault(*channel, *fault);
+    }
+    let demo = cli.demo.clone();
+    thread::Builder::new()
+        .name("simulator".into())
+        .spawn(move || sim.run(&demo))
+        .unwrap();
+
+    let mut map = ChannelMap::v1();
+    map.roles
+        .extend((0..link.extra_channels).map(ChannelRole::Other));
+    let emulator = teensy_emulator::TeensyEmulator::new(0x7ee5)
+        .with_protocol(link.protocol)
+        .with_channel_map(map)
+        .with_garbage(link.garbage)
+        .with_crc_errors(link.crc_errors)
+        .with_partial_frames(link.partial_frames);
+    let mut stream = teensy_emulator::EmulatedStream::new(rx, emulator);
+
+    let result = match output {
+        Some(path) => std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .and_then(|mut out| {
+                info!("Emulated Teensy writing to {:?}", path);
+                std::io::copy(&mut stream, &mut out)
+            }),
+        #[cfg(unix)]
+        None => teensy_emulator::open_pty().and_then(|mut pty| {
+            println!("Emulated Teensy on {}", pty.slave_path);
+            println!(
+                "Connect with: steel-capture --simulate false --port {}",
+                pty.slave_path
+            );
+            // Host commands arrive on the master side
+            let mut commands_in = pty.master.try_clone()?;
+            let mut commands = stream.command_input();
+            thread::Builder::new()
+                .name("emulator-commands".into())
+                .spawn(move || std::io::copy(&mut commands_in, &mut commands))
+                .unwrap();
+            std::io::copy(&mut stream, &mut pty.master)
+        }),
+        #[cfg(not(unix))]
+        None => Err(std::io::Error::other(
+            "pseudo-terminals need a Unix host; pass --output",
+        )),
+    };
+    if let Err(e) = result {
+        error!("Emulator stopped: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run_export(format: &ExportFormat) -> Result<(), String> {
+    match format {
+        ExportFormat::Midi {
+            input,
+            output,
+            mpe,
+            bend_range,
+            tempo,
+        } => {
+            let (name, mut reader) = open_session(input)?;
+            let mut skipped = 0usize;
+            let smf = SmfExport::new()
+                .with_mpe(*mpe)
+                .with_bend_range(*bend_range)
+                .with_tempo(*tempo)
+                .export(&name, session_frames(&mut reader, &mut skipped));
+            write_export(input, output, "mid", smf, skipped)
+        }
+        ExportFormat::Musicxml {
+            input,
+            output,
+            tempo,
+            grid,
+        } => {
+            if ![4, 8, 16, 32].contains(grid) {
+                return Err(format!("--grid must be 4, 8, 16 or 32, got {}", grid));
+            }
+            let (name, mut reader) = open_session(input)?;
+            let tuning = reader
+                .header
+                .open_strings()
+                .unwrap_or(geoff_derby_e9().open_strings);
+            let mut skipped = 0usize;
+            let notes = note_events(session_frames(&mut reader, &mut skipped));
+            let xml = MusicXmlExport::new(tuning)
+                .with_tempo(*tempo)
+                .with_grid(*grid)
+                .export(&name, &notes);
+            write_export(input, output, "musicxml", xml, skipped)
+        }
+        ExportFormat::Tab {
+            input,
+            output,
+            column_ms,
+            width,
+        } => {
+            let (_, mut reader) = open_session(input)?;
+            let mut skipped = 0usize;
+            let notes = note_events(session_frames(&mut reader, &mut skipped));
+            let renderer = TabRenderer::new()
+                .with_column_ms(*column_ms)
+                .with_width(*width);
+            let tab = text_tab::render(&notes, renderer);
+            write_export(input, output, "txt", tab, skipped)
+        }
+        ExportFormat::Csv {
+            input,
+            output,
+            rate,
+            start,
+            end,
+            columns,
+        } => {
+            if end.is_some_and(|end| end <= *start) {
+                return Err(format!("--end must be after --start ({}s)", start));
+            }
+            let (_, mut reader) = open_session(input)?;
+            let export = CsvExport::new()
+                .with_rate(rate.unwrap_or(0.0))
+                .with_range(*start, *end)
+                .with_columns(columns.clone());
+            let out = output
+                .clone()
+                .unwrap_or_else(|| input.with_extension("csv"));
+            let summary = if out.as_os_str() == "-" {
+                export.write(
+                    &mut reader,
+                    std::io::BufWriter::new(std::io::stdout().lock()),
+                )?
+            } else {
+                let file = std::fs::File::create(&out).map_err(|e| format!("{:?}: {}", out, e))?;
+                export.write(&mut reader, std::io::BufWriter::new(file))?
+            };
+            if summary.skipped > 0 {
+                warn!("Skipped {} malformed lines", summary.skipped);
+            }
+            if out.as_os_str() != "-" {
+                println!("Written to {:?} ({} rows)", out, summary.rows);
+            }
+            Ok(())
+        }
+    }
+}
+
+type SessionFile = SessionReader<std::io::BufReader<std::fs::File>>;
+
+/// Open a JSONL session for export; the name is the file stem.
+fn open_session(input: &std::path::Path) -> Result<(String, SessionFile), String> {
+    let file = std::fs::File::open(input).map_err(|e| format!("{:?}: {}", input, e))?;
+    let reader = SessionReader::open(std::io::BufReader::new(file))
+        .map_err(|e| format!("{:?}: {}", input, e))?;
+    let name = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok((name, reader))
+}
+
+/// A session's frames, streamed, counting malformed lines in `skipped`.
+fn session_frames<'a>(
+    reader: &'a mut SessionFile,
+    skipped: &'a mut usize,
+) -> impl Iterator<Item = CaptureFrame> + 'a {
+    std::iter::from_fn(move || loop {
+        match reader.next_frame()? {
+            Ok(frame) => return Some(frame),
+            Err(_) => *skipped += 1,
+        }
+    })
+}
+
+fn write_export(
+    input: &std::path::Path,
+    output: &Option<PathBuf>,
+    extension: &str,
+    contents: impl AsRef<[u8]>,
+    skipped: usize,
+) -> Result<(), String> {
+    if skipped > 0 {
+        warn!("Skipped {} malformed lines", skipped);
+    }
+    let out = output
+        .clone()
+        .unwrap_or_else(|| input.with_extension(extension));
+    if out.as_os_str() == "-" {
+        use std::io::Write;
+        return std::io::stdout()
+            .write_all(contents.as_ref())
+            .map_err(|e| e.to_string());
+    }
+    std::fs::write(&out, contents).map_err(|e| format!("{:?}: {}", out, e))?;
+    println!("Written to {:?}", out);
+    Ok(())
+}
+
+fn run_benchmark(
+    cli: &Cli,
+    copedant: Copedant,
+    out: &std::path::Path,
+    tolerance_ms: u32,
+    tab: Option<Tab>,
+) {
+    let calibration = Calibration::load(&cli.calibration_file);
+    let config = benchmark::BenchmarkConfig {
+        demo: cli.demo.clone(),
+        sensor_rate_hz: cli.sensor_rate,
+        audio_model: cli.audio_model,
+        impairments: impairments_from_cli(cli),
+        sensor_faults: cli.sensor_fault.clone(),
+        improv: improv_from_cli(cli),
+        tab,
+        tolerance_ms,
+        string_thresholds: calibration
+            .as_ref()
+            .map(|c| (c.onset_thresholds(), c.release_thresholds())),
+    };
+    info!("Benchmarking '{}' (offline)...", config.demo);
+    let report = benchmark::run(&config, copedant);
+
+    let a = &report.attacks;
+    println!(
+        "Attacks:  P={:.3} R={:.3} F1={:.3}  ({} true, {} detected, ±{} ms)",
+        a.precision, a.recall, a.f1, a.truth, a.detected, a.tolerance_ms
+    );
+    if let Some(lat) = &report.onset_latency_ms {
+        println!(
+            "Latency:  mean {:.1} ms  median {:.1} ms  p95 {:.1} ms",
+            lat.mean, lat.median, lat.p95
+        );
+    }
+    if let Some(err) = &report.bar.abs_error_cents {
+        println!(
+            "Bar:      mean {:.1}¢  p95 {:.1}¢  ({} missed, {} phantom frames)",
+            err.mean, err.p95, report.bar.missed_frames, report.bar.phantom_frames
+        );
+    }
+    let src = &report.bar_source;
+    println!(
+        "Source:   none {}  sensor {}  audio {}  fused {}",
+        src.none, src.sensor, src.audio, src.fused
+    );
+
+    let json = serde_json::to_string_pretty(&report).expect("report serializes");
+    match std::fs::write(out, json) {
+        Ok(_) => println!("Report written to {:?}", out),
+        Err(e) => {
+            error!("Failed to write {:?}: {}", out, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Collect the impairment options into one configuration.
+fn impairments_from_cli(cli: &Cli) -> Impairments {
+    let mut imp = Impairments::default();
+    if let Some(hz) = cli.hum {
+        imp = imp.with_hum(hz, cli.hum_level);
+    }
+    if let Some(snr) = cli.noise_snr {
+        imp = imp.with_noise(cli.noise_color, snr);
+    }
+    if let Some(level) = cli.buzz {
+        imp = imp.with_buzz(level);
+    }
+    if let Some(threshold) = cli.clip {
+        imp = imp.with_clip(threshold);
+    }
+    if let Some(level) = cli.sympathetic {
+        imp = imp.with_sympathetic(level);
+    }
+    imp
+}
+
+/// Collect the improv options into generator parameters.
+fn improv_from_cli(cli: &Cli) -> simulator::ImprovParams {
+    simulator::ImprovParams {
+        seed: cli.improv_seed,
+        beats: cli.improv_beats,
+        tempo_bpm: cli.improv_tempo,
+        key_fret: cli.improv_key_fret,
+        pedal_density: cli.improv_pedal_density,
+        vibrato_hz: cli.improv_vibrato_hz,
+        chords: cli.improv_chords.clone(),
+    }
+}
+
+/// The --tab file at the --tab-tempo and --tab-grid, if given.
+fn tab_from_cli(cli: &Cli) -> Result<Option<Tab>, String> {
+    if ![4, 8, 16, 32].contains(&cli.tab_grid) {
+        return Err(format!(
+            "--tab-grid must be 4, 8, 16 or 32, not {}",
+            cli.tab_grid
+        ));
+    }
+    let Some(path) = &cli.tab else {
+        return Ok(None);
+    };
+    let tab = Tab::load(path)?
+        .with_tempo(cli.tab_tempo)
+        .with_grid(cli.tab_grid);
+    Ok(Some(tab))
+}
+
+fn parse_seed(s: &str) -> Result<u64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|e| format!("invalid seed '{}': {}", s, e))
+}
+
+/// A control channel for one consumer, if OSC control is enabled.
+fn control_channel(
+    txs: &mut Vec<crossbeam_channel::Sender<ControlCommand>>,
+    enabled: bool,
+) -> Option<crossbeam_channel::Receiver<ControlCommand>> {
+    enabled.then(|| {
+        let (tx, rx) = unbounded();
+        txs.push(tx);
+        rx
+    })
+}
+
+/// Spawn a thread writing ground truth to `path`; returns the sender to hand
+/// to the simulator.
+fn spawn_truth_writer(
+    path: PathBuf,
+    demo: String,
+    improv: simulator::ImprovParams,
+) -> crossbeam_channel::Sender<simulator::GroundTruth> {
+    let (tx, rx) = unbounded();
+    thread::Builder::new()
+        .name("truth-writer".into())
+        .spawn(move || {
+            if let Err(e) = simulator::write_ground_truth(&path, rx, &demo, &improv) {
+                error!("Failed to write ground truth to {:?}: {}", path, e);
+            }
+        })
+        .unwrap();
+    tx
+}
+
+/// Start the simulator on the input channel, plus the --audio-file player.
+/// Real audio (a WAV file or --audio-pipe) replaces the synthesized audio.
+fn spawn_simulator(
+    cli: &Cli,
+    clock: &SessionClock,
+    input_tx: &crossbeam_channel::Sender<InputEvent>,
+    copedant: &Copedant,
+    tab: Option<Tab>,
+    control: Option<crossbeam_channel::Receiver<ControlCommand>>,
+) -> Vec<thread::JoinHandle<()>> {
+    let mut handles = Vec::new();
+    let sim_clock = clock.clone();
+    let sim_tx = input_tx.clone();
+    let sim_cop = copedant.clone();
+    let rate = cli.sensor_rate;
+    let demo = cli.demo.clone();
+    let suppress_audio = cli.audio_file.is_some() || cli.audio_pipe.is_some();
+    let audio_model = cli.audio_model;
+    let impairments = impairments_from_cli(cli);
+    if impairments.is_active() {
+        info!("Audio impairments: {:?}", impairments);
+    }
+    let sensor_faults = cli.sensor_fault.clone();
+    let improv = improv_from_cli(cli);
+    let truth_tx = cli
+        .ground_truth_out
+        .clone()
+        .map(|path| spawn_truth_writer(path, demo.clone(), improv.clone()));
+    handles.push(
+        thread::Builder::new()
+            .name("simulator".into())
+            .spawn(move || {
+                let mut sim = simulator::Simulator::new(sim_clock, sim_tx, sim_cop, rate)
+                    .with_audio_model(audio_model)
+                    .with_impairments(&impairments)
+                    .with_improv(improv);
+                if let Some(tab) = tab {
+                    sim = sim.with_tab(tab);
+                }
+                for (channel, fault) in sensor_faults {
+                    sim = sim.with_sensor_fault(channel, fault);
+                }
+                if let Some(tx) = truth_tx {
+                    sim = sim.with_ground_truth(tx);
+                }
+                if suppress_audio {
+                    sim = sim.with_suppress_audio();
+                }
+                if let Some(rx) = control {
+                    sim = sim.with_control(rx);
+                }
+                sim.run(&demo);
+            })
+            .unwrap(),
+    );
+    handles.extend(spawn_wav_player(cli, clock, input_tx));
+    handles
+}
+
+/// Stream the --audio-file, if given, alongside whichever sensor input runs.
+fn spawn_wav_player(
+    cli: &Cli,
+    clock: &SessionClock,
+    input_tx: &crossbeam_channel::Sender<InputEvent>,
+) -> Option<thread::JoinHandle<()>> {
+    let path = cli.audio_file.clone()?;
+    let wav_clock = clock.clone();
+    let wav_tx = input_tx.clone();
+    let impairments = impairments_from_cli(cli);
+    Some(
+        thread::Builder::new()
+            .name("wav-player".into())
+            .spawn(move || {
+                wav_player::WavPlayer::new(path, wav_tx, wav_clock)
+                    .with_impairments(&impairments)
+                    .run();
+            })
+            .unwrap(),
+    )
 }
 
 #[cfg(feature = "calibration")]
@@ -159,15 +1033,141 @@ fn finish_calibration(cal: steel_capture::calibration::Calibration, path: &std::
     }
 }
 
+/// Device settings from the command line, sent on every connect.
+#[cfg(feature = "hardware")]
+fn device_commands_from_cli(cli: &Cli) -> Vec<DeviceCommand> {
+    let mut commands = Vec::new();
+    commands.extend(cli.device_rate.map(DeviceCommand::SetRate));
+    commands.extend(cli.device_averaging.map(DeviceCommand::SetAveraging));
+    commands.extend(cli.device_resolution.map(DeviceCommand::SetResolution));
+    commands.extend(cli.identify.map(DeviceCommand::Identify));
+    commands
+}
+
+#[cfg(feature = "hardware")]
+fn run_sensor_calibration(cli: &Cli, clock: &SessionClock) {
+    let (tx, rx) = crossbeam_channel::unbounded::<InputEvent>();
+    let port = cli.port.clone();
+    let ser_clock = clock.clone();
+    // Steadier readings while capturing; the firmware restores its
+    // settings when the port closes
+    let mut commands = device_commands_from_cli(cli);
+    commands.push(DeviceCommand::CalibrationStream(true));
+    thread::Builder::new()
+        .name("cal-serial".into())
+        .spawn(move || {
+            serial_reader::SerialReader::new(port, tx, ser_clock)
+                .with_calibration(serial_reader::Calibration::identity())
+                .with_startup_commands(commands)
+                .run();
+        })
+        .unwrap();
+
+    let cal = steel_capture::sensor_calibrator::SensorCalibrator::new(rx)
+        .with_points(cli.sensor_cal_points)
+        .with_curve_fit(cli.sensor_curve)
+        .run();
+    match cal.save(&cli.sensor_calibration_file) {
+        Ok(_) => println!("Saved to {:?}", cli.sensor_calibration_file),
+        Err(e) => error!("Failed to save sensor calibration: {}", e),
+    }
+}
+
+/// Only one input can read stdin: MIDI bytes and PCM audio can't share it.
+fn check_stdin_inputs(cli: &Cli) -> Result<(), String> {
+    let stdin = |p: &Option<PathBuf>| p.as_deref() == Some(std::path::Path::new("-"));
+    if stdin(&cli.midi_input) && stdin(&cli.audio_pipe) {
+        return Err("--midi-input - and --audio-pipe - cannot both read stdin".into());
+    }
+    Ok(())
+}
+
+/// Audio detection is on when: hardware mode, UDP input, --detect-strings,
+/// or real audio (WAV file or pipe) is provided. MIDI input plays strings
+/// through its `string:N` note mappings, so it keeps the sensor's string
+/// state unless real audio or --detect-strings asks otherwise.
+fn use_audio_detection(cli: &Cli) -> bool {
+    let audio = cli.detect_strings || cli.audio_file.is_some() || cli.audio_pipe.is_some();
+    if cli.midi_input.is_some() && cli.udp_input.is_none() {
+        return audio;
+    }
+    audio || !cli.simulate || cli.udp_input.is_some()
+}
+
 fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
         .format_timestamp_millis()
         .init();
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
     let copedant = geoff_derby_e9();
     let clock = SessionClock::new();
 
+    if let Err(e) = check_stdin_inputs(&cli) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = improv_from_cli(&cli).validate() {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+    let tab = match tab_from_cli(&cli) {
+        Ok(tab) => tab,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(t) = &tab {
+        info!(
+            "Tab {:?}: {} columns with notes, {:.0} ms per column",
+            cli.tab.as_ref().unwrap(),
+            t.columns.len(),
+            t.column_ms()
+        );
+        cli.demo = "tab".into();
+    }
+    #[cfg(feature = "hardware")]
+    for command in device_commands_from_cli(&cli) {
+        if let Err(e) = command.validate() {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    match &cli.command {
+        Some(Command::Benchmark { out, tolerance_ms }) => {
+            run_benchmark(&cli, copedant, out, *tolerance_ms, tab);
+            return;
+        }
+        Some(Command::Emulate {
+            output,
+            garbage,
+            crc_errors,
+            partial_frames,
+            protocol,
+            extra_channels,
+        }) => {
+            let link = EmulatedLink {
+                protocol: *protocol,
+                extra_channels: *extra_channels,
+                garbage: *garbage,
+                crc_errors: *crc_errors,
+                partial_frames: *partial_frames,
+            };
+            run_emulator(&cli, copedant, output.as_deref(), link, tab);
+            return;
+        }
+        Some(Command::Export { format }) => {
+            if let Err(e) = run_export(format) {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
+
     // ─── Calibration mode (--features calibration) ────────────────
     #[cfg(feature = "calibration")]
     if cli.calibrate {
@@ -175,6 +1175,13 @@ fn main() {
         return;
     }
 
+    // ─── Sensor calibration mode (--features hardware) ──────────────
+    #[cfg(feature = "hardware")]
+    if cli.calibrate_sensors {
+        run_sensor_calibration(&cli, &clock);
+        return;
+    }
+
     // ─── Load calibration if present ────────────────────────────────
     let calibration = Calibration::load(&cli.calibration_file);
     if calibration.is_some() {
@@ -220,6 +1227,10 @@ fn main() {
     // Audio logging channel
     let (audio_log_tx, audio_log_rx) = unbounded::<AudioChunk>();
 
+    // Channels: OSC control → logger, coordinator, simulator
+    let mut control_txs = Vec::new();
+    let remote = cli.osc_control.is_some();
+
     let mut handles = Vec::new();
 
     // ─── Input trace relay (opt-in, for hardware debugging) ──────────
@@ -285,6 +1296,9 @@ fn main() {
                                     );
                                 }
                             }
+                            InputEvent::Status(status) => {
+                                let _ = writeln!(stderr.lock(), "STATUS  {}", status);
+                            }
                         }
                         // Forward to coordinator
                         if fwd_tx.send(event).is_err() {
@@ -318,13 +1332,43 @@ fn main() {
     if cli.osc {
         let (tx, rx) = bounded::<CaptureFrame>(1024);
         frame_txs.push(tx);
-        let target = cli.osc_target.clone();
+        let sender = osc_sender::OscSender::new(rx, cli.osc_target.clone())
+            .with_rate(cli.osc_rate)
+            .with_deadband(cli.osc_deadband, cli.osc_pitch_deadband);
         handles.push(
             thread::Builder::new()
                 .name("osc".into())
-                .spawn(move || {
-                    osc_sender::OscSender::new(rx, target).run();
-                })
+                .spawn(move || sender.run())
+                .unwrap(),
+        );
+    }
+
+    // ─── MPE output ──────────────────────────────────────────────────
+    if let Some(path) = cli.mpe_out.clone() {
+        let (tx, rx) = bounded::<CaptureFrame>(1024);
+        frame_txs.push(tx);
+        let encoder = MpeEncoder::new()
+            .with_bend_range(cli.mpe_bend_range)
+            .with_rate(cli.mpe_rate);
+        handles.push(
+            thread::Builder::new()
+                .name("mpe".into())
+                .spawn(move || MpeOutput::new(rx, path).with_encoder(encoder).run())
+                .unwrap(),
+        );
+    }
+
+    // ─── Live text tab ───────────────────────────────────────────────
+    if let Some(path) = cli.tab_out.clone() {
+        let (tx, rx) = bounded::<CaptureFrame>(1024);
+        frame_txs.push(tx);
+        let renderer = TabRenderer::new()
+            .with_column_ms(cli.tab_column_ms)
+            .with_width(cli.tab_width);
+        handles.push(
+            thread::Builder::new()
+                .name("tab".into())
+                .spawn(move || TabOutput::new(rx, path).with_renderer(renderer).run())
                 .unwrap(),
         );
     }
@@ -335,11 +1379,19 @@ fn main() {
         frame_txs.push(tx);
         let output_dir = cli.output_dir.clone();
         let cop = copedant.clone();
+        let control = control_channel(&mut control_txs, remote);
+        let recording = !(remote && cli.log_wait);
         handles.push(
             thread::Builder::new()
                 .name("logger".into())
                 .spawn(move || {
-                    data_logger::DataLogger::new(rx, audio_log_rx, &output_dir, cop).run();
+                    let mut logger =
+                        data_logger::DataLogger::new(rx, audio_log_rx, &output_dir, cop)
+                            .with_recording(recording);
+                    if let Some(rx) = control {
+                        logger = logger.with_control(rx);
+                    }
+                    logger.run();
                 })
                 .unwrap(),
         );
@@ -394,10 +1446,11 @@ fn main() {
     } else {
         None
     };
-    // Audio detection is on when: hardware mode, --detect-strings, or a WAV file is provided.
-    let use_audio_detect = cli.detect_strings || !cli.simulate || cli.audio_file.is_some();
+    let use_audio_detect = use_audio_detection(&cli);
     let cal_onset = calibration.as_ref().map(|c| c.onset_thresholds());
     let cal_release = calibration.as_ref().map(|c| c.release_thresholds());
+    let string_channels = cli.string_channels;
+    let coord_control = control_channel(&mut control_txs, remote);
     handles.push(
         thread::Builder::new()
             .name("coordinator".into())
@@ -407,46 +1460,69 @@ fn main() {
                 if let (Some(onset), Some(release)) = (cal_onset, cal_release) {
                     coord = coord.with_string_thresholds(onset, release);
                 }
+                if let Some(map) = string_channels {
+                    coord = coord.with_string_channels(map);
+                }
+                if let Some(rx) = coord_control {
+                    coord = coord.with_control(rx);
+                }
                 coord.run();
             })
             .unwrap(),
     );
 
     // ─── Input source ───────────────────────────────────────────────
-    if cli.simulate {
-        info!("Starting simulator...");
-        let sim_clock = clock.clone();
-        let sim_tx = input_tx.clone();
-        let sim_cop = copedant.clone();
-        let rate = cli.sensor_rate;
-        let suppress_audio = cli.audio_file.is_some();
+    if let Some(addr) = cli.udp_input.clone() {
+        let sensor_cal =
+            steel_capture::serial_protocol::Calibration::load(&cli.sensor_calibration_file)
+                .unwrap_or_default();
+        let input = match udp_input::UdpInput::bind(&addr, input_tx.clone(), clock.clone()) {
+            Ok(input) => input.with_calibration(sensor_cal),
+            Err(e) => {
+                error!("Failed to bind UDP input {}: {}", addr, e);
+                std::process::exit(1);
+            }
+        };
         handles.push(
             thread::Builder::new()
-                .name("simulator".into())
-                .spawn(move || {
-                    let demo = cli.demo.clone();
-                    let mut sim = simulator::Simulator::new(sim_clock, sim_tx, sim_cop, rate);
-                    if suppress_audio {
-                        sim = sim.with_suppress_audio();
-                    }
-                    sim.run(&demo);
-                })
+                .name("udp-input".into())
+                .spawn(move || input.run())
                 .unwrap(),
         );
-
-        // ─── WAV file audio input ────────────────────────────────────────
-        if let Some(path) = cli.audio_file.clone() {
-            let wav_clock = clock.clone();
-            let wav_tx = input_tx.clone();
-            handles.push(
-                thread::Builder::new()
-                    .name("wav-player".into())
-                    .spawn(move || {
-                        wav_player::WavPlayer::new(path, wav_tx, wav_clock).run();
-                    })
-                    .unwrap(),
-            );
-        }
+        handles.extend(spawn_wav_player(&cli, &clock, &input_tx));
+    } else if let Some(path) = cli.midi_input.clone() {
+        let reader: Box<dyn std::io::Read + Send> = if path.as_os_str() == "-" {
+            Box::new(std::io::stdin())
+        } else {
+            match std::fs::File::open(&path) {
+                Ok(file) => Box::new(file),
+                Err(e) => {
+                    error!("Failed to open MIDI input {:?}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+        };
+        let input = midi_input::MidiInput::new(reader, input_tx.clone(), clock.clone())
+            .with_map(midi_input::MidiMap::from_entries(cli.midi_map.clone()))
+            .with_rate(cli.sensor_rate);
+        handles.push(
+            thread::Builder::new()
+                .name("midi-input".into())
+                .spawn(move || input.run())
+                .unwrap(),
+        );
+        handles.extend(spawn_wav_player(&cli, &clock, &input_tx));
+    } else if cli.simulate {
+        info!("Starting simulator...");
+        let control = control_channel(&mut control_txs, remote);
+        handles.extend(spawn_simulator(
+            &cli,
+            &clock,
+            &input_tx,
+            &copedant,
+            tab.clone(),
+            control,
+        ));
     } else {
         #[cfg(feature = "hardware")]
         {
@@ -454,11 +1530,19 @@ fn main() {
             let ser_clock = clock.clone();
             let ser_tx = input_tx.clone();
             let port = cli.port.clone();
+            let sensor_cal =
+                serial_reader::Calibration::load(&cli.sensor_calibration_file).unwrap_or_default();
+            let policy = cli.disconnect_policy;
+            let commands = device_commands_from_cli(&cli);
             handles.push(
                 thread::Builder::new()
                     .name("serial".into())
                     .spawn(move || {
-                        serial_reader::SerialReader::new(port, ser_tx, ser_clock).run();
+                        serial_reader::SerialReader::new(port, ser_tx, ser_clock)
+                            .with_calibration(sensor_cal)
+                            .with_disconnect_policy(policy)
+                            .with_startup_commands(commands)
+                            .run();
                     })
                     .unwrap(),
             );
@@ -466,22 +1550,62 @@ fn main() {
         #[cfg(not(feature = "hardware"))]
         {
             error!("Hardware mode requires 'hardware' feature. Falling back to simulator.");
-            let sim_clock = clock.clone();
-            let sim_tx = input_tx.clone();
-            let sim_cop = copedant.clone();
-            let rate = cli.sensor_rate;
-            let demo = cli.demo.clone();
-            handles.push(
+            let control = control_channel(&mut control_txs, remote);
+            handles.extend(spawn_simulator(
+                &cli,
+                &clock,
+                &input_tx,
+                &copedant,
+                tab.clone(),
+                control,
+            ));
+        }
+    }
+
+    // ─── OSC control ─────────────────────────────────────────────────
+    if let Some(addr) = cli.osc_control.clone() {
+        if cli.log_wait && !cli.log_data {
+            warn!("--log-wait has no effect without --log-data");
+        }
+        match osc_control::OscControl::bind(&addr, control_txs) {
+            Ok(control) => handles.push(
                 thread::Builder::new()
-                    .name("simulator".into())
-                    .spawn(move || {
-                        simulator::Simulator::new(sim_clock, sim_tx, sim_cop, rate).run(&demo);
-                    })
+                    .name("osc-control".into())
+                    .spawn(move || control.run())
                     .unwrap(),
-            );
+            ),
+            Err(e) => {
+                error!("Failed to bind OSC control {}: {}", addr, e);
+                std::process::exit(1);
+            }
         }
     }
 
+    // ─── Piped audio input ──────────────────────────────────────────
+    if let Some(path) = cli.audio_pipe.clone() {
+        let reader: Box<dyn std::io::Read + Send> = if path.as_os_str() == "-" {
+            Box::new(std::io::stdin())
+        } else {
+            match std::fs::File::open(&path) {
+                Ok(file) => Box::new(file),
+                Err(e) => {
+                    error!("Failed to open audio pipe {:?}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+        };
+        let pipe = AudioPipe::new(reader, input_tx.clone(), clock.clone())
+            .with_format(cli.audio_pipe_format)
+            .with_sample_rate(cli.audio_pipe_rate)
+            .with_channels(cli.audio_pipe_channels);
+        handles.push(
+            thread::Builder::new()
+                .name("audio-pipe".into())
+                .spawn(move || pipe.run())
+                .unwrap(),
+        );
+    }
+
     // ─── Launch WebView on main thread (blocks until window closes) ──
     //
     // WKWebView (via wry/tao) MUST run on the main thread on macOS.
@@ -502,3 +1626,36 @@ fn main() {
         let _ = h.join();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_detection_flags() {
+        let detect = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["steel-capture"], args].concat()).unwrap();
+            use_audio_detection(&cli)
+        };
+        assert!(!detect(&[]));
+        assert!(detect(&["--simulate", "false"]));
+        assert!(detect(&["--udp-input", "0.0.0.0:9100"]));
+        assert!(detect(&["--audio-file", "take.wav"]));
+        // MIDI notes drive the strings, even with --simulate false
+        assert!(!detect(&["--midi-input", "-"]));
+        assert!(!detect(&["--simulate", "false", "--midi-input", "-"]));
+        assert!(detect(&["--midi-input", "-", "--detect-strings"]));
+        assert!(detect(&["--midi-input", "-", "--audio-file", "take.wav"]));
+        assert!(detect(&["--midi-input", "pedals.mid", "--audio-pipe", "-"]));
+    }
+
+    #[test]
+    fn test_conflicting_inputs_rejected() {
+        let parse = |args: &[&str]| Cli::try_parse_from([&["steel-capture"], args].concat());
+        assert!(parse(&["--midi-input", "-", "--udp-input", "0.0.0.0:9100"]).is_err());
+        let cli = parse(&["--midi-input", "-", "--audio-pipe", "-"]).unwrap();
+        assert!(check_stdin_inputs(&cli).is_err());
+        let cli = parse(&["--midi-input", "pedals.mid", "--audio-pipe", "-"]).unwrap();
+        assert!(check_stdin_inputs(&cli).is_ok());
+    }
+}
@@ -0,0 +1,529 @@
+//! MIDI controller input: expression pedals and foot controllers standing
+//! in for the steel's own sensors.
+//!
+//! Reads a raw MIDI byte stream (a file, FIFO or stdin — e.g. piped from
+//! `amidi -d` or `cat /dev/snd/midiC1D0`) and maps control changes,
+//! pitch-bend and notes onto pedals, levers, volume, bar position and
+//! strings. The result is ordinary `SensorFrame`s, sent at a fixed rate
+//! like the Teensy's, so attacks and pitches come out of the coordinator
+//! unchanged.
+
+use crate::bar_sensor::simulate_bar_readings;
+use crate::sensor_faults::SensorChannel;
+use crate::types::*;
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
+use log::{debug, info, warn};
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Bar position at full controller travel. Zero lifts the bar, so the
+/// usable span matches the hall array (frets 0–15).
+const BAR_MAX_FRET: f32 = 15.0;
+
+/// A channel message from the stream (channels 0–15).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    /// 14-bit, 8192 = centre
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+    ChannelPressure {
+        channel: u8,
+        value: u8,
+    },
+}
+
+/// Byte-at-a-time MIDI parser: running status, real-time bytes anywhere,
+/// SysEx skipped.
+#[derive(Debug, Default)]
+pub struct MidiParser {
+    status: Option<u8>,
+    data: Vec<u8>,
+    in_sysex: bool,
+}
+
+impl MidiParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one byte; returns a message once one is complete.
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        match byte {
+            // Real-time (clock, start, stop, active sensing): no effect on
+            // running status
+            0xF8..=0xFF => return None,
+            0xF0 => {
+                self.in_sysex = true;
+                self.status = None;
+                return None;
+            }
+            0xF7 => {
+                self.in_sysex = false;
+                return None;
+            }
+            // Other system common messages cancel running status
+            0xF1..=0xF6 => {
+                self.status = None;
+                self.in_sysex = false;
+                return None;
+            }
+            0x80..=0xEF => {
+                self.status = Some(byte);
+                self.in_sysex = false;
+                self.data.clear();
+                return None;
+            }
+            _ => {}
+        }
+        if self.in_sysex {
+            return None;
+        }
+        let status = self.status?;
+        self.data.push(byte);
+        let needed = match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            _ => 2,
+        };
+        if self.data.len() < needed {
+            return None;
+        }
+        let channel = status & 0x0F;
+        let (d0, d1) = (self.data[0], self.data.get(1).copied().unwrap_or(0));
+        self.data.clear();
+        match status & 0xF0 {
+            0x80 => Some(MidiMessage::NoteOff { channel, note: d0 }),
+            0x90 if d1 == 0 => Some(MidiMessage::NoteOff { channel, note: d0 }),
+            0x90 => Some(MidiMessage::NoteOn {
+                channel,
+                note: d0,
+                velocity: d1,
+            }),
+            0xB0 => Some(MidiMessage::ControlChange {
+                channel,
+                controller: d0,
+                value: d1,
+            }),
+            0xE0 => Some(MidiMessage::PitchBend {
+                channel,
+                value: d0 as u16 | (d1 as u16) << 7,
+            }),
+            0xD0 => Some(MidiMessage::ChannelPressure { channel, value: d0 }),
+            // Aftertouch, program change
+            _ => None,
+        }
+    }
+}
+
+/// Where a mapped value comes from (any MIDI channel).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiSource {
+    Cc(u8),
+    PitchBend,
+    /// Held = 1.0, released = 0.0
+    Note(u8),
+}
+
+/// Parses `cc<0-127>`, `bend` or `note<0-127>`.
+impl FromStr for MidiSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |rest: &str| rest.parse::<u8>().ok().filter(|&n| n < 128);
+        let source = if s == "bend" {
+            Some(Self::PitchBend)
+        } else if let Some(rest) = s.strip_prefix("cc") {
+            number(rest).map(Self::Cc)
+        } else if let Some(rest) = s.strip_prefix("note") {
+            number(rest).map(Self::Note)
+        } else {
+            None
+        };
+        source.ok_or_else(|| {
+            format!(
+                "unknown MIDI source '{}' (expected cc0-cc127, bend, note0-note127)",
+                s
+            )
+        })
+    }
+}
+
+impl fmt::Display for MidiSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cc(n) => write!(f, "cc{}", n),
+            Self::PitchBend => write!(f, "bend"),
+            Self::Note(n) => write!(f, "note{}", n),
+        }
+    }
+}
+
+/// What a MIDI source drives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiTarget {
+    /// A pedal, lever, the volume pedal or one raw bar hall sensor
+    Sensor(SensorChannel),
+    /// Bar position: 0 lifts the bar, otherwise frets 0–15 over the range
+    Bar,
+    /// String sounding (index 0–9) while the value is at least half
+    String(usize),
+}
+
+/// Parses `bar`, `string:1`–`string:10`, or a sensor channel (`pedal:A`,
+/// `lever:RKR`, `volume`, `bar:0`–`bar:3`).
+impl FromStr for MidiTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "bar" {
+            return Ok(Self::Bar);
+        }
+        if let Some(n) = s.strip_prefix("string:") {
+            return n
+                .parse::<usize>()
+                .ok()
+                .filter(|n| (1..=10).contains(n))
+                .map(|n| Self::String(n - 1))
+                .ok_or_else(|| format!("string '{}' out of range (string:1-string:10)", n));
+        }
+        s.parse()
+            .map(Self::Sensor)
+            .map_err(|e: String| format!("{}, bar or string:1-10", e))
+    }
+}
+
+impl fmt::Display for MidiTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sensor(channel) => write!(f, "{}", channel),
+            Self::Bar => write!(f, "bar"),
+            Self::String(i) => write!(f, "string:{}", i + 1),
+        }
+    }
+}
+
+/// Parse a `TARGET=SOURCE` spec, e.g. `pedal:A=cc20` or `bar=bend`.
+pub fn parse_midi_map_spec(s: &str) -> Result<(MidiTarget, MidiSource), String> {
+    let (target, source) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected TARGET=SOURCE, got '{}'", s))?;
+    Ok((target.parse()?, source.parse()?))
+}
+
+/// Which MIDI sources drive which targets. A source may drive several.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiMap {
+    pub entries: Vec<(MidiTarget, MidiSource)>,
+}
+
+impl Default for MidiMap {
+    /// Volume on CC 7, pedals A–C on CC 20–22, levers LKL–RKR on CC 23–27,
+    /// bar on CC 28 (an expression pedal at rest lifts it), strings 1–10 on
+    /// notes 60–69. `bar=bend` is opt-in: a bend wheel springs back to
+    /// centre, which would park the bar at fret 7.5.
+    fn default() -> Self {
+        let mut entries = vec![(MidiTarget::Sensor(SensorChannel::Volume), MidiSource::Cc(7))];
+        entries.extend((0..3).map(|i| {
+            (
+                MidiTarget::Sensor(SensorChannel::Pedal(i)),
+                MidiSource::Cc(20 + i as u8),
+            )
+        }));
+        entries.extend((0..5).map(|i| {
+            (
+                MidiTarget::Sensor(SensorChannel::Lever(i)),
+                MidiSource::Cc(23 + i as u8),
+            )
+        }));
+        entries.push((MidiTarget::Bar, MidiSource::Cc(28)));
+        entries.extend((0..10).map(|i| (MidiTarget::String(i), MidiSource::Note(60 + i as u8))));
+        Self { entries }
+    }
+}
+
+impl MidiMap {
+    /// Only the given mappings (an empty list means the default map).
+    pub fn from_entries(entries: Vec<(MidiTarget, MidiSource)>) -> Self {
+        if entries.is_empty() {
+            Self::default()
+        } else {
+            Self { entries }
+        }
+    }
+
+    /// Apply one message to `frame`; false if nothing is mapped to it.
+    pub fn apply(&self, message: &MidiMessage, frame: &mut SensorFrame) -> bool {
+        let (source, value) = match *message {
+            MidiMessage::ControlChange {
+                controller, value, ..
+            } => (MidiSource::Cc(controller), value as f32 / 127.0),
+            MidiMessage::PitchBend { value, .. } => (MidiSource::PitchBend, value as f32 / 16383.0),
+            MidiMessage::NoteOn { note, .. } => (MidiSource::Note(note), 1.0),
+            MidiMessage::NoteOff { note, .. } => (MidiSource::Note(note), 0.0),
+            MidiMessage::ChannelPressure { .. } => return false,
+        };
+        let mut mapped = false;
+        for (target, _) in self.entries.iter().filter(|(_, s)| *s == source) {
+            mapped = true;
+            match *target {
+                MidiTarget::Sensor(channel) => *channel.value_mut(frame) = value,
+                MidiTarget::Bar if value <= 0.0 => frame.bar_sensors = [0.0; 4],
+                MidiTarget::Bar => frame.bar_sensors = simulate_bar_readings(value * BAR_MAX_FRET),
+                MidiTarget::String(i) => frame.string_active[i] = value >= 0.5,
+            }
+        }
+        mapped
+    }
+}
+
+/// Turns a raw MIDI stream into `InputEvent::Sensor` frames.
+///
+/// Frames go out at `rate_hz` for as long as the stream is open, carrying
+/// the latest controller state; raw MIDI has no timestamps, so a file is
+/// applied as fast as it reads.
+pub struct MidiInput {
+    reader: Box<dyn Read + Send>,
+    tx: Sender<InputEvent>,
+    clock: SessionClock,
+    map: MidiMap,
+    rate_hz: u32,
+}
+
+impl MidiInput {
+    pub fn new(
+        reader: impl Read + Send + 'static,
+        tx: Sender<InputEvent>,
+        clock: SessionClock,
+    ) -> Self {
+        Self {
+            reader: Box::new(reader),
+            tx,
+            clock,
+            map: MidiMap::default(),
+            rate_hz: 1000,
+        }
+    }
+
+    pub fn with_map(mut self, map: MidiMap) -> Self {
+        self.map = map;
+        self
+    }
+
+    /// Frame rate (default 1000 Hz, the Teensy's).
+    pub fn with_rate(mut self, rate_hz: u32) -> Self {
+        self.rate_hz = rate_hz.max(1);
+        self
+    }
+
+    /// Run until the stream ends or the receiving side of the channel is
+    /// dropped. Blocks the calling thread.
+    pub fn run(self) {
+        let (msg_tx, msg_rx) = unbounded();
+        let mut reader = self.reader;
+        std::thread::Builder::new()
+            .name("midi-reader".into())
+            .spawn(move || {
+                let mut parser = MidiParser::new();
+                let mut buf = [0u8; 256];
+                loop {
+                    let n = match reader.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(n) => n,
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            warn!("MIDI read failed: {}", e);
+                            break;
+                        }
+                    };
+                    for message in buf[..n].iter().filter_map(|&b| parser.push(b)) {
+                        if msg_tx.send(message).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+
+        info!(
+            "MIDI input: {} mappings at {} Hz",
+            self.map.entries.len(),
+            self.rate_hz
+        );
+        let interval = Duration::from_secs_f64(1.0 / self.rate_hz as f64);
+        let mut frame = SensorFrame::at_rest(0);
+        let mut next_tick = Instant::now();
+        loop {
+            let ended = loop {
+                match msg_rx.recv_deadline(next_tick) {
+                    Ok(message) => {
+                        if !self.map.apply(&message, &mut frame) {
+                            debug!("Unmapped MIDI {:?}", message);
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => break false,
+                    Err(RecvTimeoutError::Disconnected) => break true,
+                }
+            };
+            frame.timestamp_us = self.clock.now_us();
+            if self.tx.send(InputEvent::Sensor(frame)).is_err() || ended {
+                break;
+            }
+            next_tick += interval;
+        }
+        info!("MIDI input ended");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<MidiMessage> {
+        let mut parser = MidiParser::new();
+        bytes.iter().filter_map(|&b| parser.push(b)).collect()
+    }
+
+    #[test]
+    fn test_parser_running_status_realtime_and_sysex() {
+        let messages = parse(&[
+            0xB0, 20, 64, // CC 20 = 64
+            21, 127,  // running status: CC 21 = 127
+            0xF8, // clock between messages
+            22, 0xFE, 5, // active sensing mid-message
+            0xF0, 0x7E, 0x01, 0xF7, // SysEx skipped
+            0x91, 60, 100, 60, 0, // note on, then running-status note off
+            0xC0, 3, // program change ignored
+            0xE0, 0x00, 0x40, // pitch bend centre
+        ]);
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::ControlChange {
+                    channel: 0,
+                    controller: 20,
+                    value: 64
+                },
+                MidiMessage::ControlChange {
+                    channel: 0,
+                    controller: 21,
+                    value: 127
+                },
+                MidiMessage::ControlChange {
+                    channel: 0,
+                    controller: 22,
+                    value: 5
+                },
+                MidiMessage::NoteOn {
+                    channel: 1,
+                    note: 60,
+                    velocity: 100
+                },
+                MidiMessage::NoteOff {
+                    channel: 1,
+                    note: 60
+                },
+                MidiMessage::PitchBend {
+                    channel: 0,
+                    value: 8192
+                },
+            ]
+        );
+        // Data bytes after SysEx need a fresh status byte
+        assert!(parse(&[0xB0, 1, 2, 0xF0, 0xF7, 3, 4]).len() == 1);
+    }
+
+    #[test]
+    fn test_map_specs() {
+        assert_eq!(
+            parse_midi_map_spec("pedal:B=cc21").unwrap(),
+            (
+                MidiTarget::Sensor(SensorChannel::Pedal(1)),
+                MidiSource::Cc(21)
+            )
+        );
+        assert_eq!(
+            parse_midi_map_spec("bar=bend").unwrap(),
+            (MidiTarget::Bar, MidiSource::PitchBend)
+        );
+        assert_eq!(
+            parse_midi_map_spec("string:10=note52").unwrap(),
+            (MidiTarget::String(9), MidiSource::Note(52))
+        );
+        for bad in [
+            "volume",
+            "volume=cc128",
+            "string:11=note1",
+            "knee=cc1",
+            "bar=mod",
+        ] {
+            assert!(parse_midi_map_spec(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(MidiMap::from_entries(vec![]), MidiMap::default());
+    }
+
+    #[test]
+    fn test_map_applies_to_frame() {
+        let map = MidiMap::default();
+        let mut frame = SensorFrame::at_rest(0);
+        let cc = |controller, value| MidiMessage::ControlChange {
+            channel: 3,
+            controller,
+            value,
+        };
+        assert!(map.apply(&cc(21, 127), &mut frame));
+        assert!(map.apply(&cc(27, 127), &mut frame));
+        assert!(map.apply(&cc(7, 0), &mut frame));
+        assert!(!map.apply(&cc(64, 127), &mut frame));
+        assert_eq!(frame.pedals, [0.0, 1.0, 0.0]);
+        assert_eq!(frame.knee_levers[4], 1.0);
+        assert_eq!(frame.volume, 0.0);
+
+        // Bar: a pedal at rest lifts it, the rest of its travel spans
+        // frets 0–15
+        map.apply(&cc(28, 127), &mut frame);
+        assert_eq!(frame.bar_sensors, simulate_bar_readings(15.0));
+        map.apply(&cc(28, 0), &mut frame);
+        assert_eq!(frame.bar_sensors, [0.0; 4]);
+        // The bend wheel drives it only when mapped
+        let bend = |value| MidiMessage::PitchBend { channel: 0, value };
+        assert!(!map.apply(&bend(8192), &mut frame));
+        let wheel = MidiMap::from_entries(vec![parse_midi_map_spec("bar=bend").unwrap()]);
+        wheel.apply(&bend(16383), &mut frame);
+        assert_eq!(frame.bar_sensors, simulate_bar_readings(15.0));
+
+        map.apply(
+            &MidiMessage::NoteOn {
+                channel: 0,
+                note: 62,
+                velocity: 90,
+            },
+            &mut frame,
+        );
+        assert!(frame.string_active[2]);
+        map.apply(
+            &MidiMessage::NoteOff {
+                channel: 0,
+                note: 62,
+            },
+            &mut frame,
+        );
+        assert!(!frame.string_active[2]);
+    }
+}
@@ -0,0 +1,382 @@
+//! MPE (MIDI Polyphonic Expression) output: one member channel per string.
+//!
+//! The lower zone is used, master channel 1 and members 2–11, so string 1
+//! plays on channel 2 and string 10 on channel 11. Each sounding string
+//! holds one note; bar slides, vibrato, pedals and levers move it with
+//! per-channel pitch bend, and the volume pedal is channel pressure. An
+//! attack (a pick, or a pedal/lever re-articulation) ends the string's note
+//! and starts a new one at the current pitch.
+//!
+//! The output is a plain MIDI byte stream with no running status, written
+//! to anything that accepts bytes: an ALSA raw MIDI device
+//! (`/dev/snd/midiC1D0`, or a `snd-virmidi` port), a FIFO, or a file.
+
+use crate::copedant::hz_to_midi;
+use crate::types::*;
+use crossbeam_channel::Receiver;
+use log::{error, info};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Master channel of the lower zone (channel 1).
+const MASTER: u8 = 0;
+/// Member channel of string 1 (channel 2); string `i` uses `FIRST_MEMBER + i`.
+const FIRST_MEMBER: u8 = 1;
+/// Velocity when the detector has no amplitude for a string yet
+/// (e.g. simulator ground truth ahead of the audio).
+const DEFAULT_VELOCITY: u8 = 100;
+
+/// The note a string is holding and the expression last sent for it.
+#[derive(Debug, Clone, Copy)]
+struct Voice {
+    note: u8,
+    bend: u16,
+    pressure: u8,
+}
+
+/// Turns `CaptureFrame`s into MPE messages.
+pub struct MpeEncoder {
+    bend_range: u8,
+    /// Minimum time between pitch-bend/pressure updates (µs)
+    interval_us: u64,
+    last_update_us: Option<u64>,
+    voices: [Option<Voice>; 10],
+}
+
+impl MpeEncoder {
+    /// ±48 semitones of member-channel pitch bend (the MPE default) and
+    /// expression updates at up to 500 Hz unless configured otherwise.
+    pub fn new() -> Self {
+        Self {
+            bend_range: 48,
+            interval_us: 2_000,
+            last_update_us: None,
+            voices: [None; 10],
+        }
+    }
+
+    /// Member-channel pitch-bend range in semitones (1–96). Slides wider
+    /// than this from the note's start are clamped.
+    pub fn with_bend_range(mut self, semitones: u8) -> Self {
+        self.bend_range = semitones.clamp(1, 96);
+        self
+    }
+
+    /// Pitch-bend and pressure updates per second; 0 updates on every
+    /// frame. Note on/off are never delayed.
+    pub fn with_rate(mut self, rate_hz: u32) -> Self {
+        self.interval_us = match rate_hz {
+            0 => 0,
+            hz => 1_000_000 / hz as u64,
+        };
+        self
+    }
+
+    /// Zone configuration: the MPE Configuration Message (RPN 6) on the
+    /// master channel, then the pitch-bend range (RPN 0) on every member.
+    pub fn init(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        rpn(&mut out, MASTER, 6, 10);
+        for i in 0..10 {
+            rpn(&mut out, FIRST_MEMBER + i, 0, self.bend_range);
+        }
+        out
+    }
+
+    /// Messages for one frame.
+    pub fn encode(&mut self, frame: &CaptureFrame) -> Vec<u8> {
+        let mut out = Vec::new();
+        let pressure = (frame.volume.clamp(0.0, 1.0) * 127.0).round() as u8;
+        let update = self
+            .last_update_us
+            .is_none_or(|last| frame.timestamp_us >= last + self.interval_us);
+        if update {
+            self.last_update_us = Some(frame.timestamp_us);
+        }
+
+        for i in 0..10 {
+            let ch = FIRST_MEMBER + i as u8;
+            let hz = frame.string_pitches_hz[i];
+            let active = frame.string_active[i] && hz > 0.0;
+
+            if let Some(voice) = self.voices[i] {
+                if !active || frame.attacks[i] {
+                    out.extend_from_slice(&[0x80 | ch, voice.note, 64]);
+                    self.voices[i] = None;
+                }
+            }
+            if !active {
+                continue;
+            }
+            let midi = hz_to_midi(hz);
+
+            match &mut self.voices[i] {
+                None => {
+                    let note = midi.round().clamp(0.0, 127.0) as u8;
+                    let bend = bend_value(self.bend_range, midi - note as f64);
+                    let velocity = match frame.string_amplitude[i] {
+                        a if a > 0.0 => (a.min(1.0) * 127.0).round().max(1.0) as u8,
+                        _ => DEFAULT_VELOCITY,
+                    };
+                    // Expression first, so the note starts at the right pitch
+                    pitch_bend(&mut out, ch, bend);
+                    out.extend_from_slice(&[0xD0 | ch, pressure]);
+                    out.extend_from_slice(&[0x90 | ch, note, velocity]);
+                    self.voices[i] = Some(Voice {
+                        note,
+                        bend,
+                        pressure,
+                    });
+                }
+                Some(voice) if update => {
+                    let bend = bend_value(self.bend_range, midi - voice.note as f64);
+                    if bend != voice.bend {
+                        pitch_bend(&mut out, ch, bend);
+                        voice.bend = bend;
+                    }
+                    if pressure != voice.pressure {
+                        out.extend_from_slice(&[0xD0 | ch, pressure]);
+                        voice.pressure = pressure;
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        out
+    }
+
+    /// Note-off for every held note.
+    pub fn all_notes_off(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (i, voice) in self.voices.iter_mut().enumerate() {
+            if let Some(v) = voice.take() {
+                out.extend_from_slice(&[0x80 | (FIRST_MEMBER + i as u8), v.note, 64]);
+            }
+        }
+        out
+    }
+}
+
+impl Default for MpeEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 14-bit pitch-bend value for an offset in semitones (8192 = none).
+fn bend_value(range: u8, semitones: f64) -> u16 {
+    (8192.0 + semitones / range as f64 * 8192.0)
+        .round()
+        .clamp(0.0, 16383.0) as u16
+}
+
+fn pitch_bend(out: &mut Vec<u8>, ch: u8, value: u16) {
+    out.extend_from_slice(&[0xE0 | ch, (value & 0x7F) as u8, (value >> 7) as u8]);
+}
+
+/// Set registered parameter `param` to `value` (coarse), then deselect.
+fn rpn(out: &mut Vec<u8>, ch: u8, param: u8, value: u8) {
+    let cc = 0xB0 | ch;
+    out.extend_from_slice(&[cc, 101, 0, cc, 100, param, cc, 6, value, cc, 38, 0]);
+    out.extend_from_slice(&[cc, 101, 127, cc, 100, 127]);
+}
+
+/// Writes the MPE stream for every frame to a device, FIFO or file.
+pub struct MpeOutput {
+    rx: Receiver<CaptureFrame>,
+    path: PathBuf,
+    encoder: MpeEncoder,
+}
+
+impl MpeOutput {
+    pub fn new(rx: Receiver<CaptureFrame>, path: PathBuf) -> Self {
+        Self {
+            rx,
+            path,
+            encoder: MpeEncoder::new(),
+        }
+    }
+
+    pub fn with_encoder(mut self, encoder: MpeEncoder) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Run the output loop. Blocks the calling thread; opening a FIFO
+    /// waits until something reads it.
+    pub fn run(mut self) {
+        let file = match open_output(&self.path) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Failed to open MPE output {:?}: {}", self.path, e);
+                return;
+            }
+        };
+        info!("MPE output → {:?}", self.path);
+        let mut out = BufWriter::new(file);
+        if let Err(e) = self.write_all(&mut out) {
+            error!("MPE output {:?}: {}", self.path, e);
+        }
+        info!("MPE output shutting down");
+    }
+
+    fn write_all(&mut self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.encoder.init())?;
+        out.flush()?;
+        for frame in self.rx.iter() {
+            let bytes = self.encoder.encode(&frame);
+            if !bytes.is_empty() {
+                out.write_all(&bytes)?;
+                out.flush()?;
+            }
+        }
+        out.write_all(&self.encoder.all_notes_off())?;
+        out.flush()
+    }
+}
+
+/// Devices and FIFOs are written as they are; anything else is created
+/// (or truncated) as a regular file.
+fn open_output(path: &Path) -> io::Result<File> {
+    match fs::metadata(path) {
+        Ok(meta) if !meta.is_file() => OpenOptions::new().write(true).open(path),
+        _ => File::create(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::copedant::midi_to_hz;
+    use crate::midi_input::{MidiMessage, MidiParser};
+
+    fn parse(bytes: &[u8]) -> Vec<MidiMessage> {
+        let mut parser = MidiParser::new();
+        bytes.iter().filter_map(|&b| parser.push(b)).collect()
+    }
+
+    #[test]
+    fn test_init_configures_zone_and_bend_range() {
+        let msgs = parse(&MpeEncoder::new().with_bend_range(24).init());
+        let cc = |channel, controller, value| MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        };
+        // MCM: 10 member channels on master channel 1
+        assert_eq!(msgs[..3], [cc(0, 101, 0), cc(0, 100, 6), cc(0, 6, 10)]);
+        // Bend range on channel 2 (string 1) through 11 (string 10)
+        assert!(msgs.contains(&cc(1, 100, 0)));
+        assert!(msgs.contains(&cc(10, 6, 24)));
+        assert!(!msgs.iter().any(|m| matches!(m,
+            MidiMessage::ControlChange { channel, .. } if *channel > 10)));
+    }
+
+    #[test]
+    fn test_note_bend_pressure_and_rearticulation() {
+        let mut enc = MpeEncoder::new().with_rate(0);
+        let mut f = CaptureFrame::at_rest(0);
+        // String 3 picked a quarter-tone above A4 at half volume
+        f.string_pitches_hz[2] = midi_to_hz(69.25);
+        f.string_active[2] = true;
+        f.attacks[2] = true;
+        f.string_amplitude[2] = 0.5;
+        f.volume = 0.5;
+        assert_eq!(
+            parse(&enc.encode(&f)),
+            [
+                MidiMessage::PitchBend {
+                    channel: 3,
+                    value: 8235
+                },
+                MidiMessage::ChannelPressure {
+                    channel: 3,
+                    value: 64
+                },
+                MidiMessage::NoteOn {
+                    channel: 3,
+                    note: 69,
+                    velocity: 64
+                },
+            ]
+        );
+
+        // Bar slides up two frets: the same note bends
+        f = CaptureFrame {
+            timestamp_us: 1000,
+            attacks: [false; 10],
+            ..f
+        };
+        f.string_pitches_hz[2] = midi_to_hz(71.25);
+        assert_eq!(
+            parse(&enc.encode(&f)),
+            [MidiMessage::PitchBend {
+                channel: 3,
+                value: 8576
+            }]
+        );
+        // Nothing changed: nothing sent
+        f.timestamp_us = 2000;
+        assert!(enc.encode(&f).is_empty());
+
+        // Re-articulation: note off, then a new note at the current pitch
+        f.timestamp_us = 3000;
+        f.attacks[2] = true;
+        let msgs = parse(&enc.encode(&f));
+        assert_eq!(
+            msgs[0],
+            MidiMessage::NoteOff {
+                channel: 3,
+                note: 69
+            }
+        );
+        assert_eq!(
+            msgs[3],
+            MidiMessage::NoteOn {
+                channel: 3,
+                note: 71,
+                velocity: 64
+            }
+        );
+
+        // Release, and the note ends
+        f.timestamp_us = 4000;
+        f.attacks[2] = false;
+        f.string_active[2] = false;
+        assert_eq!(
+            parse(&enc.encode(&f)),
+            [MidiMessage::NoteOff {
+                channel: 3,
+                note: 71
+            }]
+        );
+        assert!(enc.all_notes_off().is_empty());
+    }
+
+    #[test]
+    fn test_expression_rate_limit() {
+        let mut enc = MpeEncoder::new().with_rate(100);
+        let mut f = CaptureFrame {
+            volume: 1.0,
+            string_pitches_hz: [440.0; 10],
+            ..CaptureFrame::at_rest(0)
+        };
+        f.string_active[0] = true;
+        enc.encode(&f);
+        // Volume moves every millisecond; pressure follows at 100 Hz
+        let updates = (1..=30)
+            .filter(|&t| {
+                f.timestamp_us = t * 1000;
+                f.volume = 1.0 - t as f32 / 100.0;
+                !enc.encode(&f).is_empty()
+            })
+            .count();
+        assert_eq!(updates, 3);
+        // A pick is never delayed
+        f.timestamp_us = 31_000;
+        f.string_active[1] = true;
+        assert!(!enc.encode(&f).is_empty());
+        assert_eq!(parse(&enc.all_notes_off()).len(), 2);
+    }
+}
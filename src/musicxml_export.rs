@@ -0,0 +1,477 @@
+//! MusicXML export: a session's note events as an editable score.
+//!
+//! One part with two staves: standard notation (treble clef, octave down,
+//! as for guitar) and a 10-line steel tab staff tuned to the copedant's
+//! open strings, string 1 on the top line. Each tab note carries its string
+//! and fret. Pedals and levers held for a chord are written under the tab
+//! as words (`A`, `B LKL`), the way players mark `10A` on paper.
+//!
+//! Notes are quantized to a grid at a tempo: a chord starts on the grid
+//! step nearest its attack and lasts until the next chord or its longest
+//! release, whichever is sooner. Notes crossing a barline are tied. A note
+//! reached by sliding the bar (`NoteEvent::slid`) is joined to the one
+//! before it on that string with a slide on the tab and a glissando on the
+//! staff.
+
+use crate::note_events::NoteEvent;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const STEPS: [(&str, i8); 12] = [
+    ("C", 0),
+    ("C", 1),
+    ("D", 0),
+    ("D", 1),
+    ("E", 0),
+    ("F", 0),
+    ("F", 1),
+    ("G", 0),
+    ("G", 1),
+    ("A", 0),
+    ("A", 1),
+    ("B", 0),
+];
+
+/// Beats per measure (4/4).
+const BEATS: u64 = 4;
+
+/// Builds a MusicXML document from note events.
+pub struct MusicXmlExport {
+    tempo_bpm: f64,
+    /// Grid steps per quarter note; also the MusicXML divisions
+    divisions: u64,
+    open_strings: [f64; 10],
+}
+
+/// One chord or rest on the grid, in steps.
+struct Event<'a> {
+    start: u64,
+    len: u64,
+    notes: Vec<&'a NoteEvent>,
+}
+
+impl MusicXmlExport {
+    /// 120 BPM on a sixteenth-note grid. `open_strings` (MIDI notes, string
+    /// 1 first) tunes the tab staff.
+    pub fn new(open_strings: [f64; 10]) -> Self {
+        Self {
+            tempo_bpm: 120.0,
+            divisions: 4,
+            open_strings,
+        }
+    }
+
+    pub fn with_tempo(mut self, bpm: f64) -> Self {
+        self.tempo_bpm = bpm.clamp(10.0, 400.0);
+        self
+    }
+
+    /// Quantization grid as a note value: 4 = quarters, 8 = eighths,
+    /// 16 = sixteenths, 32 = thirty-seconds. Rounded up to a power of two.
+    pub fn with_grid(mut self, note_value: u32) -> Self {
+        self.divisions = note_value.clamp(4, 32).next_power_of_two() as u64 / 4;
+        self
+    }
+
+    /// The whole document. The score starts at the first note.
+    pub fn export(&self, title: &str, notes: &[NoteEvent]) -> String {
+        let events = self.quantize(notes);
+        let measure_len = BEATS * self.divisions;
+        let end = events.last().map_or(0, |e| e.start + e.len);
+        let measures = end.div_ceil(measure_len).max(1);
+
+        // Strings that slide into their next note
+        let slides_out: Vec<(usize, u64)> = notes
+            .iter()
+            .filter(|n| n.slid)
+            .map(|n| (n.string, n.start_us))
+            .collect();
+        let slides_on = |n: &NoteEvent| slides_out.contains(&(n.string, n.end_us));
+
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+        out.push_str(
+            "<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \
+             \"http://www.musicxml.org/dtds/partwise.dtd\">\n",
+        );
+        out.push_str("<score-partwise version=\"4.0\">\n");
+        let _ = writeln!(
+            out,
+            "  <work><work-title>{}</work-title></work>",
+            escape(title)
+        );
+        out.push_str(
+            "  <identification><encoding><software>steel-capture</software></encoding></identification>\n",
+        );
+        out.push_str("  <part-list>\n    <score-part id=\"P1\"><part-name>Pedal Steel</part-name></score-part>\n  </part-list>\n");
+        out.push_str("  <part id=\"P1\">\n");
+
+        for m in 0..measures {
+            let (from, to) = (m * measure_len, (m + 1) * measure_len);
+            let _ = writeln!(out, "    <measure number=\"{}\">", m + 1);
+            if m == 0 {
+                self.write_attributes(&mut out);
+            }
+            // Pieces of events inside this measure, rests filling the gaps
+            let mut pieces = Vec::new();
+            let mut t = from;
+            for e in &events {
+                let (s, f) = (e.start.max(from), (e.start + e.len).min(to));
+                if s >= f {
+                    continue;
+                }
+                if s > t {
+                    pieces.push((t, s - t, None));
+                }
+                pieces.push((s, f - s, Some(e)));
+                t = f;
+            }
+            if t < to {
+                pieces.push((t, to - t, None));
+            }
+
+            for staff in [1, 2] {
+                if staff == 2 {
+                    let _ = writeln!(
+                        out,
+                        "      <backup><duration>{}</duration></backup>",
+                        measure_len
+                    );
+                }
+                for &(start, len, event) in &pieces {
+                    let mut at = start;
+                    for (value, kind, dotted) in self.note_values(len) {
+                        let Some(e) = event else {
+                            self.write_rest(&mut out, staff, value, kind, dotted);
+                            continue;
+                        };
+                        let first = at == e.start;
+                        let last = at + value == e.start + e.len;
+                        if staff == 2 && first {
+                            write_changers(&mut out, e);
+                        }
+                        for (i, n) in e.notes.iter().enumerate() {
+                            let note = Piece {
+                                chord: i > 0,
+                                value,
+                                kind,
+                                dotted,
+                                tie_stop: !first,
+                                tie_start: !last,
+                                slide_stop: first && n.slid,
+                                slide_start: last && slides_on(n),
+                            };
+                            self.write_note(&mut out, staff, n, &note);
+                        }
+                        at += value;
+                    }
+                }
+            }
+            out.push_str("    </measure>\n");
+        }
+        out.push_str("  </part>\n</score-partwise>\n");
+        out
+    }
+
+    /// Group notes into chords on the grid, each lasting until the next
+    /// chord or its longest note's release.
+    fn quantize<'a>(&self, notes: &'a [NoteEvent]) -> Vec<Event<'a>> {
+        let Some(t0) = notes.iter().map(|n| n.start_us).min() else {
+            return Vec::new();
+        };
+        let step = |t: u64| {
+            let quarters = (t - t0) as f64 * self.tempo_bpm / 60e6;
+            (quarters * self.divisions as f64).round() as u64
+        };
+        let mut chords: BTreeMap<u64, Vec<&NoteEvent>> = BTreeMap::new();
+        for n in notes {
+            let chord = chords.entry(step(n.start_us)).or_default();
+            // Two picks of one string on the same step: keep the later
+            chord.retain(|c| c.string != n.string);
+            chord.push(n);
+        }
+        let starts: Vec<u64> = chords.keys().copied().collect();
+        chords
+            .into_iter()
+            .enumerate()
+            .map(|(i, (start, mut notes))| {
+                notes.sort_by_key(|n| n.string);
+                let release = notes.iter().map(|n| step(n.end_us)).max().unwrap_or(start);
+                let next = starts.get(i + 1).copied().unwrap_or(u64::MAX);
+                let end = release.max(start + 1).min(next);
+                Event {
+                    start,
+                    len: end - start,
+                    notes,
+                }
+            })
+            .collect()
+    }
+
+    /// Split a length in grid steps into written values, longest first:
+    /// (steps, MusicXML type, dotted).
+    fn note_values(&self, mut len: u64) -> Vec<(u64, &'static str, bool)> {
+        let q = self.divisions;
+        let plain = [
+            (4 * q, "whole"),
+            (2 * q, "half"),
+            (q, "quarter"),
+            (q / 2, "eighth"),
+            (q / 4, "16th"),
+            (q / 8, "32nd"),
+        ];
+        let mut values = Vec::new();
+        for (steps, kind) in plain.into_iter().filter(|(s, _)| *s > 0) {
+            let dotted = steps + steps / 2;
+            if steps % 2 == 0 && steps < 4 * q && len >= dotted {
+                values.push((dotted, kind, true));
+                len -= dotted;
+            }
+            while len >= steps {
+                values.push((steps, kind, false));
+                len -= steps;
+            }
+        }
+        values
+    }
+
+    fn write_attributes(&self, out: &mut String) {
+        out.push_str("      <attributes>\n");
+        let _ = writeln!(out, "        <divisions>{}</divisions>", self.divisions);
+        out.push_str("        <key><fifths>0</fifths></key>\n");
+        let _ = writeln!(
+            out,
+            "        <time><beats>{}</beats><beat-type>4</beat-type></time>",
+            BEATS
+        );
+        out.push_str("        <staves>2</staves>\n");
+        out.push_str("        <clef number=\"1\"><sign>G</sign><line>2</line><clef-octave-change>-1</clef-octave-change></clef>\n");
+        out.push_str("        <clef number=\"2\"><sign>TAB</sign><line>5</line></clef>\n");
+        out.push_str("        <staff-details number=\"2\">\n");
+        out.push_str("          <staff-lines>10</staff-lines>\n");
+        // Line 1 is the bottom line: string 10
+        for line in 1..=10 {
+            let midi = self.open_strings[10 - line].round() as i32;
+            let (step, alter, octave) = spell(midi);
+            let alter = if alter != 0 {
+                format!("<tuning-alter>{}</tuning-alter>", alter)
+            } else {
+                String::new()
+            };
+            let _ = writeln!(
+                out,
+                "          <staff-tuning line=\"{}\"><tuning-step>{}</tuning-step>{}<tuning-octave>{}</tuning-octave></staff-tuning>",
+                line, step, alter, octave
+            );
+        }
+        out.push_str("        </staff-details>\n");
+        out.push_str("      </attributes>\n");
+        let bpm = self.tempo_bpm.round();
+        let _ = writeln!(
+            out,
+            "      <direction placement=\"above\"><direction-type><metronome><beat-unit>quarter</beat-unit><per-minute>{}</per-minute></metronome></direction-type><staff>1</staff><sound tempo=\"{}\"/></direction>",
+            bpm, bpm
+        );
+    }
+
+    fn write_rest(&self, out: &mut String, staff: u8, value: u64, kind: &str, dotted: bool) {
+        let dot = if dotted { "<dot/>" } else { "" };
+        let _ = writeln!(
+            out,
+            "      <note><rest/><duration>{}</duration><voice>{}</voice><type>{}</type>{}<staff>{}</staff></note>",
+            value,
+            voice(staff),
+            kind,
+            dot,
+            staff
+        );
+    }
+
+    fn write_note(&self, out: &mut String, staff: u8, n: &NoteEvent, p: &Piece) {
+        let (step, alter, octave) = spell(n.midi as i32);
+        out.push_str("      <note>");
+        if p.chord {
+            out.push_str("<chord/>");
+        }
+        let _ = write!(out, "<pitch><step>{}</step>", step);
+        if alter != 0 {
+            let _ = write!(out, "<alter>{}</alter>", alter);
+        }
+        let _ = write!(
+            out,
+            "<octave>{}</octave></pitch><duration>{}</duration>",
+            octave, p.value
+        );
+        let mut notations = String::new();
+        for (on, kind) in [(p.tie_stop, "stop"), (p.tie_start, "start")] {
+            if on {
+                let _ = write!(out, "<tie type=\"{}\"/>", kind);
+                let _ = write!(notations, "<tied type=\"{}\"/>", kind);
+            }
+        }
+        let _ = write!(
+            out,
+            "<voice>{}</voice><type>{}</type>{}<staff>{}</staff>",
+            voice(staff),
+            p.kind,
+            if p.dotted { "<dot/>" } else { "" },
+            staff
+        );
+        // Slides are numbered by string so a sliding grip's lines pair up
+        let line = if staff == 1 { "glissando" } else { "slide" };
+        for (on, kind) in [(p.slide_stop, "stop"), (p.slide_start, "start")] {
+            if on {
+                let _ = write!(
+                    notations,
+                    "<{} type=\"{}\" number=\"{}\" line-type=\"solid\"/>",
+                    line,
+                    kind,
+                    n.string + 1
+                );
+            }
+        }
+        if staff == 2 {
+            let _ = write!(
+                notations,
+                "<technical><string>{}</string><fret>{}</fret></technical>",
+                n.string + 1,
+                n.fret
+            );
+        }
+        if !notations.is_empty() {
+            let _ = write!(out, "<notations>{}</notations>", notations);
+        }
+        out.push_str("</note>\n");
+    }
+}
+
+/// How one written piece of a chord is drawn.
+struct Piece {
+    chord: bool,
+    value: u64,
+    kind: &'static str,
+    dotted: bool,
+    tie_stop: bool,
+    tie_start: bool,
+    slide_stop: bool,
+    slide_start: bool,
+}
+
+/// Pedals and levers held in a chord, as words under the tab.
+fn write_changers(out: &mut String, e: &Event) {
+    let mut names: Vec<&str> = Vec::new();
+    for n in &e.notes {
+        for name in n.changers() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    if !names.is_empty() {
+        let _ = writeln!(
+            out,
+            "      <direction placement=\"below\"><direction-type><words>{}</words></direction-type><staff>2</staff></direction>",
+            names.join(" ")
+        );
+    }
+}
+
+/// Staff 1 is voice 1, the tab staff voice 5, as notation programs write
+/// a guitar's linked staves.
+fn voice(staff: u8) -> u8 {
+    if staff == 1 {
+        1
+    } else {
+        5
+    }
+}
+
+/// MIDI note to (step, alter, octave), spelled with sharps.
+fn spell(midi: i32) -> (&'static str, i8, i32) {
+    let (step, alter) = STEPS[midi.rem_euclid(12) as usize];
+    (step, alter, midi.div_euclid(12) - 1)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::copedant::geoff_derby_e9;
+
+    fn note(string: usize, start_ms: u64, end_ms: u64, midi: u8, fret: u8) -> NoteEvent {
+        NoteEvent {
+            string,
+            start_us: 1_000_000 + start_ms * 1000,
+            end_us: 1_000_000 + end_ms * 1000,
+            midi,
+            fret,
+            pedals: [false; 3],
+            levers: [false; 5],
+            slid: false,
+            amplitude: 0.5,
+        }
+    }
+
+    fn export(notes: &[NoteEvent]) -> String {
+        MusicXmlExport::new(geoff_derby_e9().open_strings).export("t", notes)
+    }
+
+    #[test]
+    fn test_quantize_rests_and_ties() {
+        // 120 BPM sixteenths: one step is 125 ms. A dotted half (with 10 ms
+        // of slop), an eighth rest, then a note across the barline.
+        let notes = [note(3, 0, 1510, 74, 10), note(3, 1750, 2750, 74, 10)];
+        let xml = export(&notes);
+        let staff1: Vec<&str> = xml
+            .lines()
+            .filter(|l| l.contains("<note>") && l.contains("<staff>1</staff>"))
+            .collect();
+        assert_eq!(staff1.len(), 6, "{}", xml);
+        assert!(staff1[0].contains("<duration>12</duration>") && staff1[0].contains("<dot/>"));
+        assert!(staff1[1].contains("<rest/><duration>2</duration>"));
+        // Eighth tied to a dotted quarter, then a half and eighth rest
+        assert!(staff1[2].contains("<duration>2</duration><tie type=\"start\"/>"));
+        assert!(staff1[3].contains("<duration>6</duration><tie type=\"stop\"/>"));
+        assert!(staff1[3].contains("<type>quarter</type><dot/>"));
+        assert!(staff1[4].contains("<rest/><duration>8</duration>"));
+        assert!(staff1[5].contains("<rest/><duration>2</duration>"));
+        assert!(staff1[0].contains("<step>D</step><octave>5</octave>"));
+        assert_eq!(xml.matches("<measure number=").count(), 2);
+        // Tab staff tuned to the copedant: F#4 on the top line
+        assert!(xml.contains("<staff-lines>10</staff-lines>"));
+        assert!(xml.contains("<staff-tuning line=\"10\"><tuning-step>F</tuning-step><tuning-alter>1</tuning-alter><tuning-octave>4</tuning-octave>"));
+
+        let grid8 = MusicXmlExport::new(geoff_derby_e9().open_strings).with_grid(8);
+        assert!(grid8
+            .export("t", &notes)
+            .contains("<divisions>2</divisions>"));
+    }
+
+    #[test]
+    fn test_tab_changers_and_slides() {
+        // 10A on strings 3+4, then slide to 12 on string 3
+        let mut a = note(2, 0, 500, 73, 10);
+        let mut b = note(3, 0, 1000, 71, 10);
+        a.pedals[0] = true;
+        b.levers[0] = true;
+        let mut slid = note(2, 500, 1000, 75, 12);
+        slid.slid = true;
+        let xml = export(&[a, b, slid]);
+
+        assert!(xml.contains("<words>A LKL</words>"));
+        assert!(xml.contains("<chord/>"));
+        assert!(xml.contains("<technical><string>3</string><fret>10</fret></technical>"));
+        assert!(xml.contains("<technical><string>3</string><fret>12</fret></technical>"));
+        assert!(xml.contains("<slide type=\"start\" number=\"3\" line-type=\"solid\"/>"));
+        assert!(xml.contains("<slide type=\"stop\" number=\"3\" line-type=\"solid\"/>"));
+        assert!(xml.contains("<glissando type=\"start\" number=\"3\""));
+        assert!(xml.contains("<glissando type=\"stop\" number=\"3\""));
+        assert!(!xml.contains("number=\"4\" line-type"));
+    }
+}
@@ -0,0 +1,245 @@
+//! Note events: a session's frames reduced to the notes a player would
+//! write down, one per string per pick or bar move.
+//!
+//! A note starts on an attack and ends at the string's release or its next
+//! attack. Sliding the bar to a new fret while a string rings ends the note
+//! where the bar arrives and starts a `slid` one at the new fret, so score
+//! and tab exports can draw `10/12` as two notes joined by a slide rather
+//! than a run through every fret in between. A slide needs the bar at least
+//! `SLIDE_FRETS` away from the note's fret and then resting near one fret
+//! for `SETTLE_US`; vibrato and bar drift stay inside one note.
+
+use crate::copedant::hz_to_midi;
+use crate::types::*;
+
+/// Bar movement (in frets) away from a note's fret that counts as a slide.
+const SLIDE_FRETS: f32 = 0.75;
+/// Time the bar must stay at a new fret before the slide is written there,
+/// so a slid note is reported up to this late.
+pub const SETTLE_US: u64 = 150_000;
+
+/// One note on one string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteEvent {
+    /// String index, 0 = string 1
+    pub string: usize,
+    pub start_us: u64,
+    pub end_us: u64,
+    /// Nearest MIDI note at the start
+    pub midi: u8,
+    /// Bar fret at the start, rounded; 0 with the bar lifted
+    pub fret: u8,
+    /// Pedals A–C engaged past halfway at the start
+    pub pedals: [bool; 3],
+    /// Levers LKL–RKR engaged past halfway at the start
+    pub levers: [bool; 5],
+    /// Reached by sliding the bar from the previous note on this string
+    /// rather than picked
+    pub slid: bool,
+    pub amplitude: f32,
+}
+
+impl NoteEvent {
+    /// Names of the engaged pedals then levers, e.g. `["A", "LKL"]`.
+    pub fn changers(&self) -> Vec<&'static str> {
+        let pedals = PEDAL_NAMES.iter().zip(self.pedals);
+        let levers = LEVER_NAMES.iter().zip(self.levers);
+        pedals
+            .chain(levers)
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+/// Builds note events frame by frame, for offline export or a live
+/// consumer.
+pub struct NoteTracker {
+    sounding: [Option<NoteEvent>; 10],
+    /// Mid-slide: the fret the bar is at and since when
+    arrival: [Option<(u8, u64)>; 10],
+}
+
+impl NoteTracker {
+    pub fn new() -> Self {
+        Self {
+            sounding: Default::default(),
+            arrival: [None; 10],
+        }
+    }
+
+    /// Feed one frame. Returns the notes that ended on it.
+    pub fn push(&mut self, frame: &CaptureFrame) -> Vec<NoteEvent> {
+        let t = frame.timestamp_us;
+        let mut ended = Vec::new();
+        for si in 0..10 {
+            let active = frame.string_active[si];
+            if let Some(note) = &self.sounding[si] {
+                if !active || frame.attacks[si] {
+                    ended.extend(self.end(si, t));
+                } else if let Some(bar) = frame.bar_position {
+                    let fret = fret_of(Some(bar));
+                    match self.arrival[si] {
+                        None if (bar - note.fret as f32).abs() >= SLIDE_FRETS => {
+                            self.arrival[si] = Some((fret, t));
+                        }
+                        Some(_) if fret == note.fret => self.arrival[si] = None,
+                        Some((at, _)) if at != fret => self.arrival[si] = Some((fret, t)),
+                        Some((_, since)) if t.saturating_sub(since) >= SETTLE_US => {
+                            ended.extend(self.end(si, since));
+                            self.start(si, frame, since, true);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if active && frame.attacks[si] {
+                self.start(si, frame, t, false);
+            }
+        }
+        ended
+    }
+
+    /// Notes still sounding, with `end_us` not yet known.
+    pub fn sounding(&self) -> impl Iterator<Item = &NoteEvent> {
+        self.sounding.iter().flatten()
+    }
+
+    /// End every note still sounding at `t_us`.
+    pub fn finish(&mut self, t_us: u64) -> Vec<NoteEvent> {
+        (0..10).filter_map(|si| self.end(si, t_us)).collect()
+    }
+
+    fn start(&mut self, si: usize, frame: &CaptureFrame, t_us: u64, slid: bool) {
+        let midi = hz_to_midi(frame.string_pitches_hz[si]).round();
+        self.arrival[si] = None;
+        self.sounding[si] = Some(NoteEvent {
+            string: si,
+            start_us: t_us,
+            end_us: t_us,
+            midi: midi.clamp(0.0, 127.0) as u8,
+            fret: fret_of(frame.bar_position),
+            pedals: frame.pedals.map(|p| p > 0.5),
+            levers: frame.knee_levers.map(|l| l > 0.5),
+            slid,
+            amplitude: frame.string_amplitude[si],
+        });
+    }
+
+    fn end(&mut self, si: usize, t_us: u64) -> Option<NoteEvent> {
+        self.arrival[si] = None;
+        self.sounding[si].take().map(|mut note| {
+            note.end_us = t_us;
+            note
+        })
+    }
+}
+
+impl Default for NoteTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn fret_of(bar: Option<f32>) -> u8 {
+    bar.map_or(0, |b| b.round().clamp(0.0, 24.0) as u8)
+}
+
+/// All note events of a session, ordered by start time then string.
+pub fn note_events(frames: impl IntoIterator<Item = CaptureFrame>) -> Vec<NoteEvent> {
+    let mut tracker = NoteTracker::new();
+    let mut notes = Vec::new();
+    let mut last_t = 0;
+    for frame in frames {
+        last_t = frame.timestamp_us;
+        notes.extend(tracker.push(&frame));
+    }
+    notes.extend(tracker.finish(last_t));
+    notes.sort_by_key(|n| (n.start_us, n.string));
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::copedant::midi_to_hz;
+
+    fn frame(t_ms: u64, bar: Option<f32>, active: &[usize], attacks: &[usize]) -> CaptureFrame {
+        let mut f = CaptureFrame {
+            bar_position: bar,
+            string_pitches_hz: [midi_to_hz(64.0 + bar.unwrap_or(0.0) as f64); 10],
+            string_amplitude: [0.5; 10],
+            ..CaptureFrame::at_rest(t_ms * 1000)
+        };
+        for &i in active {
+            f.string_active[i] = true;
+        }
+        for &i in attacks {
+            f.attacks[i] = true;
+        }
+        f
+    }
+
+    #[test]
+    fn test_attacks_releases_and_slides() {
+        let mut frames = vec![frame(0, Some(10.0), &[2, 3], &[2, 3])];
+        // Vibrato stays in the note
+        frames.push(frame(100, Some(10.4), &[2, 3], &[]));
+        // String 4 is picked again; string 3 keeps ringing
+        frames.push(frame(200, Some(10.0), &[2, 3], &[3]));
+        // Bar slides to 12 under both strings and rests there
+        frames.push(frame(300, Some(11.0), &[2, 3], &[]));
+        for t in [350, 450, 550] {
+            frames.push(frame(t, Some(12.0), &[2, 3], &[]));
+        }
+        // String 3 released, then string 4 at the end of the session
+        frames.push(frame(650, Some(12.0), &[3], &[]));
+        frames.push(frame(700, Some(12.0), &[3], &[]));
+
+        let notes = note_events(frames);
+        let summary: Vec<_> = notes
+            .iter()
+            .map(|n| (n.string, n.start_us / 1000, n.end_us / 1000, n.fret, n.slid))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (2, 0, 350, 10, false),
+                (3, 0, 200, 10, false),
+                (3, 200, 350, 10, false),
+                (2, 350, 650, 12, true),
+                (3, 350, 700, 12, true),
+            ]
+        );
+        assert_eq!(notes[0].midi, 74);
+        assert_eq!(notes[3].midi, 76);
+    }
+
+    #[test]
+    fn test_timestamps_stepping_back() {
+        // Bar arrives at 12, then a frame from the past: no slide yet
+        let frames = vec![
+            frame(0, Some(10.0), &[2], &[2]),
+            frame(300, Some(12.0), &[2], &[]),
+            frame(200, Some(12.0), &[2], &[]),
+            frame(500, Some(12.0), &[2], &[]),
+        ];
+        let notes = note_events(frames);
+        let summary: Vec<_> = notes
+            .iter()
+            .map(|n| (n.start_us / 1000, n.end_us / 1000, n.fret))
+            .collect();
+        assert_eq!(summary, [(0, 300, 10), (300, 500, 12)]);
+    }
+
+    #[test]
+    fn test_changers_and_open_strings() {
+        let mut f = frame(0, None, &[4], &[4]);
+        f.pedals = [1.0, 0.6, 0.2];
+        f.knee_levers = [0.9, 0.0, 0.0, 0.0, 1.0];
+        let notes = note_events([f]);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].fret, 0);
+        assert_eq!(notes[0].changers(), ["A", "B", "LKL", "RKR"]);
+    }
+}
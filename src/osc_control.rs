@@ -0,0 +1,181 @@
+//! Remote control over OSC, so a DAW or show controller can drive a
+//! running capture from its transport:
+//!
+//! ```text
+//! /steel/cmd/record  s "start"|"stop"  (or i/f/T/F: nonzero/true = start)
+//! /steel/cmd/marker  s label           (no argument: "marker")
+//! /steel/cmd/demo    s basic|e9|improv|faults
+//! /steel/cmd/reset                     clear detector and bar state
+//! ```
+//!
+//! Messages may arrive singly or in bundles. Commands are broadcast as
+//! `ControlCommand`s to every subscriber.
+
+use crate::types::ControlCommand;
+use crossbeam_channel::Sender;
+use log::{error, info, warn};
+use rosc::{OscMessage, OscPacket, OscType};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Listens for `/steel/cmd/...` messages on a UDP socket.
+pub struct OscControl {
+    socket: UdpSocket,
+    txs: Vec<Sender<ControlCommand>>,
+}
+
+impl OscControl {
+    /// Bind to `addr` (e.g. `0.0.0.0:9001`; port 0 picks a free one).
+    pub fn bind(addr: &str, txs: Vec<Sender<ControlCommand>>) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr)?,
+            txs,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Run the receive loop. Blocks the calling thread until every
+    /// subscriber has gone.
+    pub fn run(self) {
+        match self.socket.local_addr() {
+            Ok(addr) => info!("OSC control listening on {}", addr),
+            Err(e) => error!("OSC control: {}", e),
+        }
+        let mut buf = [0u8; rosc::decoder::MTU];
+        loop {
+            let (n, from) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) => {
+                    error!("OSC control receive failed: {}", e);
+                    return;
+                }
+            };
+            let packet = match rosc::decoder::decode_udp(&buf[..n]) {
+                Ok((_, packet)) => packet,
+                Err(e) => {
+                    warn!("Bad OSC control datagram from {}: {:?}", from, e);
+                    continue;
+                }
+            };
+            let mut msgs = Vec::new();
+            flatten(packet, &mut msgs);
+            for msg in msgs {
+                let cmd = match parse_command(&msg) {
+                    Ok(cmd) => cmd,
+                    Err(e) => {
+                        warn!("OSC control from {}: {}", from, e);
+                        continue;
+                    }
+                };
+                info!("OSC control from {}: {}", from, cmd);
+                let delivered = self
+                    .txs
+                    .iter()
+                    .filter(|tx| tx.send(cmd.clone()).is_ok())
+                    .count();
+                if delivered == 0 {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn flatten(packet: OscPacket, out: &mut Vec<OscMessage>) {
+    match packet {
+        OscPacket::Message(msg) => out.push(msg),
+        OscPacket::Bundle(bundle) => {
+            for p in bundle.content {
+                flatten(p, out);
+            }
+        }
+    }
+}
+
+/// Interpret one `/steel/cmd/...` message.
+pub fn parse_command(msg: &OscMessage) -> Result<ControlCommand, String> {
+    let text = match msg.args.first() {
+        Some(OscType::String(s)) => Some(s.as_str()),
+        _ => None,
+    };
+    match msg.addr.as_str() {
+        "/steel/cmd/record" => {
+            let start = match msg.args.first() {
+                Some(OscType::String(s)) => match s.to_ascii_lowercase().as_str() {
+                    "start" | "on" | "1" => true,
+                    "stop" | "off" | "0" => false,
+                    _ => return Err(format!("record: expected start or stop, got '{}'", s)),
+                },
+                Some(OscType::Int(v)) => *v != 0,
+                Some(OscType::Float(v)) => *v != 0.0,
+                Some(OscType::Bool(v)) => *v,
+                _ => return Err("record: expected start or stop".to_string()),
+            };
+            Ok(ControlCommand::Record(start))
+        }
+        "/steel/cmd/marker" => Ok(ControlCommand::Marker(text.unwrap_or("marker").to_string())),
+        "/steel/cmd/demo" => text
+            .map(|name| ControlCommand::Demo(name.to_string()))
+            .ok_or_else(|| "demo: expected a demo name".to_string()),
+        "/steel/cmd/reset" => Ok(ControlCommand::Reset),
+        other => Err(format!("unknown command {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(addr: &str, args: Vec<OscType>) -> OscMessage {
+        OscMessage {
+            addr: addr.to_string(),
+            args,
+        }
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let s = |v: &str| OscType::String(v.to_string());
+        let parse = |addr, args| parse_command(&msg(addr, args));
+
+        assert_eq!(
+            parse("/steel/cmd/record", vec![s("start")]),
+            Ok(ControlCommand::Record(true))
+        );
+        assert_eq!(
+            parse("/steel/cmd/record", vec![s("STOP")]),
+            Ok(ControlCommand::Record(false))
+        );
+        // Transport state as a number or bool
+        assert_eq!(
+            parse("/steel/cmd/record", vec![OscType::Int(1)]),
+            Ok(ControlCommand::Record(true))
+        );
+        assert_eq!(
+            parse("/steel/cmd/record", vec![OscType::Float(0.0)]),
+            Ok(ControlCommand::Record(false))
+        );
+        assert!(parse("/steel/cmd/record", vec![s("pause")]).is_err());
+        assert!(parse("/steel/cmd/record", vec![]).is_err());
+
+        assert_eq!(
+            parse("/steel/cmd/marker", vec![s("verse 2")]),
+            Ok(ControlCommand::Marker("verse 2".into()))
+        );
+        assert_eq!(
+            parse("/steel/cmd/marker", vec![]),
+            Ok(ControlCommand::Marker("marker".into()))
+        );
+        assert_eq!(
+            parse("/steel/cmd/demo", vec![s("improv")]),
+            Ok(ControlCommand::Demo("improv".into()))
+        );
+        assert!(parse("/steel/cmd/demo", vec![]).is_err());
+        assert_eq!(parse("/steel/cmd/reset", vec![]), Ok(ControlCommand::Reset));
+        assert!(parse("/steel/cmd/explode", vec![]).is_err());
+        assert!(parse("/steel/pedal/a", vec![OscType::Float(1.0)]).is_err());
+    }
+}
@@ -1,17 +1,89 @@
 use crate::types::*;
 use crossbeam_channel::Receiver;
 use log::{debug, error, info};
-use rosc::{OscMessage, OscPacket, OscType};
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+use std::collections::HashMap;
+use std::fmt;
 use std::net::UdpSocket;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
+/// Every value is resent at least this often, so a receiver that starts
+/// late or drops a packet catches up.
+const REFRESH_US: u64 = 1_000_000;
+
+/// An OSC destination, `HOST:PORT` with an optional address prefix
+/// replacing `/steel` (`127.0.0.1:57120/sc/steel`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OscTarget {
+    pub addr: String,
+    pub prefix: String,
+}
+
+impl FromStr for OscTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.find('/') {
+            Some(i) => (&s[..i], s[i..].trim_end_matches('/')),
+            None => (s, "/steel"),
+        };
+        if !addr.contains(':') {
+            return Err(format!("OSC target '{}' needs HOST:PORT", s));
+        }
+        Ok(Self {
+            addr: addr.to_string(),
+            prefix: prefix.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for OscTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.addr, self.prefix)
+    }
+}
+
+/// Sends capture frames as timestamped OSC bundles, one per output tick.
+///
+/// Frames arrive at the coordinator's 1 kHz; the sender forwards at most
+/// `rate_hz` of them, latching attacks from skipped frames like
+/// `WsServer`. Within a bundle only values that moved past their deadband
+/// since they were last sent are included (all of them once a second),
+/// and attack note events always are.
 pub struct OscSender {
     rx: Receiver<CaptureFrame>,
-    target: String,
+    targets: Vec<OscTarget>,
+    rate_hz: u32,
+    deadband: f32,
+    pitch_deadband_cents: f32,
 }
 
 impl OscSender {
-    pub fn new(rx: Receiver<CaptureFrame>, target: String) -> Self {
-        Self { rx, target }
+    /// 100 bundles/s with 0.002 and 1-cent deadbands unless configured
+    /// otherwise.
+    pub fn new(rx: Receiver<CaptureFrame>, targets: Vec<OscTarget>) -> Self {
+        Self {
+            rx,
+            targets,
+            rate_hz: 100,
+            deadband: 0.002,
+            pitch_deadband_cents: 1.0,
+        }
+    }
+
+    /// Bundles per second; 0 sends every frame.
+    pub fn with_rate(mut self, rate_hz: u32) -> Self {
+        self.rate_hz = rate_hz;
+        self
+    }
+
+    /// Smallest change that is resent: `level` for controls, bar and
+    /// amplitudes, `cents` for pitches. Zero sends every change.
+    pub fn with_deadband(mut self, level: f32, cents: f32) -> Self {
+        self.deadband = level.max(0.0);
+        self.pitch_deadband_cents = cents.max(0.0);
+        self
     }
 
     /// Run the OSC sender loop. Blocks the calling thread.
@@ -23,84 +95,348 @@ impl OscSender {
                 return;
             }
         };
-        info!("OSC sender → {}", self.target);
+        for target in &self.targets {
+            info!("OSC sender → {}", target);
+        }
+
+        let interval_us = match self.rate_hz {
+            0 => 0,
+            hz => 1_000_000 / hz as u64,
+        };
+        let mut throttle = Throttle::new(interval_us, self.deadband, self.pitch_deadband_cents);
+        // Wall-clock time of session time zero, for bundle timetags
+        let mut epoch: Option<SystemTime> = None;
 
         for frame in self.rx.iter() {
-            if let Err(e) = self.send_frame(&socket, &frame) {
-                debug!("OSC send error: {}", e);
+            let Some(msgs) = throttle.push(&frame) else {
+                continue;
+            };
+            let start = *epoch.get_or_insert_with(|| {
+                SystemTime::now() - Duration::from_micros(frame.timestamp_us)
+            });
+            let timetag = OscTime::try_from(start + Duration::from_micros(frame.timestamp_us))
+                .unwrap_or(OscTime {
+                    seconds: 0,
+                    fractional: 1,
+                });
+            for target in &self.targets {
+                if let Err(e) = send_bundle(&socket, target, timetag, &msgs) {
+                    debug!("OSC send error ({}): {}", target, e);
+                }
             }
         }
         info!("OSC sender shutting down");
     }
+}
+
+fn send_bundle(
+    socket: &UdpSocket,
+    target: &OscTarget,
+    timetag: OscTime,
+    msgs: &[OscMessage],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = msgs
+        .iter()
+        .map(|m| {
+            let path = m.addr.strip_prefix("/steel").unwrap_or(&m.addr);
+            OscPacket::Message(OscMessage {
+                addr: format!("{}{}", target.prefix, path),
+                args: m.args.clone(),
+            })
+        })
+        .collect();
+    let buf = rosc::encoder::encode(&OscPacket::Bundle(OscBundle { timetag, content }))?;
+    socket.send_to(&buf, &target.addr)?;
+    Ok(())
+}
 
-    fn send_frame(
-        &self,
-        socket: &UdpSocket,
-        frame: &CaptureFrame,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Pedals
-        for (i, &val) in frame.pedals.iter().enumerate() {
-            let addr = format!("/steel/pedal/{}", ["a", "b", "c"][i]);
-            self.send_float(socket, &addr, val)?;
+/// Output rate, attack latching and change-only filtering.
+struct Throttle {
+    interval_us: u64,
+    deadband: f32,
+    pitch_deadband_cents: f32,
+    last_send_us: Option<u64>,
+    last_refresh_us: Option<u64>,
+    pending_attacks: [bool; 10],
+    /// Arguments last sent on each address
+    sent: HashMap<String, Vec<OscType>>,
+}
+
+impl Throttle {
+    fn new(interval_us: u64, deadband: f32, pitch_deadband_cents: f32) -> Self {
+        Self {
+            interval_us,
+            deadband,
+            pitch_deadband_cents,
+            last_send_us: None,
+            last_refresh_us: None,
+            pending_attacks: [false; 10],
+            sent: HashMap::new(),
         }
+    }
 
-        // Knee levers
-        for (i, &val) in frame.knee_levers.iter().enumerate() {
-            let addr = format!("/steel/knee/{}", i);
-            self.send_float(socket, &addr, val)?;
+    /// Messages to send for this frame, or None if it falls between
+    /// output ticks or nothing changed.
+    fn push(&mut self, frame: &CaptureFrame) -> Option<Vec<OscMessage>> {
+        // Latch attacks until the next tick
+        for (pending, &attack) in self.pending_attacks.iter_mut().zip(&frame.attacks) {
+            *pending |= attack;
         }
+        let now = frame.timestamp_us;
+        if self
+            .last_send_us
+            .is_some_and(|last| now < last + self.interval_us)
+        {
+            return None;
+        }
+        self.last_send_us = Some(now);
 
-        // Volume
-        self.send_float(socket, "/steel/volume", frame.volume)?;
+        let mut send_frame = frame.clone();
+        send_frame.attacks = std::mem::take(&mut self.pending_attacks);
 
-        // Bar position
-        match frame.bar_position {
-            Some(pos) => {
-                self.send_float(socket, "/steel/bar/pos", pos)?;
-                self.send_float(socket, "/steel/bar/confidence", frame.bar_confidence)?;
-            }
-            None => {
-                self.send_float(socket, "/steel/bar/pos", -1.0)?;
-                self.send_float(socket, "/steel/bar/confidence", 0.0)?;
+        let refresh = self
+            .last_refresh_us
+            .is_none_or(|last| now >= last + REFRESH_US);
+        if refresh {
+            self.last_refresh_us = Some(now);
+        }
+
+        let mut msgs = frame_messages(&send_frame);
+        msgs.retain(|m| m.addr.starts_with("/steel/attack/") || refresh || self.changed(m));
+        for m in &msgs {
+            if !m.addr.starts_with("/steel/attack/") {
+                self.sent.insert(m.addr.clone(), m.args.clone());
             }
         }
+        (!msgs.is_empty()).then_some(msgs)
+    }
 
-        // Bar source: 0=none, 1=sensor, 2=audio, 3=fused
-        let source_val = match frame.bar_source {
-            BarSource::None => 0.0,
-            BarSource::Sensor => 1.0,
-            BarSource::Audio => 2.0,
-            BarSource::Fused => 3.0,
+    fn changed(&self, msg: &OscMessage) -> bool {
+        let Some(prev) = self.sent.get(&msg.addr) else {
+            return true;
         };
-        self.send_float(socket, "/steel/bar/source", source_val)?;
-
-        // Raw bar sensor readings (for calibration/diagnostics)
-        for (i, &val) in frame.bar_sensors.iter().enumerate() {
-            let addr = format!("/steel/bar/sensor/{}", i);
-            self.send_float(socket, &addr, val)?;
+        match (prev.first(), msg.args.first()) {
+            (Some(OscType::Float(old)), Some(OscType::Float(new))) => {
+                if msg.addr.starts_with("/steel/pitch/") && *old > 0.0 && *new > 0.0 {
+                    (1200.0 * (new / old).log2()).abs() > self.pitch_deadband_cents
+                } else {
+                    (new - old).abs() > self.deadband
+                }
+            }
+            _ => *prev != msg.args,
         }
+    }
+}
 
-        // Per-string pitches
-        for (i, &hz) in frame.string_pitches_hz.iter().enumerate() {
-            let addr = format!("/steel/pitch/{}", i);
-            self.send_float(socket, &addr, hz as f32)?;
-        }
+fn float(addr: String, val: f32) -> OscMessage {
+    OscMessage {
+        addr,
+        args: vec![OscType::Float(val)],
+    }
+}
+
+/// Every message for one frame, state first, then one
+/// `/steel/attack/{n}` per string attacked this frame.
+fn frame_messages(frame: &CaptureFrame) -> Vec<OscMessage> {
+    let mut msgs = Vec::with_capacity(64);
 
-        Ok(())
+    // Pedals
+    for (i, &val) in frame.pedals.iter().enumerate() {
+        let addr = format!("/steel/pedal/{}", ["a", "b", "c"][i]);
+        msgs.push(float(addr, val));
     }
 
-    fn send_float(
-        &self,
-        socket: &UdpSocket,
-        addr: &str,
-        val: f32,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let msg = OscPacket::Message(OscMessage {
-            addr: addr.to_string(),
-            args: vec![OscType::Float(val)],
+    // Knee levers, by index and by name
+    for (i, &val) in frame.knee_levers.iter().enumerate() {
+        msgs.push(float(format!("/steel/knee/{}", i), val));
+        let addr = format!("/steel/knee/{}", LEVER_NAMES[i].to_ascii_lowercase());
+        msgs.push(float(addr, val));
+    }
+
+    // Volume
+    msgs.push(float("/steel/volume".into(), frame.volume));
+
+    // Bar position
+    let (pos, confidence) = match frame.bar_position {
+        Some(pos) => (pos, frame.bar_confidence),
+        None => (-1.0, 0.0),
+    };
+    msgs.push(float("/steel/bar/pos".into(), pos));
+    msgs.push(float("/steel/bar/confidence".into(), confidence));
+
+    // Bar source: 0=none, 1=sensor, 2=audio, 3=fused
+    let source_val = match frame.bar_source {
+        BarSource::None => 0.0,
+        BarSource::Sensor => 1.0,
+        BarSource::Audio => 2.0,
+        BarSource::Fused => 3.0,
+    };
+    msgs.push(float("/steel/bar/source".into(), source_val));
+
+    // Raw bar sensor readings (for calibration/diagnostics)
+    for (i, &val) in frame.bar_sensors.iter().enumerate() {
+        msgs.push(float(format!("/steel/bar/sensor/{}", i), val));
+    }
+
+    // Per-string pitches
+    for (i, &hz) in frame.string_pitches_hz.iter().enumerate() {
+        msgs.push(float(format!("/steel/pitch/{}", i), hz as f32));
+    }
+
+    // Per-string state
+    for i in 0..10 {
+        msgs.push(OscMessage {
+            addr: format!("/steel/string/{}/active", i),
+            args: vec![OscType::Int(frame.string_active[i] as i32)],
+        });
+        msgs.push(float(
+            format!("/steel/string/{}/amp", i),
+            frame.string_amplitude[i],
+        ));
+    }
+
+    // Note events: pitch and amplitude of each new attack
+    for i in (0..10).filter(|&i| frame.attacks[i]) {
+        msgs.push(OscMessage {
+            addr: format!("/steel/attack/{}", i),
+            args: vec![
+                OscType::Float(frame.string_pitches_hz[i] as f32),
+                OscType::Float(frame.string_amplitude[i]),
+            ],
         });
-        let buf = rosc::encoder::encode(&msg)?;
-        socket.send_to(&buf, &self.target)?;
-        Ok(())
+    }
+
+    msgs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(msgs: &'a [OscMessage], addr: &str) -> Option<&'a [OscType]> {
+        msgs.iter()
+            .find(|m| m.addr == addr)
+            .map(|m| m.args.as_slice())
+    }
+
+    #[test]
+    fn test_strings_and_attacks() {
+        let mut frame = CaptureFrame::at_rest(0);
+        frame.string_pitches_hz[2] = 415.3;
+        frame.string_active[2] = true;
+        frame.string_amplitude[2] = 0.6;
+        frame.attacks[2] = true;
+        frame.string_active[4] = true;
+        frame.string_amplitude[4] = 0.3;
+
+        let msgs = frame_messages(&frame);
+        assert_eq!(
+            find(&msgs, "/steel/string/2/active"),
+            Some(&[OscType::Int(1)][..])
+        );
+        assert_eq!(
+            find(&msgs, "/steel/string/4/amp"),
+            Some(&[OscType::Float(0.3)][..])
+        );
+        assert_eq!(
+            find(&msgs, "/steel/string/0/active"),
+            Some(&[OscType::Int(0)][..])
+        );
+        assert_eq!(
+            find(&msgs, "/steel/attack/2"),
+            Some(&[OscType::Float(415.3), OscType::Float(0.6)][..])
+        );
+        // Sustained string: state only, no note event
+        assert!(find(&msgs, "/steel/attack/4").is_none());
+        // Attacks come after the state they refer to
+        assert_eq!(msgs.last().unwrap().addr, "/steel/attack/2");
+    }
+
+    #[test]
+    fn test_lever_names() {
+        let mut frame = CaptureFrame::at_rest(0);
+        frame.knee_levers[2] = 0.75;
+        let msgs = frame_messages(&frame);
+        assert_eq!(find(&msgs, "/steel/knee/2"), find(&msgs, "/steel/knee/lkv"));
+        assert_eq!(
+            find(&msgs, "/steel/knee/lkv"),
+            Some(&[OscType::Float(0.75)][..])
+        );
+        assert!(find(&msgs, "/steel/knee/rkr").is_some());
+        assert_eq!(
+            find(&msgs, "/steel/bar/pos"),
+            Some(&[OscType::Float(-1.0)][..])
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_latches_attacks() {
+        // 100 Hz out of 1 kHz frames
+        let mut throttle = Throttle::new(10_000, 0.002, 1.0);
+        let mut f = CaptureFrame::at_rest(0);
+        assert!(throttle.push(&f).is_some());
+
+        // An attack between ticks is held for the next bundle
+        f.timestamp_us = 3_000;
+        f.attacks[6] = true;
+        f.string_pitches_hz[6] = 185.0;
+        assert!(throttle.push(&f).is_none());
+        f.timestamp_us = 6_000;
+        f.attacks[6] = false;
+        assert!(throttle.push(&f).is_none());
+        f.timestamp_us = 10_000;
+        let msgs = throttle.push(&f).unwrap();
+        assert!(find(&msgs, "/steel/attack/6").is_some());
+
+        // Sent once only
+        f.timestamp_us = 20_000;
+        f.volume = 0.5;
+        let msgs = throttle.push(&f).unwrap();
+        assert!(find(&msgs, "/steel/attack/6").is_none());
+    }
+
+    #[test]
+    fn test_deadbands_and_refresh() {
+        let mut throttle = Throttle::new(0, 0.01, 5.0);
+        let mut f = CaptureFrame {
+            volume: 1.0,
+            string_pitches_hz: [440.0; 10],
+            ..CaptureFrame::at_rest(0)
+        };
+        let all = throttle.push(&f).unwrap().len();
+
+        // Below both deadbands: nothing to send
+        f.timestamp_us = 1_000;
+        f.volume = 0.995;
+        f.string_pitches_hz[0] = 441.0; // ~3.9 cents
+        assert!(throttle.push(&f).is_none());
+
+        // Past them: only the moved values
+        f.timestamp_us = 2_000;
+        f.volume = 0.98;
+        f.string_pitches_hz[0] = 442.0; // ~7.9 cents from the last sent
+        f.string_active[1] = true;
+        let msgs = throttle.push(&f).unwrap();
+        let addrs: Vec<&str> = msgs.iter().map(|m| m.addr.as_str()).collect();
+        assert_eq!(
+            addrs,
+            ["/steel/volume", "/steel/pitch/0", "/steel/string/1/active"]
+        );
+
+        // Everything again after a second
+        f.timestamp_us = 1_000_000;
+        assert_eq!(throttle.push(&f).unwrap().len(), all);
+    }
+
+    #[test]
+    fn test_target_parse() {
+        let t: OscTarget = "127.0.0.1:9000".parse().unwrap();
+        assert_eq!(t.addr, "127.0.0.1:9000");
+        assert_eq!(t.prefix, "/steel");
+        let t: OscTarget = "localhost:57120/sc/steel/".parse().unwrap();
+        assert_eq!(t.addr, "localhost:57120");
+        assert_eq!(t.prefix, "/sc/steel");
+        assert_eq!(t.to_string(), "localhost:57120/sc/steel");
+        assert!("9000".parse::<OscTarget>().is_err());
     }
 }
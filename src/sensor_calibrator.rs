@@ -0,0 +1,321 @@
+//! Interactive ADC calibrator for pedals, knee levers, volume and bar sensors.
+//!
+//! Reads `InputEvent::Sensor` frames decoded with `Calibration::identity()`
+//! (so values are raw counts / 4095) — from the serial port, or from the
+//! emulator over a pty.
+//!
+//! Protocol: everything at rest with the bar off the strings (rest values
+//! and bar baselines), then each pedal, lever and the volume pedal pushed
+//! to full travel in turn, then the bar laid over each hall sensor. Each
+//! capture averages 2 s of frames. Channels that fall as travel increases
+//! come out inverted (`full < rest`) automatically.
+//!
+//! With `with_points(n)` for n > 2, each pedal, lever and the volume pedal
+//! is also held at evenly spaced partial travel (a block of known thickness
+//! under the pedal rod works well), and a linearization curve is fitted to
+//! the captures.
+
+use crate::sensor_curve::{Curve, CurveFit};
+use crate::serial_protocol::{sensor_to_raw, slot_channel, Calibration, NUM_CHANNELS};
+use crate::types::{InputEvent, LEVER_NAMES, PEDAL_NAMES};
+use crossbeam_channel::Receiver;
+use log::warn;
+use std::io::{self, Write};
+use std::time::Duration;
+
+const CAPTURE_SECS: f64 = 2.0;
+/// A channel that moves less than this many counts is left at its default
+const MIN_TRAVEL: f32 = 200.0;
+/// Hall sensor positions along the bar rail
+const BAR_SENSOR_FRETS: [u32; 4] = [0, 5, 10, 15];
+
+/// Slots that get partial-travel captures (pedals, levers, volume)
+const TRAVEL_SLOTS: usize = 9;
+
+pub struct SensorCalibrator {
+    rx: Receiver<InputEvent>,
+    points: usize,
+    fit: CurveFit,
+}
+
+impl SensorCalibrator {
+    pub fn new(rx: Receiver<InputEvent>) -> Self {
+        Self {
+            rx,
+            points: 2,
+            fit: CurveFit::Table,
+        }
+    }
+
+    /// Capture positions per travel channel, counting rest and full travel.
+    /// More than 2 fits a linearization curve.
+    pub fn with_points(mut self, points: usize) -> Self {
+        self.points = points.max(2);
+        self
+    }
+
+    pub fn with_curve_fit(mut self, fit: CurveFit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    pub fn run(&self) -> Calibration {
+        println!("\n╔═══════════════════════════════════════════════╗");
+        println!("║   Steel Capture — Sensor Calibration          ║");
+        println!("╠═══════════════════════════════════════════════╣");
+        println!("║  For each step: get into position, press      ║");
+        println!(
+            "║  Enter, and hold still for {:.0}s.                 ║",
+            CAPTURE_SECS
+        );
+        println!("╚═══════════════════════════════════════════════╝\n");
+
+        let rest = self
+            .step("Release all pedals and knee levers, volume pedal at rest, bar off the strings.");
+        let Some(rest) = rest else {
+            warn!("No sensor frames received — keeping default calibration");
+            return Calibration::default();
+        };
+
+        let mut captures: [Vec<(f32, f32)>; NUM_CHANNELS] = Default::default();
+        for (slot, points) in captures.iter_mut().enumerate() {
+            if slot >= TRAVEL_SLOTS {
+                let prompt = format!(
+                    "Lay the bar on the strings directly over fret {}.",
+                    BAR_SENSOR_FRETS[slot - TRAVEL_SLOTS]
+                );
+                points.extend(self.step(&prompt).map(|means| (1.0, means[slot])));
+                continue;
+            }
+            let name = match slot {
+                0..=2 => format!("pedal {}", PEDAL_NAMES[slot]),
+                3..=7 => format!("knee lever {}", LEVER_NAMES[slot - 3]),
+                _ => "the volume pedal".to_string(),
+            };
+            for k in 1..self.points {
+                let travel = k as f32 / (self.points - 1) as f32;
+                let prompt = if k + 1 == self.points {
+                    format!("Push {} all the way and hold it.", name)
+                } else {
+                    format!("Hold {} at {:.0}% of its travel.", name, travel * 100.0)
+                };
+                points.extend(self.step(&prompt).map(|means| (travel, means[slot])));
+            }
+        }
+
+        let cal = calibration_from_captures(&rest, &captures, self.fit);
+        println!("\nCalibration complete:\n");
+        for (slot, &(r, f)) in cal.ranges.iter().enumerate() {
+            println!(
+                "   {:<10} rest {:>4}  full {:>4}{}{}",
+                slot_channel(slot).to_string(),
+                r,
+                f,
+                if cal.is_inverted(slot) {
+                    "  (inverted)"
+                } else {
+                    ""
+                },
+                if cal.curves[slot].is_linear() {
+                    String::new()
+                } else {
+                    format!("  ({} curve)", self.fit)
+                }
+            );
+        }
+        println!();
+        cal
+    }
+
+    /// Prompt, wait for Enter, then capture.
+    fn step(&self, prompt: &str) -> Option<[f32; NUM_CHANNELS]> {
+        print!("── {}\n   Press Enter when ready...", prompt);
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        io::stdin().read_line(&mut line).ok();
+
+        // Drain frames accumulated during the stdin wait.
+        while self.rx.try_recv().is_ok() {}
+
+        let means = self.capture(CAPTURE_SECS);
+        println!("   done.");
+        means
+    }
+
+    /// Average raw counts per channel over `secs` of sensor time. `None` if
+    /// the stream ended before any frame arrived.
+    pub fn capture(&self, secs: f64) -> Option<[f32; NUM_CHANNELS]> {
+        let identity = Calibration::identity();
+        let mut sums = [0.0f64; NUM_CHANNELS];
+        let mut count = 0u32;
+        let mut start = None;
+        let span_us = (secs * 1_000_000.0) as u64;
+
+        loop {
+            match self.rx.recv_timeout(Duration::from_millis(500)) {
+                Ok(InputEvent::Sensor(frame)) => {
+                    let t0 = *start.get_or_insert(frame.timestamp_us);
+                    for (sum, raw) in sums.iter_mut().zip(sensor_to_raw(&frame, &identity)) {
+                        *sum += raw as f64;
+                    }
+                    count += 1;
+                    if frame.timestamp_us.saturating_sub(t0) >= span_us {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => {
+                    warn!("Sensor stream closed or timed out during calibration.");
+                    break;
+                }
+            }
+        }
+
+        (count > 0).then(|| sums.map(|s| (s / count as f64) as f32))
+    }
+}
+
+/// Build a calibration from the rest capture and each channel's
+/// `(travel, raw)` captures; travel 1.0 is full travel. Channels without a
+/// full-travel capture, or that barely moved, keep their defaults. Partial
+/// travel captures are fitted into a curve with `fit`.
+pub fn calibration_from_captures(
+    rest: &[f32; NUM_CHANNELS],
+    captures: &[Vec<(f32, f32)>; NUM_CHANNELS],
+    fit: CurveFit,
+) -> Calibration {
+    let mut cal = Calibration::default();
+    for (slot, (range, curve)) in cal.ranges.iter_mut().zip(&mut cal.curves).enumerate() {
+        let Some(f) = captures[slot]
+            .iter()
+            .find(|&&(travel, _)| travel >= 1.0)
+            .map(|&(_, raw)| raw)
+        else {
+            continue;
+        };
+        let r = rest[slot];
+        if (f - r).abs() < MIN_TRAVEL {
+            warn!(
+                "{} moved only {:.0} counts ({:.0} → {:.0}) — check the sensor and magnet; keeping default",
+                slot_channel(slot),
+                (f - r).abs(),
+                r,
+                f
+            );
+            continue;
+        }
+        *range = (r.round() as u16, f.round() as u16);
+
+        let normalized: Vec<(f32, f32)> = captures[slot]
+            .iter()
+            .map(|&(travel, raw)| (travel, (raw - r) / (f - r)))
+            .collect();
+        match Curve::fit(&normalized, fit) {
+            Ok(c) => *curve = c,
+            Err(e) => warn!(
+                "{}: {} — recapture it; using a straight line",
+                slot_channel(slot),
+                e
+            ),
+        }
+    }
+    cal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SensorFrame;
+    use crossbeam_channel::unbounded;
+
+    #[test]
+    fn test_captures_to_calibration() {
+        let mut rest = [250.0; NUM_CHANNELS];
+        let mut captures: [Vec<(f32, f32)>; NUM_CHANNELS] =
+            std::array::from_fn(|_| vec![(1.0, 3700.0)]);
+        rest[4] = 3600.0; // LKR mounted upside down: falls with travel
+        captures[4] = vec![(1.0, 400.0)];
+        captures[6] = vec![(1.0, 300.0)]; // RKL barely moved
+        captures[12].clear(); // bar sensor 3 skipped
+
+        let cal = calibration_from_captures(&rest, &captures, CurveFit::Table);
+        assert!(cal.curves.iter().all(Curve::is_linear));
+        assert_eq!(cal.ranges[0], (250, 3700));
+        assert_eq!(cal.ranges[4], (3600, 400));
+        assert!(cal.is_inverted(4));
+        assert_eq!(cal.ranges[6], Calibration::default().ranges[6]);
+        assert_eq!(cal.ranges[12], Calibration::default().ranges[12]);
+
+        // Inverted channel: rest reads 0, full travel 1, halfway 0.5
+        assert_eq!(cal.to_unit(4, 3600), 0.0);
+        assert_eq!(cal.to_unit(4, 400), 1.0);
+        assert!((cal.to_unit(4, 2000) - 0.5).abs() < 1e-3);
+        assert_eq!(cal.to_raw(4, 1.0), 400);
+    }
+
+    #[test]
+    fn test_partial_travel_captures_fit_curve() {
+        let mut rest = [300.0; NUM_CHANNELS];
+        let mut captures: [Vec<(f32, f32)>; NUM_CHANNELS] = Default::default();
+        // Pedal A reads only 10% of its span at half travel; inverted LKL
+        // likewise; RKR's half-travel capture went backwards
+        captures[0] = vec![(0.5, 650.0), (1.0, 3800.0)];
+        rest[3] = 3600.0;
+        captures[3] = vec![(0.5, 3280.0), (1.0, 400.0)];
+        captures[7] = vec![(0.5, 200.0), (1.0, 3800.0)];
+
+        let cal = calibration_from_captures(&rest, &captures, CurveFit::Table);
+        assert_eq!(
+            cal.curves[0],
+            Curve::Table(vec![[0.0, 0.0], [0.1, 0.5], [1.0, 1.0]])
+        );
+        assert!((cal.to_unit(0, 650) - 0.5).abs() < 1e-3);
+        assert_eq!(cal.to_raw(0, 0.5), 650);
+        assert!(cal.is_inverted(3));
+        assert!((cal.to_unit(3, 3280) - 0.5).abs() < 1e-3);
+        assert!(cal.curves[7].is_linear());
+
+        let json = cal.to_json();
+        assert_eq!(Calibration::from_json(&json).unwrap(), cal);
+        assert!(Calibration::from_json(
+            r#"{"channels": [{"channel": "pedal:A", "rest": 0, "full": 1, "curve": {"table": [[0.5, 0.5]]}}]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_capture_averages_raw_counts() {
+        let (tx, rx) = unbounded();
+        let identity = Calibration::identity();
+        for k in 0..100u64 {
+            let mut frame = SensorFrame::at_rest(k * 1000);
+            frame.pedals[1] = identity.to_unit(1, if k % 2 == 0 { 1000 } else { 1200 });
+            frame.bar_sensors[2] = identity.to_unit(11, 3000);
+            tx.send(InputEvent::Sensor(frame)).unwrap();
+        }
+        drop(tx);
+        let means = SensorCalibrator::new(rx).capture(1.0).unwrap();
+        assert!((means[1] - 1100.0).abs() < 1.0, "pedal B {}", means[1]);
+        assert!((means[11] - 3000.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut cal = Calibration::default();
+        cal.ranges[4] = (3600, 400);
+        cal.ranges[10] = (180, 2900);
+        let json = cal.to_json();
+        assert!(json.contains("\"lever:LKR\""));
+        assert_eq!(Calibration::from_json(&json).unwrap(), cal);
+
+        // Partial files keep defaults for unlisted channels
+        let partial = r#"{"channels": [{"channel": "volume", "rest": 3900, "full": 300}]}"#;
+        let loaded = Calibration::from_json(partial).unwrap();
+        assert_eq!(loaded.ranges[8], (3900, 300));
+        assert_eq!(loaded.ranges[0], Calibration::default().ranges[0]);
+        assert!(Calibration::from_json(
+            r#"{"channels": [{"channel": "pedal:Z", "rest": 0, "full": 1}]}"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,295 @@
+//! Sensor linearization curves.
+//!
+//! An SS49E's output falls off roughly with the square of magnet distance,
+//! so a straight rest→full line reads a pedal as half engaged well before
+//! it is halfway down. A `Curve` maps the linearly normalized reading
+//! (0 at rest, 1 at full travel) to actual travel, and back. Curves are
+//! fitted from captures at known travel fractions: a lookup table passes
+//! through every capture, a polynomial smooths noisy ones.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Reading → travel mapping on 0.0–1.0.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    /// Reading is travel (the plain rest/full calibration)
+    #[default]
+    Linear,
+    /// `[reading, travel]` points, readings strictly increasing, travel
+    /// non-decreasing; interpolated linearly between points
+    Table(Vec<[f32; 2]>),
+    /// Coefficients, constant term first: travel = c0 + c1·r + c2·r² + …
+    Polynomial(Vec<f32>),
+}
+
+/// How to fit a curve from multi-point captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveFit {
+    Table,
+    /// Least-squares polynomial of this degree
+    Polynomial(usize),
+}
+
+impl FromStr for CurveFit {
+    type Err = String;
+
+    /// `table`, or `polyN` for a degree-N polynomial.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            _ => s
+                .strip_prefix("poly")
+                .and_then(|d| d.parse().ok())
+                .filter(|&d| (1..=6).contains(&d))
+                .map(Self::Polynomial)
+                .ok_or_else(|| {
+                    format!("unknown curve fit '{}' (expected table or poly1–poly6)", s)
+                }),
+        }
+    }
+}
+
+impl fmt::Display for CurveFit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Table => write!(f, "table"),
+            Self::Polynomial(d) => write!(f, "poly{}", d),
+        }
+    }
+}
+
+impl Curve {
+    pub fn is_linear(&self) -> bool {
+        matches!(self, Self::Linear)
+    }
+
+    /// Normalized reading → travel, both clamped to 0.0–1.0.
+    pub fn apply(&self, reading: f32) -> f32 {
+        let r = reading.clamp(0.0, 1.0);
+        let travel = match self {
+            Self::Linear => r,
+            Self::Table(points) => interpolate(points.iter().map(|p| (p[0], p[1])), r),
+            Self::Polynomial(coeffs) => coeffs.iter().rev().fold(0.0, |acc, &c| acc * r + c),
+        };
+        travel.clamp(0.0, 1.0)
+    }
+
+    /// Travel → normalized reading, the inverse of `apply`.
+    pub fn invert(&self, travel: f32) -> f32 {
+        let t = travel.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::Table(points) => interpolate(points.iter().map(|p| (p[1], p[0])), t),
+            Self::Polynomial(_) => {
+                // Monotonic over 0–1 after validation: bisect
+                let (mut lo, mut hi) = (0.0f32, 1.0f32);
+                for _ in 0..30 {
+                    let mid = 0.5 * (lo + hi);
+                    if self.apply(mid) < t {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                0.5 * (lo + hi)
+            }
+        }
+    }
+
+    /// Check a curve read from a file is usable.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Linear => Ok(()),
+            Self::Table(points) => {
+                if points.len() < 2 {
+                    return Err("table needs at least 2 points".into());
+                }
+                for pair in points.windows(2) {
+                    if pair[1][0] <= pair[0][0] || pair[1][1] < pair[0][1] {
+                        return Err(format!(
+                            "table must increase: {:?} then {:?}",
+                            pair[0], pair[1]
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            Self::Polynomial(coeffs) => {
+                if coeffs.is_empty() {
+                    return Err("polynomial needs coefficients".into());
+                }
+                let mut last = f32::NEG_INFINITY;
+                for k in 0..=100 {
+                    let raw: f32 = coeffs
+                        .iter()
+                        .rev()
+                        .fold(0.0, |acc, &c| acc * (k as f32 / 100.0) + c);
+                    if raw < last - 1e-4 {
+                        return Err("polynomial is not increasing over 0–1".into());
+                    }
+                    last = raw;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Fit from `(travel, reading)` captures, with readings already
+    /// normalized to 0 at rest and 1 at full travel. The end points (0, 0)
+    /// and (1, 1) are implied.
+    pub fn fit(captures: &[(f32, f32)], method: CurveFit) -> Result<Self, String> {
+        let mut points: Vec<(f32, f32)> = captures
+            .iter()
+            .filter(|&&(t, _)| t > 0.0 && t < 1.0)
+            .map(|&(t, r)| (r, t))
+            .collect();
+        if points.is_empty() {
+            return Ok(Self::Linear);
+        }
+        points.push((0.0, 0.0));
+        points.push((1.0, 1.0));
+        points.sort_by(|a, b| a.1.total_cmp(&b.1));
+        for pair in points.windows(2) {
+            if pair[1].0 <= pair[0].0 {
+                return Err(format!(
+                    "reading {:.3} at travel {:.2} does not increase on {:.3} at travel {:.2}",
+                    pair[1].0, pair[1].1, pair[0].0, pair[0].1
+                ));
+            }
+        }
+
+        let curve = match method {
+            CurveFit::Table => Self::Table(points.iter().map(|&(r, t)| [r, t]).collect()),
+            CurveFit::Polynomial(degree) => {
+                Self::Polynomial(least_squares(&points, degree.min(points.len() - 1))?)
+            }
+        };
+        curve.validate()?;
+        Ok(curve)
+    }
+}
+
+/// Piecewise-linear interpolation over points sorted by x; clamps to the
+/// end values outside the table.
+fn interpolate(points: impl Iterator<Item = (f32, f32)>, x: f32) -> f32 {
+    let mut prev: Option<(f32, f32)> = None;
+    for (px, py) in points {
+        if x <= px {
+            return match prev {
+                Some((qx, qy)) if px > qx => qy + (py - qy) * (x - qx) / (px - qx),
+                _ => py,
+            };
+        }
+        prev = Some((px, py));
+    }
+    prev.map_or(x, |(_, y)| y)
+}
+
+/// Polynomial coefficients (constant first) minimizing squared error over
+/// `(x, y)` points, via the normal equations.
+fn least_squares(points: &[(f32, f32)], degree: usize) -> Result<Vec<f32>, String> {
+    let n = degree + 1;
+    // Augmented normal-equation matrix [XᵀX | Xᵀy]
+    let mut m = vec![vec![0.0f64; n + 1]; n];
+    for &(x, y) in points {
+        let powers: Vec<f64> = (0..n).map(|k| (x as f64).powi(k as i32)).collect();
+        for (row, &pr) in m.iter_mut().zip(&powers) {
+            for (cell, &pc) in row.iter_mut().zip(&powers) {
+                *cell += pr * pc;
+            }
+            row[n] += pr * y as f64;
+        }
+    }
+    // Gauss-Jordan with partial pivoting
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+            .unwrap();
+        if m[pivot][col].abs() < 1e-12 {
+            return Err("polynomial fit is singular — capture more distinct points".into());
+        }
+        m.swap(col, pivot);
+        let div = m[col][col];
+        for cell in m[col].iter_mut() {
+            *cell /= div;
+        }
+        let pivot_row = m[col].clone();
+        for (r, row) in m.iter_mut().enumerate() {
+            if r != col {
+                let factor = row[col];
+                for (cell, &p) in row.iter_mut().zip(&pivot_row) {
+                    *cell -= factor * p;
+                }
+            }
+        }
+    }
+    Ok(m.iter().map(|row| row[n] as f32).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Normalized hall reading at a given pedal travel: field strength
+    /// ~1/d² as the magnet approaches from 10 mm to `near_mm`.
+    fn hall_reading(travel: f32, near_mm: f32) -> f32 {
+        let field = |t: f32| 1.0 / (10.0 - (10.0 - near_mm) * t).powi(2);
+        (field(travel) - field(0.0)) / (field(1.0) - field(0.0))
+    }
+
+    fn captures(n: usize, near_mm: f32) -> Vec<(f32, f32)> {
+        (1..n)
+            .map(|k| {
+                let t = k as f32 / n as f32;
+                (t, hall_reading(t, near_mm))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_table_linearizes_hall_response() {
+        // A straight line reads half travel as far less than 0.5
+        assert!(hall_reading(0.5, 2.0) < 0.2);
+
+        let curve = Curve::fit(&captures(8, 2.0), CurveFit::Table).unwrap();
+        let mut worst = 0.0f32;
+        for k in 0..=100 {
+            let t = k as f32 / 100.0;
+            worst = worst.max((curve.apply(hall_reading(t, 2.0)) - t).abs());
+            assert!((curve.apply(curve.invert(t)) - t).abs() < 1e-4);
+        }
+        assert!(worst < 0.05, "table error {}", worst);
+    }
+
+    #[test]
+    fn test_polynomial_fit_and_inverse() {
+        // Gentler response (magnet stops at 5 mm) suits a low-order fit
+        let curve = Curve::fit(&captures(10, 5.0), CurveFit::Polynomial(3)).unwrap();
+        assert!(matches!(&curve, Curve::Polynomial(c) if c.len() == 4));
+        for k in 1..10 {
+            let t = k as f32 / 10.0;
+            let err = (curve.apply(hall_reading(t, 5.0)) - t).abs();
+            assert!(err < 0.06, "travel {} error {}", t, err);
+            assert!((curve.apply(curve.invert(t)) - t).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_fit_rejects_non_monotonic_captures() {
+        let bad = [(0.25, 0.4), (0.5, 0.3), (0.75, 0.8)];
+        assert!(Curve::fit(&bad, CurveFit::Table).is_err());
+        // Only end points: nothing to linearize
+        assert_eq!(
+            Curve::fit(&[(1.0, 1.0)], CurveFit::Table).unwrap(),
+            Curve::Linear
+        );
+        assert!(Curve::Table(vec![[0.0, 0.0], [0.0, 1.0]])
+            .validate()
+            .is_err());
+        assert!("poly3".parse::<CurveFit>().unwrap() == CurveFit::Polynomial(3));
+        assert!("spline".parse::<CurveFit>().is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Simulated sensor channel faults.
+//!
+//! The simulator's pedal, lever, volume and hall readings are otherwise
+//! perfect. `SensorFaults` corrupts individual channels the way real
+//! hardware fails — a broken wire, a shorted sensor, a slipping magnet, a
+//! noisy ADC, a flaky connector — so the fallback paths in `BarInference`
+//! and the coordinator's attack logic get exercised.
+
+use crate::simulator::Rng;
+use crate::types::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// One analog channel of a `SensorFrame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorChannel {
+    Pedal(usize),
+    Lever(usize),
+    Volume,
+    /// Hall sensor index (0–3, at frets 0/5/10/15)
+    Bar(usize),
+}
+
+impl SensorChannel {
+    /// The reading this channel feeds in `frame`.
+    pub fn value_mut(self, frame: &mut SensorFrame) -> &mut f32 {
+        match self {
+            Self::Pedal(i) => &mut frame.pedals[i],
+            Self::Lever(i) => &mut frame.knee_levers[i],
+            Self::Volume => &mut frame.volume,
+            Self::Bar(i) => &mut frame.bar_sensors[i],
+        }
+    }
+}
+
+impl fmt::Display for SensorChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pedal(i) => write!(f, "pedal:{}", PEDAL_NAMES[*i]),
+            Self::Lever(i) => write!(f, "lever:{}", LEVER_NAMES[*i]),
+            Self::Volume => write!(f, "volume"),
+            Self::Bar(i) => write!(f, "bar:{}", i),
+        }
+    }
+}
+
+/// Parses `pedal:A`, `lever:RKR`, `volume` or `bar:0`–`bar:3`.
+impl FromStr for SensorChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, index) = s.split_once(':').unwrap_or((s, ""));
+        let lookup = |names: &[&str]| names.iter().position(|n| n.eq_ignore_ascii_case(index));
+        let channel = match kind {
+            "pedal" => lookup(&PEDAL_NAMES).map(Self::Pedal),
+            "lever" => lookup(&LEVER_NAMES).map(Self::Lever),
+            "volume" if index.is_empty() => Some(Self::Volume),
+            "bar" => index.parse().ok().filter(|&i: &usize| i < 4).map(Self::Bar),
+            _ => None,
+        };
+        channel.ok_or_else(|| {
+            format!(
+                "unknown sensor channel '{}' (expected pedal:A-C, lever:LKL/LKR/LKV/RKL/RKR, volume, bar:0-3)",
+                s
+            )
+        })
+    }
+}
+
+/// How a channel misbehaves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultKind {
+    /// Open circuit: reads 0
+    Dead,
+    /// Shorted to the rail: reads full scale (1.0)
+    Railed,
+    /// Offset grows by `per_sec` (normalized units) every second
+    Drift { per_sec: f32 },
+    /// Uniform ADC noise of ±`amplitude`
+    Noise { amplitude: f32 },
+    /// Intermittent connector: on average `per_sec` dropouts per second,
+    /// each reading 0 for `ms` milliseconds
+    Dropout { per_sec: f32, ms: u32 },
+}
+
+/// Parses `dead`, `railed`, `drift:<per_sec>`, `noise:<amplitude>` or
+/// `dropout:<per_sec>:<ms>`.
+impl FromStr for FaultKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let num = |i: usize| -> Result<f32, String> {
+            parts
+                .get(i)
+                .ok_or_else(|| format!("fault '{}' is missing a parameter", s))?
+                .parse::<f32>()
+                .map_err(|e| format!("fault '{}': {}", s, e))
+        };
+        match parts[0] {
+            "dead" => Ok(Self::Dead),
+            "railed" => Ok(Self::Railed),
+            "drift" => Ok(Self::Drift { per_sec: num(1)? }),
+            "noise" => Ok(Self::Noise { amplitude: num(1)? }),
+            "dropout" => Ok(Self::Dropout {
+                per_sec: num(1)?,
+                ms: num(2)? as u32,
+            }),
+            other => Err(format!(
+                "unknown fault '{}' (expected: dead, railed, drift:R, noise:A, dropout:R:MS)",
+                other
+            )),
+        }
+    }
+}
+
+/// Parse a `CHANNEL=FAULT` spec, e.g. `bar:1=dead` or `pedal:A=noise:0.05`.
+pub fn parse_fault_spec(s: &str) -> Result<(SensorChannel, FaultKind), String> {
+    let (channel, fault) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected CHANNEL=FAULT, got '{}'", s))?;
+    Ok((channel.parse()?, fault.parse()?))
+}
+
+struct ActiveFault {
+    channel: SensorChannel,
+    kind: FaultKind,
+    /// Time since the fault was injected
+    elapsed_us: u64,
+    /// Remaining time in the current dropout burst
+    dropout_left_us: u64,
+}
+
+/// The set of faults currently applied to outgoing sensor frames.
+pub struct SensorFaults {
+    active: Vec<ActiveFault>,
+    rng: Rng,
+}
+
+impl SensorFaults {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            active: Vec::new(),
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Inject a fault, replacing any existing fault on the same channel.
+    pub fn set(&mut self, channel: SensorChannel, kind: FaultKind) {
+        self.clear(channel);
+        self.active.push(ActiveFault {
+            channel,
+            kind,
+            elapsed_us: 0,
+            dropout_left_us: 0,
+        });
+    }
+
+    /// Repair one channel.
+    pub fn clear(&mut self, channel: SensorChannel) {
+        self.active.retain(|f| f.channel != channel);
+    }
+
+    /// Repair every channel.
+    pub fn clear_all(&mut self) {
+        self.active.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Corrupt `frame` in place. `dt_us` is the time since the previous frame.
+    pub fn apply(&mut self, frame: &mut SensorFrame, dt_us: u64) {
+        for fault in self.active.iter_mut() {
+            fault.elapsed_us += dt_us;
+            let value = fault.channel.value_mut(frame);
+            *value = match fault.kind {
+                FaultKind::Dead => 0.0,
+                FaultKind::Railed => 1.0,
+                FaultKind::Drift { per_sec } => {
+                    *value + per_sec * fault.elapsed_us as f32 / 1_000_000.0
+                }
+                FaultKind::Noise { amplitude } => *value + amplitude * self.rng.bipolar(),
+                FaultKind::Dropout { per_sec, ms } => {
+                    if fault.dropout_left_us == 0 {
+                        let p = per_sec * dt_us as f32 / 1_000_000.0;
+                        if (self.rng.bipolar() + 1.0) / 2.0 < p {
+                            fault.dropout_left_us = ms as u64 * 1000;
+                        }
+                    }
+                    if fault.dropout_left_us > 0 {
+                        fault.dropout_left_us = fault.dropout_left_us.saturating_sub(dt_us);
+                        0.0
+                    } else {
+                        *value
+                    }
+                }
+            }
+            .clamp(0.0, 1.0);
+        }
+    }
+}
+
+impl Default for SensorFaults {
+    fn default() -> Self {
+        Self::new(0xfa017)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> SensorFrame {
+        SensorFrame {
+            timestamp_us: 0,
+            pedals: [0.5; 3],
+            knee_levers: [0.5; 5],
+            volume: 0.5,
+            bar_sensors: [0.5; 4],
+            string_active: [false; 10],
+        }
+    }
+
+    #[test]
+    fn test_parse_specs() {
+        assert_eq!(
+            parse_fault_spec("bar:1=dead").unwrap(),
+            (SensorChannel::Bar(1), FaultKind::Dead)
+        );
+        assert_eq!(
+            parse_fault_spec("lever:rkr=noise:0.05").unwrap(),
+            (
+                SensorChannel::Lever(4),
+                FaultKind::Noise { amplitude: 0.05 }
+            )
+        );
+        assert_eq!(
+            parse_fault_spec("volume=dropout:2:30").unwrap(),
+            (
+                SensorChannel::Volume,
+                FaultKind::Dropout {
+                    per_sec: 2.0,
+                    ms: 30
+                }
+            )
+        );
+        assert!(parse_fault_spec("bar:4=dead").is_err());
+        assert!(parse_fault_spec("pedal:A=drift").is_err());
+        assert!(parse_fault_spec("pedal:A").is_err());
+        assert_eq!(SensorChannel::Pedal(2).to_string(), "pedal:C");
+    }
+
+    #[test]
+    fn test_dead_and_railed() {
+        let mut faults = SensorFaults::default();
+        faults.set(SensorChannel::Bar(0), FaultKind::Dead);
+        faults.set(SensorChannel::Pedal(1), FaultKind::Railed);
+        let mut f = frame();
+        faults.apply(&mut f, 1000);
+        assert_eq!(f.bar_sensors, [0.0, 0.5, 0.5, 0.5]);
+        assert_eq!(f.pedals, [0.5, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_drift_accumulates_and_clamps() {
+        let mut faults = SensorFaults::default();
+        faults.set(SensorChannel::Volume, FaultKind::Drift { per_sec: 0.2 });
+        let mut f = frame();
+        for _ in 0..1000 {
+            f = frame();
+            faults.apply(&mut f, 1000);
+        }
+        assert!((f.volume - 0.7).abs() < 1e-3, "volume {}", f.volume);
+        for _ in 0..5000 {
+            f = frame();
+            faults.apply(&mut f, 1000);
+        }
+        assert_eq!(f.volume, 1.0);
+    }
+
+    #[test]
+    fn test_noise_is_bounded() {
+        let mut faults = SensorFaults::default();
+        faults.set(
+            SensorChannel::Lever(0),
+            FaultKind::Noise { amplitude: 0.05 },
+        );
+        let mut spread = 0.0f32;
+        for _ in 0..1000 {
+            let mut f = frame();
+            faults.apply(&mut f, 1000);
+            let dev = (f.knee_levers[0] - 0.5).abs();
+            assert!(dev <= 0.05);
+            spread = spread.max(dev);
+        }
+        assert!(spread > 0.04, "noise should use its full range");
+    }
+
+    #[test]
+    fn test_dropout_bursts_and_clear() {
+        let mut faults = SensorFaults::default();
+        faults.set(
+            SensorChannel::Bar(2),
+            FaultKind::Dropout {
+                per_sec: 5.0,
+                ms: 20,
+            },
+        );
+        let dropped = (0..2000)
+            .filter(|_| {
+                let mut f = frame();
+                faults.apply(&mut f, 1000);
+                f.bar_sensors[2] == 0.0
+            })
+            .count();
+        // ~10 bursts × 20 ms over 2 s
+        assert!((50..500).contains(&dropped), "dropped {} ticks", dropped);
+
+        faults.clear(SensorChannel::Bar(2));
+        assert!(faults.is_empty());
+        let mut f = frame();
+        faults.apply(&mut f, 1000);
+        assert_eq!(f.bar_sensors[2], 0.5);
+    }
+}
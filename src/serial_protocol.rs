@@ -0,0 +1,1423 @@
+//! Teensy serial wire format, shared by the serial reader and the emulator.
+//!
+//! Always compiled (no `serialport` dependency) so the frame codec can be
+//! tested and driven without hardware. Two protocol versions share the
+//! stream decoder, which tells them apart by sync word:
+//!
+//! - v1 (0xBEEF): fixed 34-byte frames, 13 channels in a fixed order.
+//! - v2 (0xBEE2): a version byte, packet type, sequence number and channel
+//!   count, plus a descriptor packet mapping channels to roles, so the
+//!   hardware can grow channels without breaking the host.
+
+use crate::device_clock::{ClockStats, DeviceClock};
+use crate::sensor_curve::Curve;
+use crate::sensor_faults::SensorChannel;
+use crate::types::*;
+use byteorder::{LittleEndian, ReadBytesExt};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+/// Binary protocol from Teensy:
+///
+/// | Offset | Size | Field        |
+/// |--------|------|--------------|
+/// | 0      | 2    | sync (0xBEEF)|
+/// | 2      | 4    | timestamp_us (u32, wrapping) |
+/// | 6      | 2×13 | ADC values (u16 × 13 channels) |
+/// | 32     | 2    | CRC16        |
+/// | Total: 34 bytes              |
+///
+/// Channel order: A0=pedal_A, A1=pedal_B, A2=pedal_C,
+///   A3=LKL, A4=LKR, A5=LKV, A6=RKL, A7=RKR, A8=volume,
+///   A9=bar_fret0, A10=bar_fret5, A11=bar_fret10, A12=bar_fret15
+pub const FRAME_SIZE: usize = 34;
+pub const SYNC_WORD: u16 = 0xBEEF;
+pub const NUM_CHANNELS: usize = 13;
+
+/// Protocol v2 packet (little-endian):
+///
+/// | Offset | Size | Field        |
+/// |--------|------|--------------|
+/// | 0      | 2    | sync (0xBEE2)|
+/// | 2      | 1    | version (2)  |
+/// | 3      | 1    | type: 0 = data, 1 = descriptor, 2 = command reply |
+/// | 4      | 2    | sequence (u16, wrapping, counts every packet) |
+/// | 6      | 1    | channel count N (replies: payload length) |
+///
+/// Data packets continue with timestamp_us (u32) and N × u16 ADC values;
+/// descriptors with N × (role u8, index u8); replies with command, id,
+/// status and N payload bytes. All end with a CRC16 over everything before
+/// it. Data: 13 + 2N bytes; descriptor: 9 + 2N bytes; reply: 12 + N bytes.
+pub const SYNC_WORD_V2: u16 = 0xBEE2;
+pub const V2_HEADER_SIZE: usize = 7;
+/// Largest channel count the host accepts in a v2 packet
+pub const MAX_CHANNELS_V2: usize = 32;
+const V2_DATA: u8 = 0;
+const V2_DESCRIPTOR: u8 = 1;
+const V2_REPLY: u8 = 2;
+
+/// Host → device command frame (little-endian):
+///
+/// | Offset | Size | Field        |
+/// |--------|------|--------------|
+/// | 0      | 2    | sync (0xBEC0)|
+/// | 2      | 1    | command code |
+/// | 3      | 1    | id (echoed in the reply) |
+/// | 4      | 1    | payload length N |
+/// | 5      | N    | payload      |
+/// | 5 + N  | 2    | CRC16        |
+///
+/// The device answers every command with a v2 reply packet carrying its
+/// settings after the command, so replies interleave with data frames on the
+/// same stream (in either protocol version).
+pub const COMMAND_SYNC: u16 = 0xBEC0;
+pub const COMMAND_HEADER_SIZE: usize = 5;
+/// Bytes of `DeviceInfo` at the start of every reply payload
+pub const DEVICE_INFO_SIZE: usize = 9;
+/// Sample rates the firmware accepts (Hz)
+pub const DEVICE_RATE_RANGE: std::ops::RangeInclusive<u16> = 10..=4000;
+/// Hardware averaging counts the Teensy ADC supports
+pub const DEVICE_AVERAGING: [u8; 5] = [1, 4, 8, 16, 32];
+/// ADC resolutions the firmware accepts; values are always sent on the
+/// 12-bit scale
+pub const DEVICE_RESOLUTIONS: [u8; 3] = [8, 10, 12];
+
+/// Wire protocol version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    V1,
+    V2,
+}
+
+impl std::str::FromStr for ProtocolVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v1" | "1" => Ok(Self::V1),
+            "v2" | "2" => Ok(Self::V2),
+            other => Err(format!("unknown protocol '{}' (expected v1 or v2)", other)),
+        }
+    }
+}
+
+/// What a v2 channel measures. Roles the host has no slot for (a fourth
+/// pedal, a second bar rail, a pick sensor) decode as `Other` and are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelRole {
+    Pedal(u8),
+    Lever(u8),
+    Volume,
+    Bar(u8),
+    Other(u8),
+}
+
+impl ChannelRole {
+    fn from_wire(role: u8, index: u8) -> Self {
+        match role {
+            1 if (index as usize) < 3 => Self::Pedal(index),
+            2 if (index as usize) < 5 => Self::Lever(index),
+            3 => Self::Volume,
+            4 if (index as usize) < 4 => Self::Bar(index),
+            _ => Self::Other(index),
+        }
+    }
+
+    fn to_wire(self) -> [u8; 2] {
+        match self {
+            Self::Pedal(i) => [1, i],
+            Self::Lever(i) => [2, i],
+            Self::Volume => [3, 0],
+            Self::Bar(i) => [4, i],
+            Self::Other(i) => [0xFF, i],
+        }
+    }
+
+    /// Position in the v1 channel order, which is also the calibration index.
+    pub fn slot(self) -> Option<usize> {
+        match self {
+            Self::Pedal(i) => Some(i as usize),
+            Self::Lever(i) => Some(3 + i as usize),
+            Self::Volume => Some(8),
+            Self::Bar(i) => Some(9 + i as usize),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+/// Role of each channel, in wire order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMap {
+    pub roles: Vec<ChannelRole>,
+}
+
+impl ChannelMap {
+    /// The fixed v1 layout: pedals A–C, levers, volume, bar sensors.
+    pub fn v1() -> Self {
+        let roles = (0..3)
+            .map(ChannelRole::Pedal)
+            .chain((0..5).map(ChannelRole::Lever))
+            .chain(std::iter::once(ChannelRole::Volume))
+            .chain((0..4).map(ChannelRole::Bar))
+            .collect();
+        Self { roles }
+    }
+
+    /// Calibrate raw counts into a sensor frame. Slots no channel maps to
+    /// read 0.
+    pub fn to_sensor_frame(
+        &self,
+        raw: &[u16],
+        cal: &Calibration,
+        timestamp_us: u64,
+    ) -> SensorFrame {
+        let mut slots = [0.0f32; NUM_CHANNELS];
+        for (role, &value) in self.roles.iter().zip(raw) {
+            if let Some(slot) = role.slot() {
+                slots[slot] = cal.to_unit(slot, value);
+            }
+        }
+        frame_from_slots(&slots, timestamp_us)
+    }
+
+    /// Raw counts for a sensor frame in this map's channel order. Channels
+    /// without a slot read 0.
+    pub fn raw_from(&self, frame: &SensorFrame, cal: &Calibration) -> Vec<u16> {
+        let slots = sensor_to_raw(frame, cal);
+        self.roles
+            .iter()
+            .map(|role| role.slot().map_or(0, |s| slots[s]))
+            .collect()
+    }
+}
+
+impl Default for ChannelMap {
+    fn default() -> Self {
+        Self::v1()
+    }
+}
+
+/// A decoded v2 packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketV2 {
+    Data {
+        seq: u16,
+        timestamp_us: u32,
+        raw: Vec<u16>,
+    },
+    Descriptor {
+        seq: u16,
+        map: ChannelMap,
+    },
+    Reply {
+        seq: u16,
+        reply: CommandReply,
+    },
+}
+
+impl PacketV2 {
+    pub fn seq(&self) -> u16 {
+        match self {
+            Self::Data { seq, .. } | Self::Descriptor { seq, .. } | Self::Reply { seq, .. } => *seq,
+        }
+    }
+}
+
+/// Commands the host can send the Teensy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceCommand {
+    /// Query firmware version and current settings
+    Ping,
+    /// Frames per second
+    SetRate(u16),
+    /// ADC hardware averaging: samples per reading
+    SetAveraging(u8),
+    /// ADC resolution in bits
+    SetResolution(u8),
+    /// Calibration streaming: maximum averaging at 100 Hz for steady
+    /// readings; switching it off restores the previous settings
+    CalibrationStream(bool),
+    /// Blink the LED fast for this many seconds, to find the right unit
+    Identify(u8),
+}
+
+impl DeviceCommand {
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Ping => 0x01,
+            Self::SetRate(_) => 0x02,
+            Self::SetAveraging(_) => 0x03,
+            Self::SetResolution(_) => 0x04,
+            Self::CalibrationStream(_) => 0x05,
+            Self::Identify(_) => 0x06,
+        }
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        match *self {
+            Self::Ping => Vec::new(),
+            Self::SetRate(hz) => hz.to_le_bytes().to_vec(),
+            Self::SetAveraging(n) | Self::SetResolution(n) | Self::Identify(n) => vec![n],
+            Self::CalibrationStream(on) => vec![on as u8],
+        }
+    }
+
+    /// Decode a command frame. `None` for codes this version doesn't know or
+    /// a payload of the wrong size.
+    pub fn from_frame(frame: &CommandFrame) -> Option<Self> {
+        let p = &frame.payload;
+        match (frame.code, p.len()) {
+            (0x01, 0) => Some(Self::Ping),
+            (0x02, 2) => Some(Self::SetRate(u16::from_le_bytes([p[0], p[1]]))),
+            (0x03, 1) => Some(Self::SetAveraging(p[0])),
+            (0x04, 1) => Some(Self::SetResolution(p[0])),
+            (0x05, 1) => Some(Self::CalibrationStream(p[0] != 0)),
+            (0x06, 1) => Some(Self::Identify(p[0])),
+            _ => None,
+        }
+    }
+
+    /// Check the argument against what the firmware accepts.
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::SetRate(hz) if !DEVICE_RATE_RANGE.contains(&hz) => Err(format!(
+                "sample rate {} Hz outside {}–{}",
+                hz,
+                DEVICE_RATE_RANGE.start(),
+                DEVICE_RATE_RANGE.end()
+            )),
+            Self::SetAveraging(n) if !DEVICE_AVERAGING.contains(&n) => {
+                Err(format!("averaging {} not one of {:?}", n, DEVICE_AVERAGING))
+            }
+            Self::SetResolution(bits) if !DEVICE_RESOLUTIONS.contains(&bits) => Err(format!(
+                "resolution {} bits not one of {:?}",
+                bits, DEVICE_RESOLUTIONS
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A CRC-checked host → device frame, possibly with a code this host
+/// doesn't know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandFrame {
+    pub code: u8,
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Build a command frame.
+pub fn encode_command(id: u8, command: &DeviceCommand) -> Vec<u8> {
+    let payload = command.payload();
+    let mut buf = Vec::with_capacity(COMMAND_HEADER_SIZE + payload.len() + 2);
+    buf.extend_from_slice(&COMMAND_SYNC.to_le_bytes());
+    buf.push(command.code());
+    buf.push(id);
+    buf.push(payload.len() as u8);
+    buf.extend_from_slice(&payload);
+    push_crc(buf)
+}
+
+/// Reassembles host → device command frames (the device side of the
+/// command channel, used by the emulator).
+#[derive(Default)]
+pub struct CommandDecoder {
+    buf: Vec<u8>,
+}
+
+impl CommandDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Next CRC-valid command frame, or `None` until more bytes arrive.
+    pub fn next_command(&mut self) -> Option<CommandFrame> {
+        loop {
+            let sync = COMMAND_SYNC.to_le_bytes();
+            let Some(pos) = self.buf.windows(2).position(|w| w == sync) else {
+                let keep = self.buf.len().min(1);
+                self.buf.drain(..self.buf.len() - keep);
+                return None;
+            };
+            self.buf.drain(..pos);
+            if self.buf.len() < COMMAND_HEADER_SIZE {
+                return None;
+            }
+            let len = COMMAND_HEADER_SIZE + self.buf[4] as usize + 2;
+            if self.buf.len() < len {
+                return None;
+            }
+            let crc = u16::from_le_bytes([self.buf[len - 2], self.buf[len - 1]]);
+            if crc != crc16(&self.buf[..len - 2]) {
+                debug!("Command CRC mismatch");
+                self.buf.drain(..2);
+                continue;
+            }
+            let frame = CommandFrame {
+                code: self.buf[2],
+                id: self.buf[3],
+                payload: self.buf[COMMAND_HEADER_SIZE..len - 2].to_vec(),
+            };
+            self.buf.drain(..len);
+            return Some(frame);
+        }
+    }
+}
+
+/// Outcome of a command on the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyStatus {
+    Ok,
+    InvalidArgument,
+    UnknownCommand,
+    Other(u8),
+}
+
+impl ReplyStatus {
+    fn from_wire(code: u8) -> Self {
+        match code {
+            0 => Self::Ok,
+            1 => Self::InvalidArgument,
+            2 => Self::UnknownCommand,
+            other => Self::Other(other),
+        }
+    }
+
+    fn to_wire(self) -> u8 {
+        match self {
+            Self::Ok => 0,
+            Self::InvalidArgument => 1,
+            Self::UnknownCommand => 2,
+            Self::Other(code) => code,
+        }
+    }
+}
+
+/// Firmware version and acquisition settings, carried by every reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// (major, minor, patch)
+    pub firmware: (u8, u8, u8),
+    /// Wire protocol of the data frames (1 or 2)
+    pub protocol: u8,
+    pub rate_hz: u16,
+    pub resolution_bits: u8,
+    pub averaging: u8,
+    pub calibration_streaming: bool,
+}
+
+impl DeviceInfo {
+    fn to_wire(self) -> [u8; DEVICE_INFO_SIZE] {
+        let (major, minor, patch) = self.firmware;
+        let rate = self.rate_hz.to_le_bytes();
+        [
+            major,
+            minor,
+            patch,
+            self.protocol,
+            rate[0],
+            rate[1],
+            self.resolution_bits,
+            self.averaging,
+            self.calibration_streaming as u8,
+        ]
+    }
+
+    fn from_wire(b: &[u8]) -> Self {
+        Self {
+            firmware: (b[0], b[1], b[2]),
+            protocol: b[3],
+            rate_hz: u16::from_le_bytes([b[4], b[5]]),
+            resolution_bits: b[6],
+            averaging: b[7],
+            calibration_streaming: b[8] != 0,
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (major, minor, patch) = self.firmware;
+        write!(
+            f,
+            "firmware {}.{}.{}, protocol v{}, {} Hz, {}-bit, averaging {}{}",
+            major,
+            minor,
+            patch,
+            self.protocol,
+            self.rate_hz,
+            self.resolution_bits,
+            self.averaging,
+            if self.calibration_streaming {
+                ", calibration streaming"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// The device's answer to one command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandReply {
+    /// Code of the command answered
+    pub code: u8,
+    pub id: u8,
+    pub status: ReplyStatus,
+    /// Settings after the command
+    pub info: DeviceInfo,
+}
+
+/// Calibration: maps raw ADC (0–4095 for Teensy's 12-bit ADC) to 0.0–1.0.
+/// Each channel has a rest and a full-travel raw value; a channel whose
+/// reading falls as travel increases simply has `full < rest`. For bar
+/// sensors, rest is the baseline with no bar and full the reading with the
+/// bar directly over the sensor.
+///
+/// The rest→full line gives a normalized reading; each channel's `Curve`
+/// then maps that reading to travel, correcting the hall sensors'
+/// nonlinear response.
+///
+/// Persisted as JSON (`sensor_calibration.json`), one entry per channel:
+/// `{"channels": [{"channel": "pedal:A", "rest": 212, "full": 3710}, ...]}`,
+/// with an optional `"curve": {"table": [[0.0, 0.0], [0.12, 0.5], ...]}` or
+/// `"curve": {"polynomial": [c0, c1, ...]}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    /// (rest_raw, full_raw) for each of 13 channels, in v1 channel order
+    pub ranges: [(u16, u16); NUM_CHANNELS],
+    /// Linearization per channel
+    pub curves: [Curve; NUM_CHANNELS],
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            // Default range: 200–3800 out of 0–4095 (Teensy 12-bit ADC).
+            // Margins avoid noise near rails: SS49E outputs ~0.2V at rest
+            // (ADC ~200) and most hall/pot sensors don't reach full 3.3V
+            // (ADC ~3800). Real calibration should replace these per-channel
+            // by observing actual sensor values at rest and fully engaged.
+            ranges: [(200, 3800); NUM_CHANNELS],
+            curves: Default::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CalibrationFile {
+    channels: Vec<ChannelCalibration>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChannelCalibration {
+    channel: String,
+    rest: u16,
+    full: u16,
+    #[serde(default, skip_serializing_if = "Curve::is_linear")]
+    curve: Curve,
+}
+
+/// The sensor behind each v1 channel slot.
+pub fn slot_channel(slot: usize) -> SensorChannel {
+    match slot {
+        0..=2 => SensorChannel::Pedal(slot),
+        3..=7 => SensorChannel::Lever(slot - 3),
+        8 => SensorChannel::Volume,
+        _ => SensorChannel::Bar(slot - 9),
+    }
+}
+
+impl Calibration {
+    /// Passes raw counts through unchanged (0–4095 ↔ 0.0–1.0), for
+    /// capturing raw values during calibration.
+    pub fn identity() -> Self {
+        Self {
+            ranges: [(0, 4095); NUM_CHANNELS],
+            curves: Default::default(),
+        }
+    }
+
+    /// Map a raw ADC count on `channel` to 0.0–1.0.
+    pub fn to_unit(&self, channel: usize, raw: u16) -> f32 {
+        let (rest, full) = self.ranges[channel];
+        let span = full as f32 - rest as f32;
+        if span.abs() < 1.0 {
+            return 0.0;
+        }
+        self.curves[channel].apply((raw as f32 - rest as f32) / span)
+    }
+
+    /// Inverse of the calibration: a 0.0–1.0 value back to a raw ADC count.
+    pub fn to_raw(&self, channel: usize, value: f32) -> u16 {
+        let (rest, full) = self.ranges[channel];
+        let reading = self.curves[channel].invert(value);
+        (rest as f32 + reading * (full as f32 - rest as f32)).round() as u16
+    }
+
+    pub fn is_inverted(&self, channel: usize) -> bool {
+        let (rest, full) = self.ranges[channel];
+        full < rest
+    }
+
+    pub fn to_json(&self) -> String {
+        let file = CalibrationFile {
+            channels: self
+                .ranges
+                .iter()
+                .enumerate()
+                .zip(&self.curves)
+                .map(|((slot, &(rest, full)), curve)| ChannelCalibration {
+                    channel: slot_channel(slot).to_string(),
+                    rest,
+                    full,
+                    curve: curve.clone(),
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&file).expect("calibration serializes")
+    }
+
+    /// Parse the JSON form. Channels not listed keep their defaults.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let file: CalibrationFile = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut cal = Self::default();
+        for entry in file.channels {
+            let channel: SensorChannel = entry.channel.parse()?;
+            let slot = (0..NUM_CHANNELS)
+                .find(|&s| slot_channel(s) == channel)
+                .ok_or_else(|| format!("no ADC channel for {}", channel))?;
+            entry
+                .curve
+                .validate()
+                .map_err(|e| format!("{}: {}", channel, e))?;
+            cal.ranges[slot] = (entry.rest, entry.full);
+            cal.curves[slot] = entry.curve;
+        }
+        Ok(cal)
+    }
+
+    /// Load from a JSON file. Returns None if file is absent or malformed.
+    pub fn load(path: &std::path::Path) -> Option<Self> {
+        let data = std::fs::read_to_string(path).ok()?;
+        match Self::from_json(&data) {
+            Ok(c) => {
+                info!("Loaded sensor calibration from {:?}", path);
+                Some(c)
+            }
+            Err(e) => {
+                warn!("Failed to parse sensor calibration file {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json())?;
+        info!("Sensor calibration saved to {:?}", path);
+        Ok(())
+    }
+}
+
+/// Raw ADC counts for a sensor frame, in wire channel order.
+pub fn sensor_to_raw(frame: &SensorFrame, cal: &Calibration) -> [u16; NUM_CHANNELS] {
+    let values = frame
+        .pedals
+        .iter()
+        .chain(&frame.knee_levers)
+        .chain(std::iter::once(&frame.volume))
+        .chain(&frame.bar_sensors);
+    let mut raw = [0u16; NUM_CHANNELS];
+    for (i, (r, &v)) in raw.iter_mut().zip(values).enumerate() {
+        *r = cal.to_raw(i, v);
+    }
+    raw
+}
+
+/// Build a frame with a valid CRC.
+pub fn encode_frame(raw: &[u16; NUM_CHANNELS], timestamp_us: u32) -> [u8; FRAME_SIZE] {
+    let mut buf = [0u8; FRAME_SIZE];
+    buf[0..2].copy_from_slice(&SYNC_WORD.to_le_bytes());
+    buf[2..6].copy_from_slice(&timestamp_us.to_le_bytes());
+    for (i, value) in raw.iter().enumerate() {
+        buf[6 + i * 2..8 + i * 2].copy_from_slice(&value.to_le_bytes());
+    }
+    let crc = crc16(&buf[..FRAME_SIZE - 2]);
+    buf[FRAME_SIZE - 2..].copy_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+fn v2_header(packet_type: u8, seq: u16, count: usize) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(13 + 2 * count);
+    buf.extend_from_slice(&SYNC_WORD_V2.to_le_bytes());
+    buf.push(2);
+    buf.push(packet_type);
+    buf.extend_from_slice(&seq.to_le_bytes());
+    buf.push(count as u8);
+    buf
+}
+
+fn push_crc(mut buf: Vec<u8>) -> Vec<u8> {
+    let crc = crc16(&buf);
+    buf.extend_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+/// Build a v2 data packet.
+pub fn encode_data_v2(seq: u16, timestamp_us: u32, raw: &[u16]) -> Vec<u8> {
+    let mut buf = v2_header(V2_DATA, seq, raw.len());
+    buf.extend_from_slice(&timestamp_us.to_le_bytes());
+    for value in raw {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+    push_crc(buf)
+}
+
+/// Build a v2 descriptor packet.
+pub fn encode_descriptor_v2(seq: u16, map: &ChannelMap) -> Vec<u8> {
+    let mut buf = v2_header(V2_DESCRIPTOR, seq, map.roles.len());
+    for role in &map.roles {
+        buf.extend_from_slice(&role.to_wire());
+    }
+    push_crc(buf)
+}
+
+/// Build a v2 reply packet.
+pub fn encode_reply_v2(seq: u16, reply: &CommandReply) -> Vec<u8> {
+    let mut buf = v2_header(V2_REPLY, seq, DEVICE_INFO_SIZE);
+    buf.extend_from_slice(&[reply.code, reply.id, reply.status.to_wire()]);
+    buf.extend_from_slice(&reply.info.to_wire());
+    push_crc(buf)
+}
+
+/// Total length of a v2 packet from its 7-byte header.
+fn v2_packet_len(header: &[u8]) -> Result<usize, String> {
+    let (version, packet_type, count) = (header[2], header[3], header[6] as usize);
+    if version != 2 {
+        return Err(format!("unsupported protocol version {}", version));
+    }
+    if count > MAX_CHANNELS_V2 {
+        return Err(format!(
+            "channel count {} exceeds {}",
+            count, MAX_CHANNELS_V2
+        ));
+    }
+    match packet_type {
+        V2_DATA => Ok(V2_HEADER_SIZE + 4 + 2 * count + 2),
+        V2_DESCRIPTOR => Ok(V2_HEADER_SIZE + 2 * count + 2),
+        V2_REPLY if count < DEVICE_INFO_SIZE => {
+            Err(format!("reply payload {} bytes is too short", count))
+        }
+        V2_REPLY => Ok(V2_HEADER_SIZE + 3 + count + 2),
+        other => Err(format!("unknown packet type {}", other)),
+    }
+}
+
+/// Parse one complete v2 packet.
+pub fn parse_packet_v2(data: &[u8]) -> Result<PacketV2, String> {
+    if data.len() < V2_HEADER_SIZE {
+        return Err(format!("wrong size: {}", data.len()));
+    }
+    let len = v2_packet_len(data)?;
+    if data.len() != len {
+        return Err(format!("wrong size: {} (expected {})", data.len(), len));
+    }
+    let sync = u16::from_le_bytes([data[0], data[1]]);
+    if sync != SYNC_WORD_V2 {
+        return Err(format!("bad sync: 0x{:04X}", sync));
+    }
+    let received_crc = u16::from_le_bytes([data[len - 2], data[len - 1]]);
+    let computed_crc = crc16(&data[..len - 2]);
+    if received_crc != computed_crc {
+        return Err(format!(
+            "CRC mismatch: received 0x{:04X}, computed 0x{:04X}",
+            received_crc, computed_crc
+        ));
+    }
+
+    let seq = u16::from_le_bytes([data[4], data[5]]);
+    let body = &data[V2_HEADER_SIZE..len - 2];
+    if data[3] == V2_REPLY {
+        // Fields past the known `DeviceInfo` are from newer firmware
+        return Ok(PacketV2::Reply {
+            seq,
+            reply: CommandReply {
+                code: body[0],
+                id: body[1],
+                status: ReplyStatus::from_wire(body[2]),
+                info: DeviceInfo::from_wire(&body[3..]),
+            },
+        });
+    }
+    if data[3] == V2_DESCRIPTOR {
+        let roles = body
+            .chunks_exact(2)
+            .map(|e| ChannelRole::from_wire(e[0], e[1]))
+            .collect();
+        return Ok(PacketV2::Descriptor {
+            seq,
+            map: ChannelMap { roles },
+        });
+    }
+    let timestamp_us = u32::from_le_bytes([body[0], body[1], body[2], body[3]]);
+    let raw = body[4..]
+        .chunks_exact(2)
+        .map(|v| u16::from_le_bytes([v[0], v[1]]))
+        .collect();
+    Ok(PacketV2::Data {
+        seq,
+        timestamp_us,
+        raw,
+    })
+}
+
+/// Reassembles frames from an arbitrarily chunked byte stream, skipping
+/// garbage, truncated frames and CRC failures. v1 and v2 packets are told
+/// apart by sync word, so either firmware works without configuration.
+#[derive(Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    /// Frames decoded successfully
+    pub frames: u64,
+    /// Candidate frames rejected (bad CRC, truncated)
+    pub errors: u64,
+    /// Protocol of the most recent valid packet
+    pub version: Option<ProtocolVersion>,
+    /// v2 packets missing from the sequence numbering
+    pub lost_packets: u64,
+    /// Channel layout from the latest v2 descriptor
+    channel_map: Option<ChannelMap>,
+    last_seq: Option<u16>,
+    /// Maps device timestamps onto the host clock
+    device_clock: DeviceClock,
+    /// Command replies not yet collected
+    replies: VecDeque<CommandReply>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Device timing: drift, jitter, frames missing from the timestamps.
+    pub fn clock_stats(&self) -> ClockStats {
+        self.device_clock.stats()
+    }
+
+    /// Next command reply decoded from the stream. Replies are collected
+    /// as `next_frame` walks past them.
+    pub fn next_reply(&mut self) -> Option<CommandReply> {
+        self.replies.pop_front()
+    }
+
+    /// Channel layout announced by the device (v2 only).
+    pub fn channel_map(&self) -> Option<&ChannelMap> {
+        self.channel_map.as_ref()
+    }
+
+    /// Next valid frame in the buffer, or `None` until more bytes arrive.
+    pub fn next_frame(&mut self, cal: &Calibration, clock: &SessionClock) -> Option<SensorFrame> {
+        while self.buf.len() >= 2 {
+            let Some(sync_pos) = find_sync(&self.buf) else {
+                // No sync found — discard all but last byte
+                let keep = self.buf.len().saturating_sub(1);
+                self.buf.drain(..keep);
+                return None;
+            };
+            if sync_pos > 0 {
+                // Discard bytes before sync
+                debug!("Skipping {} bytes to sync", sync_pos);
+                self.buf.drain(..sync_pos);
+            }
+
+            let v2 = self.buf[0] == 0xE2;
+            let len = if v2 {
+                if self.buf.len() < V2_HEADER_SIZE {
+                    return None;
+                }
+                match v2_packet_len(&self.buf) {
+                    Ok(len) => len,
+                    Err(e) => {
+                        self.reject(&e);
+                        continue;
+                    }
+                }
+            } else {
+                FRAME_SIZE
+            };
+            if self.buf.len() < len {
+                return None;
+            }
+
+            let result = if v2 {
+                parse_packet_v2(&self.buf[..len]).map(|p| self.accept_v2(p, cal, clock))
+            } else {
+                parse_frame_device(&self.buf[..len], cal).map(|(mut f, device_us)| {
+                    self.version = Some(ProtocolVersion::V1);
+                    f.timestamp_us = self.device_clock.map(device_us, clock.now_us());
+                    Some(f)
+                })
+            };
+            match result {
+                Ok(frame) => {
+                    self.buf.drain(..len);
+                    if let Some(sensor) = frame {
+                        self.frames += 1;
+                        return Some(sensor);
+                    }
+                }
+                Err(e) => self.reject(&e),
+            }
+        }
+        None
+    }
+
+    fn reject(&mut self, e: &str) {
+        // Drop only the sync word: a truncated frame may be followed by a
+        // good one within the bytes it claimed.
+        self.errors += 1;
+        debug!("Frame parse error: {}", e);
+        self.buf.drain(..2);
+    }
+
+    /// Track sequence and layout; a data packet becomes a frame once its
+    /// channel layout is known (a descriptor, or 13 channels = v1 layout).
+    fn accept_v2(
+        &mut self,
+        packet: PacketV2,
+        cal: &Calibration,
+        clock: &SessionClock,
+    ) -> Option<SensorFrame> {
+        let seq = packet.seq();
+        if let Some(last) = self.last_seq {
+            let gap = seq.wrapping_sub(last).wrapping_sub(1);
+            if gap < 0x8000 {
+                self.lost_packets += gap as u64;
+            }
+        }
+        self.last_seq = Some(seq);
+
+        match packet {
+            PacketV2::Reply { reply, .. } => {
+                // Replies come as v2 packets whichever data protocol is in use
+                self.replies.push_back(reply);
+                None
+            }
+            PacketV2::Descriptor { map, .. } => {
+                self.version = Some(ProtocolVersion::V2);
+                if self.channel_map.as_ref() != Some(&map) {
+                    info!("Device channel layout: {:?}", map.roles);
+                    self.channel_map = Some(map);
+                }
+                None
+            }
+            PacketV2::Data {
+                raw, timestamp_us, ..
+            } => {
+                self.version = Some(ProtocolVersion::V2);
+                let map = match &self.channel_map {
+                    Some(map) if map.roles.len() == raw.len() => map,
+                    _ if raw.len() == NUM_CHANNELS => &ChannelMap::v1(),
+                    _ => {
+                        debug!("{}-channel packet before its descriptor", raw.len());
+                        return None;
+                    }
+                };
+                let host_us = self.device_clock.map(timestamp_us, clock.now_us());
+                Some(map.to_sensor_frame(&raw, cal, host_us))
+            }
+        }
+    }
+}
+
+/// Position of the first v1 (0xBEEF) or v2 (0xBEE2) sync word.
+pub fn find_sync(buf: &[u8]) -> Option<usize> {
+    (0..buf.len().saturating_sub(1))
+        .find(|&i| (buf[i] == 0xEF || buf[i] == 0xE2) && buf[i + 1] == 0xBE)
+}
+
+/// Parse a v1 frame, stamped with the host receive time.
+pub fn parse_frame(
+    data: &[u8],
+    cal: &Calibration,
+    clock: &SessionClock,
+) -> Result<SensorFrame, String> {
+    let (mut frame, _device_us) = parse_frame_device(data, cal)?;
+    frame.timestamp_us = clock.now_us();
+    Ok(frame)
+}
+
+/// Parse a v1 frame, returning it with its raw device timestamp (the
+/// frame's own `timestamp_us` is left 0 for the caller to map).
+pub fn parse_frame_device(data: &[u8], cal: &Calibration) -> Result<(SensorFrame, u32), String> {
+    if data.len() != FRAME_SIZE {
+        return Err(format!("wrong size: {}", data.len()));
+    }
+
+    let mut cursor = Cursor::new(data);
+
+    // Sync word
+    let sync = cursor
+        .read_u16::<LittleEndian>()
+        .map_err(|e| e.to_string())?;
+    if sync != SYNC_WORD {
+        return Err(format!("bad sync: 0x{:04X}", sync));
+    }
+
+    // Timestamp from Teensy (u32 microseconds, wrapping)
+    let device_us = cursor
+        .read_u32::<LittleEndian>()
+        .map_err(|e| e.to_string())?;
+
+    // ADC values (13 channels, u16 each)
+    let mut raw = [0u16; NUM_CHANNELS];
+    for ch in raw.iter_mut() {
+        *ch = cursor
+            .read_u16::<LittleEndian>()
+            .map_err(|e| e.to_string())?;
+    }
+
+    // CRC16
+    let received_crc = cursor
+        .read_u16::<LittleEndian>()
+        .map_err(|e| e.to_string())?;
+    let computed_crc = crc16(&data[..FRAME_SIZE - 2]);
+    if received_crc != computed_crc {
+        return Err(format!(
+            "CRC mismatch: received 0x{:04X}, computed 0x{:04X}",
+            received_crc, computed_crc
+        ));
+    }
+
+    // Calibrate: map raw ADC to 0.0–1.0
+    let mut calibrated = [0.0f32; NUM_CHANNELS];
+    for (i, value) in calibrated.iter_mut().enumerate() {
+        *value = cal.to_unit(i, raw[i]);
+    }
+
+    Ok((frame_from_slots(&calibrated, 0), device_us))
+}
+
+/// Build a sensor frame from calibrated values in v1 channel order.
+fn frame_from_slots(slots: &[f32; NUM_CHANNELS], timestamp_us: u64) -> SensorFrame {
+    SensorFrame {
+        timestamp_us,
+        pedals: [slots[0], slots[1], slots[2]],
+        knee_levers: [slots[3], slots[4], slots[5], slots[6], slots[7]],
+        volume: slots[8],
+        bar_sensors: [slots[9], slots[10], slots[11], slots[12]],
+        // Hardware doesn't know which strings are picked — audio detection handles this.
+        string_active: [false; 10],
+    }
+}
+
+/// CRC-16/CCITT-FALSE
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a valid 34-byte frame with correct CRC.
+    fn make_frame(adc_values: &[u16; NUM_CHANNELS], timestamp: u32) -> Vec<u8> {
+        let mut buf = vec![0u8; FRAME_SIZE];
+        // Sync
+        buf[0] = (SYNC_WORD & 0xFF) as u8;
+        buf[1] = (SYNC_WORD >> 8) as u8;
+        // Timestamp
+        buf[2] = (timestamp & 0xFF) as u8;
+        buf[3] = ((timestamp >> 8) & 0xFF) as u8;
+        buf[4] = ((timestamp >> 16) & 0xFF) as u8;
+        buf[5] = ((timestamp >> 24) & 0xFF) as u8;
+        // ADC values
+        for i in 0..NUM_CHANNELS {
+            buf[6 + i * 2] = (adc_values[i] & 0xFF) as u8;
+            buf[6 + i * 2 + 1] = (adc_values[i] >> 8) as u8;
+        }
+        // CRC over first 32 bytes
+        let crc = crc16(&buf[..FRAME_SIZE - 2]);
+        buf[FRAME_SIZE - 2] = (crc & 0xFF) as u8;
+        buf[FRAME_SIZE - 1] = (crc >> 8) as u8;
+        buf
+    }
+
+    #[test]
+    fn test_crc16() {
+        let data = b"123456789";
+        let crc = crc16(data);
+        assert_eq!(crc, 0x29B1, "CRC-16/CCITT-FALSE of '123456789'");
+    }
+
+    #[test]
+    fn test_find_sync() {
+        let buf = [0x00, 0x00, 0xEF, 0xBE, 0x01, 0x02];
+        assert_eq!(find_sync(&buf), Some(2));
+    }
+
+    #[test]
+    fn test_find_sync_at_start() {
+        let buf = [0xEF, 0xBE, 0x01, 0x02];
+        assert_eq!(find_sync(&buf), Some(0));
+    }
+
+    #[test]
+    fn test_find_sync_not_found() {
+        let buf = [0x00, 0x01, 0x02, 0x03];
+        assert_eq!(find_sync(&buf), None);
+    }
+
+    #[test]
+    fn test_find_sync_partial_at_end() {
+        // 0xEF at last byte — can't confirm sync, should return None
+        let buf = [0x00, 0x01, 0xEF];
+        assert_eq!(find_sync(&buf), None);
+    }
+
+    #[test]
+    fn test_find_sync_empty() {
+        assert_eq!(find_sync(&[]), None);
+        assert_eq!(find_sync(&[0xEF]), None);
+    }
+
+    #[test]
+    fn test_parse_valid_frame() {
+        let adc = [2000u16; NUM_CHANNELS];
+        let frame = make_frame(&adc, 1000);
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let result = parse_frame(&frame, &cal, &clock);
+        assert!(result.is_ok());
+        let sf = result.unwrap();
+        // With default cal (200, 3800), raw 2000 → (2000-200)/3600 ≈ 0.5
+        assert!((sf.pedals[0] - 0.5).abs() < 0.01);
+        assert!((sf.volume - 0.5).abs() < 0.01);
+        assert!((sf.bar_sensors[0] - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_frame_bad_crc() {
+        let adc = [2000u16; NUM_CHANNELS];
+        let mut frame = make_frame(&adc, 1000);
+        frame[FRAME_SIZE - 1] ^= 0xFF;
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let result = parse_frame(&frame, &cal, &clock);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("CRC mismatch"));
+    }
+
+    #[test]
+    fn test_parse_frame_bad_sync() {
+        let adc = [2000u16; NUM_CHANNELS];
+        let mut frame = make_frame(&adc, 1000);
+        frame[0] = 0x00;
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let result = parse_frame(&frame, &cal, &clock);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("bad sync"));
+    }
+
+    #[test]
+    fn test_parse_frame_wrong_size() {
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let result = parse_frame(&[0u8; 10], &cal, &clock);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("wrong size"));
+    }
+
+    #[test]
+    fn test_calibration_clamps() {
+        let mut adc = [0u16; NUM_CHANNELS];
+        adc[0] = 0; // below min (200) → clamps to 0.0
+        adc[1] = 4095; // above max (3800) → clamps to 1.0
+        adc[2] = 200; // exactly at min → 0.0
+        let frame = make_frame(&adc, 500);
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let sf = parse_frame(&frame, &cal, &clock).unwrap();
+        assert_eq!(sf.pedals[0], 0.0, "below min clamps to 0");
+        assert_eq!(sf.pedals[1], 1.0, "above max clamps to 1");
+        assert_eq!(sf.pedals[2], 0.0, "exactly at min = 0");
+    }
+
+    #[test]
+    fn test_find_sync_with_garbage() {
+        let mut buf = vec![0xAA, 0xBB, 0xCC, 0xDD, 0xEE];
+        buf.push(0xEF);
+        buf.push(0xBE);
+        buf.push(0x00);
+        assert_eq!(find_sync(&buf), Some(5));
+    }
+
+    #[test]
+    fn test_channel_mapping() {
+        let mut adc = [0u16; NUM_CHANNELS];
+        adc[0] = 3800;
+        adc[1] = 3800;
+        adc[2] = 3800; // pedals → 1.0
+        adc[3] = 200;
+        adc[4] = 200;
+        adc[5] = 200;
+        adc[6] = 200;
+        adc[7] = 200; // levers → 0.0
+        adc[8] = 2000; // volume → ~0.5
+        adc[9] = 3000;
+        adc[10] = 3000;
+        adc[11] = 3000;
+        adc[12] = 3000; // bar → ~0.78
+        let frame = make_frame(&adc, 0);
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let sf = parse_frame(&frame, &cal, &clock).unwrap();
+        assert!((sf.pedals[0] - 1.0).abs() < 0.01);
+        assert!((sf.pedals[1] - 1.0).abs() < 0.01);
+        assert!((sf.pedals[2] - 1.0).abs() < 0.01);
+        for i in 0..5 {
+            assert_eq!(sf.knee_levers[i], 0.0);
+        }
+        assert!((sf.volume - 0.5).abs() < 0.01);
+        let expected = (3000.0 - 200.0) / 3600.0;
+        for i in 0..4 {
+            assert!((sf.bar_sensors[i] - expected).abs() < 0.01);
+        }
+        assert_eq!(sf.string_active, [false; 10]);
+    }
+
+    #[test]
+    fn test_encode_matches_reference() {
+        let mut adc = [0u16; NUM_CHANNELS];
+        for (i, v) in adc.iter_mut().enumerate() {
+            *v = 300 + i as u16 * 250;
+        }
+        assert_eq!(
+            encode_frame(&adc, 0xDEADBEEF).to_vec(),
+            make_frame(&adc, 0xDEADBEEF)
+        );
+    }
+
+    #[test]
+    fn test_sensor_raw_roundtrip() {
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let sf = SensorFrame {
+            timestamp_us: 0,
+            pedals: [1.0, 0.25, 0.0],
+            knee_levers: [0.0, 0.5, 0.0, 0.75, 1.0],
+            volume: 0.6,
+            bar_sensors: [0.1, 0.9, 0.3, 0.0],
+            string_active: [false; 10],
+        };
+        let decoded =
+            parse_frame(&encode_frame(&sensor_to_raw(&sf, &cal), 0), &cal, &clock).unwrap();
+        for (a, b) in decoded.pedals.iter().zip(&sf.pedals) {
+            assert!((a - b).abs() < 1e-3);
+        }
+        for (a, b) in decoded.knee_levers.iter().zip(&sf.knee_levers) {
+            assert!((a - b).abs() < 1e-3);
+        }
+        assert!((decoded.volume - 0.6).abs() < 1e-3);
+        for (a, b) in decoded.bar_sensors.iter().zip(&sf.bar_sensors) {
+            assert!((a - b).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_decoder_recovers_from_garbage_and_truncation() {
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let good = encode_frame(&[2000; NUM_CHANNELS], 1);
+        let mut stream = vec![0x12, 0xEF, 0xBE, 0x55];
+        stream.extend_from_slice(&good);
+        stream.extend_from_slice(&good[..20]); // truncated frame
+        stream.extend_from_slice(&good);
+        let mut bad_crc = good;
+        bad_crc[10] ^= 0x01;
+        stream.extend_from_slice(&bad_crc);
+        stream.extend_from_slice(&good);
+
+        // Feed in awkward chunks
+        let mut decoder = FrameDecoder::new();
+        let mut decoded = 0;
+        for chunk in stream.chunks(7) {
+            decoder.push(chunk);
+            while decoder.next_frame(&cal, &clock).is_some() {
+                decoded += 1;
+            }
+        }
+        assert_eq!(decoded, 3);
+        assert_eq!(decoder.frames, 3);
+        assert!(decoder.errors >= 2);
+    }
+
+    #[test]
+    fn test_v2_packet_roundtrip() {
+        let raw: Vec<u16> = (0..15).map(|i| 200 + i * 100).collect();
+        let bytes = encode_data_v2(0xFFFE, 123_456, &raw);
+        assert_eq!(bytes.len(), 13 + 2 * 15);
+        assert_eq!(
+            parse_packet_v2(&bytes).unwrap(),
+            PacketV2::Data {
+                seq: 0xFFFE,
+                timestamp_us: 123_456,
+                raw
+            }
+        );
+
+        let mut map = ChannelMap::v1();
+        map.roles.push(ChannelRole::Pedal(3)); // a fourth pedal has no slot
+        map.roles.push(ChannelRole::Other(7));
+        let bytes = encode_descriptor_v2(9, &map);
+        let PacketV2::Descriptor { seq, map: decoded } = parse_packet_v2(&bytes).unwrap() else {
+            panic!("expected descriptor");
+        };
+        assert_eq!(seq, 9);
+        assert_eq!(decoded.roles[..13], map.roles[..13]);
+        assert_eq!(decoded.roles[13], ChannelRole::Other(3));
+        assert_eq!(decoded.roles[14], ChannelRole::Other(7));
+
+        let mut bad = encode_data_v2(1, 0, &[0; 13]);
+        bad[2] = 3;
+        assert!(parse_packet_v2(&bad).unwrap_err().contains("version"));
+    }
+
+    #[test]
+    fn test_decoder_autodetects_and_counts_lost_packets() {
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let mut stream = encode_frame(&[3800; NUM_CHANNELS], 0).to_vec();
+        // v2 without a descriptor: 13 channels fall back to the v1 layout
+        stream.extend(encode_data_v2(0, 0, &[200; NUM_CHANNELS]));
+        stream.extend(encode_data_v2(1, 0, &[200; NUM_CHANNELS]));
+        stream.extend(encode_data_v2(5, 0, &[200; NUM_CHANNELS]));
+        // Swapped layout: volume first
+        let mut map = ChannelMap::v1();
+        map.roles.swap(0, 8);
+        stream.extend(encode_descriptor_v2(6, &map));
+        let mut raw = [200; NUM_CHANNELS];
+        raw[0] = 3800;
+        stream.extend(encode_data_v2(7, 0, &raw));
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&stream);
+        let first = decoder.next_frame(&cal, &clock).unwrap();
+        assert_eq!(first.pedals[0], 1.0);
+        assert_eq!(decoder.version, Some(ProtocolVersion::V1));
+        for _ in 0..3 {
+            assert_eq!(decoder.next_frame(&cal, &clock).unwrap().pedals[0], 0.0);
+        }
+        assert_eq!(decoder.version, Some(ProtocolVersion::V2));
+        let last = decoder.next_frame(&cal, &clock).unwrap();
+        assert_eq!(last.volume, 1.0);
+        assert_eq!(last.pedals[0], 0.0);
+        assert!(decoder.next_frame(&cal, &clock).is_none());
+        assert_eq!(decoder.lost_packets, 3);
+        assert_eq!(decoder.errors, 0);
+    }
+
+    #[test]
+    fn test_decoder_counts_dropped_frames_from_timestamps() {
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let mut decoder = FrameDecoder::new();
+        let mut last = 0;
+        for k in (0..20u32).filter(|k| !(8..11).contains(k)) {
+            decoder.push(&encode_frame(&[2000; NUM_CHANNELS], k * 1000));
+            let frame = decoder.next_frame(&cal, &clock).unwrap();
+            assert!(frame.timestamp_us >= last, "timestamps stay monotonic");
+            last = frame.timestamp_us;
+        }
+        let stats = decoder.clock_stats();
+        assert_eq!(stats.frames, 17);
+        assert_eq!(stats.gaps, 1);
+        assert_eq!(stats.dropped_frames, 3);
+    }
+
+    #[test]
+    fn test_command_roundtrip_and_replies_between_frames() {
+        let commands = [
+            DeviceCommand::Ping,
+            DeviceCommand::SetRate(2000),
+            DeviceCommand::SetAveraging(16),
+            DeviceCommand::SetResolution(10),
+            DeviceCommand::CalibrationStream(true),
+            DeviceCommand::Identify(5),
+        ];
+        let mut decoder = CommandDecoder::new();
+        decoder.push(&[0xC0, 0x13]); // noise, including half a sync word
+        for (id, command) in commands.iter().enumerate() {
+            let mut bytes = encode_command(id as u8, command);
+            if id == 2 {
+                // A corrupted copy first: skipped
+                let mut bad = bytes.clone();
+                bad[5] ^= 1;
+                decoder.push(&bad);
+            }
+            decoder.push(&bytes.drain(..3).collect::<Vec<_>>());
+            decoder.push(&bytes);
+        }
+        decoder.push(&[0xC0, 0xBE, 0x7F, 9, 0, 0x00, 0x00]);
+        for (id, command) in commands.iter().enumerate() {
+            let frame = decoder.next_command().unwrap();
+            assert_eq!(frame.id, id as u8);
+            assert_eq!(DeviceCommand::from_frame(&frame), Some(*command));
+        }
+        // Unknown code with a bad CRC never surfaces
+        assert!(decoder.next_command().is_none());
+        assert!(DeviceCommand::SetRate(5).validate().is_err());
+        assert!(DeviceCommand::SetAveraging(3).validate().is_err());
+
+        // Replies interleave with v1 frames without disturbing them
+        let reply = CommandReply {
+            code: 0x02,
+            id: 1,
+            status: ReplyStatus::Ok,
+            info: DeviceInfo {
+                firmware: (1, 2, 0),
+                protocol: 1,
+                rate_hz: 2000,
+                resolution_bits: 12,
+                averaging: 4,
+                calibration_streaming: false,
+            },
+        };
+        let cal = Calibration::default();
+        let clock = SessionClock::new();
+        let mut stream = encode_frame(&[2000; NUM_CHANNELS], 0).to_vec();
+        stream.extend(encode_reply_v2(0, &reply));
+        stream.extend(encode_frame(&[2000; NUM_CHANNELS], 1000));
+        let mut frames = FrameDecoder::new();
+        frames.push(&stream);
+        assert!(frames.next_frame(&cal, &clock).is_some());
+        assert!(frames.next_reply().is_none());
+        assert!(frames.next_frame(&cal, &clock).is_some());
+        assert_eq!(frames.next_reply(), Some(reply));
+        assert_eq!(frames.version, Some(ProtocolVersion::V1));
+        assert_eq!(frames.errors, 0);
+    }
+}
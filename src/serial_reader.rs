@@ -1,64 +1,190 @@
+use crate::serial_protocol::{encode_command, CommandReply, FrameDecoder, ReplyStatus};
+pub use crate::serial_protocol::{
+    Calibration, DeviceCommand, DeviceInfo, FRAME_SIZE, NUM_CHANNELS, SYNC_WORD,
+};
 use crate::types::*;
-use byteorder::{LittleEndian, ReadBytesExt};
-use crossbeam_channel::Sender;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
 use log::{debug, error, info, warn};
-use std::io::{self, Cursor, Read};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-/// Binary protocol from Teensy:
-///
-/// | Offset | Size | Field        |
-/// |--------|------|--------------|
-/// | 0      | 2    | sync (0xBEEF)|
-/// | 2      | 4    | timestamp_us (u32, wrapping) |
-/// | 6      | 2×13 | ADC values (u16 × 13 channels) |
-/// | 32     | 2    | CRC16        |
-/// | Total: 34 bytes              |
-///
-/// Channel order: A0=pedal_A, A1=pedal_B, A2=pedal_C,
-///   A3=LKL, A4=LKR, A5=LKV, A6=RKL, A7=RKR, A8=volume,
-///   A9=bar_fret0, A10=bar_fret5, A11=bar_fret10, A12=bar_fret15
-const FRAME_SIZE: usize = 34;
-const SYNC_WORD: u16 = 0xBEEF;
-const NUM_CHANNELS: usize = 13;
-
-/// Calibration: maps raw ADC (0–4095 for Teensy's 12-bit ADC) to 0.0–1.0.
-/// Each channel has min/max raw values.
+/// A byte stream with no data for this long counts as a lost device (the
+/// Teensy sends ~1000 frames/s).
+const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long `DeviceHandle::send` waits for the device to answer
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A duplex device stream: frames in, commands out.
+pub trait Port: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Port for T {}
+
+/// Opens the device stream. Called again after every disconnect.
+pub trait PortOpener: Send {
+    fn open(&mut self) -> io::Result<Box<dyn Port>>;
+}
+
+impl<F> PortOpener for F
+where
+    F: FnMut() -> io::Result<Box<dyn Port>> + Send,
+{
+    fn open(&mut self) -> io::Result<Box<dyn Port>> {
+        self()
+    }
+}
+
+/// Opens a real serial port (or pty) with `serialport`.
+#[cfg(feature = "hardware")]
+struct SerialPortOpener {
+    port_name: String,
+    baud_rate: u32,
+}
+
+#[cfg(feature = "hardware")]
+impl PortOpener for SerialPortOpener {
+    fn open(&mut self) -> io::Result<Box<dyn Port>> {
+        let port = serialport::new(&self.port_name, self.baud_rate)
+            .timeout(Duration::from_millis(100))
+            .open()?;
+        Ok(Box::new(port))
+    }
+}
+
+/// What downstream sees while the device is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisconnectPolicy {
+    /// Send nothing: consumers keep the last pedal/lever/bar state
+    #[default]
+    Hold,
+    /// Send one all-zero frame: everything released, volume off, bar lifted
+    Zero,
+}
+
+impl FromStr for DisconnectPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hold" => Ok(Self::Hold),
+            "zero" => Ok(Self::Zero),
+            _ => Err(format!("unknown disconnect policy '{}' (hold, zero)", s)),
+        }
+    }
+}
+
+/// Why `read_stream` returned.
+#[derive(Debug)]
+pub enum StreamEnd {
+    /// The stream reported end of file (device gone, emulator finished)
+    Eof,
+    Error(io::Error),
+    /// No bytes for this long
+    Stalled(Duration),
+    /// Nobody is listening any more
+    Closed,
+}
+
+impl std::fmt::Display for StreamEnd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Eof => write!(f, "end of stream"),
+            Self::Error(e) => write!(f, "{}", e),
+            Self::Stalled(quiet) => write!(f, "no data for {:?}", quiet),
+            Self::Closed => write!(f, "receiver closed"),
+        }
+    }
+}
+
+/// A command waiting to be written to the device.
+struct PendingCommand {
+    command: DeviceCommand,
+    reply_tx: Sender<CommandReply>,
+    deadline: Instant,
+}
+
+/// Sends commands to the device through a running `SerialReader`, from any
+/// thread.
 #[derive(Clone)]
-pub struct Calibration {
-    /// (min_raw, max_raw) for each of 13 channels
-    pub ranges: [(u16, u16); NUM_CHANNELS],
+pub struct DeviceHandle {
+    tx: Sender<PendingCommand>,
 }
 
-impl Default for Calibration {
-    fn default() -> Self {
-        Self {
-            // Default range: 200–3800 out of 0–4095 (Teensy 12-bit ADC).
-            // Margins avoid noise near rails: SS49E outputs ~0.2V at rest
-            // (ADC ~200) and most hall/pot sensors don't reach full 3.3V
-            // (ADC ~3800). Real calibration should replace these per-channel
-            // by observing actual sensor values at rest and fully engaged.
-            ranges: [(200, 3800); NUM_CHANNELS],
+impl DeviceHandle {
+    /// Send a command and wait for the device's reply; returns the settings
+    /// after the command. Fails if the device rejects it, or doesn't answer
+    /// within a second (e.g. while disconnected).
+    pub fn send(&self, command: DeviceCommand) -> Result<DeviceInfo, String> {
+        command.validate()?;
+        let (reply_tx, reply_rx) = bounded(1);
+        let deadline = Instant::now() + COMMAND_TIMEOUT;
+        self.tx
+            .send(PendingCommand {
+                command,
+                reply_tx,
+                deadline,
+            })
+            .map_err(|_| "serial reader has stopped".to_string())?;
+        let reply = reply_rx
+            .recv_deadline(deadline)
+            .map_err(|_| format!("no reply to {:?}", command))?;
+        match reply.status {
+            ReplyStatus::Ok => Ok(reply.info),
+            status => Err(format!("device rejected {:?}: {:?}", command, status)),
         }
     }
+
+    /// Firmware version and current settings.
+    pub fn ping(&self) -> Result<DeviceInfo, String> {
+        self.send(DeviceCommand::Ping)
+    }
 }
 
+/// Reads Teensy frames (see `serial_protocol`; v1 or v2, auto-detected)
+/// from a serial port and forwards them as `InputEvent::Sensor`.
+///
+/// A lost device (read error, end of stream, or silence) is reported as
+/// `InputEvent::Status`, and the port is reopened with exponential backoff
+/// until it comes back — unplugging the Teensy mid-session doesn't need a
+/// restart.
+///
+/// Commands go the other way through a `DeviceHandle`; startup commands
+/// are re-sent on every connection, so settings survive a replug.
 pub struct SerialReader {
     port_name: String,
     baud_rate: u32,
     tx: Sender<InputEvent>,
     clock: SessionClock,
     calibration: Calibration,
+    opener: Option<Box<dyn PortOpener>>,
+    policy: DisconnectPolicy,
+    stall_timeout: Duration,
+    backoff_min: Duration,
+    backoff_max: Duration,
+    startup_commands: Vec<DeviceCommand>,
+    command_tx: Sender<PendingCommand>,
+    command_rx: Receiver<PendingCommand>,
 }
 
 impl SerialReader {
     pub fn new(port_name: String, tx: Sender<InputEvent>, clock: SessionClock) -> Self {
+        let (command_tx, command_rx) = unbounded();
         Self {
             port_name,
             baud_rate: 115200,
             tx,
             clock,
             calibration: Calibration::default(),
+            opener: None,
+            policy: DisconnectPolicy::Hold,
+            stall_timeout: STALL_TIMEOUT,
+            backoff_min: Duration::from_millis(250),
+            backoff_max: Duration::from_secs(5),
+            startup_commands: Vec::new(),
+            command_tx,
+            command_rx,
         }
     }
 
@@ -67,352 +193,349 @@ impl SerialReader {
         self
     }
 
-    /// Run the serial reader loop. Blocks the calling thread.
-    pub fn run(&self) {
+    /// Open the device with `opener` instead of the serial port (tests,
+    /// other transports).
+    pub fn with_opener(mut self, opener: impl PortOpener + 'static) -> Self {
+        self.opener = Some(Box::new(opener));
+        self
+    }
+
+    pub fn with_disconnect_policy(mut self, policy: DisconnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Reconnect delay: starts at `min`, doubles per failed attempt, capped
+    /// at `max`.
+    pub fn with_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.backoff_min = min;
+        self.backoff_max = max.max(min);
+        self
+    }
+
+    /// Commands sent each time the device connects (sample rate, averaging,
+    /// ...). Replies are logged.
+    pub fn with_startup_commands(mut self, commands: Vec<DeviceCommand>) -> Self {
+        self.startup_commands = commands;
+        self
+    }
+
+    /// Handle for sending commands to the device while `run` is going.
+    pub fn device_handle(&self) -> DeviceHandle {
+        DeviceHandle {
+            tx: self.command_tx.clone(),
+        }
+    }
+
+    /// Run the serial reader loop. Blocks the calling thread until the
+    /// receiving side of the channel is dropped.
+    pub fn run(mut self) {
         info!(
             "Opening serial port: {} @ {}",
             self.port_name, self.baud_rate
         );
-
-        let port = serialport::new(&self.port_name, self.baud_rate)
-            .timeout(Duration::from_millis(100))
-            .open();
-
-        let mut port = match port {
-            Ok(p) => p,
-            Err(e) => {
-                error!("Failed to open serial port {}: {}", self.port_name, e);
-                error!("Is the Teensy connected? Run with --simulate for dev mode.");
-                return;
-            }
+        let Some(mut opener) = self.opener.take().or_else(|| self.serial_opener()) else {
+            error!("Built without the hardware feature: no serial port support");
+            return;
         };
 
-        info!("Serial port opened. Reading frames...");
-        let mut buf = [0u8; 256];
-        let mut frame_buf = Vec::with_capacity(FRAME_SIZE * 4);
-        let mut frame_count: u64 = 0;
-        let mut error_count: u64 = 0;
-
+        let mut attempt = 0u32;
         loop {
-            match port.read(&mut buf) {
-                Ok(n) => {
-                    frame_buf.extend_from_slice(&buf[..n]);
-
-                    // Process all complete frames in the buffer
-                    while frame_buf.len() >= FRAME_SIZE {
-                        // Find sync word
-                        if let Some(sync_pos) = find_sync(&frame_buf) {
-                            if sync_pos > 0 {
-                                // Discard bytes before sync
-                                debug!("Skipping {} bytes to sync", sync_pos);
-                                frame_buf.drain(..sync_pos);
-                            }
-                            if frame_buf.len() < FRAME_SIZE {
-                                break;
-                            }
-
-                            let frame_bytes: Vec<u8> = frame_buf.drain(..FRAME_SIZE).collect();
-
-                            match parse_frame(&frame_bytes, &self.calibration, &self.clock) {
-                                Ok(sensor) => {
-                                    let _ = self.tx.send(InputEvent::Sensor(sensor));
-                                    frame_count += 1;
-                                    if frame_count.is_multiple_of(5000) {
-                                        info!(
-                                            "Serial: {} frames, {} errors",
-                                            frame_count, error_count
-                                        );
-                                    }
-                                }
-                                Err(e) => {
-                                    error_count += 1;
-                                    debug!("Frame parse error: {}", e);
-                                }
-                            }
-                        } else {
-                            // No sync found — discard all but last byte
-                            let keep = frame_buf.len().saturating_sub(1);
-                            frame_buf.drain(..keep);
-                        }
+            match opener.open() {
+                Ok(stream) => {
+                    attempt = 0;
+                    info!("Serial port {} opened. Reading frames...", self.port_name);
+                    if !self.status(DeviceStatus::Connected {
+                        device: self.port_name.clone(),
+                    }) {
+                        return;
+                    }
+                    let end = self.read_stream(stream);
+                    if matches!(end, StreamEnd::Closed) {
+                        return;
+                    }
+                    warn!("Lost {}: {}", self.port_name, end);
+                    if !self.status(DeviceStatus::Disconnected {
+                        device: self.port_name.clone(),
+                        reason: end.to_string(),
+                    }) {
+                        return;
+                    }
+                    if self.policy == DisconnectPolicy::Zero {
+                        let _ = self
+                            .tx
+                            .send(InputEvent::Sensor(zeroed(self.clock.now_us())));
                     }
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
-                    continue;
                 }
                 Err(e) => {
-                    warn!("Serial read error: {}", e);
-                    std::thread::sleep(Duration::from_millis(100));
+                    if attempt == 0 {
+                        error!("Failed to open serial port {}: {}", self.port_name, e);
+                        error!(
+                            "Is the Teensy connected? Retrying; run with --simulate for dev mode."
+                        );
+                    }
                 }
             }
-        }
-    }
-}
-
-fn find_sync(buf: &[u8]) -> Option<usize> {
-    (0..buf.len().saturating_sub(1)).find(|&i| buf[i] == 0xEF && buf[i + 1] == 0xBE)
-}
 
-fn parse_frame(
-    data: &[u8],
-    cal: &Calibration,
-    clock: &SessionClock,
-) -> Result<SensorFrame, String> {
-    if data.len() != FRAME_SIZE {
-        return Err(format!("wrong size: {}", data.len()));
+            attempt += 1;
+            let delay = self.backoff(attempt);
+            if !self.status(DeviceStatus::Reconnecting {
+                device: self.port_name.clone(),
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+            }) {
+                return;
+            }
+            std::thread::sleep(delay);
+        }
     }
 
-    let mut cursor = Cursor::new(data);
-
-    // Sync word
-    let sync = cursor
-        .read_u16::<LittleEndian>()
-        .map_err(|e| e.to_string())?;
-    if sync != SYNC_WORD {
-        return Err(format!("bad sync: 0x{:04X}", sync));
+    #[cfg(feature = "hardware")]
+    fn serial_opener(&self) -> Option<Box<dyn PortOpener>> {
+        Some(Box::new(SerialPortOpener {
+            port_name: self.port_name.clone(),
+            baud_rate: self.baud_rate,
+        }))
     }
 
-    // Timestamp from Teensy (u32 microseconds, wrapping)
-    let _teensy_ts = cursor
-        .read_u32::<LittleEndian>()
-        .map_err(|e| e.to_string())?;
-
-    // ADC values (13 channels, u16 each)
-    let mut raw = [0u16; NUM_CHANNELS];
-    for ch in raw.iter_mut() {
-        *ch = cursor
-            .read_u16::<LittleEndian>()
-            .map_err(|e| e.to_string())?;
+    #[cfg(not(feature = "hardware"))]
+    fn serial_opener(&self) -> Option<Box<dyn PortOpener>> {
+        None
     }
 
-    // CRC16
-    let received_crc = cursor
-        .read_u16::<LittleEndian>()
-        .map_err(|e| e.to_string())?;
-    let computed_crc = crc16(&data[..FRAME_SIZE - 2]);
-    if received_crc != computed_crc {
-        return Err(format!(
-            "CRC mismatch: received 0x{:04X}, computed 0x{:04X}",
-            received_crc, computed_crc
-        ));
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff_min
+            .saturating_mul(1u32 << (attempt - 1).min(16))
+            .min(self.backoff_max)
     }
 
-    // Calibrate: map raw ADC to 0.0–1.0
-    let mut calibrated = [0.0f32; NUM_CHANNELS];
-    for i in 0..NUM_CHANNELS {
-        let (lo, hi) = cal.ranges[i];
-        let range = (hi as f32 - lo as f32).max(1.0);
-        calibrated[i] = ((raw[i] as f32 - lo as f32) / range).clamp(0.0, 1.0);
+    /// Send a status event; false once the receiver is gone.
+    fn status(&self, status: DeviceStatus) -> bool {
+        self.tx.send(InputEvent::Status(status)).is_ok()
     }
 
-    // Use host clock for consistent timestamps (Teensy clock may drift)
-    let timestamp_us = clock.now_us();
-
-    Ok(SensorFrame {
-        timestamp_us,
-        pedals: [calibrated[0], calibrated[1], calibrated[2]],
-        knee_levers: [
-            calibrated[3],
-            calibrated[4],
-            calibrated[5],
-            calibrated[6],
-            calibrated[7],
-        ],
-        volume: calibrated[8],
-        bar_sensors: [
-            calibrated[9],
-            calibrated[10],
-            calibrated[11],
-            calibrated[12],
-        ],
-        // Hardware doesn't know which strings are picked — audio detection handles this.
-        string_active: [false; 10],
-    })
-}
+    /// Decode frames from any byte stream (a serial port, a pty, the
+    /// emulator) until it ends, fails or goes quiet, writing queued commands
+    /// to it between reads.
+    pub fn read_stream<R: Read + Write>(&self, mut stream: R) -> StreamEnd {
+        let mut buf = [0u8; 256];
+        let mut decoder = FrameDecoder::new();
+        let mut version = None;
+        let mut last_data = Instant::now();
+        // Command id → the command and who is waiting (None: startup)
+        let mut in_flight: HashMap<u8, (DeviceCommand, Option<Sender<CommandReply>>)> =
+            HashMap::new();
+        let mut next_id = 0u8;
+        let mut outgoing: Vec<(DeviceCommand, Option<Sender<CommandReply>>)> = self
+            .startup_commands
+            .iter()
+            .map(|&command| (command, None))
+            .collect();
+
+        let end = loop {
+            outgoing.extend(
+                self.command_rx
+                    .try_iter()
+                    .filter(|p| p.deadline > Instant::now())
+                    .map(|p| (p.command, Some(p.reply_tx))),
+            );
+            if !outgoing.is_empty() {
+                let mut bytes = Vec::new();
+                for (command, waiter) in outgoing.drain(..) {
+                    debug!("Sending {:?} (id {})", command, next_id);
+                    bytes.extend(encode_command(next_id, &command));
+                    in_flight.insert(next_id, (command, waiter));
+                    next_id = next_id.wrapping_add(1);
+                }
+                if let Err(e) = stream.write_all(&bytes).and_then(|_| stream.flush()) {
+                    break StreamEnd::Error(e);
+                }
+            }
 
-/// CRC-16/CCITT-FALSE
-fn crc16(data: &[u8]) -> u16 {
-    let mut crc: u16 = 0xFFFF;
-    for &byte in data {
-        crc ^= (byte as u16) << 8;
-        for _ in 0..8 {
-            if crc & 0x8000 != 0 {
-                crc = (crc << 1) ^ 0x1021;
-            } else {
-                crc <<= 1;
+            match stream.read(&mut buf) {
+                Ok(0) => break StreamEnd::Eof,
+                Ok(n) => {
+                    last_data = Instant::now();
+                    decoder.push(&buf[..n]);
+                    while let Some(sensor) = decoder.next_frame(&self.calibration, &self.clock) {
+                        if decoder.version != version {
+                            version = decoder.version;
+                            info!("Teensy protocol detected: {:?}", decoder.version.unwrap());
+                        }
+                        if self.tx.send(InputEvent::Sensor(sensor)).is_err() {
+                            return StreamEnd::Closed;
+                        }
+                        if decoder.frames.is_multiple_of(5000) {
+                            log_diagnostics(&decoder);
+                        }
+                    }
+                    while let Some(reply) = decoder.next_reply() {
+                        deliver_reply(reply, &mut in_flight);
+                    }
+                }
+                Err(ref e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+                    ) =>
+                {
+                    if last_data.elapsed() >= self.stall_timeout {
+                        break StreamEnd::Stalled(self.stall_timeout);
+                    }
+                }
+                Err(e) => break StreamEnd::Error(e),
             }
-        }
+        };
+        info!("Serial stream ended: {}", end);
+        log_diagnostics(&decoder);
+        end
     }
-    crc
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    /// Build a valid 34-byte frame with correct CRC.
-    fn make_frame(adc_values: &[u16; NUM_CHANNELS], timestamp: u32) -> Vec<u8> {
-        let mut buf = vec![0u8; FRAME_SIZE];
-        // Sync
-        buf[0] = (SYNC_WORD & 0xFF) as u8;
-        buf[1] = (SYNC_WORD >> 8) as u8;
-        // Timestamp
-        buf[2] = (timestamp & 0xFF) as u8;
-        buf[3] = ((timestamp >> 8) & 0xFF) as u8;
-        buf[4] = ((timestamp >> 16) & 0xFF) as u8;
-        buf[5] = ((timestamp >> 24) & 0xFF) as u8;
-        // ADC values
-        for i in 0..NUM_CHANNELS {
-            buf[6 + i * 2] = (adc_values[i] & 0xFF) as u8;
-            buf[6 + i * 2 + 1] = (adc_values[i] >> 8) as u8;
+/// Hand a reply to whoever sent the command, or log it for startup
+/// commands.
+fn deliver_reply(
+    reply: CommandReply,
+    in_flight: &mut HashMap<u8, (DeviceCommand, Option<Sender<CommandReply>>)>,
+) {
+    let Some((command, waiter)) = in_flight.remove(&reply.id) else {
+        debug!("Unexpected reply id {}", reply.id);
+        return;
+    };
+    match waiter {
+        Some(tx) => {
+            let _ = tx.send(reply);
         }
-        // CRC over first 32 bytes
-        let crc = crc16(&buf[..FRAME_SIZE - 2]);
-        buf[FRAME_SIZE - 2] = (crc & 0xFF) as u8;
-        buf[FRAME_SIZE - 1] = (crc >> 8) as u8;
-        buf
-    }
-
-    #[test]
-    fn test_crc16() {
-        let data = b"123456789";
-        let crc = crc16(data);
-        assert_eq!(crc, 0x29B1, "CRC-16/CCITT-FALSE of '123456789'");
-    }
-
-    #[test]
-    fn test_find_sync() {
-        let buf = [0x00, 0x00, 0xEF, 0xBE, 0x01, 0x02];
-        assert_eq!(find_sync(&buf), Some(2));
-    }
-
-    #[test]
-    fn test_find_sync_at_start() {
-        let buf = [0xEF, 0xBE, 0x01, 0x02];
-        assert_eq!(find_sync(&buf), Some(0));
-    }
-
-    #[test]
-    fn test_find_sync_not_found() {
-        let buf = [0x00, 0x01, 0x02, 0x03];
-        assert_eq!(find_sync(&buf), None);
-    }
-
-    #[test]
-    fn test_find_sync_partial_at_end() {
-        // 0xEF at last byte — can't confirm sync, should return None
-        let buf = [0x00, 0x01, 0xEF];
-        assert_eq!(find_sync(&buf), None);
+        None if reply.status == ReplyStatus::Ok => {
+            info!("Teensy {:?}: {}", command, reply.info)
+        }
+        None => warn!("Teensy rejected {:?}: {:?}", command, reply.status),
     }
+}
 
-    #[test]
-    fn test_find_sync_empty() {
-        assert_eq!(find_sync(&[]), None);
-        assert_eq!(find_sync(&[0xEF]), None);
+/// Everything released and silent, bar off the strings.
+fn zeroed(timestamp_us: u64) -> SensorFrame {
+    SensorFrame {
+        volume: 0.0,
+        ..SensorFrame::at_rest(timestamp_us)
     }
+}
 
-    #[test]
-    fn test_parse_valid_frame() {
-        let adc = [2000u16; NUM_CHANNELS];
-        let frame = make_frame(&adc, 1000);
-        let cal = Calibration::default();
-        let clock = SessionClock::new();
-        let result = parse_frame(&frame, &cal, &clock);
-        assert!(result.is_ok());
-        let sf = result.unwrap();
-        // With default cal (200, 3800), raw 2000 → (2000-200)/3600 ≈ 0.5
-        assert!((sf.pedals[0] - 0.5).abs() < 0.01);
-        assert!((sf.volume - 0.5).abs() < 0.01);
-        assert!((sf.bar_sensors[0] - 0.5).abs() < 0.01);
-    }
+/// Link and timing health: decode errors, lost v2 packets, and what the
+/// device timestamps say about dropped frames, drift and USB jitter.
+fn log_diagnostics(decoder: &FrameDecoder) {
+    let clock = decoder.clock_stats();
+    info!(
+        "Serial: {} frames, {} errors, {} lost packets, {} dropped frames in {} gaps; drift {:+.1} ppm, jitter {:.0} µs, {} resyncs",
+        decoder.frames,
+        decoder.errors,
+        decoder.lost_packets,
+        clock.dropped_frames,
+        clock.gaps,
+        clock.drift_ppm,
+        clock.jitter_us,
+        clock.resyncs
+    );
+}
 
-    #[test]
-    fn test_parse_frame_bad_crc() {
-        let adc = [2000u16; NUM_CHANNELS];
-        let mut frame = make_frame(&adc, 1000);
-        frame[FRAME_SIZE - 1] ^= 0xFF;
-        let cal = Calibration::default();
-        let clock = SessionClock::new();
-        let result = parse_frame(&frame, &cal, &clock);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("CRC mismatch"));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_protocol::encode_frame;
+    use std::io::Cursor;
+
+    /// A connected device that sends `bytes` and then goes quiet.
+    struct QuietAfter(Cursor<Vec<u8>>);
+
+    impl Read for QuietAfter {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buf)? {
+                0 => {
+                    std::thread::sleep(Duration::from_millis(1));
+                    Err(io::ErrorKind::TimedOut.into())
+                }
+                n => Ok(n),
+            }
+        }
     }
 
-    #[test]
-    fn test_parse_frame_bad_sync() {
-        let adc = [2000u16; NUM_CHANNELS];
-        let mut frame = make_frame(&adc, 1000);
-        frame[0] = 0x00;
-        let cal = Calibration::default();
-        let clock = SessionClock::new();
-        let result = parse_frame(&frame, &cal, &clock);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("bad sync"));
+    impl Write for QuietAfter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
     }
 
     #[test]
-    fn test_parse_frame_wrong_size() {
-        let cal = Calibration::default();
-        let clock = SessionClock::new();
-        let result = parse_frame(&[0u8; 10], &cal, &clock);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("wrong size"));
+    fn test_backoff_and_policy() {
+        let (tx, _rx) = unbounded();
+        let reader = SerialReader::new("mock".into(), tx, SessionClock::new())
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(500));
+        let delays: Vec<u128> = (1..=5).map(|a| reader.backoff(a).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+        assert_eq!("hold".parse(), Ok(DisconnectPolicy::Hold));
+        assert_eq!("zero".parse(), Ok(DisconnectPolicy::Zero));
+        assert!("drop".parse::<DisconnectPolicy>().is_err());
     }
 
     #[test]
-    fn test_calibration_clamps() {
-        let mut adc = [0u16; NUM_CHANNELS];
-        adc[0] = 0; // below min (200) → clamps to 0.0
-        adc[1] = 4095; // above max (3800) → clamps to 1.0
-        adc[2] = 200; // exactly at min → 0.0
-        let frame = make_frame(&adc, 500);
-        let cal = Calibration::default();
-        let clock = SessionClock::new();
-        let sf = parse_frame(&frame, &cal, &clock).unwrap();
-        assert_eq!(sf.pedals[0], 0.0, "below min clamps to 0");
-        assert_eq!(sf.pedals[1], 1.0, "above max clamps to 1");
-        assert_eq!(sf.pedals[2], 0.0, "exactly at min = 0");
-    }
+    fn test_stalled_device_reconnects_and_holds() {
+        let raw = [2048u16; NUM_CHANNELS];
+        let bytes: Vec<u8> = (0..3u32)
+            .flat_map(|t| encode_frame(&raw, t * 1000))
+            .collect();
+        // Plugged in but goes silent, then gone
+        let mut stream = Some(QuietAfter(Cursor::new(bytes)));
+        let opener = move || -> io::Result<Box<dyn Port>> {
+            stream
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Port>)
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        };
 
-    #[test]
-    fn test_find_sync_with_garbage() {
-        let mut buf = vec![0xAA, 0xBB, 0xCC, 0xDD, 0xEE];
-        buf.push(0xEF);
-        buf.push(0xBE);
-        buf.push(0x00);
-        assert_eq!(find_sync(&buf), Some(5));
-    }
+        let (tx, rx) = unbounded();
+        let mut reader = SerialReader::new("mock".into(), tx, SessionClock::new())
+            .with_opener(opener)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(2));
+        reader.stall_timeout = Duration::from_millis(20);
+        let reader = std::thread::spawn(move || reader.run());
 
-    #[test]
-    fn test_channel_mapping() {
-        let mut adc = [0u16; NUM_CHANNELS];
-        adc[0] = 3800;
-        adc[1] = 3800;
-        adc[2] = 3800; // pedals → 1.0
-        adc[3] = 200;
-        adc[4] = 200;
-        adc[5] = 200;
-        adc[6] = 200;
-        adc[7] = 200; // levers → 0.0
-        adc[8] = 2000; // volume → ~0.5
-        adc[9] = 3000;
-        adc[10] = 3000;
-        adc[11] = 3000;
-        adc[12] = 3000; // bar → ~0.78
-        let frame = make_frame(&adc, 0);
-        let cal = Calibration::default();
-        let clock = SessionClock::new();
-        let sf = parse_frame(&frame, &cal, &clock).unwrap();
-        assert!((sf.pedals[0] - 1.0).abs() < 0.01);
-        assert!((sf.pedals[1] - 1.0).abs() < 0.01);
-        assert!((sf.pedals[2] - 1.0).abs() < 0.01);
-        for i in 0..5 {
-            assert_eq!(sf.knee_levers[i], 0.0);
-        }
-        assert!((sf.volume - 0.5).abs() < 0.01);
-        let expected = (3000.0 - 200.0) / 3600.0;
-        for i in 0..4 {
-            assert!((sf.bar_sensors[i] - expected).abs() < 0.01);
+        let mut events = Vec::new();
+        while events.len() < 8 {
+            events.push(rx.recv_timeout(Duration::from_secs(5)).unwrap());
         }
-        assert_eq!(sf.string_active, [false; 10]);
+        drop(rx);
+        reader.join().unwrap();
+
+        let summary: Vec<String> = events
+            .iter()
+            .map(|e| match e {
+                InputEvent::Sensor(_) => "frame".into(),
+                InputEvent::Status(DeviceStatus::Connected { .. }) => "up".into(),
+                InputEvent::Status(DeviceStatus::Disconnected { reason, .. }) => {
+                    format!("down:{}", reason)
+                }
+                InputEvent::Status(DeviceStatus::Reconnecting {
+                    attempt, delay_ms, ..
+                }) => format!("retry{}:{}", attempt, delay_ms),
+                _ => "other".into(),
+            })
+            .collect();
+        // Hold policy: no zero frame after the disconnect
+        assert_eq!(
+            summary,
+            [
+                "up",
+                "frame",
+                "frame",
+                "frame",
+                "down:no data for 20ms",
+                "retry1:1",
+                "retry2:2",
+                "retry3:2"
+            ]
+        );
     }
 }
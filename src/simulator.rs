@@ -1,8 +1,14 @@
 use crate::bar_sensor::simulate_bar_readings;
+use crate::coordinator::ArticulationTracker;
 use crate::copedant::{midi_to_hz, CopedantEngine};
+use crate::impairments::{ImpairmentChain, Impairments};
+use crate::sensor_faults::{FaultKind, SensorChannel, SensorFaults};
+use crate::string_synth::{AudioModel, SteelSynth};
+use crate::tab_import::{Articulation, Tab};
 use crate::types::*;
-use crossbeam_channel::Sender;
-use log::info;
+use crossbeam_channel::{Receiver, Sender};
+use log::{info, warn};
+use serde::Serialize;
 use std::f32::consts::PI;
 use std::thread;
 use std::time::Duration;
@@ -22,6 +28,152 @@ pub struct Simulator {
     /// When true, suppress synthetic AudioChunk events (e.g. when a real
     /// WAV file is being streamed instead).
     suppress_audio: bool,
+    /// Which synthesis model generates the audio.
+    audio_model: AudioModel,
+    /// Plucked-string voices, used when `audio_model` is `Steel`.
+    synth: SteelSynth,
+    /// Optional noise/hum/interference layered onto the synthetic audio.
+    impairments: Option<ImpairmentChain>,
+    /// Channel faults applied to outgoing sensor frames.
+    faults: SensorFaults,
+    /// When false, run on a virtual clock as fast as possible (no sleeping).
+    realtime: bool,
+    /// Virtual session time, used when `realtime` is false.
+    virtual_us: u64,
+    /// Optional per-tick ground truth output (for benchmarks).
+    truth_tx: Option<Sender<GroundTruth>>,
+    /// Strings picked since the last tick, reported as ground-truth attacks.
+    pending_picks: [bool; 10],
+    /// Pedal/lever re-articulations on the true (unfaulted) state.
+    articulation: ArticulationTracker,
+    /// Parameters for the "improv" sequence.
+    improv: ImprovParams,
+    /// Tab played by the "tab" sequence.
+    tab: Option<Tab>,
+    /// Remote commands (`Demo` is acted on here)
+    control_rx: Option<Receiver<ControlCommand>>,
+    /// Demo requested remotely; the current sequence stops at the next tick.
+    switch_to: Option<String>,
+}
+
+/// Demo sequence names accepted by `run` and `play`.
+pub const DEMOS: [&str; 4] = ["basic", "e9", "improv", "faults"];
+
+/// What the simulator actually played on one tick — the true state, before
+/// sensor faults and audio impairments are applied.
+#[derive(Debug, Clone, Serialize)]
+pub struct GroundTruth {
+    pub timestamp_us: u64,
+    pub pedals: [f32; 3],
+    pub knee_levers: [f32; 5],
+    pub volume: f32,
+    /// True bar position in frets, or None when the bar is lifted
+    pub bar_fret: Option<f32>,
+    pub string_active: [bool; 10],
+    /// Strings picked on this tick, or re-articulated by a pedal/lever change
+    pub attacks: [bool; 10],
+}
+
+/// Write ground truth as JSONL: a header line describing how it was
+/// produced (demo name, improv parameters), then one `GroundTruth` per tick.
+/// Returns the number of ticks written once `rx` closes.
+pub fn write_ground_truth(
+    path: &std::path::Path,
+    rx: Receiver<GroundTruth>,
+    demo: &str,
+    improv: &ImprovParams,
+) -> std::io::Result<u64> {
+    use std::io::Write;
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let header = serde_json::json!({
+        "format": "steel-capture-truth",
+        "version": 1,
+        "demo": demo,
+        "improv": improv,
+    });
+    writeln!(out, "{}", header)?;
+    let mut count = 0;
+    for truth in rx.iter() {
+        serde_json::to_writer(&mut out, &truth)?;
+        out.write_all(b"\n")?;
+        count += 1;
+        // Live runs never close the channel; keep the file current
+        if count % 1000 == 0 {
+            out.flush()?;
+        }
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// Parameters for the algorithmic "improv" sequence. The same parameters
+/// always produce the same performance.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImprovParams {
+    pub seed: u64,
+    /// Length of the performance in beats
+    pub beats: u32,
+    pub tempo_bpm: f32,
+    /// Transpose the chord vocabulary up this many frets (0 = key of E)
+    pub key_fret: f32,
+    /// 0.0 favors bar slides to other frets, 1.0 favors pedal/lever moves
+    /// at the current fret
+    pub pedal_density: f32,
+    pub vibrato_hz: f32,
+    /// Chord labels to draw from (see `improv_chord_labels`); empty = all
+    pub chords: Vec<String>,
+}
+
+impl Default for ImprovParams {
+    fn default() -> Self {
+        Self {
+            seed: 0xc0ffee,
+            beats: 200,
+            tempo_bpm: 72.0,
+            key_fret: 0.0,
+            pedal_density: 0.5,
+            vibrato_hz: 5.2,
+            chords: Vec::new(),
+        }
+    }
+}
+
+/// Labels of the improv chord vocabulary, in order.
+pub fn improv_chord_labels() -> Vec<&'static str> {
+    CHORD_VOCAB.iter().map(|c| c.label).collect()
+}
+
+impl ImprovParams {
+    /// Check the chord subset and ranges before a performance starts.
+    pub fn validate(&self) -> Result<(), String> {
+        let labels = improv_chord_labels();
+        if let Some(bad) = self.chords.iter().find(|c| !labels.contains(&c.as_str())) {
+            return Err(format!(
+                "unknown improv chord '{}' (available: {})",
+                bad,
+                labels.join(", ")
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.pedal_density) {
+            return Err(format!(
+                "pedal density {} out of range 0.0-1.0",
+                self.pedal_density
+            ));
+        }
+        if !(10.0..=400.0).contains(&self.tempo_bpm) {
+            return Err(format!("tempo {} BPM out of range 10-400", self.tempo_bpm));
+        }
+        if !(self.vibrato_hz.is_finite() && self.vibrato_hz > 0.0) {
+            return Err(format!(
+                "vibrato rate {} Hz must be positive",
+                self.vibrato_hz
+            ));
+        }
+        if !(0.0..=12.0).contains(&self.key_fret) {
+            return Err(format!("key fret {} out of range 0-12", self.key_fret));
+        }
+        Ok(())
+    }
 }
 
 /// Mutable state that evolves as gestures are applied.
@@ -61,6 +213,19 @@ impl Simulator {
             sensor_rate_hz,
             sample_counter: 0,
             suppress_audio: false,
+            audio_model: AudioModel::Sine,
+            synth: SteelSynth::new(48000),
+            impairments: None,
+            faults: SensorFaults::default(),
+            realtime: true,
+            virtual_us: 0,
+            truth_tx: None,
+            pending_picks: [false; 10],
+            articulation: ArticulationTracker::new(),
+            improv: ImprovParams::default(),
+            tab: None,
+            control_rx: None,
+            switch_to: None,
         }
     }
 
@@ -70,28 +235,134 @@ impl Simulator {
         self
     }
 
-    /// Run a named demo sequence: "basic" (default), "e9", or "improv".
-    /// Blocks the calling thread.
+    /// Select the audio synthesis model (default: `Sine`).
+    pub fn with_audio_model(mut self, model: AudioModel) -> Self {
+        self.audio_model = model;
+        self
+    }
+
+    /// Layer impairments (hum, noise, buzz, clipping, sympathetic resonance)
+    /// onto the synthetic audio. While any are active, audio is emitted on
+    /// every tick — the noise floor doesn't stop when the strings do.
+    pub fn with_impairments(mut self, impairments: &Impairments) -> Self {
+        self.impairments = impairments
+            .is_active()
+            .then(|| impairments.chain(self.sample_rate));
+        self
+    }
+
+    /// Start with a sensor channel fault already present. Gestures can
+    /// inject and clear further faults during the sequence.
+    pub fn with_sensor_fault(mut self, channel: SensorChannel, kind: FaultKind) -> Self {
+        self.faults.set(channel, kind);
+        self
+    }
+
+    /// Run on a virtual clock instead of wall time: timestamps advance by
+    /// one tick per frame and nothing sleeps, so a sequence plays as fast as
+    /// the consumer can keep up. For benchmarks and tests.
+    pub fn with_offline(mut self) -> Self {
+        self.realtime = false;
+        self
+    }
+
+    /// Parameters for the "improv" sequence (seed, length, style).
+    pub fn with_improv(mut self, params: ImprovParams) -> Self {
+        self.improv = params;
+        self
+    }
+
+    /// A tab for the "tab" sequence to play.
+    pub fn with_tab(mut self, tab: Tab) -> Self {
+        self.tab = Some(tab);
+        self
+    }
+
+    /// Emit the true state of every tick on `tx`, alongside the sensor frames.
+    pub fn with_ground_truth(mut self, tx: Sender<GroundTruth>) -> Self {
+        self.truth_tx = Some(tx);
+        self
+    }
+
+    /// Accept remote commands; `Demo` abandons the current sequence and
+    /// starts the named one from a silent, at-rest state.
+    pub fn with_control(mut self, rx: Receiver<ControlCommand>) -> Self {
+        self.control_rx = Some(rx);
+        self
+    }
+
+    /// Run a named demo sequence: "basic" (default), "e9", "improv",
+    /// "faults", or "tab" with `with_tab`. Blocks the calling thread.
     pub fn run(&mut self, demo: &str) {
+        let tick_us = 1_000_000 / self.sensor_rate_hz as u64;
+        let mut demo = demo.to_string();
+        loop {
+            let state = self.perform(&demo);
+            if self.switch_to.is_none() {
+                info!("Sequence '{}' complete. Holding final state...", demo);
+            }
+            // Hold so the system stays alive, until another demo is requested
+            while self.switch_to.is_none() {
+                self.emit_tick(&state, tick_us);
+            }
+            demo = self.switch_to.take().unwrap_or(demo);
+            info!("Switching to demo '{}'", demo);
+            self.synth = SteelSynth::new(self.sample_rate);
+            self.pending_picks = [false; 10];
+            self.articulation = ArticulationTracker::new();
+        }
+    }
+
+    /// Act on pending remote commands.
+    fn poll_control(&mut self) {
+        let Some(rx) = &self.control_rx else {
+            return;
+        };
+        for cmd in rx.try_iter() {
+            if let ControlCommand::Demo(name) = cmd {
+                if DEMOS.contains(&name.as_str()) || (name == "tab" && self.tab.is_some()) {
+                    self.switch_to = Some(name);
+                } else {
+                    warn!("Unknown demo '{}' (expected one of {:?})", name, DEMOS);
+                }
+            }
+        }
+    }
+
+    /// Play a named demo sequence once and return, instead of holding the
+    /// final state forever like `run`. Dropping the simulator afterwards
+    /// closes its channels, which lets downstream threads finish.
+    pub fn play(&mut self, demo: &str) {
+        self.perform(demo);
+        info!("Sequence '{}' complete.", demo);
+    }
+
+    fn perform(&mut self, demo: &str) -> SimState {
         info!("Simulator starting '{}' sequence...", demo);
         let mut state = SimState::default();
         let tick_us = 1_000_000 / self.sensor_rate_hz as u64;
 
         let gestures = match demo {
             "e9" => e9_moves_sequence(),
-            "improv" => improvise_sequence(0xc0ffee_u64, 200),
+            "improv" => improvise_sequence(&self.improv),
+            "faults" => faults_sequence(),
+            "tab" => match &self.tab {
+                Some(tab) => tab_sequence(tab),
+                None => {
+                    warn!("No tab loaded; playing the basic demo");
+                    demo_sequence()
+                }
+            },
             _ => demo_sequence(),
         };
 
         for gesture in &gestures {
+            if self.switch_to.is_some() {
+                break;
+            }
             self.execute(gesture, &mut state, tick_us);
         }
-
-        info!("Sequence '{}' complete. Holding final state...", demo);
-        // Hold indefinitely so the system stays alive
-        loop {
-            self.emit_tick(&state, tick_us);
-        }
+        state
     }
 
     fn execute(&mut self, gesture: &Gesture, state: &mut SimState, tick_us: u64) {
@@ -207,27 +478,70 @@ impl Simulator {
                 for &si in strings {
                     if si < 10 {
                         state.string_active[si] = true;
+                        self.pending_picks[si] = true;
+                        self.synth.pluck(si, state.bar_fret.unwrap_or(0.0), 1.0);
                     }
                 }
             }
 
+            Gesture::Pick { strings, keep } => {
+                let names: Vec<String> = strings.iter().map(|s| format!("{}", s + 1)).collect();
+                let kept: Vec<String> = keep.iter().map(|s| format!("{}", s + 1)).collect();
+                info!("  pick [{}] over [{}]", names.join(", "), kept.join(", "));
+                let ringing = state.string_active;
+                state.string_active = [false; 10];
+                for &si in keep.iter().filter(|&&si| si < 10) {
+                    state.string_active[si] = ringing[si];
+                }
+                for &si in strings.iter().filter(|&&si| si < 10) {
+                    state.string_active[si] = true;
+                    self.pending_picks[si] = true;
+                    self.synth.pluck(si, state.bar_fret.unwrap_or(0.0), 1.0);
+                }
+            }
+
             Gesture::MuteAll => {
                 info!("  mute all strings");
                 state.string_active = [false; 10];
             }
+
+            Gesture::SensorFault { channel, fault } => {
+                info!("  fault {}: {:?}", channel, fault);
+                self.faults.set(*channel, *fault);
+            }
+
+            Gesture::ClearFault { channel } => {
+                info!("  fault {} cleared", channel);
+                self.faults.clear(*channel);
+            }
+
+            Gesture::ClearFaults => {
+                info!("  all faults cleared");
+                self.faults.clear_all();
+            }
         }
     }
 
     /// Emit one tick: send a SensorFrame and a corresponding AudioChunk.
     fn emit_tick(&mut self, state: &SimState, tick_us: u64) {
-        let ts = self.clock.now_us();
+        // A switch is pending: let the rest of the sequence run out silently
+        self.poll_control();
+        if self.switch_to.is_some() {
+            return;
+        }
+
+        let ts = if self.realtime {
+            self.clock.now_us()
+        } else {
+            self.virtual_us
+        };
 
         // Sensor frame (what the Teensy would send)
         let bar_sensors = match state.bar_fret {
             Some(fret) => simulate_bar_readings(fret),
             None => [0.0; 4],
         };
-        let sensor = SensorFrame {
+        let mut sensor = SensorFrame {
             timestamp_us: ts,
             pedals: state.pedals,
             knee_levers: state.knee_levers,
@@ -235,22 +549,47 @@ impl Simulator {
             bar_sensors,
             string_active: state.string_active,
         };
+        if let Some(tx) = &self.truth_tx {
+            let mut attacks = self.articulation.update(&sensor, &state.string_active);
+            for (attack, picked) in attacks.iter_mut().zip(self.pending_picks) {
+                *attack |= picked;
+            }
+            let _ = tx.send(GroundTruth {
+                timestamp_us: ts,
+                pedals: state.pedals,
+                knee_levers: state.knee_levers,
+                volume: state.volume,
+                bar_fret: state.bar_fret,
+                string_active: state.string_active,
+                attacks,
+            });
+        }
+        self.pending_picks = [false; 10];
+
+        // Faults corrupt only what the hardware reports; audio still
+        // follows the true state.
+        self.faults.apply(&mut sensor, tick_us);
         let _ = self.tx.send(InputEvent::Sensor(sensor));
 
         // Synthetic audio: generate sine waves matching the current pitch state.
         // Uses sample_counter for phase-continuous audio across ticks.
         // Suppressed when a real WAV file is being streamed instead.
         let any_active = state.string_active.iter().any(|&a| a);
-        if !self.suppress_audio && state.bar_fret.is_some() && state.volume > 0.01 && any_active {
+        let sounding = state.bar_fret.is_some() && state.volume > 0.01 && any_active;
+        if !self.suppress_audio && (sounding || self.impairments.is_some()) {
             let chunk = self.generate_audio(state, ts);
             let _ = self.tx.send(InputEvent::Audio(chunk));
         }
 
-        thread::sleep(Duration::from_micros(tick_us));
+        if self.realtime {
+            thread::sleep(Duration::from_micros(tick_us));
+        } else {
+            self.virtual_us += tick_us;
+        }
     }
 
-    /// Generate a short audio chunk (one tick's worth of samples) containing
-    /// sine waves at the pitches implied by the current state.
+    /// Generate a short audio chunk (one tick's worth of samples) at the
+    /// pitches implied by the current state, using the selected audio model.
     /// Uses sample_counter for phase continuity across chunks.
     fn generate_audio(&mut self, state: &SimState, ts: u64) -> AudioChunk {
         let sensor = SensorFrame {
@@ -275,24 +614,48 @@ impl Simulator {
             0.0
         };
 
-        for (si, open_pitch) in open.iter().enumerate() {
-            if !state.string_active[si] {
-                continue;
+        match self.audio_model {
+            AudioModel::Sine => {
+                for (si, open_pitch) in open.iter().enumerate() {
+                    if !state.string_active[si] {
+                        continue;
+                    }
+                    let freq = midi_to_hz(*open_pitch + bar_fret as f64);
+                    for (j, sample) in samples.iter_mut().enumerate() {
+                        // Use sample_counter for monotonic, jitter-free phase
+                        let t = (self.sample_counter + j as u64) as f64 / self.sample_rate as f64;
+                        *sample +=
+                            amp_per_string * (2.0 * std::f64::consts::PI * freq * t).sin() as f32;
+                    }
+                }
             }
-            let freq = midi_to_hz(*open_pitch + bar_fret as f64);
-            for (j, sample) in samples.iter_mut().enumerate() {
-                // Use sample_counter for monotonic, jitter-free phase
-                let t = (self.sample_counter + j as u64) as f64 / self.sample_rate as f64;
-                *sample += amp_per_string * (2.0 * std::f64::consts::PI * freq * t).sin() as f32;
+            AudioModel::Steel => {
+                let mut pitches = open;
+                for p in pitches.iter_mut() {
+                    *p += bar_fret as f64;
+                }
+                self.synth.render(
+                    &pitches,
+                    bar_fret,
+                    &state.string_active,
+                    amp_per_string,
+                    &mut samples,
+                );
             }
         }
 
+        if let Some(chain) = self.impairments.as_mut() {
+            let pitches_hz = open.map(|p| midi_to_hz(p + bar_fret as f64));
+            chain.process_with_strings(&mut samples, &pitches_hz, &state.string_active);
+        }
+
         self.sample_counter += samples_per_tick as u64;
 
         AudioChunk {
             timestamp_us: ts,
             samples,
             sample_rate: self.sample_rate,
+            channels: Vec::new(),
         }
     }
 }
@@ -341,8 +704,25 @@ enum Gesture {
     PickStrings {
         strings: Vec<usize>,
     },
+    /// Pick `strings` while those of `keep` that are ringing carry on;
+    /// every other string is blocked. With no `strings` it only blocks.
+    Pick {
+        strings: Vec<usize>,
+        keep: Vec<usize>,
+    },
     /// Mute all strings.
     MuteAll,
+    /// Inject a fault on one sensor channel (replaces any existing fault).
+    SensorFault {
+        channel: SensorChannel,
+        fault: FaultKind,
+    },
+    /// Repair one sensor channel.
+    ClearFault {
+        channel: SensorChannel,
+    },
+    /// Repair every sensor channel.
+    ClearFaults,
 }
 
 /// A demo sequence that exercises all the major pedal steel gestures.
@@ -437,6 +817,85 @@ fn demo_sequence() -> Vec<Gesture> {
     ]
 }
 
+/// Plays a simple phrase while sensor channels fail one after another:
+/// a dead hall sensor, all hall sensors dead (audio-only bar), a noisy
+/// pedal, drifting volume, and a flaky lever connector.
+fn faults_sequence() -> Vec<Gesture> {
+    vec![
+        Gesture::Hold { ms: 200 },
+        Gesture::BarPlace { fret: 5.0 },
+        Gesture::PickStrings {
+            strings: vec![2, 3, 4],
+        },
+        Gesture::VolumeSwell {
+            from: 0.0,
+            to: 0.8,
+            ms: 300,
+        },
+        Gesture::Hold { ms: 800 },
+        // Hall sensor at fret 5 goes open-circuit
+        Gesture::SensorFault {
+            channel: SensorChannel::Bar(1),
+            fault: FaultKind::Dead,
+        },
+        Gesture::BarSlide { to: 7.0, ms: 600 },
+        Gesture::Hold { ms: 800 },
+        // Whole hall array lost: bar position must come from audio
+        Gesture::SensorFault {
+            channel: SensorChannel::Bar(0),
+            fault: FaultKind::Dead,
+        },
+        Gesture::SensorFault {
+            channel: SensorChannel::Bar(2),
+            fault: FaultKind::Dead,
+        },
+        Gesture::SensorFault {
+            channel: SensorChannel::Bar(3),
+            fault: FaultKind::Dead,
+        },
+        Gesture::Hold { ms: 1000 },
+        Gesture::BarSlide { to: 5.0, ms: 800 },
+        Gesture::Hold { ms: 600 },
+        Gesture::ClearFaults,
+        // Noisy pedal A through an engage/release
+        Gesture::SensorFault {
+            channel: SensorChannel::Pedal(0),
+            fault: FaultKind::Noise { amplitude: 0.05 },
+        },
+        Gesture::PedalEngage { index: 0, ms: 300 },
+        Gesture::Hold { ms: 600 },
+        Gesture::PedalRelease { index: 0, ms: 300 },
+        Gesture::ClearFault {
+            channel: SensorChannel::Pedal(0),
+        },
+        Gesture::Hold { ms: 400 },
+        // Volume pot creeping and a flaky knee lever connector
+        Gesture::SensorFault {
+            channel: SensorChannel::Volume,
+            fault: FaultKind::Drift { per_sec: -0.1 },
+        },
+        Gesture::SensorFault {
+            channel: SensorChannel::Lever(0),
+            fault: FaultKind::Dropout {
+                per_sec: 3.0,
+                ms: 40,
+            },
+        },
+        Gesture::LeverEngage { index: 0, ms: 200 },
+        Gesture::Hold { ms: 1500 },
+        Gesture::LeverRelease { index: 0, ms: 200 },
+        Gesture::ClearFaults,
+        Gesture::VolumeSwell {
+            from: 0.8,
+            to: 0.0,
+            ms: 500,
+        },
+        Gesture::MuteAll,
+        Gesture::BarLift,
+        Gesture::Hold { ms: 500 },
+    ]
+}
+
 /// Slow, idiomatic E9 movement sequence — tertian harmony, 5-string voicings,
 /// standard copedant combinations. ~90 seconds at 72 BPM.
 ///
@@ -632,10 +1091,10 @@ fn e9_moves_sequence() -> Vec<Gesture> {
 
 // ─── Xorshift64 PRNG (no external crates) ────────────────────────────────────
 
-struct Rng(u64);
+pub(crate) struct Rng(u64);
 
 impl Rng {
-    fn new(seed: u64) -> Self {
+    pub(crate) fn new(seed: u64) -> Self {
         Rng(seed.max(1))
     }
     fn next(&mut self) -> u64 {
@@ -645,17 +1104,22 @@ impl Rng {
         self.0
     }
     /// Uniform in [lo, hi] inclusive.
-    fn range(&mut self, lo: u32, hi: u32) -> u32 {
+    pub(crate) fn range(&mut self, lo: u32, hi: u32) -> u32 {
         lo + (self.next() % (hi - lo + 1) as u64) as u32
     }
     /// True with probability 1/n.
     fn one_in(&mut self, n: u64) -> bool {
         self.next().is_multiple_of(n)
     }
+    /// Uniform in [-1.0, 1.0).
+    pub(crate) fn bipolar(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
 }
 
 // ─── E9 chord vocabulary (shared by improv + future uses) ────────────────────
 
+#[derive(Clone, Copy)]
 struct ChordV {
     label: &'static str,
     fret: f32,
@@ -841,9 +1305,12 @@ static CHORD_VOCAB: &[ChordV] = &[
 ];
 
 /// Pick next chord index: weighted by fret proximity, never same chord twice.
-fn pick_next_chord(cur: usize, rng: &mut Rng) -> usize {
-    let cur_fret = CHORD_VOCAB[cur].fret;
-    let weights: Vec<u32> = CHORD_VOCAB
+/// `pedal_density` shifts weight between same-fret chords (pedal/lever
+/// moves) and chords at other frets (bar slides); 0.5 gives 3:2:1 for
+/// same fret : within 4 frets : farther.
+fn pick_next_chord(vocab: &[ChordV], cur: usize, pedal_density: f32, rng: &mut Rng) -> usize {
+    let cur_fret = vocab[cur].fret;
+    let weights: Vec<u32> = vocab
         .iter()
         .enumerate()
         .map(|(i, v)| {
@@ -851,16 +1318,25 @@ fn pick_next_chord(cur: usize, rng: &mut Rng) -> usize {
                 return 0;
             }
             let d = (v.fret - cur_fret).abs();
-            if d < 0.1 {
-                3
+            let w = if d < 0.1 {
+                6.0 * pedal_density
             } else if d <= 4.0 {
-                2
+                4.0 * (1.0 - pedal_density)
             } else {
-                1
-            }
+                2.0 * (1.0 - pedal_density)
+            };
+            // At least 1 so a one-sided density can't strand the walk
+            ((w * 10.0).round() as u32).max(1)
         })
         .collect();
+    // Reduce by the common factor: the default density draws from exactly
+    // 3:2:1, so a seed plays the same walk it always has
+    let common = weights.iter().fold(0, |a, &w| gcd(a, w)).max(1);
+    let weights: Vec<u32> = weights.iter().map(|w| w / common).collect();
     let total: u32 = weights.iter().sum();
+    if total == 0 {
+        return cur;
+    }
     let mut pick = (rng.next() % total as u64) as u32;
     for (i, &w) in weights.iter().enumerate() {
         if pick < w {
@@ -871,6 +1347,14 @@ fn pick_next_chord(cur: usize, rng: &mut Rng) -> usize {
     0
 }
 
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
 /// Append release + slide + engage gestures for transitioning old → new chord.
 fn chord_transition(g: &mut Vec<Gesture>, old: &ChordV, new: &ChordV, _rng: &mut Rng, beat: u32) {
     let sq = beat * 2;
@@ -927,21 +1411,50 @@ fn chord_transition(g: &mut Vec<Gesture>, old: &ChordV, new: &ChordV, _rng: &mut
 
 /// Algorithmic improvisation — weighted random walk over the E9 chord vocabulary.
 ///
-/// Generates `total_beats` beats of musically coherent gestures at 72 BPM.
-/// Volume pedal is active throughout: duck-and-swell attacks, phrase arcs, breathing.
-/// Pass different `seed` values for different performances.
+/// Generates `params.beats` beats of musically coherent gestures at
+/// `params.tempo_bpm`. Volume pedal is active throughout: duck-and-swell
+/// attacks, phrase arcs, breathing. Pass different seeds for different
+/// performances.
 #[allow(clippy::vec_init_then_push)]
-fn improvise_sequence(seed: u64, total_beats: u32) -> Vec<Gesture> {
-    let beat = 833_u32; // 72 BPM
-    let mut rng = Rng::new(seed);
+fn improvise_sequence(params: &ImprovParams) -> Vec<Gesture> {
+    let beat = (60_000.0 / params.tempo_bpm) as u32;
+    let total_beats = params.beats;
+    let mut rng = Rng::new(params.seed);
     let mut g = Vec::<Gesture>::new();
 
+    // Chord subset, transposed to the key
+    let vocab: Vec<ChordV> = CHORD_VOCAB
+        .iter()
+        .filter(|c| params.chords.is_empty() || params.chords.iter().any(|l| l == c.label))
+        .map(|c| ChordV {
+            fret: c.fret + params.key_fret,
+            ..*c
+        })
+        .collect();
+    let vocab = if vocab.is_empty() {
+        CHORD_VOCAB.to_vec()
+    } else {
+        vocab
+    };
+
     // Intro: silence → place bar → pick → swell in
     g.push(Gesture::Hold { ms: 300 });
-    g.push(Gesture::BarPlace { fret: 0.0 });
+    g.push(Gesture::BarPlace {
+        fret: vocab[0].fret,
+    });
     g.push(Gesture::PickStrings {
-        strings: CHORD_VOCAB[0].strings.to_vec(),
+        strings: vocab[0].strings.to_vec(),
     });
+    for i in 0..3 {
+        if vocab[0].ped[i] {
+            g.push(Gesture::PedalEngage { index: i, ms: 120 });
+        }
+    }
+    for i in 0..5 {
+        if vocab[0].lev[i] {
+            g.push(Gesture::LeverEngage { index: i, ms: 120 });
+        }
+    }
     g.push(Gesture::VolumeSwell {
         from: 0.0,
         to: 0.82,
@@ -975,9 +1488,9 @@ fn improvise_sequence(seed: u64, total_beats: u32) -> Vec<Gesture> {
             phrase_beats_left = rng.range(8, 13);
         }
 
-        let next_idx = pick_next_chord(cur_idx, &mut rng);
-        let cur = &CHORD_VOCAB[cur_idx];
-        let next = &CHORD_VOCAB[next_idx];
+        let next_idx = pick_next_chord(&vocab, cur_idx, params.pedal_density, &mut rng);
+        let cur = &vocab[cur_idx];
+        let next = &vocab[next_idx];
 
         // ── Duck-and-swell attack (~70% of transitions) ──────────────────
         // The most idiomatic steel move: dip vol before the chord "speaks",
@@ -1057,7 +1570,7 @@ fn improvise_sequence(seed: u64, total_beats: u32) -> Vec<Gesture> {
             let width = 0.08 + rng.range(0, 7) as f32 * 0.01;
             g.push(Gesture::BarVibrato {
                 width,
-                rate_hz: 5.2,
+                rate_hz: params.vibrato_hz,
                 ms: beat * 2,
             });
             elapsed_beats += 2;
@@ -1072,18 +1585,14 @@ fn improvise_sequence(seed: u64, total_beats: u32) -> Vec<Gesture> {
         );
     }
 
-    // Outro: resolve to E major, long fade
-    chord_transition(
-        &mut g,
-        &CHORD_VOCAB[cur_idx],
-        &CHORD_VOCAB[0],
-        &mut rng,
-        beat,
-    );
+    // Outro: resolve to the first chord (the tonic for the full vocabulary), long fade
+    if cur_idx != 0 {
+        chord_transition(&mut g, &vocab[cur_idx], &vocab[0], &mut rng, beat);
+    }
     g.push(Gesture::Hold { ms: beat * 2 });
     g.push(Gesture::BarVibrato {
         width: 0.10,
-        rate_hz: 5.2,
+        rate_hz: params.vibrato_hz,
         ms: beat * 4,
     });
     g.push(Gesture::VolumeSwell {
@@ -1103,6 +1612,159 @@ fn lerp(a: f32, b: f32, t: f32) -> f32 {
     a + (b - a) * t
 }
 
+/// Pedal/lever travel for a change written in a tab.
+const TAB_CHANGER_MS: u32 = 40;
+/// Silence between blocking a ringing string and picking it again.
+const TAB_BLOCK_MS: u32 = 20;
+/// Time before the tab's first column: silence, then the volume swell.
+const TAB_INTRO_MS: u32 = 400;
+
+/// Plays a tab column by column at its tempo (see `tab_import` for what a
+/// column does). Before a picked column the other strings are blocked and
+/// the pedals and levers set, so the pick lands on time; a slide takes the
+/// second half of the time since the previous column; a hammer-on moves on
+/// the column.
+fn tab_sequence(tab: &Tab) -> Vec<Gesture> {
+    let column_ms = tab.column_ms();
+    let mut g = vec![Gesture::VolumeSwell {
+        from: 0.0,
+        to: 0.8,
+        ms: 200,
+    }];
+    // Time reached by the gestures so far; the tab starts at TAB_INTRO_MS
+    let mut elapsed: u32 = 200;
+    let mut bar: Option<u8> = None;
+    let mut pedals = [false; 3];
+    let mut levers = [false; 5];
+    let mut ringing = [false; 10];
+
+    for column in &tab.columns {
+        let at = TAB_INTRO_MS + (column.step as f64 * column_ms).round() as u32;
+        let fret = column.fret();
+        // Slid or hammered strings still ringing carry on; the rest are picked
+        let (keep, picks): (Vec<_>, Vec<_>) = column
+            .notes
+            .iter()
+            .partition(|n| n.articulation != Articulation::Picked && ringing[n.string]);
+        let slide = fret > 0
+            && bar != Some(fret)
+            && keep.iter().any(|n| n.articulation == Articulation::Slide);
+        let keep: Vec<usize> = keep.iter().map(|n| n.string).collect();
+        let picks: Vec<usize> = picks.iter().map(|n| n.string).collect();
+
+        let mut changes = Vec::new();
+        for (i, on) in pedals.iter_mut().enumerate() {
+            let want = column.notes.iter().any(|n| n.pedals[i]);
+            if want != *on {
+                changes.push(match want {
+                    true => Gesture::PedalEngage {
+                        index: i,
+                        ms: TAB_CHANGER_MS,
+                    },
+                    false => Gesture::PedalRelease {
+                        index: i,
+                        ms: TAB_CHANGER_MS,
+                    },
+                });
+                *on = want;
+            }
+        }
+        for (i, on) in levers.iter_mut().enumerate() {
+            let want = column.notes.iter().any(|n| n.levers[i]);
+            if want != *on {
+                changes.push(match want {
+                    true => Gesture::LeverEngage {
+                        index: i,
+                        ms: TAB_CHANGER_MS,
+                    },
+                    false => Gesture::LeverRelease {
+                        index: i,
+                        ms: TAB_CHANGER_MS,
+                    },
+                });
+                *on = want;
+            }
+        }
+        let change_ms = changes.len() as u32 * TAB_CHANGER_MS;
+        // A ringing string is heard to stop before it's picked again
+        let block_ms = match picks.iter().any(|&si| ringing[si]) {
+            true => TAB_BLOCK_MS,
+            false => 0,
+        };
+        // Set changers before a pick; otherwise they are the articulation
+        let lead_ms = if picks.is_empty() {
+            0
+        } else {
+            change_ms + block_ms
+        };
+        let available = at.saturating_sub(elapsed);
+        let slide_ms = if slide {
+            available.saturating_sub(lead_ms) / 2
+        } else {
+            0
+        };
+        let hold = available.saturating_sub(slide_ms + lead_ms);
+        if hold > 0 {
+            g.push(Gesture::Hold { ms: hold });
+        }
+        elapsed += hold + slide_ms + change_ms + block_ms;
+
+        if (0..10).any(|si| ringing[si] && !keep.contains(&si)) {
+            g.push(Gesture::Pick {
+                strings: Vec::new(),
+                keep: keep.clone(),
+            });
+        }
+        if slide {
+            g.push(Gesture::BarSlide {
+                to: fret as f32,
+                ms: slide_ms,
+            });
+        }
+        if !picks.is_empty() {
+            g.append(&mut changes);
+        }
+        if block_ms > 0 {
+            g.push(Gesture::Hold { ms: block_ms });
+        }
+        match fret {
+            0 if bar.is_some() => g.push(Gesture::BarLift),
+            0 => {}
+            // Also lands a slide exactly on the fret
+            _ => g.push(Gesture::BarPlace { fret: fret as f32 }),
+        }
+        bar = (fret > 0).then_some(fret);
+        if !picks.is_empty() {
+            g.push(Gesture::Pick {
+                strings: picks.clone(),
+                keep: keep.clone(),
+            });
+        }
+        g.append(&mut changes);
+
+        ringing = [false; 10];
+        for &si in keep.iter().chain(&picks) {
+            ringing[si] = true;
+        }
+    }
+
+    let end = TAB_INTRO_MS + (tab.steps as f64 * column_ms).round() as u32;
+    g.push(Gesture::Hold {
+        ms: end.saturating_sub(elapsed),
+    });
+    g.extend([
+        Gesture::VolumeSwell {
+            from: 0.8,
+            to: 0.0,
+            ms: 400,
+        },
+        Gesture::MuteAll,
+        Gesture::BarLift,
+        Gesture::Hold { ms: 500 },
+    ]);
+    g
+}
+
 /// Smooth interpolation (ease in/out)
 fn smoothstep(t: f32) -> f32 {
     let t = t.clamp(0.0, 1.0);
@@ -0,0 +1,370 @@
+//! Standard MIDI File (Type 1) export of a captured session.
+//!
+//! Notes and pitch bends come from the same encoder as the live MPE output
+//! (`mpe_output::MpeEncoder`), so a file and a live stream of the same
+//! performance agree: a note starts on each attack at the nearest semitone,
+//! bar and pedal movement is per-string pitch bend, a release ends it.
+//!
+//! Tracks:
+//!
+//! ```text
+//! 0       conductor: name, tempo, 4/4
+//! 1–10    strings 1–10
+//! 11–19   volume, pedals A–C, levers LKL–RKR as CC 7, 20–22, 23–27
+//! ```
+//!
+//! The control numbers match `--midi-input`'s default map. In the default
+//! layout strings use channels 1–9 and 11 (10 is General MIDI drums) and
+//! controls channel 16. The MPE layout uses the lower zone like
+//! `--mpe-out`: strings on channels 2–11 with channel pressure from the
+//! volume pedal, controls on master channel 1.
+
+use crate::mpe_output::MpeEncoder;
+use crate::types::*;
+
+/// Controller for the volume pedal, then pedals A–C and levers LKL–RKR.
+const CONTROL_CCS: [u8; 9] = [7, 20, 21, 22, 23, 24, 25, 26, 27];
+
+/// One track's events: (absolute tick, message bytes).
+type Events = Vec<(u64, Vec<u8>)>;
+
+/// Builds a Type 1 SMF from capture frames.
+pub struct SmfExport {
+    mpe: bool,
+    bend_range: u8,
+    tempo_bpm: f64,
+    ppq: u16,
+}
+
+impl SmfExport {
+    /// Per-string layout, ±48 semitone bends, 120 BPM at 480 ticks per
+    /// quarter note unless configured otherwise.
+    pub fn new() -> Self {
+        Self {
+            mpe: false,
+            bend_range: 48,
+            tempo_bpm: 120.0,
+            ppq: 480,
+        }
+    }
+
+    /// Use the MPE channel layout.
+    pub fn with_mpe(mut self, mpe: bool) -> Self {
+        self.mpe = mpe;
+        self
+    }
+
+    /// Pitch-bend range in semitones, written as RPN 0 on each string's
+    /// channel.
+    pub fn with_bend_range(mut self, semitones: u8) -> Self {
+        self.bend_range = semitones.clamp(1, 96);
+        self
+    }
+
+    /// Tempo for the bar grid. The performance keeps its real timing
+    /// whatever the tempo; this only decides where barlines fall.
+    pub fn with_tempo(mut self, bpm: f64) -> Self {
+        self.tempo_bpm = bpm.clamp(10.0, 400.0);
+        self
+    }
+
+    /// The whole file. Frame timestamps are relative to the first frame.
+    pub fn export(&self, name: &str, frames: impl IntoIterator<Item = CaptureFrame>) -> Vec<u8> {
+        let mut encoder = MpeEncoder::new()
+            .with_bend_range(self.bend_range)
+            .with_rate(0);
+        let mut strings: [Events; 10] = Default::default();
+        let mut controls: [Events; 9] = Default::default();
+        let mut last_cc = [None; 9];
+
+        let init = encoder.init();
+        self.route(&init, 0, &mut strings, &mut controls);
+
+        let mut start_us = None;
+        let mut end_tick = 0;
+        for frame in frames {
+            let start = *start_us.get_or_insert(frame.timestamp_us);
+            let tick = self.ticks(frame.timestamp_us.saturating_sub(start));
+            end_tick = end_tick.max(tick);
+
+            let bytes = encoder.encode(&frame);
+            self.route(&bytes, tick, &mut strings, &mut controls);
+
+            let values = std::iter::once(frame.volume)
+                .chain(frame.pedals)
+                .chain(frame.knee_levers);
+            for (k, v) in values.enumerate() {
+                let value = (v.clamp(0.0, 1.0) * 127.0).round() as u8;
+                if last_cc[k] != Some(value) {
+                    last_cc[k] = Some(value);
+                    let cc = 0xB0 | self.control_channel();
+                    controls[k].push((tick, vec![cc, CONTROL_CCS[k], value]));
+                }
+            }
+        }
+        let bytes = encoder.all_notes_off();
+        self.route(&bytes, end_tick, &mut strings, &mut controls);
+
+        let mut tracks = vec![self.conductor(name)];
+        for (i, events) in strings.iter().enumerate() {
+            tracks.push(track(&format!("String {}", E9_STRING_NAMES[i]), events));
+        }
+        let control_names = std::iter::once("Volume".to_string())
+            .chain(PEDAL_NAMES.iter().map(|p| format!("Pedal {}", p)))
+            .chain(LEVER_NAMES.iter().map(|l| l.to_string()));
+        for (name, events) in control_names.zip(&controls) {
+            tracks.push(track(&name, events));
+        }
+
+        let mut out = b"MThd".to_vec();
+        out.extend_from_slice(&6u32.to_be_bytes());
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.ppq.to_be_bytes());
+        for t in tracks {
+            out.extend_from_slice(b"MTrk");
+            out.extend_from_slice(&(t.len() as u32).to_be_bytes());
+            out.extend_from_slice(&t);
+        }
+        out
+    }
+
+    fn ticks(&self, us: u64) -> u64 {
+        (us as f64 * self.ppq as f64 * self.tempo_bpm / 60e6).round() as u64
+    }
+
+    fn control_channel(&self) -> u8 {
+        if self.mpe {
+            0
+        } else {
+            15
+        }
+    }
+
+    /// Split encoder output into the string and control tracks, moving
+    /// strings onto their channels for the layout.
+    fn route(
+        &self,
+        bytes: &[u8],
+        tick: u64,
+        strings: &mut [Events; 10],
+        controls: &mut [Events; 9],
+    ) {
+        let mut rest = bytes;
+        while let Some(&status) = rest.first() {
+            let len = match status & 0xF0 {
+                0xC0 | 0xD0 => 2,
+                _ => 3,
+            };
+            let (msg, tail) = rest.split_at(len.min(rest.len()));
+            rest = tail;
+            let mut msg = msg.to_vec();
+            match (status & 0x0F) as usize {
+                // Master channel: the zone configuration
+                0 if self.mpe => controls[0].push((tick, msg)),
+                0 => {}
+                member @ 1..=10 => {
+                    let si = member - 1;
+                    if !self.mpe {
+                        if status & 0xF0 == 0xD0 {
+                            continue;
+                        }
+                        let ch = if si < 9 { si as u8 } else { 10 };
+                        msg[0] = status & 0xF0 | ch;
+                    }
+                    strings[si].push((tick, msg));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn conductor(&self, name: &str) -> Vec<u8> {
+        let tempo = (60e6 / self.tempo_bpm).round() as u32;
+        let mut events: Events = vec![(0, meta(0x51, &tempo.to_be_bytes()[1..]))];
+        // 4/4, 24 clocks per click, 8 32nds per quarter
+        events.push((0, meta(0x58, &[4, 2, 24, 8])));
+        track(name, &events)
+    }
+}
+
+impl Default for SmfExport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn meta(kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0xFF, kind];
+    vlq(&mut out, data.len() as u64);
+    out.extend_from_slice(data);
+    out
+}
+
+/// Track chunk body: a name, the events in time order, end of track.
+fn track(name: &str, events: &Events) -> Vec<u8> {
+    let mut out = Vec::new();
+    vlq(&mut out, 0);
+    out.extend(meta(0x03, name.as_bytes()));
+    let mut now = 0;
+    for (tick, msg) in events {
+        // A timestamp stepping backwards is written at the current tick
+        vlq(&mut out, tick.saturating_sub(now));
+        out.extend_from_slice(msg);
+        now = now.max(*tick);
+    }
+    vlq(&mut out, 0);
+    out.extend(meta(0x2F, &[]));
+    out
+}
+
+/// Variable-length quantity: 7 bits per byte, most significant first.
+fn vlq(out: &mut Vec<u8>, mut value: u64) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(t_ms: u64, active: &[usize], hz: f64, volume: f32) -> CaptureFrame {
+        let mut string_active = [false; 10];
+        for &i in active {
+            string_active[i] = true;
+        }
+        CaptureFrame {
+            volume,
+            string_pitches_hz: [hz; 10],
+            string_active,
+            ..CaptureFrame::at_rest(5_000_000 + t_ms * 1000)
+        }
+    }
+
+    /// (track name, [(tick, message)]) for each track.
+    fn read_tracks(smf: &[u8]) -> Vec<(String, Events)> {
+        assert_eq!(&smf[..4], b"MThd");
+        let count = u16::from_be_bytes([smf[10], smf[11]]) as usize;
+        let mut pos = 14;
+        let mut tracks = Vec::new();
+        for _ in 0..count {
+            assert_eq!(&smf[pos..pos + 4], b"MTrk");
+            let len = u32::from_be_bytes(smf[pos + 4..pos + 8].try_into().unwrap()) as usize;
+            let body = &smf[pos + 8..pos + 8 + len];
+            pos += 8 + len;
+            let (mut i, mut tick, mut name, mut events) = (0, 0, String::new(), Vec::new());
+            while i < body.len() {
+                let mut delta = 0u64;
+                loop {
+                    let b = body[i];
+                    i += 1;
+                    delta = delta << 7 | (b & 0x7F) as u64;
+                    if b & 0x80 == 0 {
+                        break;
+                    }
+                }
+                tick += delta;
+                if body[i] == 0xFF {
+                    let (kind, len) = (body[i + 1], body[i + 2] as usize);
+                    if kind == 0x03 {
+                        name = String::from_utf8(body[i + 3..i + 3 + len].to_vec()).unwrap();
+                    }
+                    i += 3 + len;
+                    continue;
+                }
+                let len = if matches!(body[i] & 0xF0, 0xC0 | 0xD0) {
+                    2
+                } else {
+                    3
+                };
+                events.push((tick, body[i..i + len].to_vec()));
+                i += len;
+            }
+            tracks.push((name, events));
+        }
+        assert_eq!(pos, smf.len());
+        tracks
+    }
+
+    #[test]
+    fn test_vlq() {
+        for (value, bytes) in [
+            (0u64, vec![0x00]),
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x81, 0x00]),
+            (0x0FFFFFFF, vec![0xFF, 0xFF, 0xFF, 0x7F]),
+        ] {
+            let mut out = Vec::new();
+            vlq(&mut out, value);
+            assert_eq!(out, bytes);
+        }
+    }
+
+    #[test]
+    fn test_tracks_notes_and_controls() {
+        // 120 BPM, 480 PPQ: 1 ms = 0.96 ticks
+        let mut frames = vec![frame(0, &[], 440.0, 0.5)];
+        let mut pick = frame(500, &[3], 440.0, 0.5);
+        pick.attacks[3] = true;
+        frames.push(pick);
+        frames.push(frame(1000, &[3], 440.0, 1.0));
+        frames.push(frame(1500, &[], 440.0, 1.0));
+        let tracks = read_tracks(&SmfExport::new().export("take 1", frames.clone()));
+
+        assert_eq!(tracks.len(), 20);
+        assert_eq!(tracks[0].0, "take 1");
+        assert_eq!(tracks[4].0, "String 4:E4");
+        assert_eq!(tracks[11].0, "Volume");
+        assert_eq!(tracks[19].0, "RKR");
+
+        // String 4 on channel 4: bend range, then the note at 480..1440
+        let s4: Vec<&(u64, Vec<u8>)> = tracks[4].1.iter().filter(|(t, _)| *t > 0).collect();
+        assert_eq!(
+            s4,
+            [
+                &(480, vec![0xE3, 0x00, 0x40]),
+                &(480, vec![0x93, 69, 100]),
+                &(1440, vec![0x83, 69, 64]),
+            ]
+        );
+        assert!(tracks[4].1.contains(&(0, vec![0xB3, 6, 48])));
+        // String 10 skips the drum channel
+        assert!(tracks[10].1.iter().all(|(_, m)| m[0] & 0x0F == 10));
+        // Volume pedal changes once, on channel 16
+        assert_eq!(
+            tracks[11].1,
+            [(0, vec![0xBF, 7, 64]), (960, vec![0xBF, 7, 127])]
+        );
+        assert_eq!(tracks[12].1, [(0, vec![0xBF, 20, 0])]);
+
+        // MPE: member channels with pressure, zone configuration on master
+        let tracks = read_tracks(&SmfExport::new().with_mpe(true).export("take 1", frames));
+        assert!(tracks[4].1.contains(&(480, vec![0xD4, 64])));
+        assert!(tracks[4].1.contains(&(480, vec![0x94, 69, 100])));
+        assert!(tracks[11].1.contains(&(0, vec![0xB0, 100, 6])));
+        assert!(tracks[11].1.contains(&(960, vec![0xB0, 7, 127])));
+    }
+
+    #[test]
+    fn test_timestamps_stepping_back() {
+        let frames = vec![
+            frame(0, &[], 440.0, 0.5),
+            frame(1000, &[], 440.0, 1.0),
+            frame(500, &[], 440.0, 0.5),
+        ];
+        let tracks = read_tracks(&SmfExport::new().export("take 1", frames));
+        assert_eq!(
+            tracks[11].1,
+            [
+                (0, vec![0xBF, 7, 64]),
+                (960, vec![0xBF, 7, 127]),
+                (960, vec![0xBF, 7, 64])
+            ]
+        );
+    }
+}
@@ -2,6 +2,62 @@ use crate::copedant::{midi_to_hz, CopedantEngine};
 use crate::dsp::{compute_rms, goertzel_magnitude};
 use crate::types::*;
 use log::trace;
+use std::fmt;
+use std::str::FromStr;
+
+/// RMS below this is indistinguishable from quantization/electronic noise
+/// in a typical audio interface.
+const SILENCE_RMS: f32 = 0.003;
+
+/// Which input channel carries each string, for a hexaphonic (per-string)
+/// pickup. Unmapped strings use the mono analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StringChannels(pub [Option<usize>; 10]);
+
+impl StringChannels {
+    /// Input channel (0-based) for string index `si`.
+    pub fn channel(&self, si: usize) -> Option<usize> {
+        self.0[si]
+    }
+}
+
+/// Parses a comma-separated list of 1-based channel numbers, one per string
+/// from string 1; `-` leaves a string unmapped, missing entries are
+/// unmapped. `1,2,3,4,5,6` maps strings 1–6 to channels 1–6.
+impl FromStr for StringChannels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entries: Vec<&str> = s.split(',').map(str::trim).collect();
+        if entries.len() > 10 {
+            return Err(format!("{} entries for 10 strings", entries.len()));
+        }
+        let mut map = [None; 10];
+        for (si, entry) in entries.iter().enumerate() {
+            if *entry == "-" {
+                continue;
+            }
+            let channel: usize = entry
+                .parse()
+                .ok()
+                .filter(|&c| c >= 1)
+                .ok_or_else(|| format!("string {}: bad channel '{}'", si + 1, entry))?;
+            map[si] = Some(channel - 1);
+        }
+        Ok(Self(map))
+    }
+}
+
+impl fmt::Display for StringChannels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = self
+            .0
+            .iter()
+            .map(|c| c.map_or("-".to_string(), |c| (c + 1).to_string()))
+            .collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
 
 /// Per-string onset/release detector using constrained spectral analysis.
 ///
@@ -30,6 +86,14 @@ use log::trace;
 /// - Only works when bar position is known (need sensor or prior audio estimate).
 /// - Fast picking rolls (<5ms between attacks) may not resolve at the
 ///   ~42ms analysis rate.
+///
+/// # Hexaphonic pickups
+///
+/// With a `StringChannels` map and multichannel audio, each mapped string
+/// is analyzed on its own channel instead: Goertzel at its expected
+/// frequency when the bar is known (rejecting bleed from neighbouring
+/// strings), or the channel's level when it isn't — a per-string pickup
+/// doesn't need the bar to tell which string sounds.
 pub struct StringDetector {
     /// Per-string smoothed Goertzel energy (raw, unnormalized)
     energy: [f64; 10],
@@ -54,6 +118,10 @@ pub struct StringDetector {
     pub analysis_interval: usize,
     /// Cached sample rate
     sample_rate: u32,
+    /// Per-string input channels (hexaphonic pickup)
+    string_channels: Option<StringChannels>,
+    /// Per-channel ring buffers, filled from multichannel chunks
+    channel_bufs: Vec<Vec<f32>>,
 }
 
 impl StringDetector {
@@ -70,6 +138,8 @@ impl StringDetector {
             samples_since_analysis: 0,
             analysis_interval: 2048, // run every ~42ms
             sample_rate: 48000,
+            string_channels: None,
+            channel_bufs: Vec::new(),
         }
     }
 
@@ -80,18 +150,41 @@ impl StringDetector {
         self
     }
 
+    /// Analyze mapped strings on their own input channels.
+    pub fn with_string_channels(mut self, channels: StringChannels) -> Self {
+        self.string_channels = Some(channels);
+        self
+    }
+
     /// Push new audio samples into the internal buffer.
     pub fn push_audio(&mut self, chunk: &AudioChunk) {
         self.sample_rate = chunk.sample_rate;
         self.audio_buf.extend_from_slice(&chunk.samples);
         self.samples_since_analysis += chunk.samples.len();
 
-        // Keep buffer bounded
+        // Keep buffers bounded
         let max_len = self.analysis_window * 2;
         if self.audio_buf.len() > max_len {
             let excess = self.audio_buf.len() - max_len;
             self.audio_buf.drain(..excess);
         }
+        if self.string_channels.is_some() {
+            self.channel_bufs.resize(chunk.channels.len(), Vec::new());
+            for (buf, samples) in self.channel_bufs.iter_mut().zip(&chunk.channels) {
+                buf.extend_from_slice(samples);
+                if buf.len() > max_len {
+                    let excess = buf.len() - max_len;
+                    buf.drain(..excess);
+                }
+            }
+        }
+    }
+
+    /// Input channel holding string `si`'s own signal, if mapped and present.
+    fn channel_for(&self, si: usize) -> Option<usize> {
+        self.string_channels
+            .and_then(|m| m.channel(si))
+            .filter(|&c| c < self.channel_bufs.len())
     }
 
     /// Returns true if enough audio has accumulated for analysis.
@@ -106,7 +199,8 @@ impl StringDetector {
     /// the frame where string i transitions from inactive → active.
     ///
     /// Requires `bar_position` to compute expected frequencies. If bar
-    /// position is unknown, all strings are marked inactive.
+    /// position is unknown, all strings without their own pickup channel
+    /// are marked inactive.
     pub fn detect(
         &mut self,
         sensor: &SensorFrame,
@@ -119,90 +213,64 @@ impl StringDetector {
         }
         self.samples_since_analysis = 0;
 
-        let bar_fret = match bar_position {
-            Some(f) => f,
-            None => {
-                // No bar position → can't determine frequencies → all inactive
-                self.active = [false; 10];
-                self.energy = [0.0; 10];
-                return (self.active, [false; 10], [0.0; 10]);
-            }
-        };
-
         // Use the most recent analysis_window samples
         let start = self.audio_buf.len().saturating_sub(self.analysis_window);
         let samples = &self.audio_buf[start..];
         let n = samples.len();
         let sr = self.sample_rate as f64;
 
-        // Global silence threshold. RMS below this is indistinguishable from
-        // quantization/electronic noise in a typical audio interface.
-        let rms = compute_rms(samples);
-        if rms < 0.003 {
-            // Silence — all strings inactive
-            for i in 0..10 {
-                self.energy[i] *= 0.5; // Decay energy toward zero
-            }
-            self.active = [false; 10];
-            return (self.active, [false; 10], self.amplitude());
-        }
+        // Global silence threshold for the mono analysis
+        let silent = compute_rms(samples) < SILENCE_RMS;
 
         // Compute expected frequency for each string
         let open = engine.effective_open_pitches(sensor);
+        let freq = |si: usize| bar_position.map(|fret| midi_to_hz(open[si] + fret as f64));
+
+        let readings: Vec<Reading> = (0..10)
+            .map(|si| {
+                // Hexaphonic pickup: the string's own channel
+                if let Some(ch) = self.channel_for(si) {
+                    let buf = &self.channel_bufs[ch];
+                    let own = &buf[buf.len().saturating_sub(self.analysis_window)..];
+                    if own.is_empty() || compute_rms(own) < SILENCE_RMS {
+                        return Reading::Silent;
+                    }
+                    return Reading::Energy(
+                        match freq(si).filter(|&f| f >= 20.0 && f * 2.0 < sr / 2.0) {
+                            Some(f) => harmonic_energy(own, f, sr),
+                            // No bar: the channel's level; for a sine, RMS/√2
+                            // equals the normalized Goertzel magnitude, so
+                            // thresholds hold
+                            None => compute_rms(own) as f64 * std::f64::consts::FRAC_1_SQRT_2,
+                        },
+                    );
+                }
+                match freq(si) {
+                    // No bar position → can't determine frequencies
+                    None => Reading::Unknown,
+                    Some(_) if silent => Reading::Silent,
+                    // Outside audible/Nyquist range
+                    Some(f) if !(20.0..=sr / 2.0).contains(&f) => Reading::Unknown,
+                    Some(f) if f * 2.0 < sr / 2.0 => {
+                        Reading::Energy(harmonic_energy(samples, f, sr))
+                    }
+                    Some(f) => Reading::Energy(goertzel_magnitude(samples, f, sr, n) / n as f64),
+                }
+            })
+            .collect();
 
         let mut attacks = [false; 10];
-
-        for si in 0..10 {
-            let freq = midi_to_hz(open[si] + bar_fret as f64);
-
-            // Skip frequencies outside audible/Nyquist range
-            if freq < 20.0 || freq > sr / 2.0 {
-                self.energy[si] = 0.0;
-                self.active[si] = false;
-                continue;
-            }
-
-            // Goertzel magnitude at the expected fundamental
-            let mag = goertzel_magnitude(samples, freq, sr, n);
-
-            // Also check 2nd harmonic (helps distinguish from noise)
-            let mag2 = if freq * 2.0 < sr / 2.0 {
-                goertzel_magnitude(samples, freq * 2.0, sr, n)
-            } else {
-                0.0
-            };
-
-            // Combined energy: fundamental + weighted 2nd harmonic.
-            // The 0.3 weight is empirical: harmonic confirms string identity
-            // without dominating (real strings have strong 2nd harmonics, noise does not).
-            let raw_energy = mag + 0.3 * mag2;
-
-            // Normalize by number of samples for consistent thresholds
-            let normalized = raw_energy / n as f64;
-
-            // Smooth the energy
-            self.energy[si] =
-                self.energy[si] * self.smoothing + normalized * (1.0 - self.smoothing);
-
-            // Track peak energy for normalizing amplitude to 0.0-1.0.
-            // Adapts over ~3.6s half-life at ~24Hz analysis rate.
-            if self.energy[si] > self.peak_energy[si] {
-                self.peak_energy[si] = self.energy[si];
-            } else {
-                self.peak_energy[si] = (self.peak_energy[si] * 0.992).max(0.01);
-            }
-
-            // Threshold with hysteresis (per-string calibrated values)
-            if self.active[si] {
-                // Currently active — need to drop below release threshold
-                if self.energy[si] < self.release_threshold[si] {
+        for (si, reading) in readings.into_iter().enumerate() {
+            match reading {
+                Reading::Energy(normalized) => self.update_string(si, normalized, &mut attacks),
+                Reading::Silent => {
+                    // Decay energy toward zero
+                    self.energy[si] *= 0.5;
                     self.active[si] = false;
                 }
-            } else {
-                // Currently inactive — need to rise above onset threshold
-                if self.energy[si] > self.onset_threshold[si] {
-                    self.active[si] = true;
-                    attacks[si] = true;
+                Reading::Unknown => {
+                    self.energy[si] = 0.0;
+                    self.active[si] = false;
                 }
             }
         }
@@ -224,6 +292,34 @@ impl StringDetector {
         (self.active, attacks, self.amplitude())
     }
 
+    /// Smooth one string's new energy reading and apply the thresholds.
+    fn update_string(&mut self, si: usize, normalized: f64, attacks: &mut [bool; 10]) {
+        // Smooth the energy
+        self.energy[si] = self.energy[si] * self.smoothing + normalized * (1.0 - self.smoothing);
+
+        // Track peak energy for normalizing amplitude to 0.0-1.0.
+        // Adapts over ~3.6s half-life at ~24Hz analysis rate.
+        if self.energy[si] > self.peak_energy[si] {
+            self.peak_energy[si] = self.energy[si];
+        } else {
+            self.peak_energy[si] = (self.peak_energy[si] * 0.992).max(0.01);
+        }
+
+        // Threshold with hysteresis (per-string calibrated values)
+        if self.active[si] {
+            // Currently active — need to drop below release threshold
+            if self.energy[si] < self.release_threshold[si] {
+                self.active[si] = false;
+            }
+        } else {
+            // Currently inactive — need to rise above onset threshold
+            if self.energy[si] > self.onset_threshold[si] {
+                self.active[si] = true;
+                attacks[si] = true;
+            }
+        }
+    }
+
     /// Per-string amplitude normalized to 0.0-1.0 (energy / peak_energy).
     fn amplitude(&self) -> [f32; 10] {
         let mut out = [0.0f32; 10];
@@ -239,10 +335,31 @@ impl StringDetector {
         self.peak_energy = [0.01; 10];
         self.active = [false; 10];
         self.audio_buf.clear();
+        self.channel_bufs.clear();
         self.samples_since_analysis = 0;
     }
 }
 
+/// One string's input for an analysis pass.
+enum Reading {
+    /// Normalized energy at the string's frequency (or on its own channel)
+    Energy(f64),
+    Silent,
+    /// Frequency unknown (no bar) or out of range
+    Unknown,
+}
+
+/// Goertzel magnitude at the fundamental plus the weighted 2nd harmonic,
+/// normalized by window length for consistent thresholds. The 0.3 weight
+/// is empirical: the harmonic confirms string identity without dominating
+/// (real strings have strong 2nd harmonics, noise does not).
+fn harmonic_energy(samples: &[f32], freq: f64, sr: f64) -> f64 {
+    let n = samples.len();
+    let mag = goertzel_magnitude(samples, freq, sr, n);
+    let mag2 = goertzel_magnitude(samples, freq * 2.0, sr, n);
+    (mag + 0.3 * mag2) / n as f64
+}
+
 impl Default for StringDetector {
     fn default() -> Self {
         Self::new()
@@ -272,6 +389,7 @@ mod tests {
             timestamp_us: 0,
             samples: samples.to_vec(),
             sample_rate: sr,
+            channels: Vec::new(),
         };
         det.push_audio(&chunk);
         // Force analysis readiness
@@ -454,4 +572,46 @@ mod tests {
             "active string should have positive amplitude"
         );
     }
+
+    #[test]
+    fn test_hex_channels_detect_without_bar() {
+        let engine = make_engine();
+        let map: StringChannels = "1,2,3".parse().unwrap();
+        let mut det = StringDetector::new().with_string_channels(map);
+        let sensor = SensorFrame::at_rest(0);
+
+        // Channel 2 (string 2) rings; channel 1 silent; channel 3 faint bleed
+        let tone = sine_wave(440.0, 0.5, 48000, 100);
+        let bleed = sine_wave(440.0, 0.001, 48000, 100);
+        let silent = vec![0.0; tone.len()];
+        let mono: Vec<f32> = tone.iter().map(|s| s / 3.0).collect();
+        det.push_audio(&AudioChunk {
+            timestamp_us: 0,
+            samples: mono,
+            sample_rate: 48000,
+            channels: vec![silent, tone, bleed],
+        });
+        det.samples_since_analysis = det.analysis_interval;
+        let (active, attacks, _) = det.detect(&sensor, None, &engine);
+        assert_eq!(
+            active,
+            [false, true, false, false, false, false, false, false, false, false]
+        );
+        assert!(attacks[1]);
+    }
+
+    #[test]
+    fn test_string_channels_parse() {
+        let map: StringChannels = "1, 2,-,4".parse().unwrap();
+        assert_eq!(map.channel(0), Some(0));
+        assert_eq!(map.channel(2), None);
+        assert_eq!(map.channel(3), Some(3));
+        assert_eq!(map.channel(9), None);
+        assert_eq!(map.to_string(), "1,2,-,4,-,-,-,-,-,-");
+        assert_eq!(map.to_string().parse::<StringChannels>().unwrap(), map);
+
+        assert!("0".parse::<StringChannels>().is_err());
+        assert!("a".parse::<StringChannels>().is_err());
+        assert!("1,2,3,4,5,6,7,8,9,10,11".parse::<StringChannels>().is_err());
+    }
 }
@@ -0,0 +1,357 @@
+//! Plucked-string synthesis for the simulator.
+//!
+//! The original simulator audio is a sum of pure sines, which flatters the
+//! string detector: no harmonics, no pick noise, no decay. `SteelSynth`
+//! models the things that make real pedal steel audio hard to analyze:
+//!
+//! - **Harmonic partials** with a plucked-string spectrum (pick position comb)
+//! - **Inharmonicity**: partial n sits at `n·f0·√(1 + B·n²)` (stiff string)
+//! - **Pick transient**: a short filtered noise burst at each pluck
+//! - **Exponential decay** per string, faster for higher partials
+//! - **Pickup comb filtering**: the pickup sits a fixed distance from the
+//!   bridge, so partials with a node near the pickup are attenuated. The
+//!   vibrating length shortens as the bar moves up, which moves the notches.
+
+use crate::copedant::midi_to_hz;
+use crate::simulator::Rng;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+/// Which synthesis model the simulator uses for its audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioModel {
+    /// One pure sine per active string (ideal signal)
+    Sine,
+    /// Plucked-string model: partials, decay, pick transient, pickup comb
+    Steel,
+}
+
+impl FromStr for AudioModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sine" => Ok(Self::Sine),
+            "steel" => Ok(Self::Steel),
+            other => Err(format!(
+                "unknown audio model '{}' (expected: sine, steel)",
+                other
+            )),
+        }
+    }
+}
+
+/// Physical and spectral parameters of the string model.
+#[derive(Debug, Clone)]
+pub struct SynthParams {
+    /// Partials per string, including the fundamental
+    pub partials: usize,
+    /// Inharmonicity coefficient B per string (wound strings are stiffer)
+    pub inharmonicity: [f64; 10],
+    /// Time for the fundamental to fall by 60 dB, per string (seconds)
+    pub t60_s: [f32; 10],
+    /// Extra decay rate per partial above the fundamental, as a fraction of
+    /// the fundamental's rate. Upper partials die out first.
+    pub partial_damping: f32,
+    /// Scale length, nut to bridge (inches)
+    pub scale_length_in: f64,
+    /// Distance from the bridge to the pickup's sensing point (inches)
+    pub pickup_distance_in: f64,
+    /// Distance from the bridge to where the string is picked (inches)
+    pub pick_distance_in: f64,
+    /// Pick transient duration (milliseconds)
+    pub transient_ms: f32,
+    /// Pick transient peak level, relative to the string body
+    pub transient_level: f32,
+    /// Time constant for damping a string that stops sounding (milliseconds)
+    pub mute_ms: f32,
+}
+
+impl Default for SynthParams {
+    fn default() -> Self {
+        Self {
+            partials: 8,
+            // Strings 1–5 plain, 6–10 wound
+            inharmonicity: [
+                5e-5, 5e-5, 5e-5, 5e-5, 5e-5, 1.5e-4, 1.5e-4, 1.5e-4, 1.5e-4, 1.5e-4,
+            ],
+            // Low strings ring longer
+            t60_s: [4.0, 4.0, 4.5, 4.5, 5.0, 5.5, 5.5, 6.0, 6.5, 7.0],
+            partial_damping: 0.35,
+            scale_length_in: 24.0,
+            pickup_distance_in: 1.75,
+            pick_distance_in: 4.5,
+            transient_ms: 6.0,
+            transient_level: 0.6,
+            mute_ms: 25.0,
+        }
+    }
+}
+
+/// State of one vibrating string.
+#[derive(Clone)]
+struct Voice {
+    /// Phase per partial (radians). Accumulated per sample so pitch changes
+    /// from bar slides and pedals stay phase-continuous.
+    phases: Vec<f64>,
+    /// Current vibration amplitude per partial (decaying envelope)
+    amps: Vec<f32>,
+    /// Loudness normalization fixed at pluck time
+    norm: f32,
+    /// Pluck velocity (scales the transient)
+    velocity: f32,
+    /// Remaining / total samples of the pick transient
+    transient_left: usize,
+    transient_total: usize,
+    /// One-pole lowpass state for the transient noise
+    noise_lp: f32,
+}
+
+impl Voice {
+    fn new(partials: usize) -> Self {
+        Self {
+            phases: vec![0.0; partials],
+            amps: vec![0.0; partials],
+            norm: 0.0,
+            velocity: 0.0,
+            transient_left: 0,
+            transient_total: 0,
+            noise_lp: 0.0,
+        }
+    }
+
+    fn is_silent(&self) -> bool {
+        self.transient_left == 0 && self.amps.iter().all(|&a| a < 1e-5)
+    }
+}
+
+/// Ten-voice plucked-string synthesizer.
+///
+/// Call [`pluck`](Self::pluck) when a string is picked, then
+/// [`render`](Self::render) once per chunk with the current pitches.
+pub struct SteelSynth {
+    params: SynthParams,
+    sample_rate: u32,
+    voices: Vec<Voice>,
+    rng: Rng,
+}
+
+impl SteelSynth {
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_params(sample_rate, SynthParams::default())
+    }
+
+    pub fn with_params(sample_rate: u32, params: SynthParams) -> Self {
+        let voices = (0..10).map(|_| Voice::new(params.partials)).collect();
+        Self {
+            params,
+            sample_rate,
+            voices,
+            rng: Rng::new(0x5eed_5717),
+        }
+    }
+
+    /// Vibrating length with the bar at `bar_fret` (inches).
+    fn vibrating_length(&self, bar_fret: f32) -> f64 {
+        self.params.scale_length_in * 2f64.powf(-bar_fret as f64 / 12.0)
+    }
+
+    /// Pickup response per partial: |sin(nπ·p)| where p is the pickup's
+    /// position as a fraction of the vibrating length.
+    fn pickup_weights(&self, bar_fret: f32) -> Vec<f32> {
+        let p = self.params.pickup_distance_in / self.vibrating_length(bar_fret);
+        (1..=self.params.partials)
+            .map(|n| (n as f64 * PI * p).sin().abs() as f32)
+            .collect()
+    }
+
+    /// Pick string `string` (0-indexed) with the bar at `bar_fret`.
+    /// Velocity 1.0 is a normal pick. Re-picking a sounding string restarts it.
+    pub fn pluck(&mut self, string: usize, bar_fret: f32, velocity: f32) {
+        if string >= 10 {
+            return;
+        }
+        // Plucked-string spectrum: partial n has amplitude |sin(nπ·q)| / n,
+        // q = pick position as a fraction of the vibrating length.
+        let q = self.params.pick_distance_in / self.vibrating_length(bar_fret);
+        let pickup = self.pickup_weights(bar_fret);
+        let amps: Vec<f32> = (1..=self.params.partials)
+            .map(|n| ((n as f64 * PI * q).sin().abs() / n as f64) as f32)
+            .collect();
+
+        // Normalize so a fresh voice has the same RMS as a unit sine at the
+        // pickup. Keeps levels comparable to the sine model.
+        let power: f32 = amps
+            .iter()
+            .zip(&pickup)
+            .map(|(a, w)| (a * w) * (a * w))
+            .sum();
+        let norm = if power > 0.0 { 1.0 / power.sqrt() } else { 0.0 };
+
+        let transient = (self.params.transient_ms * self.sample_rate as f32 / 1000.0) as usize;
+        let voice = &mut self.voices[string];
+        voice.amps = amps;
+        voice.norm = norm;
+        voice.velocity = velocity;
+        voice.transient_left = transient;
+        voice.transient_total = transient.max(1);
+        voice.noise_lp = 0.0;
+    }
+
+    /// Mix all sounding voices into `out` (added to its existing contents).
+    ///
+    /// `pitches_midi` are the sounding pitches (open + pedals/levers + bar).
+    /// Strings not in `active` are damped quickly rather than cut, so muting
+    /// doesn't click. `gain` scales each voice (volume pedal × headroom).
+    pub fn render(
+        &mut self,
+        pitches_midi: &[f64; 10],
+        bar_fret: f32,
+        active: &[bool; 10],
+        gain: f32,
+        out: &mut [f32],
+    ) {
+        let sr = self.sample_rate as f64;
+        let nyquist = sr / 2.0;
+        let pickup = self.pickup_weights(bar_fret);
+        let mute_decay = (-1000.0 / (self.params.mute_ms as f64 * sr)).exp() as f32;
+
+        for si in 0..10 {
+            if self.voices[si].is_silent() {
+                continue;
+            }
+            let f0 = midi_to_hz(pitches_midi[si]);
+            let b = self.params.inharmonicity[si];
+            let base_rate = 0.001f64.ln() / (self.params.t60_s[si] as f64 * sr);
+
+            // Per-partial phase increment and per-sample decay factor
+            let mut incs = Vec::with_capacity(self.params.partials);
+            let mut decays = Vec::with_capacity(self.params.partials);
+            for n in 1..=self.params.partials {
+                let nf = n as f64;
+                let fn_hz = nf * f0 * (1.0 + b * nf * nf).sqrt();
+                incs.push(if fn_hz < nyquist {
+                    2.0 * PI * fn_hz / sr
+                } else {
+                    0.0
+                });
+                let rate = base_rate * (1.0 + self.params.partial_damping as f64 * (nf - 1.0));
+                let mut d = rate.exp() as f32;
+                if !active[si] {
+                    d *= mute_decay;
+                }
+                decays.push(d);
+            }
+
+            let transient_level = self.params.transient_level;
+            let voice = &mut self.voices[si];
+            for sample in out.iter_mut() {
+                let mut v = 0.0f32;
+                for n in 0..incs.len() {
+                    if incs[n] == 0.0 {
+                        continue;
+                    }
+                    voice.phases[n] += incs[n];
+                    v += voice.amps[n] * pickup[n] * voice.phases[n].sin() as f32;
+                    voice.amps[n] *= decays[n];
+                }
+                v *= voice.norm;
+
+                if voice.transient_left > 0 {
+                    let env = voice.transient_left as f32 / voice.transient_total as f32;
+                    voice.noise_lp += 0.35 * (self.rng.bipolar() - voice.noise_lp);
+                    v += transient_level * voice.velocity * env * env * voice.noise_lp;
+                    voice.transient_left -= 1;
+                }
+
+                *sample += gain * v;
+            }
+
+            for phase in voice.phases.iter_mut() {
+                *phase %= 2.0 * PI;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::{compute_rms, goertzel_magnitude};
+
+    fn render_ms(synth: &mut SteelSynth, pitches: &[f64; 10], fret: f32, ms: u32) -> Vec<f32> {
+        let mut active = [false; 10];
+        active[3] = true;
+        let mut out = vec![0.0f32; (48 * ms) as usize];
+        synth.render(pitches, fret, &active, 1.0, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_parse_audio_model() {
+        assert_eq!("sine".parse::<AudioModel>(), Ok(AudioModel::Sine));
+        assert_eq!("steel".parse::<AudioModel>(), Ok(AudioModel::Steel));
+        assert!("square".parse::<AudioModel>().is_err());
+    }
+
+    #[test]
+    fn test_unplucked_string_is_silent() {
+        let mut synth = SteelSynth::new(48000);
+        let out = render_ms(&mut synth, &[64.0; 10], 0.0, 50);
+        assert!(out.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_pluck_decays() {
+        let mut synth = SteelSynth::new(48000);
+        synth.pluck(3, 0.0, 1.0);
+        let pitches = [64.0; 10];
+        let early = render_ms(&mut synth, &pitches, 0.0, 100);
+        let _ = render_ms(&mut synth, &pitches, 0.0, 1000);
+        let late = render_ms(&mut synth, &pitches, 0.0, 100);
+        let (e, l) = (compute_rms(&early), compute_rms(&late));
+        assert!(e > 0.1, "fresh pluck should be loud: rms {:.3}", e);
+        assert!(l < e * 0.8, "should decay: {:.3} → {:.3}", e, l);
+    }
+
+    #[test]
+    fn test_upper_partials_are_inharmonic() {
+        let params = SynthParams {
+            inharmonicity: [2e-3; 10], // exaggerated for a clear measurement
+            transient_level: 0.0,
+            ..Default::default()
+        };
+        let mut synth = SteelSynth::with_params(48000, params);
+        synth.pluck(3, 0.0, 1.0);
+        let pitches = [64.0; 10];
+        let out = render_ms(&mut synth, &pitches, 0.0, 200);
+
+        let f0 = midi_to_hz(64.0);
+        let n = out.len();
+        let harmonic = goertzel_magnitude(&out, 4.0 * f0, 48000.0, n);
+        let stretched =
+            goertzel_magnitude(&out, 4.0 * f0 * (1.0 + 2e-3 * 16.0f64).sqrt(), 48000.0, n);
+        assert!(
+            stretched > harmonic * 2.0,
+            "4th partial should sit above 4·f0: harmonic {:.1}, stretched {:.1}",
+            harmonic,
+            stretched
+        );
+    }
+
+    #[test]
+    fn test_muted_string_damps_quickly() {
+        let mut synth = SteelSynth::new(48000);
+        synth.pluck(3, 0.0, 1.0);
+        let pitches = [64.0; 10];
+        let _ = render_ms(&mut synth, &pitches, 0.0, 20);
+        let mut out = vec![0.0f32; 48 * 400];
+        synth.render(&pitches, 0.0, &[false; 10], 1.0, &mut out);
+        let tail = compute_rms(&out[out.len() - 4800..]);
+        assert!(
+            tail < 1e-3,
+            "muted string should be near silent: {:.5}",
+            tail
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! Tab import: reads plain-text pedal steel tab so the simulator can play
+//! it (`--tab FILE`). The format is the one `export tab` writes, plus
+//! hammer-ons, so a recorded session can be played back from its tab.
+//!
+//! ```text
+//! Intro (any line that isn't a string line is ignored)
+//!  3|-10A---/12----h12B--|
+//!  4|-10A---/12----------|
+//!  5|-10A---/12---12-----|
+//! ```
+//!
+//! - A string line is a string number (1–10) and `|`, then cells separated
+//!   by dashes; a closing `|` is optional. Consecutive string lines form a
+//!   block, and blocks play one after another. Strings a block leaves out
+//!   are silent in it.
+//! - A cell is a fret (0 = open, bar lifted) followed by the pedals and
+//!   levers held for it: `A` `B` `C` `LKL` `LKR` `LKV` `RKL` `RKR`, as in
+//!   `10A` or `3ALKL`. A `/` or `\` before the fret slides the bar there
+//!   while the string rings; `h` hammers on, moving bar, pedals or levers
+//!   without picking again.
+//! - Cells starting in overlapping character positions are one column,
+//!   played together, so they must all want the bar at the same fret.
+//!   Between columns, the first dash is a separator and every two more are
+//!   an empty column: `-3-5-` is two neighbouring columns, `-3---5-` has
+//!   one empty column between them.
+//! - Each column with notes sounds exactly its strings: the rest are
+//!   blocked, written strings are picked, and slid or hammered strings
+//!   that are still ringing carry on.
+//!
+//! Columns are a fixed note value at the tab's tempo (`with_tempo`,
+//! `with_grid`); the defaults, sixteenths at 120 BPM, match `export tab`'s
+//! 125 ms columns.
+//!
+//! `tab/gone_country_intro.txt` is a full example.
+
+use std::ops::Range;
+use std::path::Path;
+
+/// How a note is sounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Articulation {
+    Picked,
+    /// The bar slides to the fret (`/12`, `\8`)
+    Slide,
+    /// Changes under a ringing string without picking (`h12B`)
+    HammerOn,
+}
+
+/// One cell of a tab.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabNote {
+    /// String index, 0 = string 1
+    pub string: usize,
+    /// Bar fret; 0 with the bar lifted
+    pub fret: u8,
+    pub pedals: [bool; 3],
+    pub levers: [bool; 5],
+    pub articulation: Articulation,
+}
+
+/// Notes played together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabColumn {
+    /// Position in columns from the start of the tab
+    pub step: u32,
+    pub notes: Vec<TabNote>,
+}
+
+impl TabColumn {
+    /// The bar fret every note in the column shares.
+    pub fn fret(&self) -> u8 {
+        self.notes.first().map_or(0, |n| n.fret)
+    }
+}
+
+/// A parsed tab: its note columns and the tempo to play them at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tab {
+    /// Columns with notes, in order
+    pub columns: Vec<TabColumn>,
+    /// Length in columns, empty ones included
+    pub steps: u32,
+    tempo_bpm: f32,
+    grid: u32,
+}
+
+const CHANGERS: [(&str, Changer); 8] = [
+    ("LKL", Changer::Lever(0)),
+    ("LKR", Changer::Lever(1)),
+    ("LKV", Changer::Lever(2)),
+    ("RKL", Changer::Lever(3)),
+    ("RKR", Changer::Lever(4)),
+    ("A", Changer::Pedal(0)),
+    ("B", Changer::Pedal(1)),
+    ("C", Changer::Pedal(2)),
+];
+
+/// A note and the line it came from.
+type LineNote = (usize, TabNote);
+
+#[derive(Clone, Copy)]
+enum Changer {
+    Pedal(usize),
+    Lever(usize),
+}
+
+impl Tab {
+    /// Parse tab text. Errors name the line at fault.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut tab = Self {
+            columns: Vec::new(),
+            steps: 0,
+            tempo_bpm: 120.0,
+            grid: 16,
+        };
+        let mut block: Vec<(usize, usize, &str)> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            match string_line(line) {
+                Some((string, cells)) => {
+                    let string = string.map_err(|e| format!("line {}: {}", i + 1, e))?;
+                    if let Some((at, _, _)) = block.iter().find(|(_, s, _)| *s == string) {
+                        return Err(format!(
+                            "line {}: string {} already appears on line {} of this block",
+                            i + 1,
+                            string + 1,
+                            at
+                        ));
+                    }
+                    block.push((i + 1, string, cells));
+                }
+                None => tab.add_block(&std::mem::take(&mut block))?,
+            }
+        }
+        tab.add_block(&block)?;
+        if tab.columns.is_empty() {
+            return Err("no notes found (expected string lines like ' 3|-10A-|')".into());
+        }
+        Ok(tab)
+    }
+
+    /// Read and parse a tab file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{:?}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{:?}: {}", path, e))
+    }
+
+    /// Tempo in beats per minute (clamped to 10–400).
+    pub fn with_tempo(mut self, bpm: f32) -> Self {
+        self.tempo_bpm = bpm.clamp(10.0, 400.0);
+        self
+    }
+
+    /// Note value of one column: 4 = quarter notes, 16 = sixteenths
+    /// (clamped to 1–64).
+    pub fn with_grid(mut self, note_value: u32) -> Self {
+        self.grid = note_value.clamp(1, 64);
+        self
+    }
+
+    /// Length of one column in milliseconds.
+    pub fn column_ms(&self) -> f64 {
+        60_000.0 / self.tempo_bpm as f64 * 4.0 / self.grid as f64
+    }
+
+    /// Append one block of string lines (line number, string, cell text).
+    fn add_block(&mut self, block: &[(usize, usize, &str)]) -> Result<(), String> {
+        if block.is_empty() {
+            return Ok(());
+        }
+        let mut cells: Vec<(Range<usize>, usize, TabNote)> = Vec::new();
+        for &(line, string, text) in block {
+            for span in cell_spans(text) {
+                let note = parse_cell(&text[span.clone()], string)
+                    .map_err(|e| format!("line {}: {}", line, e))?;
+                cells.push((span, line, note));
+            }
+        }
+        cells.sort_by_key(|(span, _, _)| span.start);
+
+        // Group overlapping cells into columns
+        let mut groups: Vec<(Range<usize>, Vec<LineNote>)> = Vec::new();
+        for (span, line, note) in cells {
+            match groups.last_mut() {
+                Some((range, notes)) if span.start < range.end => {
+                    range.end = range.end.max(span.end);
+                    notes.push((line, note));
+                }
+                _ => groups.push((span, vec![(line, note)])),
+            }
+        }
+
+        let mut end = 0;
+        for (range, notes) in groups {
+            self.steps += gap_columns(range.start - end);
+            end = range.end;
+            let (first_line, first) = &notes[0];
+            if let Some((line, other)) = notes.iter().find(|(_, n)| n.fret != first.fret) {
+                return Err(format!(
+                    "line {}: string {} needs the bar at fret {} but string {} (line {}) \
+                     at fret {} in the same column",
+                    line,
+                    other.string + 1,
+                    other.fret,
+                    first.string + 1,
+                    first_line,
+                    first.fret
+                ));
+            }
+            let mut notes: Vec<TabNote> = notes.into_iter().map(|(_, n)| n).collect();
+            notes.sort_by_key(|n| n.string);
+            self.columns.push(TabColumn {
+                step: self.steps,
+                notes,
+            });
+            self.steps += 1;
+        }
+        let width = block.iter().map(|(_, _, t)| t.len()).max().unwrap_or(0);
+        self.steps += gap_columns(width.saturating_sub(end));
+        Ok(())
+    }
+}
+
+/// `Some((string, cells))` for a string line: the string index (or why
+/// it's unusable) and the text after the `|`, without a closing `|`.
+fn string_line(line: &str) -> Option<(Result<usize, String>, &str)> {
+    let trimmed = line.trim_start();
+    let digits = trimmed.len()
+        - trimmed
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    if digits == 0 {
+        return None;
+    }
+    let cells = trimmed[digits..].trim_start().strip_prefix('|')?;
+    let cells = cells.trim_end();
+    let cells = cells.strip_suffix('|').unwrap_or(cells);
+    let string = match trimmed[..digits].parse::<usize>() {
+        Ok(n @ 1..=10) => Ok(n - 1),
+        _ => Err(format!("string '{}' is not 1–10", &trimmed[..digits])),
+    };
+    Some((string, cells))
+}
+
+/// Byte ranges of the cells in a string line: runs of anything but dashes,
+/// spaces and bar lines.
+fn cell_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        let filler = matches!(c, '-' | ' ' | '|');
+        match (start, filler) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                spans.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push(s..text.len());
+    }
+    spans
+}
+
+/// Empty columns in `dashes` characters of filler before a column.
+fn gap_columns(dashes: usize) -> u32 {
+    (dashes.saturating_sub(1) / 2) as u32
+}
+
+fn parse_cell(cell: &str, string: usize) -> Result<TabNote, String> {
+    let bad = || {
+        format!(
+            "can't read '{}' on string {} (expected e.g. 10, 10A, 3ALKL, /12, \\8, h5B)",
+            cell,
+            string + 1
+        )
+    };
+    let (articulation, rest) = match cell.chars().next() {
+        Some('/') | Some('\\') => (Articulation::Slide, &cell[1..]),
+        Some('h') => (Articulation::HammerOn, &cell[1..]),
+        _ => (Articulation::Picked, cell),
+    };
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let fret: u8 = rest[..digits].parse().map_err(|_| bad())?;
+    if fret > 24 {
+        return Err(format!("fret {} on string {} is past 24", fret, string + 1));
+    }
+    let mut note = TabNote {
+        string,
+        fret,
+        pedals: [false; 3],
+        levers: [false; 5],
+        articulation,
+    };
+    let mut suffix = &rest[digits..];
+    while !suffix.is_empty() {
+        let (name, changer) = CHANGERS
+            .iter()
+            .find(|(name, _)| suffix.starts_with(name))
+            .ok_or_else(bad)?;
+        match changer {
+            Changer::Pedal(i) => note.pedals[*i] = true,
+            Changer::Lever(i) => note.levers[*i] = true,
+        }
+        suffix = &suffix[name.len()..];
+    }
+    Ok(note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_columns_changers_and_articulations() {
+        let text = "\
+0.00s
+ 1|----------------------|
+ 3|-10ALKL---/12---------|
+ 4|-10A------h12B--------|
+ 5|-10A------------------
+10|-------------------0--|
+
+Second block, cells aligned right
+ 2|-12A-|
+ 6|--12-|
+";
+        let tab = Tab::parse(text).unwrap();
+        let summary: Vec<_> = tab
+            .columns
+            .iter()
+            .map(|c| (c.step, c.notes.iter().map(|n| n.string).collect::<Vec<_>>()))
+            .collect();
+        // Three dashes after the 6-wide "10ALKL" make one empty column
+        assert_eq!(
+            summary,
+            [
+                (0, vec![2, 3, 4]),
+                (2, vec![2, 3]),
+                (5, vec![9]),
+                (6, vec![1, 5]),
+            ]
+        );
+        assert_eq!(tab.steps, 7);
+
+        let grip = &tab.columns[0].notes;
+        assert_eq!(grip[0].pedals, [true, false, false]);
+        assert_eq!(grip[0].levers, [true, false, false, false, false]);
+        assert_eq!(grip[1].levers, [false; 5]);
+        let moves = &tab.columns[1].notes;
+        assert_eq!(
+            (moves[0].fret, moves[0].articulation),
+            (12, Articulation::Slide)
+        );
+        assert_eq!(moves[1].articulation, Articulation::HammerOn);
+        assert_eq!(moves[1].pedals, [false, true, false]);
+        assert_eq!(tab.columns[3].fret(), 12);
+        assert_eq!(tab.column_ms(), 125.0);
+        assert_eq!(tab.with_tempo(60.0).with_grid(8).column_ms(), 500.0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Tab::parse(" 3|-10X-|").unwrap_err();
+        assert!(err.starts_with("line 1: can't read '10X'"), "{}", err);
+        let err = Tab::parse("x\n 3|-10-|\n 4|-12-|").unwrap_err();
+        assert!(
+            err.contains("line 3: string 4 needs the bar at fret 12"),
+            "{}",
+            err
+        );
+        let err = Tab::parse(" 3|-1-|\n 3|-1-|").unwrap_err();
+        assert!(
+            err.contains("string 3 already appears on line 1"),
+            "{}",
+            err
+        );
+        assert!(Tab::parse("12|-3-|").unwrap_err().contains("not 1–10"));
+        assert!(Tab::parse("just words\n\n")
+            .unwrap_err()
+            .contains("no notes"));
+    }
+}
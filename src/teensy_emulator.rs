@@ -0,0 +1,568 @@
+//! Teensy emulator — encodes sensor frames into the firmware's wire format.
+//!
+//! `TeensyEmulator` turns `SensorFrame`s into the exact packets the firmware
+//! sends — 34-byte v1 frames, or v2 data packets with periodic channel
+//! descriptors — optionally corrupting the stream
+//! the way a flaky USB link does: stray garbage bytes, CRC errors and
+//! frames cut short. `EmulatedStream` wraps it as a `Read` source fed by the
+//! simulator, and `open_pty` gives it a pseudo-terminal so the real
+//! `SerialReader` can be pointed at it with `--simulate false --port`.
+//!
+//! Host commands written to the stream are answered like the firmware
+//! would. Settings are tracked and reported, but the frame rate stays the
+//! simulator's.
+
+use crate::serial_protocol::{
+    encode_data_v2, encode_descriptor_v2, encode_frame, encode_reply_v2, sensor_to_raw,
+    Calibration, ChannelMap, CommandDecoder, CommandFrame, CommandReply, DeviceCommand, DeviceInfo,
+    ProtocolVersion, ReplyStatus, FRAME_SIZE,
+};
+use crate::simulator::Rng;
+use crate::types::*;
+use crossbeam_channel::{select, unbounded, Receiver, Sender};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+/// Firmware version the emulator reports (matches `steel_capture.ino`)
+pub const EMULATED_FIRMWARE: (u8, u8, u8) = (1, 1, 0);
+
+/// Counts of what the emulator actually sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EmulatorStats {
+    /// Frames written intact
+    pub frames: u64,
+    /// Frames written with a corrupted payload (CRC mismatch)
+    pub crc_errors: u64,
+    /// Frames cut short
+    pub partial_frames: u64,
+    /// Stray bytes written between frames
+    pub garbage_bytes: u64,
+}
+
+pub struct TeensyEmulator {
+    calibration: Calibration,
+    protocol: ProtocolVersion,
+    /// v2 channel layout
+    channel_map: ChannelMap,
+    /// v2 sequence number of the next packet
+    seq: u16,
+    /// v2 data packets between descriptors
+    descriptor_interval: u32,
+    since_descriptor: Option<u32>,
+    /// Per-frame probabilities of each link fault
+    garbage_rate: f32,
+    crc_error_rate: f32,
+    partial_rate: f32,
+    rng: Rng,
+    stats: EmulatorStats,
+    /// Settings as the host sees them through commands
+    device: DeviceInfo,
+    /// Settings to restore when calibration streaming ends
+    before_calibration: Option<DeviceInfo>,
+}
+
+impl TeensyEmulator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            calibration: Calibration::default(),
+            protocol: ProtocolVersion::V2,
+            channel_map: ChannelMap::v1(),
+            seq: 0,
+            descriptor_interval: 1000,
+            since_descriptor: None,
+            garbage_rate: 0.0,
+            crc_error_rate: 0.0,
+            partial_rate: 0.0,
+            rng: Rng::new(seed),
+            stats: EmulatorStats::default(),
+            device: DeviceInfo {
+                firmware: EMULATED_FIRMWARE,
+                protocol: 2,
+                rate_hz: 1000,
+                resolution_bits: 12,
+                averaging: 4,
+                calibration_streaming: false,
+            },
+            before_calibration: None,
+        }
+    }
+
+    /// ADC ranges used to turn normalized values back into raw counts.
+    pub fn with_calibration(mut self, cal: Calibration) -> Self {
+        self.calibration = cal;
+        self
+    }
+
+    /// Wire protocol (v2 by default, like the current firmware).
+    pub fn with_protocol(mut self, protocol: ProtocolVersion) -> Self {
+        self.protocol = protocol;
+        self.device.protocol = match protocol {
+            ProtocolVersion::V1 => 1,
+            ProtocolVersion::V2 => 2,
+        };
+        self
+    }
+
+    /// v2 channel layout, e.g. with extra channels the host doesn't know.
+    /// v1 always sends the fixed 13-channel layout.
+    pub fn with_channel_map(mut self, map: ChannelMap) -> Self {
+        self.channel_map = map;
+        self
+    }
+
+    /// Send a v2 descriptor before the first data packet and then every
+    /// `packets` data packets, so a reader attaching mid-stream learns the
+    /// layout.
+    pub fn with_descriptor_interval(mut self, packets: u32) -> Self {
+        self.descriptor_interval = packets.max(1);
+        self
+    }
+
+    /// Probability per frame of a burst of 1–16 garbage bytes before it.
+    pub fn with_garbage(mut self, rate: f32) -> Self {
+        self.garbage_rate = rate;
+        self
+    }
+
+    /// Probability per frame of a flipped payload byte.
+    pub fn with_crc_errors(mut self, rate: f32) -> Self {
+        self.crc_error_rate = rate;
+        self
+    }
+
+    /// Probability per frame of the frame being cut short.
+    pub fn with_partial_frames(mut self, rate: f32) -> Self {
+        self.partial_rate = rate;
+        self
+    }
+
+    pub fn stats(&self) -> EmulatorStats {
+        self.stats
+    }
+
+    pub fn device_info(&self) -> DeviceInfo {
+        self.device
+    }
+
+    /// Apply a host command the way the firmware does and append the reply
+    /// packet to `out`.
+    pub fn handle_command(&mut self, frame: &CommandFrame, out: &mut Vec<u8>) {
+        let status = match DeviceCommand::from_frame(frame) {
+            None => ReplyStatus::UnknownCommand,
+            Some(command) if command.validate().is_err() => ReplyStatus::InvalidArgument,
+            Some(command) => {
+                match command {
+                    DeviceCommand::Ping | DeviceCommand::Identify(_) => {}
+                    DeviceCommand::SetRate(hz) => self.device.rate_hz = hz,
+                    DeviceCommand::SetAveraging(n) => self.device.averaging = n,
+                    DeviceCommand::SetResolution(bits) => self.device.resolution_bits = bits,
+                    DeviceCommand::CalibrationStream(true) => {
+                        if self.before_calibration.is_none() {
+                            self.before_calibration = Some(self.device);
+                        }
+                        self.device.rate_hz = 100;
+                        self.device.averaging = 32;
+                        self.device.calibration_streaming = true;
+                    }
+                    DeviceCommand::CalibrationStream(false) => {
+                        if let Some(before) = self.before_calibration.take() {
+                            self.device = before;
+                        }
+                    }
+                }
+                ReplyStatus::Ok
+            }
+        };
+        let reply = CommandReply {
+            code: frame.code,
+            id: frame.id,
+            status,
+            info: self.device,
+        };
+        out.extend(encode_reply_v2(self.next_seq(), &reply));
+    }
+
+    /// Encode one frame, appending the bytes to `out`. The device timestamp
+    /// is the frame's timestamp, wrapped to u32 like the firmware's `micros()`.
+    /// Link faults apply to data packets only.
+    pub fn encode(&mut self, frame: &SensorFrame, out: &mut Vec<u8>) {
+        if self.protocol == ProtocolVersion::V2 {
+            let due = self
+                .since_descriptor
+                .is_none_or(|n| n >= self.descriptor_interval);
+            if due {
+                out.extend(encode_descriptor_v2(self.next_seq(), &self.channel_map));
+                self.since_descriptor = Some(0);
+            }
+            self.since_descriptor = self.since_descriptor.map(|n| n + 1);
+        }
+
+        if self.chance(self.garbage_rate) {
+            let n = self.rng.range(1, 16) as usize;
+            for _ in 0..n {
+                out.push(self.rng.range(0, 255) as u8);
+            }
+            self.stats.garbage_bytes += n as u64;
+        }
+
+        let timestamp = frame.timestamp_us as u32;
+        let mut bytes = match self.protocol {
+            ProtocolVersion::V1 => {
+                encode_frame(&sensor_to_raw(frame, &self.calibration), timestamp).to_vec()
+            }
+            ProtocolVersion::V2 => {
+                let raw = self.channel_map.raw_from(frame, &self.calibration);
+                encode_data_v2(self.next_seq(), timestamp, &raw)
+            }
+        };
+        let len = bytes.len() as u32;
+        if self.chance(self.partial_rate) {
+            let cut = self.rng.range(2, len - 1) as usize;
+            out.extend_from_slice(&bytes[..cut]);
+            self.stats.partial_frames += 1;
+        } else if self.chance(self.crc_error_rate) {
+            // Flip a bit past the sync word, leaving sync intact
+            let at = self.rng.range(2, len - 3) as usize;
+            bytes[at] ^= 0x10;
+            out.extend_from_slice(&bytes);
+            self.stats.crc_errors += 1;
+        } else {
+            out.extend_from_slice(&bytes);
+            self.stats.frames += 1;
+        }
+    }
+
+    fn next_seq(&mut self) -> u16 {
+        let seq = self.seq;
+        self.seq = self.seq.wrapping_add(1);
+        seq
+    }
+
+    fn chance(&mut self, p: f32) -> bool {
+        p > 0.0 && (self.rng.bipolar() + 1.0) / 2.0 < p
+    }
+}
+
+/// A `Read` source that emits encoded frames for every `InputEvent::Sensor`
+/// on `rx` (audio events are ignored — the Teensy has no audio). Reads block
+/// until the next frame; the stream ends when `rx` closes. Commands written
+/// to the stream, or to a `CommandInput` from another thread (the pty's
+/// master side), are answered in the output.
+pub struct EmulatedStream {
+    rx: Receiver<InputEvent>,
+    emulator: TeensyEmulator,
+    pending: VecDeque<u8>,
+    commands: CommandDecoder,
+    command_tx: Sender<Vec<u8>>,
+    command_rx: Receiver<Vec<u8>>,
+}
+
+/// Feeds host command bytes to an `EmulatedStream` from another thread.
+pub struct CommandInput(Sender<Vec<u8>>);
+
+impl Write for CommandInput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl EmulatedStream {
+    pub fn new(rx: Receiver<InputEvent>, emulator: TeensyEmulator) -> Self {
+        let (command_tx, command_rx) = unbounded();
+        Self {
+            rx,
+            emulator,
+            pending: VecDeque::new(),
+            commands: CommandDecoder::new(),
+            command_tx,
+            command_rx,
+        }
+    }
+
+    pub fn stats(&self) -> EmulatorStats {
+        self.emulator.stats()
+    }
+
+    pub fn command_input(&self) -> CommandInput {
+        CommandInput(self.command_tx.clone())
+    }
+
+    fn accept_commands(&mut self, bytes: &[u8]) {
+        self.commands.push(bytes);
+        let mut out = Vec::new();
+        while let Some(frame) = self.commands.next_command() {
+            self.emulator.handle_command(&frame, &mut out);
+        }
+        self.pending.extend(out);
+    }
+}
+
+impl Read for EmulatedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            select! {
+                recv(self.rx) -> event => match event {
+                    Ok(InputEvent::Sensor(frame)) => {
+                        let mut bytes = Vec::with_capacity(FRAME_SIZE * 2);
+                        self.emulator.encode(&frame, &mut bytes);
+                        self.pending.extend(bytes);
+                    }
+                    Ok(_) => {}
+                    Err(_) => return Ok(0),
+                },
+                recv(self.command_rx) -> bytes => {
+                    if let Ok(bytes) = bytes {
+                        self.accept_commands(&bytes);
+                    }
+                }
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        for (dst, src) in buf.iter_mut().zip(self.pending.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for EmulatedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.accept_commands(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A pseudo-terminal pair in raw mode. Write frames to `master`; readers
+/// open `slave_path` as if it were the Teensy's serial port.
+#[cfg(unix)]
+pub struct Pty {
+    pub master: std::fs::File,
+    pub slave_path: String,
+    /// Held open so writes buffer (and eventually block) while no reader
+    /// is attached, instead of failing.
+    _slave: std::fs::File,
+}
+
+#[cfg(unix)]
+pub fn open_pty() -> io::Result<Pty> {
+    use std::os::unix::io::FromRawFd;
+
+    // SAFETY: plain libc calls on a descriptor we own; ptsname's static
+    // buffer is copied out before any other pty call.
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = std::fs::File::from_raw_fd(fd);
+        if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let name = libc::ptsname(fd);
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let slave_path = std::ffi::CStr::from_ptr(name)
+            .to_string_lossy()
+            .into_owned();
+        let slave = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&slave_path)?;
+
+        // Raw mode: no CR/LF translation, echo or line buffering
+        let slave_fd = std::os::unix::io::AsRawFd::as_raw_fd(&slave);
+        let mut tio: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(slave_fd, &mut tio) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut tio);
+        if libc::tcsetattr(slave_fd, libc::TCSANOW, &tio) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Pty {
+            master,
+            slave_path,
+            _slave: slave,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_protocol::FrameDecoder;
+
+    fn frame(t: u64) -> SensorFrame {
+        SensorFrame {
+            timestamp_us: t,
+            pedals: [0.0, 1.0, 0.5],
+            knee_levers: [0.0; 5],
+            volume: 0.8,
+            bar_sensors: [0.2, 0.9, 0.1, 0.0],
+            string_active: [false; 10],
+        }
+    }
+
+    #[test]
+    fn test_clean_stream_decodes_every_frame() {
+        let mut emu = TeensyEmulator::new(1).with_protocol(ProtocolVersion::V1);
+        let mut bytes = Vec::new();
+        for t in 0..50 {
+            emu.encode(&frame(t * 1000), &mut bytes);
+        }
+        assert_eq!(bytes.len(), 50 * FRAME_SIZE);
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&bytes);
+        let clock = SessionClock::new();
+        let cal = Calibration::default();
+        let mut n = 0;
+        while let Some(f) = decoder.next_frame(&cal, &clock) {
+            assert!((f.pedals[1] - 1.0).abs() < 1e-3);
+            assert!((f.bar_sensors[1] - 0.9).abs() < 1e-3);
+            n += 1;
+        }
+        assert_eq!(n, 50);
+        assert_eq!(decoder.errors, 0);
+        assert_eq!(decoder.version, Some(ProtocolVersion::V1));
+    }
+
+    #[test]
+    fn test_v2_extra_channels_and_descriptors() {
+        use crate::serial_protocol::ChannelRole;
+        // A 14-channel device: pick sensor first, then the usual layout
+        let mut map = ChannelMap::v1();
+        map.roles.insert(0, ChannelRole::Other(0));
+        let mut emu = TeensyEmulator::new(2)
+            .with_channel_map(map.clone())
+            .with_descriptor_interval(10);
+        let mut bytes = Vec::new();
+        for t in 0..25 {
+            emu.encode(&frame(t * 1000), &mut bytes);
+        }
+        // 3 descriptors (9 + 28 bytes) + 25 data packets (13 + 28 bytes)
+        assert_eq!(bytes.len(), 3 * 37 + 25 * 41);
+
+        // Attach mid-stream: packets before the next descriptor are skipped
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&bytes[37 + 41 * 3..]);
+        let clock = SessionClock::new();
+        let cal = Calibration::default();
+        let mut n = 0;
+        while let Some(f) = decoder.next_frame(&cal, &clock) {
+            assert!((f.pedals[1] - 1.0).abs() < 1e-3);
+            assert!((f.volume - 0.8).abs() < 1e-3);
+            n += 1;
+        }
+        assert_eq!(n, 15);
+        assert_eq!(decoder.version, Some(ProtocolVersion::V2));
+        assert_eq!(decoder.channel_map(), Some(&map));
+        assert_eq!(decoder.errors, 0);
+        assert_eq!(decoder.lost_packets, 0);
+    }
+
+    #[test]
+    fn test_faulty_stream_loses_only_faulted_frames() {
+        let mut emu = TeensyEmulator::new(7)
+            .with_garbage(0.1)
+            .with_crc_errors(0.05)
+            .with_partial_frames(0.05);
+        let mut bytes = Vec::new();
+        for t in 0..2000 {
+            emu.encode(&frame(t * 1000), &mut bytes);
+        }
+        let stats = emu.stats();
+        assert!(stats.garbage_bytes > 0 && stats.crc_errors > 0 && stats.partial_frames > 0);
+
+        let mut decoder = FrameDecoder::new();
+        let clock = SessionClock::new();
+        let cal = Calibration::default();
+        let mut n = 0;
+        for chunk in bytes.chunks(64) {
+            decoder.push(chunk);
+            while decoder.next_frame(&cal, &clock).is_some() {
+                n += 1;
+            }
+        }
+        assert_eq!(n, stats.frames);
+    }
+
+    #[test]
+    fn test_answers_commands_like_firmware() {
+        use crate::serial_protocol::encode_command;
+
+        let (tx, rx) = unbounded();
+        let mut stream = EmulatedStream::new(rx, TeensyEmulator::new(3));
+        let commands = [
+            DeviceCommand::SetRate(500),
+            DeviceCommand::SetAveraging(3),
+            DeviceCommand::CalibrationStream(true),
+            DeviceCommand::CalibrationStream(false),
+        ];
+        for (id, command) in commands.iter().enumerate() {
+            stream
+                .write_all(&encode_command(id as u8, command))
+                .unwrap();
+        }
+        // Unknown command code
+        stream
+            .write_all(&{
+                let mut bad = encode_command(9, &DeviceCommand::Ping);
+                bad[2] = 0x7E;
+                let crc = crate::serial_protocol::crc16(&bad[..5]);
+                bad[5..].copy_from_slice(&crc.to_le_bytes());
+                bad
+            })
+            .unwrap();
+        tx.send(InputEvent::Sensor(frame(0))).unwrap();
+        drop(tx);
+
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&bytes);
+        let clock = SessionClock::new();
+        assert!(decoder
+            .next_frame(&Calibration::default(), &clock)
+            .is_some());
+
+        let replies: Vec<CommandReply> = std::iter::from_fn(|| decoder.next_reply()).collect();
+        let summary: Vec<(u8, ReplyStatus, u16, u8, bool)> = replies
+            .iter()
+            .map(|r| {
+                (
+                    r.id,
+                    r.status,
+                    r.info.rate_hz,
+                    r.info.averaging,
+                    r.info.calibration_streaming,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, ReplyStatus::Ok, 500, 4, false),
+                (1, ReplyStatus::InvalidArgument, 500, 4, false),
+                (2, ReplyStatus::Ok, 100, 32, true),
+                (3, ReplyStatus::Ok, 500, 4, false),
+                (9, ReplyStatus::UnknownCommand, 500, 4, false),
+            ]
+        );
+        assert_eq!(replies[0].info.firmware, EMULATED_FIRMWARE);
+        assert_eq!(decoder.lost_packets, 0);
+    }
+}
@@ -0,0 +1,293 @@
+//! Plain-text pedal steel tab, the way players write it on paper: ten string
+//! lines, a fret number where a string is picked, and the pedals and levers
+//! held for it as a suffix (`10A`, `5B`, `3ALKL`).
+//!
+//! ```text
+//! 0.00s
+//!  1|-----------|
+//!  2|-----------|
+//!  3|-10A---/12-|
+//!  4|-10A-------|
+//!  5|-10A---\8--|
+//!  6|-----------|
+//!  7|-----------|
+//!  8|-----------|
+//!  9|-----------|
+//! 10|-----------|
+//! ```
+//!
+//! Time runs left to right in columns of a fixed length (`with_column_ms`);
+//! a note goes in the column nearest its attack, and a later note on the
+//! same string in the same column replaces it. `/12` and `\8` are the bar
+//! sliding up or down to a fret while the string rings. Lines wrap every
+//! `with_width` columns under a time stamp; stretches of columns with no
+//! notes at all are left out.
+//!
+//! `TabRenderer` works offline (`export tab`) or live: `ready` hands back
+//! lines once no note can still land in them, which is what `TabOutput`
+//! writes as frames arrive.
+
+use crate::note_events::{NoteEvent, NoteTracker, SETTLE_US};
+use crate::types::CaptureFrame;
+use crossbeam_channel::Receiver;
+use log::{error, info};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+/// Lays note events out as text tab.
+pub struct TabRenderer {
+    column_us: u64,
+    width: u64,
+    /// Time of column 0: the first note's attack
+    origin: Option<u64>,
+    /// Placed cells by column
+    columns: BTreeMap<u64, [String; 10]>,
+    last_fret: [Option<u8>; 10],
+    /// First column not yet written
+    next_column: u64,
+}
+
+impl TabRenderer {
+    /// 125 ms columns (sixteenths at 120 BPM), 32 to a line.
+    pub fn new() -> Self {
+        Self {
+            column_us: 125_000,
+            width: 32,
+            origin: None,
+            columns: BTreeMap::new(),
+            last_fret: [None; 10],
+            next_column: 0,
+        }
+    }
+
+    /// Column length in milliseconds.
+    pub fn with_column_ms(mut self, ms: u32) -> Self {
+        self.column_us = ms.max(1) as u64 * 1000;
+        self
+    }
+
+    /// Columns per line.
+    pub fn with_width(mut self, columns: usize) -> Self {
+        self.width = columns.max(1) as u64;
+        self
+    }
+
+    /// Place a note. Notes should arrive in attack order per string, so
+    /// slides know the fret they came from.
+    pub fn add(&mut self, note: &NoteEvent) {
+        let origin = *self.origin.get_or_insert(note.start_us);
+        let offset = note.start_us.saturating_sub(origin) + self.column_us / 2;
+        // Too late for a line already written: the next one
+        let column = (offset / self.column_us).max(self.next_column);
+        let slide = match self.last_fret[note.string] {
+            Some(from) if note.slid && note.fret > from => "/",
+            Some(from) if note.slid && note.fret < from => "\\",
+            _ => "",
+        };
+        self.last_fret[note.string] = Some(note.fret);
+        let cell = format!("{}{}{}", slide, note.fret, note.changers().concat());
+        self.columns.entry(column).or_default()[note.string] = cell;
+    }
+
+    /// Lines no note can still be added to at stream time `now_us`.
+    pub fn ready(&mut self, now_us: u64) -> String {
+        let mut out = String::new();
+        let Some(origin) = self.origin else {
+            return out;
+        };
+        // A slid note is placed up to SETTLE_US after its start
+        let settled = now_us.saturating_sub(origin + SETTLE_US + self.column_us);
+        while (self.next_column + self.width) * self.column_us <= settled {
+            out.push_str(&self.line(self.width));
+        }
+        out
+    }
+
+    /// Everything not yet written.
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        while let Some(&last) = self.columns.keys().next_back() {
+            let n = (last + 1 - self.next_column).min(self.width);
+            out.push_str(&self.line(n));
+        }
+        out
+    }
+
+    /// The next `n` columns as a block of string lines, or nothing if
+    /// they're empty.
+    fn line(&mut self, n: u64) -> String {
+        let from = self.next_column;
+        self.next_column += n;
+        let mut cells: Vec<[String; 10]> = (from..from + n)
+            .map(|c| self.columns.remove(&c).unwrap_or_default())
+            .collect();
+        if cells.iter().flatten().all(String::is_empty) {
+            return String::new();
+        }
+        let seconds = (from * self.column_us) as f64 / 1e6;
+        let mut out = format!("{:.2}s\n", seconds);
+        let widths: Vec<usize> = cells
+            .iter()
+            .map(|c| c.iter().map(String::len).max().unwrap_or(0).max(1))
+            .collect();
+        for si in 0..10 {
+            out.push_str(&format!("{:>2}|", si + 1));
+            for (column, width) in cells.iter_mut().zip(&widths) {
+                out.push('-');
+                out.push_str(&format!("{:-<width$}", std::mem::take(&mut column[si])));
+            }
+            out.push_str("-|\n");
+        }
+        out.push('\n');
+        out
+    }
+}
+
+impl Default for TabRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Renders a whole list of notes, ordered by attack.
+pub fn render(notes: &[NoteEvent], mut renderer: TabRenderer) -> String {
+    for note in notes {
+        renderer.add(note);
+    }
+    renderer.finish()
+}
+
+/// Live tab: writes lines to a file, or stdout for `-`, as frames arrive.
+pub struct TabOutput {
+    rx: Receiver<CaptureFrame>,
+    path: PathBuf,
+    renderer: TabRenderer,
+}
+
+impl TabOutput {
+    pub fn new(rx: Receiver<CaptureFrame>, path: PathBuf) -> Self {
+        Self {
+            rx,
+            path,
+            renderer: TabRenderer::new(),
+        }
+    }
+
+    pub fn with_renderer(mut self, renderer: TabRenderer) -> Self {
+        self.renderer = renderer;
+        self
+    }
+
+    /// Blocks until the frame channel closes.
+    pub fn run(mut self) {
+        let out: Box<dyn Write> = if self.path.as_os_str() == "-" {
+            Box::new(io::stdout())
+        } else {
+            match File::create(&self.path) {
+                Ok(f) => Box::new(BufWriter::new(f)),
+                Err(e) => {
+                    error!("Failed to create tab output {:?}: {}", self.path, e);
+                    return;
+                }
+            }
+        };
+        info!("Tab output → {:?}", self.path);
+        if let Err(e) = self.write_all(out) {
+            error!("Tab output {:?}: {}", self.path, e);
+        }
+        info!("Tab output shutting down");
+    }
+
+    fn write_all(&mut self, mut out: Box<dyn Write>) -> io::Result<()> {
+        let mut tracker = NoteTracker::new();
+        // Attack time of the last note placed on each string
+        let mut placed = [None; 10];
+        let mut last_t = 0;
+        for frame in self.rx.iter() {
+            last_t = frame.timestamp_us;
+            tracker.push(&frame);
+            for note in tracker.sounding() {
+                if placed[note.string] != Some(note.start_us) {
+                    placed[note.string] = Some(note.start_us);
+                    self.renderer.add(note);
+                }
+            }
+            let lines = self.renderer.ready(last_t);
+            if !lines.is_empty() {
+                out.write_all(lines.as_bytes())?;
+                out.flush()?;
+            }
+        }
+        tracker.finish(last_t);
+        out.write_all(self.renderer.finish().as_bytes())?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(string: usize, start_ms: u64, fret: u8) -> NoteEvent {
+        NoteEvent {
+            string,
+            start_us: 2_000_000 + start_ms * 1000,
+            end_us: 2_000_000 + start_ms * 1000 + 100_000,
+            midi: 60,
+            fret,
+            pedals: [false; 3],
+            levers: [false; 5],
+            slid: false,
+            amplitude: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_render_grip_changers_and_slides() {
+        let mut grip: Vec<NoteEvent> = (2..5).map(|s| note(s, 0, 10)).collect();
+        for n in &mut grip {
+            n.pedals[0] = true;
+        }
+        grip[0].levers[0] = true;
+        let mut up = note(2, 240, 12);
+        up.slid = true;
+        let mut down = note(4, 260, 8);
+        down.slid = true;
+        // Off the grid by 40 ms: nearest column
+        let picked = note(5, 540, 0);
+        let notes = [grip, vec![up, down, picked]].concat();
+
+        let tab = render(&notes, TabRenderer::new());
+        let lines: Vec<&str> = tab.lines().collect();
+        assert_eq!(lines[0], "0.00s");
+        assert_eq!(lines[1], " 1|------------------|");
+        assert_eq!(lines[3], " 3|-10ALKL---/12-----|");
+        assert_eq!(lines[4], " 4|-10A--------------|");
+        assert_eq!(lines[5], " 5|-10A------\\8------|");
+        assert_eq!(lines[6], " 6|----------------0-|");
+        assert_eq!(lines[10], "10|------------------|");
+        assert_eq!(lines.len(), 12);
+    }
+
+    #[test]
+    fn test_wrap_and_live_readiness() {
+        let mut renderer = TabRenderer::new().with_column_ms(100).with_width(4);
+        renderer.add(&note(0, 0, 3));
+        renderer.add(&note(0, 300, 5));
+        // Column 8 is on the third line; the second line stays empty
+        renderer.add(&note(9, 800, 7));
+
+        // The first line (0–400 ms) waits out slide settling and a column
+        assert_eq!(renderer.ready(2_000_000 + 600_000), "");
+        let first = renderer.ready(2_000_000 + 650_000);
+        assert!(first.starts_with("0.00s\n 1|-3-----5-|\n"), "{}", first);
+        assert_eq!(first.lines().count(), 12);
+
+        let rest = renderer.finish();
+        assert!(rest.starts_with("0.80s\n"), "{}", rest);
+        assert!(rest.contains("\n10|-7-|\n"), "{}", rest);
+        assert_eq!(renderer.finish(), "");
+    }
+}
@@ -62,10 +62,14 @@ impl fmt::Display for SensorFrame {
 pub struct AudioChunk {
     /// Microseconds since session start (timestamp of first sample)
     pub timestamp_us: u64,
-    /// Mono f32 samples, normalized -1.0 to 1.0
+    /// Mono f32 samples, normalized -1.0 to 1.0 (the downmix of
+    /// `channels` for multichannel sources)
     pub samples: Vec<f32>,
     /// Sample rate in Hz
     pub sample_rate: u32,
+    /// Per-channel samples from a multichannel source such as a
+    /// hexaphonic pickup, each as long as `samples`. Empty for mono.
+    pub channels: Vec<Vec<f32>>,
 }
 
 // ─── Bar state (inferred) ───────────────────────────────────────────────────
@@ -141,6 +145,26 @@ pub struct CaptureFrame {
     pub string_amplitude: [f32; 10],
 }
 
+impl CaptureFrame {
+    /// A silent frame with nothing engaged and the bar position unknown.
+    pub fn at_rest(timestamp_us: u64) -> Self {
+        Self {
+            timestamp_us,
+            pedals: [0.0; 3],
+            knee_levers: [0.0; 5],
+            volume: 0.7,
+            bar_sensors: [0.0; 4],
+            bar_position: None,
+            bar_confidence: 0.0,
+            bar_source: BarSource::None,
+            string_pitches_hz: [0.0; 10],
+            string_active: [false; 10],
+            attacks: [false; 10],
+            string_amplitude: [0.0; 10],
+        }
+    }
+}
+
 // ─── Compact serialization ──────────────────────────────────────────────────
 
 /// Short-key representation for efficient WS streaming and JSONL logging.
@@ -257,6 +281,75 @@ pub struct ChangeDef {
 pub enum InputEvent {
     Sensor(SensorFrame),
     Audio(AudioChunk),
+    /// Input device link changes (hardware sources only)
+    Status(DeviceStatus),
+}
+
+/// Link state of an input device such as the Teensy's serial port.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceStatus {
+    Connected {
+        device: String,
+    },
+    Disconnected {
+        device: String,
+        reason: String,
+    },
+    /// About to retry opening after `delay_ms`
+    Reconnecting {
+        device: String,
+        attempt: u32,
+        delay_ms: u64,
+    },
+}
+
+impl fmt::Display for DeviceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connected { device } => write!(f, "{} connected", device),
+            Self::Disconnected { device, reason } => {
+                write!(f, "{} disconnected ({})", device, reason)
+            }
+            Self::Reconnecting {
+                device,
+                attempt,
+                delay_ms,
+            } => write!(
+                f,
+                "{} reconnect attempt {} in {} ms",
+                device, attempt, delay_ms
+            ),
+        }
+    }
+}
+
+// ─── Remote control ─────────────────────────────────────────────────────────
+
+/// A command received while running (see `osc_control`). Every command is
+/// delivered to the logger, the simulator and the coordinator; each acts on
+/// the ones it handles.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    /// Start (true) or stop (false) writing the session log
+    Record(bool),
+    /// A labelled point in time, written to the session's markers file
+    Marker(String),
+    /// Switch the simulator to another demo sequence
+    Demo(String),
+    /// Clear string detector and bar inference state
+    Reset,
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Record(true) => write!(f, "record start"),
+            Self::Record(false) => write!(f, "record stop"),
+            Self::Marker(label) => write!(f, "marker {:?}", label),
+            Self::Demo(name) => write!(f, "demo {}", name),
+            Self::Reset => write!(f, "reset"),
+        }
+    }
 }
 
 // ─── Session clock ──────────────────────────────────────────────────────────
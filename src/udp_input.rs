@@ -0,0 +1,269 @@
+//! Sensor input over UDP, for ESP32 prototypes and other software.
+//!
+//! Each datagram is either Teensy wire format (see `serial_protocol`; v1
+//! frames or v2 packets, one or more per datagram) or OSC using the same
+//! `/steel/...` addresses `OscSender` emits:
+//!
+//! ```text
+//! /steel/pedal/{a,b,c}      f  0.0–1.0
+//! /steel/knee/{0..4}        f  0.0–1.0 (LKL LKR LKV RKL RKR)
+//! /steel/knee/{lkl,..,rkr}  f  0.0–1.0, by name
+//! /steel/volume             f  0.0–1.0
+//! /steel/bar/sensor/{0..3}  f  0.0–1.0
+//! ```
+//!
+//! OSC senders may update any subset of values; the rest keep their last
+//! value. Other `/steel/...` addresses (inferred bar position, pitches,
+//! string states, attacks) are ignored, so a capture's own OSC output can
+//! be fed back in.
+
+use crate::serial_protocol::{Calibration, FrameDecoder};
+use crate::types::*;
+use crossbeam_channel::Sender;
+use log::{debug, error, info};
+use rosc::{OscMessage, OscPacket, OscType};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Largest datagram accepted (a v2 packet with 32 channels is 79 bytes; an
+/// OSC bundle of every address is well under this).
+const MAX_DATAGRAM: usize = 4096;
+
+/// Receives sensor values on a UDP socket and forwards them as
+/// `InputEvent::Sensor`.
+///
+/// Datagrams that arrive together are merged: OSC updates queued since the
+/// last frame produce one frame, not one per message.
+pub struct UdpInput {
+    socket: UdpSocket,
+    tx: Sender<InputEvent>,
+    clock: SessionClock,
+    calibration: Calibration,
+}
+
+impl UdpInput {
+    /// Bind to `addr` (e.g. `0.0.0.0:9100`; port 0 picks a free one).
+    pub fn bind(addr: &str, tx: Sender<InputEvent>, clock: SessionClock) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr)?,
+            tx,
+            clock,
+            calibration: Calibration::default(),
+        })
+    }
+
+    /// ADC calibration for binary frames (OSC values are already 0.0–1.0).
+    pub fn with_calibration(mut self, cal: Calibration) -> Self {
+        self.calibration = cal;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Run the receive loop. Blocks the calling thread until the receiving
+    /// side of the channel is dropped.
+    pub fn run(self) {
+        match self.socket.local_addr() {
+            Ok(addr) => info!("UDP sensor input listening on {}", addr),
+            Err(e) => error!("UDP sensor input: {}", e),
+        }
+        let mut buf = [0u8; MAX_DATAGRAM];
+        let mut osc = OscState::new();
+        let mut decoders: HashMap<SocketAddr, FrameDecoder> = HashMap::new();
+
+        loop {
+            // Block for one datagram, then take whatever else has arrived
+            let mut pending = match self.socket.recv_from(&mut buf) {
+                Ok((n, from)) => vec![(buf[..n].to_vec(), from)],
+                Err(e) => {
+                    error!("UDP receive failed: {}", e);
+                    return;
+                }
+            };
+            if self.socket.set_nonblocking(true).is_ok() {
+                while let Ok((n, from)) = self.socket.recv_from(&mut buf) {
+                    pending.push((buf[..n].to_vec(), from));
+                }
+                let _ = self.socket.set_nonblocking(false);
+            }
+
+            for (datagram, from) in pending {
+                if !decoders.contains_key(&from) && !osc.senders.contains(&from) {
+                    info!("UDP sensor data from {}", from);
+                    let status = DeviceStatus::Connected {
+                        device: format!("udp {}", from),
+                    };
+                    if self.tx.send(InputEvent::Status(status)).is_err() {
+                        return;
+                    }
+                }
+                if is_osc(&datagram) {
+                    osc.senders.insert(from);
+                    match rosc::decoder::decode_udp(&datagram) {
+                        Ok((_, packet)) => osc.apply_packet(&packet),
+                        Err(e) => debug!("Bad OSC datagram from {}: {:?}", from, e),
+                    }
+                    continue;
+                }
+                let decoder = decoders.entry(from).or_default();
+                decoder.push(&datagram);
+                while let Some(frame) = decoder.next_frame(&self.calibration, &self.clock) {
+                    if self.tx.send(InputEvent::Sensor(frame)).is_err() {
+                        return;
+                    }
+                }
+            }
+
+            if let Some(frame) = osc.take_frame(self.clock.now_us()) {
+                if self.tx.send(InputEvent::Sensor(frame)).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// OSC messages start with an address, bundles with `#bundle`; Teensy
+/// frames start with a sync word.
+fn is_osc(datagram: &[u8]) -> bool {
+    datagram.first() == Some(&b'/') || datagram.starts_with(b"#bundle")
+}
+
+/// Sensor state built up from OSC messages.
+struct OscState {
+    frame: SensorFrame,
+    dirty: bool,
+    senders: HashSet<SocketAddr>,
+}
+
+impl OscState {
+    fn new() -> Self {
+        Self {
+            frame: SensorFrame::at_rest(0),
+            dirty: false,
+            senders: HashSet::new(),
+        }
+    }
+
+    fn apply_packet(&mut self, packet: &OscPacket) {
+        match packet {
+            OscPacket::Message(msg) => {
+                if !self.apply(msg) {
+                    debug!("Ignoring OSC {}", msg.addr);
+                }
+            }
+            OscPacket::Bundle(bundle) => {
+                for p in &bundle.content {
+                    self.apply_packet(p);
+                }
+            }
+        }
+    }
+
+    /// Update one value; false if the address or argument isn't a sensor
+    /// value.
+    fn apply(&mut self, msg: &OscMessage) -> bool {
+        let value = match msg.args.first() {
+            Some(OscType::Float(v)) => *v,
+            Some(OscType::Double(v)) => *v as f32,
+            Some(OscType::Int(v)) => *v as f32,
+            _ => return false,
+        };
+        let Some(path) = msg.addr.strip_prefix("/steel/") else {
+            return false;
+        };
+        let index = |name: &str, len: usize| name.parse::<usize>().ok().filter(|&i| i < len);
+        let slot = match path.split('/').collect::<Vec<_>>().as_slice() {
+            ["pedal", name] => ["a", "b", "c"]
+                .iter()
+                .position(|p| p == name)
+                .map(|i| &mut self.frame.pedals[i]),
+            ["knee", name] => index(name, 5)
+                .or_else(|| {
+                    LEVER_NAMES
+                        .iter()
+                        .position(|l| l.eq_ignore_ascii_case(name))
+                })
+                .map(|i| &mut self.frame.knee_levers[i]),
+            ["volume"] => Some(&mut self.frame.volume),
+            ["bar", "sensor", i] => index(i, 4).map(|i| &mut self.frame.bar_sensors[i]),
+            _ => None,
+        };
+        match slot {
+            Some(slot) => {
+                *slot = value.clamp(0.0, 1.0);
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The current state, if anything changed since the last call.
+    fn take_frame(&mut self, timestamp_us: u64) -> Option<SensorFrame> {
+        if !std::mem::take(&mut self.dirty) {
+            return None;
+        }
+        self.frame.timestamp_us = timestamp_us;
+        Some(self.frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(addr: &str, arg: OscType) -> OscPacket {
+        OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args: vec![arg],
+        })
+    }
+
+    #[test]
+    fn test_osc_addresses_update_state() {
+        let mut osc = OscState::new();
+        assert!(osc.take_frame(0).is_none());
+
+        osc.apply_packet(&msg("/steel/pedal/b", OscType::Float(0.8)));
+        osc.apply_packet(&msg("/steel/knee/4", OscType::Double(0.5)));
+        osc.apply_packet(&msg("/steel/knee/lkr", OscType::Float(0.25)));
+        osc.apply_packet(&msg("/steel/volume", OscType::Float(1.5)));
+        osc.apply_packet(&msg("/steel/bar/sensor/2", OscType::Int(1)));
+        // Outputs of a capture, not sensor values
+        osc.apply_packet(&msg("/steel/bar/pos", OscType::Float(7.0)));
+        osc.apply_packet(&msg("/steel/knee/5", OscType::Float(1.0)));
+        osc.apply_packet(&msg("/other/volume", OscType::Float(0.0)));
+
+        let frame = osc.take_frame(42).unwrap();
+        assert_eq!(frame.timestamp_us, 42);
+        assert_eq!(frame.pedals, [0.0, 0.8, 0.0]);
+        assert_eq!(frame.knee_levers, [0.0, 0.25, 0.0, 0.0, 0.5]);
+        assert_eq!(frame.volume, 1.0);
+        assert_eq!(frame.bar_sensors, [0.0, 0.0, 1.0, 0.0]);
+        assert!(osc.take_frame(43).is_none());
+
+        // Unchanged values persist
+        osc.apply_packet(&msg("/steel/pedal/a", OscType::Float(0.3)));
+        assert_eq!(osc.take_frame(44).unwrap().pedals, [0.3, 0.8, 0.0]);
+    }
+
+    #[test]
+    fn test_datagram_kind() {
+        let bundle = rosc::encoder::encode(&OscPacket::Bundle(rosc::OscBundle {
+            timetag: rosc::OscTime {
+                seconds: 0,
+                fractional: 1,
+            },
+            content: vec![msg("/steel/volume", OscType::Float(0.5))],
+        }))
+        .unwrap();
+        assert!(is_osc(&bundle));
+        assert!(is_osc(b"/steel/volume\0\0\0"));
+        assert!(!is_osc(&[0xEF, 0xBE, 0, 0]));
+        assert!(!is_osc(&[]));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::impairments::Impairments;
 use crate::types::*;
 use crossbeam_channel::Sender;
 use hound::{SampleFormat, WavReader};
@@ -19,25 +20,36 @@ pub struct WavPlayer {
     path: PathBuf,
     tx: Sender<InputEvent>,
     clock: SessionClock,
+    impairments: Impairments,
 }
 
 /// Samples sent per AudioChunk. ~21ms at 48kHz — gives the string detector
 /// enough granularity without saturating the channel.
-const CHUNK_SIZE: usize = 1024;
+pub(crate) const CHUNK_SIZE: usize = 1024;
 
 impl WavPlayer {
     pub fn new(path: PathBuf, tx: Sender<InputEvent>, clock: SessionClock) -> Self {
-        Self { path, tx, clock }
+        Self {
+            path,
+            tx,
+            clock,
+            impairments: Impairments::default(),
+        }
     }
 
-    pub fn run(&self) {
-        let reader = match WavReader::open(&self.path) {
-            Ok(r) => r,
-            Err(e) => {
-                error!("Failed to open WAV file {:?}: {}", self.path, e);
-                return;
-            }
-        };
+    /// Layer impairments onto the file's audio before streaming. String
+    /// pitches are unknown here, so sympathetic resonance is not applied.
+    pub fn with_impairments(mut self, impairments: &Impairments) -> Self {
+        self.impairments = impairments.clone();
+        self
+    }
+
+    /// Load the whole file as chunks, timestamped by position in the file.
+    /// Multichannel files (e.g. a hexaphonic pickup recording) keep their
+    /// channels alongside the mono mix.
+    pub fn chunks(&self) -> Result<Vec<AudioChunk>, String> {
+        let reader = WavReader::open(&self.path)
+            .map_err(|e| format!("Failed to open WAV file {:?}: {}", self.path, e))?;
 
         let spec = reader.spec();
         let sample_rate = spec.sample_rate;
@@ -61,7 +73,7 @@ impl WavPlayer {
         }
 
         // Read all samples as f32
-        let samples_f32: Vec<f32> = match spec.sample_format {
+        let mut samples_f32: Vec<f32> = match spec.sample_format {
             SampleFormat::Float => reader
                 .into_samples::<f32>()
                 .filter_map(|s| s.ok())
@@ -76,27 +88,62 @@ impl WavPlayer {
             }
         };
 
-        // Mix down to mono
-        let mono: Vec<f32> = if channels == 1 {
-            samples_f32
-        } else {
-            samples_f32
-                .chunks(channels)
-                .map(|frame| frame.iter().sum::<f32>() / channels as f32)
-                .collect()
+        // Drop a trailing partial frame from a truncated file
+        samples_f32.truncate(samples_f32.len() - samples_f32.len() % channels.max(1));
+        let split = split_channels(&samples_f32, channels);
+        let mono = downmix(samples_f32, channels);
+        Ok(mono
+            .chunks(CHUNK_SIZE)
+            .enumerate()
+            .map(|(i, samples)| {
+                let offset = i * CHUNK_SIZE;
+                AudioChunk {
+                    timestamp_us: offset as u64 * 1_000_000 / sample_rate as u64,
+                    samples: samples.to_vec(),
+                    sample_rate,
+                    channels: split
+                        .iter()
+                        .map(|ch| ch[offset..offset + samples.len()].to_vec())
+                        .collect(),
+                }
+            })
+            .collect())
+    }
+
+    pub fn run(&self) {
+        let chunks = match self.chunks() {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        };
+        let Some(sample_rate) = chunks.first().map(|c| c.sample_rate) else {
+            warn!("WAV: no audio");
+            return;
         };
 
-        let total_samples = mono.len();
+        let total_samples: usize = chunks.iter().map(|c| c.samples.len()).sum();
         let duration_secs = total_samples as f64 / sample_rate as f64;
         info!(
             "WAV: {:.2}s, {} samples → streaming at real-time pace",
             duration_secs, total_samples
         );
 
+        if self.impairments.sympathetic.is_some() {
+            warn!("WAV: sympathetic resonance needs string pitches; ignored for file playback");
+        }
+        let mut impairments = self.impairments.chain(sample_rate);
+        let mut channel_impairments: Vec<_> = chunks[0]
+            .channels
+            .iter()
+            .map(|_| self.impairments.chain(sample_rate))
+            .collect();
+
         let chunk_dur = Duration::from_secs_f64(CHUNK_SIZE as f64 / sample_rate as f64);
         let start = Instant::now();
 
-        for (i, chunk) in mono.chunks(CHUNK_SIZE).enumerate() {
+        for (i, mut chunk) in chunks.into_iter().enumerate() {
             // Pace to real time: wait until this chunk's expected send time
             let target = chunk_dur * i as u32;
             let elapsed = start.elapsed();
@@ -104,13 +151,13 @@ impl WavPlayer {
                 thread::sleep(target - elapsed);
             }
 
-            let event = InputEvent::Audio(AudioChunk {
-                timestamp_us: self.clock.now_us(),
-                samples: chunk.to_vec(),
-                sample_rate,
-            });
+            impairments.process(&mut chunk.samples);
+            for (samples, chain) in chunk.channels.iter_mut().zip(&mut channel_impairments) {
+                chain.process(samples);
+            }
+            chunk.timestamp_us = self.clock.now_us();
 
-            if self.tx.send(event).is_err() {
+            if self.tx.send(InputEvent::Audio(chunk)).is_err() {
                 // Coordinator shut down — stop streaming
                 break;
             }
@@ -119,3 +166,25 @@ impl WavPlayer {
         info!("WAV playback complete.");
     }
 }
+
+/// Mix interleaved samples down to mono by averaging the channels.
+pub(crate) fn downmix(samples: Vec<f32>, channels: usize) -> Vec<f32> {
+    if channels == 1 {
+        samples
+    } else {
+        samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+}
+
+/// Split interleaved samples into one buffer per channel; empty for mono.
+pub(crate) fn split_channels(samples: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    if channels < 2 {
+        return Vec::new();
+    }
+    (0..channels)
+        .map(|c| samples.iter().skip(c).step_by(channels).copied().collect())
+        .collect()
+}
@@ -18,12 +18,42 @@
  *   A11: Bar fret 10
  *   A12: Bar fret 15
  *
- * Binary protocol (34 bytes per frame):
+ * Binary protocol v1 (34 bytes per frame):
  *   [0:2]   Sync word (0xBEEF, little-endian)
  *   [2:6]   Timestamp (microseconds, uint32, little-endian)
  *   [6:32]  13× ADC values (uint16, little-endian each)
  *   [32:34] CRC-16/CCITT-FALSE (little-endian)
  *
+ * Binary protocol v2 (default; the host auto-detects either):
+ *   [0:2]   Sync word (0xBEE2, little-endian)
+ *   [2]     Version (2)
+ *   [3]     Packet type: 0 = data, 1 = descriptor
+ *   [4:6]   Sequence number (uint16, counts every packet)
+ *   [6]     Channel count N
+ *   data:       [7:11] timestamp (uint32), then N× ADC values (uint16)
+ *   descriptor: N× (role, index) byte pairs
+ *   last 2 bytes: CRC-16/CCITT-FALSE over everything before them
+ *
+ *   Roles: 1 = pedal, 2 = knee lever, 3 = volume, 4 = bar sensor,
+ *   0xFF = other (ignored by hosts that don't know it). A descriptor is
+ *   sent at startup and once a second, so adding a channel only means
+ *   extending ANALOG_PINS and CHANNEL_ROLES below.
+ *
+ * Host commands (host → device):
+ *   [0:2]   Sync word (0xBEC0, little-endian)
+ *   [2]     Command: 1 = ping, 2 = set rate (u16 Hz), 3 = set averaging
+ *           (u8), 4 = set resolution (u8 bits), 5 = calibration streaming
+ *           (u8 on/off), 6 = identify (u8 seconds)
+ *   [3]     Id, echoed in the reply
+ *   [4]     Payload length N, then N payload bytes, then CRC-16
+ *
+ *   Every command is answered with a v2 packet of type 2 (reply), in
+ *   either protocol version: command, id, status (0 = ok, 1 = invalid
+ *   argument, 2 = unknown command), then the settings after the command:
+ *   firmware major/minor/patch, protocol, rate (u16), resolution,
+ *   averaging, calibration streaming. Settings go back to the defaults
+ *   below when the host closes the port.
+ *
  * Upload via Arduino IDE or PlatformIO with Teensy 4.1 board selected.
  */
 
@@ -31,11 +61,21 @@
 
 // ─── Configuration ──────────────────────────────────────────────────────────
 
+#define FIRMWARE_MAJOR  1
+#define FIRMWARE_MINOR  1
+#define FIRMWARE_PATCH  0
+#define PROTOCOL_VERSION 2   // 1 = legacy fixed frames, 2 = versioned
 #define NUM_CHANNELS    13
-#define SAMPLE_RATE_HZ  1000
-#define ADC_RESOLUTION  12  // Teensy 4.1 supports 10, 12, or 16 bit
+#define SAMPLE_RATE_HZ  1000 // default; host can change with set rate
+#define ADC_RESOLUTION  12   // default; values are always sent on the 12-bit scale
+#define ADC_AVERAGING   4    // default hardware averaging
 #define BAUD_RATE       115200
 #define FRAME_SIZE      34
+#define V2_DATA_SIZE    (13 + 2 * NUM_CHANNELS)
+#define V2_DESC_SIZE    (9 + 2 * NUM_CHANNELS)
+#define INFO_SIZE       9
+#define V2_REPLY_SIZE   (12 + INFO_SIZE)
+#define CMD_MAX_SIZE    16
 
 const uint8_t ANALOG_PINS[NUM_CHANNELS] = {
     A0, A1, A2,         // Pedals A, B, C
@@ -45,16 +85,59 @@ const uint8_t ANALOG_PINS[NUM_CHANNELS] = {
     A9, A10, A11, A12   // Bar SS49E at frets 0, 5, 10, 15
 };
 
+// Protocol v2 descriptor: (role, index) for each entry of ANALOG_PINS
+const uint8_t CHANNEL_ROLES[NUM_CHANNELS][2] = {
+    {1, 0}, {1, 1}, {1, 2},           // Pedals A, B, C
+    {2, 0}, {2, 1}, {2, 2},           // LKL, LKR, LKV
+    {2, 3}, {2, 4},                   // RKL, RKR
+    {3, 0},                           // Volume
+    {4, 0}, {4, 1}, {4, 2}, {4, 3}    // Bar sensors
+};
+
 const uint16_t SYNC_WORD = 0xBEEF;
+const uint16_t SYNC_WORD_V2 = 0xBEE2;
+const uint16_t COMMAND_SYNC = 0xBEC0;
+
+enum Command : uint8_t {
+    CMD_PING = 1,
+    CMD_SET_RATE = 2,
+    CMD_SET_AVERAGING = 3,
+    CMD_SET_RESOLUTION = 4,
+    CMD_CALIBRATION_STREAM = 5,
+    CMD_IDENTIFY = 6,
+};
+
+enum ReplyStatus : uint8_t {
+    STATUS_OK = 0,
+    STATUS_INVALID_ARGUMENT = 1,
+    STATUS_UNKNOWN_COMMAND = 2,
+};
+
+// ─── Runtime settings (changed by host commands) ────────────────────────────
+
+struct Settings {
+    uint16_t rate_hz;
+    uint8_t resolution;
+    uint8_t averaging;
+};
+
+const Settings DEFAULT_SETTINGS = {SAMPLE_RATE_HZ, ADC_RESOLUTION, ADC_AVERAGING};
+Settings settings = DEFAULT_SETTINGS;
+Settings before_calibration;
+bool calibration_streaming = false;
+uint32_t identify_until = 0;
+bool host_was_connected = false;
 
 // ─── Frame buffer ───────────────────────────────────────────────────────────
 
 uint8_t frame[FRAME_SIZE];
+uint8_t packet[V2_DATA_SIZE];
 uint16_t adc_values[NUM_CHANNELS];
+uint16_t sequence = 0;
 
 // ─── Timing ─────────────────────────────────────────────────────────────────
 
-const uint32_t SAMPLE_INTERVAL_US = 1000000UL / SAMPLE_RATE_HZ;
+uint32_t sample_interval_us = 1000000UL / SAMPLE_RATE_HZ;
 uint32_t next_sample_time;
 
 // ─── CRC-16/CCITT-FALSE ────────────────────────────────────────────────────
@@ -98,11 +181,170 @@ void pack_frame(uint32_t timestamp, const uint16_t* adc, uint8_t* buf) {
     buf[FRAME_SIZE - 1] = (crc >> 8) & 0xFF;
 }
 
+// ─── Protocol v2 ────────────────────────────────────────────────────────────
+
+size_t pack_v2_header(uint8_t type, uint8_t* buf) {
+    buf[0] = SYNC_WORD_V2 & 0xFF;
+    buf[1] = (SYNC_WORD_V2 >> 8) & 0xFF;
+    buf[2] = 2;
+    buf[3] = type;
+    buf[4] = sequence & 0xFF;
+    buf[5] = (sequence >> 8) & 0xFF;
+    buf[6] = NUM_CHANNELS;
+    sequence++;
+    return 7;
+}
+
+void append_crc(uint8_t* buf, size_t len) {
+    uint16_t crc = crc16(buf, len);
+    buf[len] = crc & 0xFF;
+    buf[len + 1] = (crc >> 8) & 0xFF;
+}
+
+void send_descriptor() {
+    uint8_t desc[V2_DESC_SIZE];
+    size_t n = pack_v2_header(1, desc);
+    for (int i = 0; i < NUM_CHANNELS; i++) {
+        desc[n++] = CHANNEL_ROLES[i][0];
+        desc[n++] = CHANNEL_ROLES[i][1];
+    }
+    append_crc(desc, n);
+    Serial.write(desc, V2_DESC_SIZE);
+}
+
+void pack_data_v2(uint32_t timestamp, const uint16_t* adc, uint8_t* buf) {
+    size_t n = pack_v2_header(0, buf);
+    buf[n++] = timestamp & 0xFF;
+    buf[n++] = (timestamp >> 8) & 0xFF;
+    buf[n++] = (timestamp >> 16) & 0xFF;
+    buf[n++] = (timestamp >> 24) & 0xFF;
+    for (int i = 0; i < NUM_CHANNELS; i++) {
+        buf[n++] = adc[i] & 0xFF;
+        buf[n++] = (adc[i] >> 8) & 0xFF;
+    }
+    append_crc(buf, n);
+}
+
+// ─── Host commands ──────────────────────────────────────────────────────────
+
+uint8_t cmd_buf[CMD_MAX_SIZE];
+size_t cmd_len = 0;
+
+void apply_settings() {
+    analogReadResolution(settings.resolution);
+    analogReadAveraging(settings.averaging);
+    sample_interval_us = 1000000UL / settings.rate_hz;
+}
+
+void send_reply(uint8_t command, uint8_t id, uint8_t status) {
+    uint8_t reply[V2_REPLY_SIZE];
+    size_t n = pack_v2_header(2, reply);
+    reply[6] = INFO_SIZE;  // header count field = payload length
+    reply[n++] = command;
+    reply[n++] = id;
+    reply[n++] = status;
+    reply[n++] = FIRMWARE_MAJOR;
+    reply[n++] = FIRMWARE_MINOR;
+    reply[n++] = FIRMWARE_PATCH;
+    reply[n++] = PROTOCOL_VERSION;
+    reply[n++] = settings.rate_hz & 0xFF;
+    reply[n++] = (settings.rate_hz >> 8) & 0xFF;
+    reply[n++] = settings.resolution;
+    reply[n++] = settings.averaging;
+    reply[n++] = calibration_streaming ? 1 : 0;
+    append_crc(reply, n);
+    Serial.write(reply, V2_REPLY_SIZE);
+}
+
+bool valid_averaging(uint8_t n) {
+    return n == 1 || n == 4 || n == 8 || n == 16 || n == 32;
+}
+
+uint8_t run_command(uint8_t command, const uint8_t* payload, uint8_t len) {
+    switch (command) {
+    case CMD_PING:
+        return len == 0 ? STATUS_OK : STATUS_INVALID_ARGUMENT;
+    case CMD_SET_RATE: {
+        if (len != 2) return STATUS_INVALID_ARGUMENT;
+        uint16_t hz = payload[0] | (payload[1] << 8);
+        if (hz < 10 || hz > 4000) return STATUS_INVALID_ARGUMENT;
+        settings.rate_hz = hz;
+        break;
+    }
+    case CMD_SET_AVERAGING:
+        if (len != 1 || !valid_averaging(payload[0])) return STATUS_INVALID_ARGUMENT;
+        settings.averaging = payload[0];
+        break;
+    case CMD_SET_RESOLUTION:
+        if (len != 1 || (payload[0] != 8 && payload[0] != 10 && payload[0] != 12))
+            return STATUS_INVALID_ARGUMENT;
+        settings.resolution = payload[0];
+        break;
+    case CMD_CALIBRATION_STREAM:
+        if (len != 1) return STATUS_INVALID_ARGUMENT;
+        if (payload[0] && !calibration_streaming) {
+            before_calibration = settings;
+            settings.rate_hz = 100;
+            settings.averaging = 32;
+            calibration_streaming = true;
+        } else if (!payload[0] && calibration_streaming) {
+            settings = before_calibration;
+            calibration_streaming = false;
+        }
+        break;
+    case CMD_IDENTIFY:
+        if (len != 1) return STATUS_INVALID_ARGUMENT;
+        identify_until = millis() + payload[0] * 1000UL;
+        return STATUS_OK;
+    default:
+        return STATUS_UNKNOWN_COMMAND;
+    }
+    apply_settings();
+    return STATUS_OK;
+}
+
+// Collect command bytes; resync on the sync word, drop frames with a bad CRC
+void poll_commands() {
+    while (Serial.available()) {
+        uint8_t byte = Serial.read();
+        if (cmd_len == 0 && byte != (COMMAND_SYNC & 0xFF)) continue;
+        if (cmd_len == 1 && byte != (COMMAND_SYNC >> 8)) {
+            cmd_len = (byte == (COMMAND_SYNC & 0xFF)) ? 1 : 0;
+            continue;
+        }
+        cmd_buf[cmd_len++] = byte;
+        if (cmd_len < 5) continue;
+        size_t total = 5 + cmd_buf[4] + 2;
+        if (total > CMD_MAX_SIZE) {
+            cmd_len = 0;
+            continue;
+        }
+        if (cmd_len < total) continue;
+        uint16_t crc = cmd_buf[total - 2] | (cmd_buf[total - 1] << 8);
+        if (crc == crc16(cmd_buf, total - 2)) {
+            uint8_t status = run_command(cmd_buf[2], cmd_buf + 5, cmd_buf[4]);
+            send_reply(cmd_buf[2], cmd_buf[3], status);
+        }
+        cmd_len = 0;
+    }
+}
+
+// Host closed the port: forget its settings so the next session starts clean
+void check_host() {
+    bool connected = Serial.dtr();
+    if (host_was_connected && !connected) {
+        settings = DEFAULT_SETTINGS;
+        calibration_streaming = false;
+        apply_settings();
+    }
+    host_was_connected = connected;
+}
+
 // ─── Setup ──────────────────────────────────────────────────────────────────
 
 void setup() {
     Serial.begin(BAUD_RATE);
-    analogReadResolution(ADC_RESOLUTION);
+    apply_settings();
 
     // Configure analog pins (Teensy 4.1 — all analog-capable)
     for (int i = 0; i < NUM_CHANNELS; i++) {
@@ -121,26 +363,50 @@ void setup() {
 // ─── Main loop ──────────────────────────────────────────────────────────────
 
 void loop() {
+    check_host();
+    poll_commands();
+
     uint32_t now = micros();
 
     if ((int32_t)(now - next_sample_time) >= 0) {
-        next_sample_time += SAMPLE_INTERVAL_US;
+        next_sample_time += sample_interval_us;
+        if ((int32_t)(now - next_sample_time) > 0) {
+            next_sample_time = now;  // after a rate change, don't burst to catch up
+        }
 
-        // Read all channels
+        // Read all channels, scaled to 12 bits whatever the resolution
         for (int i = 0; i < NUM_CHANNELS; i++) {
-            adc_values[i] = analogRead(ANALOG_PINS[i]);
+            adc_values[i] = analogRead(ANALOG_PINS[i]) << (12 - settings.resolution);
         }
 
         // Pack and send
+#if PROTOCOL_VERSION == 2
+        static bool described = false;
+        if (!described) {
+            send_descriptor();
+            described = true;
+        }
+        pack_data_v2(now, adc_values, packet);
+        Serial.write(packet, V2_DATA_SIZE);
+#else
         pack_frame(now, adc_values, frame);
         Serial.write(frame, FRAME_SIZE);
+#endif
 
-        // Toggle LED every second for heartbeat
+        // Toggle LED every second for heartbeat (and re-send the v2
+        // descriptor for hosts that connected mid-stream)
         static uint16_t led_counter = 0;
         led_counter++;
-        if (led_counter >= SAMPLE_RATE_HZ) {
+        if ((int32_t)(millis() - identify_until) < 0 && led_counter % (settings.rate_hz / 10 + 1) == 0) {
+            // Identify: fast blink
+            digitalWriteFast(LED_BUILTIN, !digitalReadFast(LED_BUILTIN));
+        }
+        if (led_counter >= settings.rate_hz) {
             led_counter = 0;
             digitalWriteFast(LED_BUILTIN, !digitalReadFast(LED_BUILTIN));
+#if PROTOCOL_VERSION == 2
+            send_descriptor();
+#endif
         }
     }
 }
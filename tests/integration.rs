@@ -7,7 +7,7 @@
 //! The coordinator fuses these to produce CaptureFrames with bar position,
 //! string pitches, active strings, and attacks.
 
-use crossbeam_channel::bounded;
+use crossbeam_channel::{bounded, unbounded};
 use std::thread;
 use std::time::Duration;
 
@@ -15,8 +15,14 @@ use steel_capture::bar_sensor::simulate_bar_readings;
 use steel_capture::coordinator::Coordinator;
 use steel_capture::copedant::{buddy_emmons_e9, midi_to_hz, CopedantEngine};
 use steel_capture::data_logger::build_jsonl_header;
+use steel_capture::impairments::{Impairments, NoiseColor};
 use steel_capture::jsonl_reader::SessionReader;
+use steel_capture::sensor_faults::{FaultKind, SensorChannel, SensorFaults};
+use steel_capture::serial_reader::{DisconnectPolicy, SerialReader};
+use steel_capture::simulator::{write_ground_truth, GroundTruth, ImprovParams, Simulator};
 use steel_capture::string_detector::StringDetector;
+use steel_capture::string_synth::SteelSynth;
+use steel_capture::teensy_emulator::{EmulatedStream, TeensyEmulator};
 use steel_capture::types::*;
 
 // ─── Helpers ───────────────────────────────────────────────────────────────
@@ -130,12 +136,92 @@ fn make_events(
                 timestamp_us: ts,
                 samples,
                 sample_rate: sr,
+                channels: Vec::new(),
             }));
         }
     }
     events
 }
 
+/// Like `make_events`, but renders audio with the plucked-string model
+/// (harmonics, pick transient, decay, pickup comb) instead of pure sines.
+fn make_steel_events(
+    fret: f32,
+    active_strings: &[usize],
+    volume: f32,
+    n_ticks: u32,
+    sr: u32,
+) -> Vec<InputEvent> {
+    let engine = CopedantEngine::new(buddy_emmons_e9());
+    let mut synth = SteelSynth::new(sr);
+    let samples_per_tick = (sr / 1000) as usize;
+    let mut events = Vec::new();
+
+    for &si in active_strings {
+        synth.pluck(si, fret, 1.0);
+    }
+    for tick in 0..n_ticks {
+        let ts = tick as u64 * 1000;
+        let sensor =
+            sensor_with_bar_and_strings(ts, fret, active_strings, [0.0; 3], [0.0; 5], volume);
+        events.push(InputEvent::Sensor(sensor));
+
+        let mut pitches = engine.effective_open_pitches(&sensor);
+        for p in pitches.iter_mut() {
+            *p += fret as f64;
+        }
+        let mut samples = vec![0.0f32; samples_per_tick];
+        let gain = volume * 0.5 / active_strings.len().max(1) as f32;
+        synth.render(&pitches, fret, &sensor.string_active, gain, &mut samples);
+        events.push(InputEvent::Audio(AudioChunk {
+            timestamp_us: ts,
+            samples,
+            sample_rate: sr,
+            channels: Vec::new(),
+        }));
+    }
+    events
+}
+
+/// Run every audio chunk in `events` through an impairment chain, in order.
+fn impair_events(mut events: Vec<InputEvent>, impairments: &Impairments) -> Vec<InputEvent> {
+    let mut chain = impairments.chain(48000);
+    for event in events.iter_mut() {
+        if let InputEvent::Audio(chunk) = event {
+            chain.process(&mut chunk.samples);
+        }
+    }
+    events
+}
+
+/// Run a standalone StringDetector over `events` with a known bar position,
+/// returning the final detection.
+fn detect_with_known_bar(events: &[InputEvent], fret: f32) -> [bool; 10] {
+    let engine = CopedantEngine::new(buddy_emmons_e9());
+    let mut det = StringDetector::new();
+    let mut active = [false; 10];
+    for event in events {
+        match event {
+            InputEvent::Audio(chunk) => det.push_audio(chunk),
+            InputEvent::Sensor(sensor) => {
+                active = det.detect(sensor, Some(fret), &engine).0;
+            }
+            InputEvent::Status(_) => {}
+        }
+    }
+    active
+}
+
+/// Corrupt every sensor frame in `events` with `faults` (1 kHz ticks).
+fn fault_events(mut events: Vec<InputEvent>, faults: &mut SensorFaults) -> Vec<InputEvent> {
+    for event in events.iter_mut() {
+        if let InputEvent::Sensor(sensor) = event {
+            faults.apply(sensor, 1000);
+        }
+    }
+    events
+}
+
 // ─── Integration Tests ─────────────────────────────────────────────────────
 
 #[test]
@@ -255,6 +341,7 @@ fn test_pipeline_attacks_on_string_onset() {
             timestamp_us: ts,
             samples,
             sample_rate: sr,
+            channels: Vec::new(),
         }));
     }
 
@@ -327,6 +414,7 @@ fn test_pipeline_pedal_triggers_attack_on_active_strings() {
             timestamp_us: ts,
             samples,
             sample_rate: sr,
+            channels: Vec::new(),
         }));
     }
 
@@ -455,6 +543,430 @@ fn test_audio_string_detection_matches_simulator() {
     );
 }
 
+#[test]
+fn test_steel_synth_detector_with_known_bar() {
+    // Realistic plucked-string audio: upper partials, inharmonicity and the
+    // pickup comb put energy where other strings live. With the exact bar
+    // position, the detector should still find the grip.
+    let events = make_steel_events(5.0, &[2, 3, 4], 0.8, 300, 48000);
+    let active = detect_with_known_bar(&events, 5.0);
+    for si in [2, 3, 4] {
+        assert!(
+            active[si],
+            "string {} should be detected: {:?}",
+            si + 1,
+            active
+        );
+    }
+    let false_positives = (0..10)
+        .filter(|i| ![2, 3, 4].contains(i) && active[*i])
+        .count();
+    assert!(
+        false_positives <= 2,
+        "harmonic overlap should cause at most 2 false strings, got {:?}",
+        active
+    );
+}
+
+#[test]
+fn test_impaired_detector_survives_hum_and_noise() {
+    // A typical noisy rig: 60 Hz hum plus pink noise at 20 dB SNR.
+    // The grip should still be found with few false strings.
+    let clean = make_events(5.0, &[2, 3, 4], [0.0; 3], [0.0; 5], 0.8, 400, 48000);
+    let impairments = Impairments::default()
+        .with_hum(60.0, 0.02)
+        .with_noise(NoiseColor::Pink, 20.0);
+    let active = detect_with_known_bar(&impair_events(clean, &impairments), 5.0);
+    for si in [2, 3, 4] {
+        assert!(
+            active[si],
+            "string {} lost under noise: {:?}",
+            si + 1,
+            active
+        );
+    }
+    let false_positives = (0..10)
+        .filter(|i| ![2, 3, 4].contains(i) && active[*i])
+        .count();
+    assert!(
+        false_positives <= 1,
+        "noise caused false strings: {:?}",
+        active
+    );
+}
+
+#[test]
+fn test_impaired_silence_detects_no_strings() {
+    // Hum, buzz and hiss with nothing picked must not register as notes.
+    let mut events = Vec::new();
+    for tick in 0..400u64 {
+        let ts = tick * 1000;
+        let sensor = sensor_with_bar_and_strings(ts, 5.0, &[], [0.0; 3], [0.0; 5], 0.8);
+        events.push(InputEvent::Sensor(sensor));
+        events.push(InputEvent::Audio(AudioChunk {
+            timestamp_us: ts,
+            samples: vec![0.0; 48],
+            sample_rate: 48000,
+            channels: Vec::new(),
+        }));
+    }
+    let impairments = Impairments::default()
+        .with_hum(60.0, 0.02)
+        .with_buzz(0.005)
+        .with_noise(NoiseColor::White, 30.0);
+    let active = detect_with_known_bar(&impair_events(events, &impairments), 5.0);
+    assert!(
+        active.iter().all(|&a| !a),
+        "impairments alone should not trigger strings: {:?}",
+        active
+    );
+}
+
+#[test]
+fn test_impaired_clipping_keeps_grip() {
+    // Hard clipping adds odd harmonics but leaves the fundamentals dominant.
+    let clean = make_events(5.0, &[2, 3, 4], [0.0; 3], [0.0; 5], 0.8, 400, 48000);
+    let impairments = Impairments::default().with_clip(0.2);
+    let active = detect_with_known_bar(&impair_events(clean, &impairments), 5.0);
+    for si in [2, 3, 4] {
+        assert!(
+            active[si],
+            "string {} lost after clipping: {:?}",
+            si + 1,
+            active
+        );
+    }
+}
+
+#[test]
+fn test_impaired_bar_fusion_stays_near_sensor() {
+    // Noise degrades audio matching; fusion must not wander from the sensor.
+    let clean = make_steel_events(5.0, &[2, 3, 4], 0.8, 400, 48000);
+    let impairments = Impairments::default()
+        .with_hum(50.0, 0.03)
+        .with_noise(NoiseColor::White, 10.0);
+    let frames = run_pipeline(impair_events(clean, &impairments), true);
+    let late: Vec<_> = frames
+        .iter()
+        .filter(|f| f.timestamp_us > 200_000 && f.bar_position.is_some())
+        .collect();
+    assert!(!late.is_empty(), "bar should still be tracked under noise");
+    for f in late {
+        let pos = f.bar_position.unwrap();
+        assert!(
+            (pos - 5.0).abs() < 1.0,
+            "bar position {:.2} ({:?}) drifted under noise",
+            pos,
+            f.bar_source
+        );
+    }
+}
+
+#[test]
+fn test_fault_dead_hall_array_falls_back_to_audio() {
+    // Every hall sensor open-circuit: the bar must come from audio alone,
+    // continuously (not only on the ticks where analysis runs). A 5-string
+    // grip is used because E9 makes small grips ambiguous without the
+    // sensor (strings 3-4-5 at fret 5 sound the same notes as 2-5-7 at 10).
+    let clean = make_events(5.0, &[2, 3, 4, 5, 7], [0.0; 3], [0.0; 5], 0.8, 600, 48000);
+    let mut faults = SensorFaults::default();
+    for i in 0..4 {
+        faults.set(SensorChannel::Bar(i), FaultKind::Dead);
+    }
+    let frames = run_pipeline(fault_events(clean, &mut faults), false);
+    let late: Vec<_> = frames.iter().filter(|f| f.timestamp_us > 200_000).collect();
+    assert!(!late.is_empty());
+    for f in &late {
+        assert_eq!(f.bar_source, BarSource::Audio, "at {} us", f.timestamp_us);
+        let pos = f.bar_position.expect("audio should hold the bar position");
+        assert!((pos - 5.0).abs() < 1.0, "audio-only bar {:.2}", pos);
+        assert!(
+            f.bar_confidence < 0.8,
+            "audio-only should be less confident"
+        );
+    }
+}
+
+#[test]
+fn test_fault_dead_hall_sensor_under_bar() {
+    // The sensor nearest the bar dies. The remaining sensors point at the
+    // wrong neighborhood; with strings sounding, audio must win.
+    let clean = make_events(5.0, &[2, 3, 4], [0.0; 3], [0.0; 5], 0.8, 600, 48000);
+    let mut faults = SensorFaults::default();
+    faults.set(SensorChannel::Bar(1), FaultKind::Dead);
+    let frames = run_pipeline(fault_events(clean, &mut faults), false);
+    let last = frames.last().unwrap();
+    let pos = last.bar_position.expect("bar should still be tracked");
+    assert!(
+        (pos - 5.0).abs() < 1.0,
+        "bar {:.2} ({:?}) should recover from a dead sensor",
+        pos,
+        last.bar_source
+    );
+}
+
+#[test]
+fn test_fault_hall_dropouts_keep_bar() {
+    // A flaky connector drops the whole hall array for 20 ms bursts.
+    // While strings sound, the bar position should never disappear.
+    let clean = make_events(5.0, &[2, 3, 4], [0.0; 3], [0.0; 5], 0.8, 1000, 48000);
+    let mut events = clean;
+    let mut dropout = SensorFaults::new(7);
+    dropout.set(
+        SensorChannel::Bar(0),
+        FaultKind::Dropout {
+            per_sec: 4.0,
+            ms: 20,
+        },
+    );
+    // Mirror channel 0's dropouts onto the other three
+    for event in events.iter_mut() {
+        if let InputEvent::Sensor(sensor) = event {
+            let before = sensor.bar_sensors[0];
+            dropout.apply(sensor, 1000);
+            if sensor.bar_sensors[0] == 0.0 && before > 0.0 {
+                sensor.bar_sensors = [0.0; 4];
+            }
+        }
+    }
+    let frames = run_pipeline(events, false);
+    let lost = frames
+        .iter()
+        .filter(|f| f.timestamp_us > 200_000 && f.bar_position.is_none())
+        .count();
+    assert_eq!(lost, 0, "bar position lost on {} frames", lost);
+}
+
+#[test]
+fn test_fault_noisy_pedal_single_attack() {
+    // ADC noise on pedal A while it is pressed slowly through 0.5 must
+    // produce one re-articulation, not a burst of chattering attacks.
+    let engine_strings = [4usize]; // string 5 (B), raised by pedal A
+    let mut events = Vec::new();
+    let mut faults = SensorFaults::default();
+    faults.set(
+        SensorChannel::Pedal(0),
+        FaultKind::Noise { amplitude: 0.08 },
+    );
+    for tick in 0..600u64 {
+        let pedal = (tick as f32 / 500.0).min(1.0);
+        let mut sensor = sensor_with_bar_and_strings(
+            tick * 1000,
+            3.0,
+            &engine_strings,
+            [pedal, 0.0, 0.0],
+            [0.0; 5],
+            0.8,
+        );
+        faults.apply(&mut sensor, 1000);
+        events.push(InputEvent::Sensor(sensor));
+    }
+    let frames = run_pipeline(events, false);
+    let attacks = frames
+        .iter()
+        .skip(1) // the initial pick
+        .filter(|f| f.attacks[4])
+        .count();
+    assert_eq!(
+        attacks, 1,
+        "noisy pedal should re-articulate once, got {}",
+        attacks
+    );
+}
+
+#[test]
+fn test_fault_railed_volume_keeps_pitch_and_strings() {
+    // A shorted volume pot reads full scale; detection and pitch are
+    // independent of volume, so nothing else should change.
+    let clean = make_events(3.0, &[2, 3, 4], [0.0; 3], [0.0; 5], 0.4, 400, 48000);
+    let mut faults = SensorFaults::default();
+    faults.set(SensorChannel::Volume, FaultKind::Railed);
+    let faulted = run_pipeline(fault_events(clean.clone(), &mut faults), false);
+    let reference = run_pipeline(clean, false);
+    let (a, b) = (faulted.last().unwrap(), reference.last().unwrap());
+    assert_eq!(a.volume, 1.0);
+    assert_eq!(a.string_active, b.string_active);
+    assert_eq!(a.string_pitches_hz, b.string_pitches_hz);
+}
+
+#[test]
+fn test_offline_simulator_ground_truth() {
+    // Offline playback: virtual 1 ms ticks, one truth record per sensor
+    // frame, picks reported as attacks, and `play` returns at the end.
+    let (input_tx, input_rx) = unbounded::<InputEvent>();
+    let (truth_tx, truth_rx) = unbounded::<GroundTruth>();
+    let mut sim = Simulator::new(SessionClock::new(), input_tx, buddy_emmons_e9(), 1000)
+        .with_offline()
+        .with_ground_truth(truth_tx)
+        .with_sensor_fault(SensorChannel::Bar(1), FaultKind::Dead);
+    sim.play("basic");
+    drop(sim);
+
+    let sensors: Vec<SensorFrame> = input_rx
+        .iter()
+        .filter_map(|e| match e {
+            InputEvent::Sensor(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+    let truth: Vec<GroundTruth> = truth_rx.iter().collect();
+    assert_eq!(sensors.len(), truth.len());
+    for (k, (s, t)) in sensors.iter().zip(&truth).enumerate() {
+        assert_eq!(s.timestamp_us, k as u64 * 1000);
+        assert_eq!(t.timestamp_us, s.timestamp_us);
+    }
+
+    // First pick: strings 3-4-5 at fret 3
+    let first = truth.iter().find(|t| t.attacks.iter().any(|&a| a)).unwrap();
+    assert_eq!(first.bar_fret, Some(3.0));
+    assert_eq!(
+        first.attacks,
+        [false, false, true, true, true, false, false, false, false, false]
+    );
+
+    // Truth is pre-fault: the sensor frame has the dead channel, truth doesn't
+    let at = truth
+        .iter()
+        .position(|t| t.bar_fret.is_some_and(|f| (f - 5.0).abs() < 0.01))
+        .unwrap();
+    assert_eq!(sensors[at].bar_sensors[1], 0.0);
+    assert!(simulate_bar_readings(5.0)[1] > 0.9);
+}
+
+/// Play a short, fast improv offline (no audio) and collect its ground truth.
+fn improv_truth(params: ImprovParams) -> Vec<GroundTruth> {
+    let (input_tx, _input_rx) = unbounded::<InputEvent>();
+    let (truth_tx, truth_rx) = unbounded::<GroundTruth>();
+    let mut sim = Simulator::new(SessionClock::new(), input_tx, buddy_emmons_e9(), 1000)
+        .with_offline()
+        .with_suppress_audio()
+        .with_ground_truth(truth_tx)
+        .with_improv(params);
+    sim.play("improv");
+    drop(sim);
+    truth_rx.iter().collect()
+}
+
+fn short_improv(seed: u64) -> ImprovParams {
+    ImprovParams {
+        seed,
+        beats: 24,
+        tempo_bpm: 240.0,
+        ..ImprovParams::default()
+    }
+}
+
+#[test]
+fn test_improv_seed_reproducible() {
+    let json = |truth: &[GroundTruth]| serde_json::to_string(truth).unwrap();
+    let a = improv_truth(short_improv(7));
+    let b = improv_truth(short_improv(7));
+    let c = improv_truth(short_improv(8));
+    assert!(!a.is_empty());
+    assert_eq!(json(&a), json(&b), "same seed must replay identically");
+    assert_ne!(json(&a), json(&c), "different seeds should differ");
+}
+
+#[test]
+fn test_improv_chord_subset_and_key() {
+    // E (fret 0), A9 (fret 5), B (fret 7), transposed up 3 frets
+    let truth = improv_truth(ImprovParams {
+        chords: vec!["E".into(), "A9".into(), "B".into()],
+        key_fret: 3.0,
+        beats: 64,
+        ..short_improv(11)
+    });
+    let picked: Vec<f32> = truth
+        .iter()
+        .filter(|t| t.attacks.iter().any(|&a| a))
+        .filter_map(|t| t.bar_fret)
+        .collect();
+    assert!(picked.len() > 5, "only {} attacks", picked.len());
+    for fret in &picked {
+        assert!(
+            [3.0, 8.0, 10.0].iter().any(|c| (fret - c).abs() < 0.5),
+            "attack at fret {} outside the chord subset",
+            fret
+        );
+    }
+    // The transposed tonic (G at fret 3) is played
+    assert!(picked.iter().any(|f| (f - 3.0).abs() < 0.5));
+
+    let err = ImprovParams {
+        chords: vec!["Xmaj7".into()],
+        ..ImprovParams::default()
+    }
+    .validate()
+    .unwrap_err();
+    assert!(err.contains("Xmaj7"), "{}", err);
+    for (tempo_bpm, vibrato_hz) in [
+        (f32::NAN, 5.2),
+        (1000.0, 5.2),
+        (72.0, -1.0),
+        (72.0, f32::NAN),
+    ] {
+        let params = ImprovParams {
+            tempo_bpm,
+            vibrato_hz,
+            ..ImprovParams::default()
+        };
+        assert!(
+            params.validate().is_err(),
+            "{} BPM, {} Hz",
+            tempo_bpm,
+            vibrato_hz
+        );
+    }
+}
+
+#[test]
+fn test_ground_truth_export_roundtrip() {
+    let params = short_improv(0x5eed);
+    let truth = improv_truth(params.clone());
+    let (tx, rx) = unbounded::<GroundTruth>();
+    for t in &truth {
+        tx.send(t.clone()).unwrap();
+    }
+    drop(tx);
+
+    let path = std::env::temp_dir().join(format!("truth_{}.jsonl", std::process::id()));
+    let written = write_ground_truth(&path, rx, "improv", &params).unwrap();
+    assert_eq!(written as usize, truth.len());
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let mut lines = text.lines();
+    let header: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(header["demo"], "improv");
+    assert_eq!(header["improv"]["seed"], 0x5eed);
+    assert_eq!(header["improv"]["beats"], 24);
+    let first: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(first["timestamp_us"], truth[0].timestamp_us);
+    assert_eq!(lines.count() + 1, truth.len());
+}
+
+#[test]
+fn test_steel_synth_bar_fusion_rejects_octave_alias() {
+    // With a bridge pickup the fundamental is weak and the 2nd partial
+    // dominates, so audio-only matching can land an octave (12 frets) high.
+    // Fusion must keep the hall sensor's neighborhood.
+    let events = make_steel_events(5.0, &[2, 3, 4], 0.8, 400, 48000);
+    let frames = run_pipeline(events, true);
+    let fused: Vec<_> = frames
+        .iter()
+        .filter(|f| f.bar_source == BarSource::Fused)
+        .collect();
+    assert!(!fused.is_empty(), "audio should reach the fusion stage");
+    for f in fused {
+        let pos = f.bar_position.unwrap();
+        assert!(
+            (pos - 5.0).abs() < 1.0,
+            "fused bar position {:.2} should stay near fret 5",
+            pos
+        );
+    }
+}
+
 #[test]
 fn test_copedant_pitches_across_all_frets() {
     // Verify pitch calculations are correct across frets 0-12
@@ -463,13 +975,13 @@ fn test_copedant_pitches_across_all_frets() {
 
     for fret in 0..=12 {
         let pitches = engine.compute_pitches(&sensor, Some(fret as f32));
-        for si in 0..10 {
+        for (si, &hz) in pitches.iter().enumerate() {
             assert!(
-                pitches[si] > 20.0 && pitches[si] < 10000.0,
+                hz > 20.0 && hz < 10000.0,
                 "fret {} string {}: pitch {:.1} out of range",
                 fret,
                 si + 1,
-                pitches[si]
+                hz
             );
         }
         // Verify string 4 (E4 = MIDI 64) specifically
@@ -502,6 +1014,7 @@ fn test_string_detector_standalone_resolution() {
         timestamp_us: 0,
         samples: samples.clone(),
         sample_rate: sr,
+        channels: Vec::new(),
     };
     det.push_audio(&chunk);
     det.analysis_window = samples.len().min(det.analysis_window);
@@ -588,8 +1101,8 @@ fn test_ws_json_serialization() {
     let decoded: CaptureFrame = serde_json::from_str(&json).unwrap();
     assert_eq!(decoded.timestamp_us, 1234567);
     assert_eq!(decoded.bar_position, Some(5.0));
-    assert_eq!(decoded.string_active[2], true);
-    assert_eq!(decoded.attacks[2], true);
+    assert!(decoded.string_active[2]);
+    assert!(decoded.attacks[2]);
 
     // Compact format: shorter keys, same data
     let compact = CompactFrame::from(&frame);
@@ -604,8 +1117,8 @@ fn test_ws_json_serialization() {
     let back: CaptureFrame = compact_decoded.into();
     assert_eq!(back.timestamp_us, 1234567);
     assert_eq!(back.bar_position, Some(5.0));
-    assert_eq!(back.string_active[2], true);
-    assert_eq!(back.attacks[2], true);
+    assert!(back.string_active[2]);
+    assert!(back.attacks[2]);
 }
 
 // ─── JSONL format tests ─────────────────────────────────────────────────────
@@ -1148,6 +1661,7 @@ fn make_hardware_events(
                 timestamp_us: ts,
                 samples,
                 sample_rate: sr,
+                channels: Vec::new(),
             }));
         }
     }
@@ -1268,6 +1782,7 @@ fn test_hardware_mode_pedal_attack_with_audio() {
             timestamp_us: ts,
             samples,
             sample_rate: sr,
+            channels: Vec::new(),
         }));
     }
 
@@ -1363,6 +1878,7 @@ fn generate_jsonl_bar_slide(
                 timestamp_us: ts,
                 samples,
                 sample_rate: sr,
+                channels: Vec::new(),
             }));
         }
     }
@@ -1533,3 +2049,1235 @@ fn test_jsonl_reader_corrupted_frame() {
     assert_eq!(frames[0].bar_position, Some(3.0));
     assert_eq!(frames[1].bar_position, Some(5.0));
 }
+
+// ─── Teensy emulator ──────────────────────────────────────────────────────
+
+/// Stream the basic demo's sensor frames through the emulator and decode
+/// them with the real serial reader. Returns (truth, decoded, frames the
+/// emulator sent intact).
+fn emulated_serial(emulator: TeensyEmulator) -> (Vec<GroundTruth>, Vec<SensorFrame>, u64) {
+    let (sim_tx, sim_rx) = bounded::<InputEvent>(256);
+    let (truth_tx, truth_rx) = unbounded::<GroundTruth>();
+    let sim = thread::spawn(move || {
+        let mut sim = Simulator::new(SessionClock::new(), sim_tx, buddy_emmons_e9(), 1000)
+            .with_offline()
+            .with_suppress_audio()
+            .with_ground_truth(truth_tx);
+        sim.play("basic");
+    });
+
+    let mut stream = EmulatedStream::new(sim_rx, emulator);
+    let (out_tx, out_rx) = unbounded::<InputEvent>();
+    SerialReader::new("emulated".into(), out_tx, SessionClock::new()).read_stream(&mut stream);
+    sim.join().unwrap();
+
+    let decoded = out_rx
+        .try_iter()
+        .filter_map(|e| match e {
+            InputEvent::Sensor(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+    (
+        truth_rx.try_iter().collect(),
+        decoded,
+        stream.stats().frames,
+    )
+}
+
+#[test]
+fn test_serial_reader_decodes_emulated_stream() {
+    let (truth, decoded, sent) = emulated_serial(TeensyEmulator::new(1));
+    assert_eq!(decoded.len(), truth.len());
+    assert_eq!(sent as usize, truth.len());
+    // 12-bit quantization over the 200–3800 calibrated span
+    let close = |a: f32, b: f32| (a - b).abs() <= 1.0 / 3600.0;
+    for (d, t) in decoded.iter().zip(&truth) {
+        assert!(d.pedals.iter().zip(&t.pedals).all(|(&a, &b)| close(a, b)));
+        assert!(d
+            .knee_levers
+            .iter()
+            .zip(&t.knee_levers)
+            .all(|(&a, &b)| close(a, b)));
+        assert!(close(d.volume, t.volume));
+    }
+    // Bar position survives the round trip
+    let placed = truth.iter().position(|t| t.bar_fret == Some(3.0)).unwrap();
+    let mut bar = steel_capture::bar_sensor::BarSensor::new();
+    let (fret, _) = bar.estimate(&decoded[placed].bar_sensors).unwrap();
+    assert!((fret - 3.0).abs() < 0.3, "fret {}", fret);
+}
+
+#[test]
+fn test_serial_reader_survives_link_faults() {
+    let emulator = TeensyEmulator::new(9)
+        .with_garbage(0.05)
+        .with_crc_errors(0.02)
+        .with_partial_frames(0.02);
+    let (truth, decoded, sent) = emulated_serial(emulator);
+    // Every intact frame gets through; only the faulted ones are lost
+    assert_eq!(decoded.len() as u64, sent);
+    assert!(decoded.len() < truth.len());
+    assert!(decoded.len() as f64 > truth.len() as f64 * 0.9);
+}
+
+#[test]
+fn test_serial_reader_reconnects_after_unplug() {
+    use std::io::{self, Cursor};
+    use steel_capture::serial_reader::Port;
+
+    // One burst of 50 frames with pedal A down per successful open
+    let burst = || {
+        let mut emulator = TeensyEmulator::new(4);
+        let mut bytes = Vec::new();
+        for k in 0..50u64 {
+            let mut frame = SensorFrame::at_rest(k * 1000);
+            frame.pedals[0] = 0.8;
+            emulator.encode(&frame, &mut bytes);
+        }
+        bytes
+    };
+    // Absent, plugged in, unplugged, plugged in again, then gone for good
+    let mut opens = 0;
+    let opener = move || -> io::Result<Box<dyn Port>> {
+        opens += 1;
+        match opens {
+            2 | 4 => Ok(Box::new(Cursor::new(burst()))),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "no such device")),
+        }
+    };
+
+    let (tx, rx) = unbounded::<InputEvent>();
+    let reader = thread::spawn(move || {
+        SerialReader::new("mock".into(), tx, SessionClock::new())
+            .with_opener(opener)
+            .with_disconnect_policy(DisconnectPolicy::Zero)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(4))
+            .run();
+    });
+
+    let mut events = Vec::new();
+    let mut disconnects = 0;
+    while disconnects < 2 {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        if matches!(event, InputEvent::Status(DeviceStatus::Disconnected { .. })) {
+            disconnects += 1;
+        }
+        events.push(event);
+    }
+    // The zero frame follows the disconnect
+    events.push(rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    drop(rx);
+    reader.join().unwrap();
+
+    let statuses: Vec<String> = events
+        .iter()
+        .filter_map(|e| match e {
+            InputEvent::Status(DeviceStatus::Connected { .. }) => Some("up".into()),
+            InputEvent::Status(DeviceStatus::Disconnected { .. }) => Some("down".into()),
+            InputEvent::Status(DeviceStatus::Reconnecting {
+                attempt, delay_ms, ..
+            }) => Some(format!("retry{}:{}", attempt, delay_ms)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        ["retry1:1", "up", "down", "retry1:1", "retry2:2", "up", "down"]
+    );
+
+    let sensors: Vec<&SensorFrame> = events
+        .iter()
+        .filter_map(|e| match e {
+            InputEvent::Sensor(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(sensors.len(), 2 * 50 + 2);
+    let zeros: Vec<_> = sensors.iter().filter(|s| s.pedals[0] == 0.0).collect();
+    assert_eq!(zeros.len(), 2);
+    assert!(zeros.iter().all(|s| s.volume == 0.0));
+    assert!(matches!(events.last(), Some(InputEvent::Sensor(s)) if s.pedals[0] == 0.0));
+}
+
+#[test]
+fn test_device_commands_through_serial_reader() {
+    use steel_capture::serial_reader::{DeviceCommand, Port};
+    use steel_capture::teensy_emulator::EMULATED_FIRMWARE;
+
+    // A live device: frames every millisecond until the reader goes away
+    let (sim_tx, sim_rx) = bounded::<InputEvent>(64);
+    let feeder = thread::spawn(move || {
+        for k in 0u64.. {
+            let frame = InputEvent::Sensor(SensorFrame::at_rest(k * 1000));
+            if sim_tx.send(frame).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+    let mut stream = Some(EmulatedStream::new(sim_rx, TeensyEmulator::new(5)));
+    let opener = move || -> std::io::Result<Box<dyn Port>> {
+        stream
+            .take()
+            .map(|s| Box::new(s) as Box<dyn Port>)
+            .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+    };
+
+    let (tx, rx) = unbounded::<InputEvent>();
+    let reader = SerialReader::new("emulated".into(), tx, SessionClock::new())
+        .with_opener(opener)
+        .with_startup_commands(vec![DeviceCommand::SetAveraging(16)]);
+    let device = reader.device_handle();
+    let reader = thread::spawn(move || reader.run());
+
+    let info = device.ping().unwrap();
+    assert_eq!(info.firmware, EMULATED_FIRMWARE);
+    assert_eq!(info.averaging, 16, "startup command applied first");
+    assert_eq!(
+        device.send(DeviceCommand::SetRate(2000)).unwrap().rate_hz,
+        2000
+    );
+    let calibrating = device.send(DeviceCommand::CalibrationStream(true)).unwrap();
+    assert!(calibrating.calibration_streaming);
+    assert_eq!(
+        device
+            .send(DeviceCommand::CalibrationStream(false))
+            .unwrap()
+            .rate_hz,
+        2000
+    );
+    // Rejected on the host before anything is sent
+    assert!(device.send(DeviceCommand::SetResolution(11)).is_err());
+
+    // Frames kept flowing around the replies
+    let frames = rx
+        .try_iter()
+        .filter(|e| matches!(e, InputEvent::Sensor(_)))
+        .count();
+    assert!(frames > 0);
+    drop(rx);
+    reader.join().unwrap();
+    feeder.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_emulator_pty_is_raw() {
+    use std::io::{Read, Write};
+    use steel_capture::serial_protocol::{encode_frame, Calibration, FrameDecoder, FRAME_SIZE};
+
+    // Payload full of bytes a cooked tty would mangle: CR, LF, XON/XOFF, ^C
+    let raw = [
+        0x0D0A, 0x1311, 0x0A0D, 0x0303, 0x1113, 0x0D0D, 0x0A0A, 0x7F03, 0x0D11, 0x1304, 0x0A1A,
+        0x0D0D, 0x0304,
+    ];
+    let frames: Vec<u8> = (0..20u32).flat_map(|t| encode_frame(&raw, t)).collect();
+
+    let mut pty = steel_capture::teensy_emulator::open_pty().unwrap();
+    let mut reader = std::fs::File::open(&pty.slave_path).unwrap();
+    let expected = frames.len();
+    let writer = thread::spawn(move || {
+        pty.master.write_all(&frames).unwrap();
+        pty
+    });
+    let mut got = vec![0u8; expected];
+    reader.read_exact(&mut got).unwrap();
+    let _pty = writer.join().unwrap();
+
+    let mut decoder = FrameDecoder::new();
+    decoder.push(&got);
+    let clock = SessionClock::new();
+    let cal = Calibration::default();
+    let mut n = 0;
+    while decoder.next_frame(&cal, &clock).is_some() {
+        n += 1;
+    }
+    assert_eq!(n * FRAME_SIZE, expected);
+    assert_eq!(decoder.errors, 0);
+}
+
+// ─── UDP sensor input ──────────────────────────────────────────────────────
+
+/// Sensor frames from a `UdpInput` on a local port, skipping status events.
+fn udp_frames(rx: &crossbeam_channel::Receiver<InputEvent>, want: usize) -> Vec<SensorFrame> {
+    let mut frames = Vec::new();
+    while frames.len() < want {
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(InputEvent::Sensor(f)) => frames.push(f),
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    frames
+}
+
+#[test]
+fn test_udp_input_binary_and_osc() {
+    use rosc::{OscMessage, OscPacket, OscType};
+    use std::net::UdpSocket;
+    use steel_capture::serial_protocol::{encode_frame, sensor_to_raw, Calibration};
+    use steel_capture::udp_input::UdpInput;
+
+    let (tx, rx) = unbounded();
+    let input = UdpInput::bind("127.0.0.1:0", tx, SessionClock::new()).unwrap();
+    let addr = input.local_addr().unwrap();
+    thread::spawn(move || input.run());
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    // ESP32-style binary frame
+    let mut sent = SensorFrame::at_rest(0);
+    sent.pedals = [1.0, 0.5, 0.0];
+    sent.knee_levers[1] = 0.25;
+    sent.bar_sensors = [0.0, 0.9, 0.3, 0.0];
+    let raw = sensor_to_raw(&sent, &Calibration::default());
+    socket.send_to(&encode_frame(&raw, 1000), addr).unwrap();
+    let got = udp_frames(&rx, 1);
+    assert_eq!(got.len(), 1);
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+    assert!(got[0]
+        .pedals
+        .iter()
+        .zip(&sent.pedals)
+        .all(|(&a, &b)| close(a, b)));
+    assert!(close(got[0].knee_levers[1], 0.25));
+    assert!(close(got[0].bar_sensors[1], 0.9));
+
+    // OSC from another sender: only the addressed values change
+    let osc = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for (addr_str, v) in [("/steel/pedal/c", 0.75), ("/steel/volume", 0.2)] {
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr_str.into(),
+            args: vec![OscType::Float(v)],
+        });
+        osc.send_to(&rosc::encoder::encode(&packet).unwrap(), addr)
+            .unwrap();
+    }
+    let got = udp_frames(&rx, 2);
+    let last = got.last().unwrap();
+    assert_eq!(last.pedals, [0.0, 0.0, 0.75]);
+    assert_eq!(last.volume, 0.2);
+}
+
+#[test]
+fn test_udp_input_reads_osc_sender_output() {
+    use steel_capture::osc_sender::OscSender;
+    use steel_capture::udp_input::UdpInput;
+
+    let (tx, rx) = unbounded();
+    let input = UdpInput::bind("127.0.0.1:0", tx, SessionClock::new()).unwrap();
+    let addr = input.local_addr().unwrap();
+    thread::spawn(move || input.run());
+
+    // One capture's OSC output drives another capture's input
+    let (frame_tx, frame_rx) = unbounded();
+    let target = addr.to_string().parse().unwrap();
+    thread::spawn(move || OscSender::new(frame_rx, vec![target]).run());
+    let sensor = sensor_with_bar_and_strings(
+        0,
+        5.0,
+        &[2, 3],
+        [0.0, 1.0, 0.4],
+        [0.0, 0.6, 0.0, 0.0, 0.1],
+        0.9,
+    );
+    let captured = run_pipeline(vec![InputEvent::Sensor(sensor)], false);
+    frame_tx.send(captured[0].clone()).unwrap();
+
+    // Every value arrives once the whole frame has been applied
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    let mut last = None;
+    while std::time::Instant::now() < deadline {
+        if let Ok(InputEvent::Sensor(f)) = rx.recv_timeout(Duration::from_millis(200)) {
+            last = Some(f);
+            if f.bar_sensors == sensor.bar_sensors {
+                break;
+            }
+        }
+    }
+    let last = last.expect("no frames");
+    assert_eq!(last.pedals, sensor.pedals);
+    assert_eq!(last.knee_levers, sensor.knee_levers);
+    assert_eq!(last.volume, sensor.volume);
+    assert_eq!(last.bar_sensors, sensor.bar_sensors);
+}
+
+#[test]
+fn test_osc_bundles_to_multiple_targets() {
+    use rosc::OscPacket;
+    use std::net::UdpSocket;
+    use steel_capture::osc_sender::OscSender;
+
+    let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for s in [&a, &b] {
+        s.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    }
+    let targets = vec![
+        a.local_addr().unwrap().to_string().parse().unwrap(),
+        format!("{}/sc/steel", b.local_addr().unwrap())
+            .parse()
+            .unwrap(),
+    ];
+    let (frame_tx, frame_rx) = unbounded();
+    thread::spawn(move || OscSender::new(frame_rx, targets).with_rate(50).run());
+
+    // 100 ms of 1 kHz frames with one pick in between output ticks
+    let sensor = sensor_with_bar_and_strings(0, 3.0, &[], [0.0; 3], [0.0; 5], 0.8);
+    let mut frames = run_pipeline(vec![InputEvent::Sensor(sensor)], false);
+    let base = frames.remove(0);
+    for t in 0..100u64 {
+        let mut f = base.clone();
+        f.timestamp_us = t * 1000;
+        if t == 25 {
+            f.attacks[2] = true;
+            f.string_active[2] = true;
+        }
+        frame_tx.send(f).unwrap();
+    }
+    drop(frame_tx);
+
+    let bundles = |sock: &UdpSocket| {
+        let mut buf = [0u8; 8192];
+        let mut out = Vec::new();
+        while let Ok(n) = sock.recv(&mut buf) {
+            match rosc::decoder::decode_udp(&buf[..n]).unwrap().1 {
+                OscPacket::Bundle(bundle) => out.push(bundle),
+                other => panic!("expected a bundle, got {:?}", other),
+            }
+            sock.set_read_timeout(Some(Duration::from_millis(300)))
+                .unwrap();
+        }
+        out
+    };
+    let got_a = bundles(&a);
+    let got_b = bundles(&b);
+
+    // 50 Hz: one bundle per 20 ms of frames (fewer if nothing changed)
+    assert!((2..=5).contains(&got_a.len()), "{} bundles", got_a.len());
+    assert_eq!(got_a.len(), got_b.len());
+    let addrs = |bundles: &[rosc::OscBundle]| -> Vec<String> {
+        bundles
+            .iter()
+            .flat_map(|b| &b.content)
+            .map(|p| match p {
+                OscPacket::Message(m) => m.addr.clone(),
+                _ => String::new(),
+            })
+            .collect()
+    };
+    let addrs_a = addrs(&got_a);
+    assert!(addrs_a.iter().all(|a| a.starts_with("/steel/")));
+    assert!(addrs(&got_b).iter().all(|a| a.starts_with("/sc/steel/")));
+    assert_eq!(
+        addrs_a.iter().filter(|a| *a == "/steel/attack/2").count(),
+        1
+    );
+    // Timetags follow frame time
+    let t0 = std::time::SystemTime::from(got_a[0].timetag);
+    let t1 = std::time::SystemTime::from(got_a[1].timetag);
+    let gap = t1.duration_since(t0).unwrap();
+    assert!(gap >= Duration::from_millis(20), "{:?}", gap);
+    // Sustained values are not resent
+    let first_volume = got_a[0]
+        .content
+        .iter()
+        .filter(|p| matches!(p, OscPacket::Message(m) if m.addr == "/steel/volume"))
+        .count();
+    assert_eq!(first_volume, 1);
+    assert_eq!(addrs_a.iter().filter(|a| *a == "/steel/volume").count(), 1);
+}
+
+// ─── OSC control ───────────────────────────────────────────────────────────
+
+/// Send one OSC message to `to`.
+fn send_osc(to: std::net::SocketAddr, addr: &str, args: Vec<rosc::OscType>) {
+    let packet = rosc::OscPacket::Message(rosc::OscMessage {
+        addr: addr.to_string(),
+        args,
+    });
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(&rosc::encoder::encode(&packet).unwrap(), to)
+        .unwrap();
+}
+
+#[test]
+fn test_osc_control_records_and_marks_session() {
+    use rosc::OscType;
+    use steel_capture::data_logger::DataLogger;
+    use steel_capture::osc_control::OscControl;
+
+    let out = std::env::temp_dir().join(format!("control_{}", std::process::id()));
+    let (frame_tx, frame_rx) = bounded(0);
+    let (_audio_tx, audio_rx) = unbounded();
+    let (log_ctl_tx, log_ctl_rx) = unbounded();
+    let (probe_tx, probe_rx) = unbounded();
+    let logger =
+        DataLogger::new(frame_rx, audio_rx, &out, buddy_emmons_e9()).with_control(log_ctl_rx);
+    let session = logger.session_dir().to_path_buf();
+    let logger = thread::spawn(move || logger.run());
+
+    let control = OscControl::bind("127.0.0.1:0", vec![log_ctl_tx, probe_tx]).unwrap();
+    let control_addr = control.local_addr().unwrap();
+    thread::spawn(move || control.run());
+    let command = |addr: &str, args: Vec<OscType>| {
+        send_osc(control_addr, addr, args);
+        probe_rx.recv_timeout(Duration::from_secs(2)).unwrap()
+    };
+
+    let frames = |range: std::ops::Range<u64>| {
+        for k in range {
+            frame_tx
+                .send(mock_capture_frame(k * 1000, Some(3.0), 0.8))
+                .unwrap();
+        }
+    };
+    frames(0..10);
+    assert_eq!(
+        command("/steel/cmd/record", vec![OscType::String("stop".into())]),
+        ControlCommand::Record(false)
+    );
+    command("/steel/cmd/marker", vec![OscType::String("verse 2".into())]);
+    frames(10..20);
+    command("/steel/cmd/record", vec![OscType::Int(1)]);
+    frames(20..30);
+    drop(frame_tx);
+    logger.join().unwrap();
+
+    // A command takes effect from the frame after the last one delivered
+    // before it, or that frame itself if it raced the logger
+    let file = std::fs::File::open(session.join("frames.jsonl")).unwrap();
+    let written: Vec<u64> = SessionReader::open(std::io::BufReader::new(file))
+        .unwrap()
+        .read_all()
+        .iter()
+        .map(|f| f.timestamp_us / 1000)
+        .collect();
+    assert!(written.len() == 19 || written.len() == 20, "{:?}", written);
+    assert!((0..9).chain(21..30).all(|k| written.contains(&k)));
+    assert!((11..19).all(|k| !written.contains(&k)));
+
+    let events: Vec<serde_json::Value> = std::fs::read_to_string(session.join("markers.jsonl"))
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(events.len(), 3, "{:?}", events);
+    assert_eq!(events[0]["record"], "stop");
+    assert_eq!(events[1]["marker"], "verse 2");
+    assert_eq!(events[2]["record"], "start");
+    let t = |i: usize| events[i]["t"].as_u64().unwrap();
+    assert!([9000, 10000].contains(&t(0)) && t(1) >= t(0) && t(1) <= 10000);
+    assert!([19000, 20000].contains(&t(2)));
+    std::fs::remove_dir_all(&out).ok();
+}
+
+#[test]
+fn test_osc_control_switches_simulator_demo() {
+    use steel_capture::osc_control::OscControl;
+
+    let (tx, rx) = unbounded();
+    let (sim_ctl_tx, sim_ctl_rx) = unbounded();
+    let mut sim = Simulator::new(SessionClock::new(), tx, buddy_emmons_e9(), 1000)
+        .with_suppress_audio()
+        .with_control(sim_ctl_rx);
+    thread::spawn(move || sim.run("e9"));
+    let control = OscControl::bind("127.0.0.1:0", vec![sim_ctl_tx]).unwrap();
+    let control_addr = control.local_addr().unwrap();
+    thread::spawn(move || control.run());
+
+    let next_sensor = || loop {
+        if let InputEvent::Sensor(s) = rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            return s;
+        }
+    };
+    // e9 opens on an open E major: strings 3-6 and 8
+    let e_major = [
+        false, false, true, true, true, true, false, true, false, false,
+    ];
+    while next_sensor().string_active != e_major {}
+
+    // An unknown name is ignored; the faults demo opens on strings 3-5 at fret 5
+    send_osc(
+        control_addr,
+        "/steel/cmd/demo",
+        vec![rosc::OscType::String("nope".into())],
+    );
+    send_osc(
+        control_addr,
+        "/steel/cmd/demo",
+        vec![rosc::OscType::String("faults".into())],
+    );
+    let grip = [
+        false, false, true, true, true, false, false, false, false, false,
+    ];
+    let deadline = std::time::Instant::now() + Duration::from_secs(3);
+    let mut silent = false;
+    loop {
+        assert!(std::time::Instant::now() < deadline, "demo did not switch");
+        let s = next_sensor();
+        // The new demo starts from rest
+        silent |= s.string_active == [false; 10] && s.bar_sensors == [0.0; 4];
+        if silent && s.string_active == grip {
+            assert_eq!(s.bar_sensors, simulate_bar_readings(5.0));
+            break;
+        }
+    }
+}
+
+// ─── MIDI controller input ─────────────────────────────────────────────────
+
+#[test]
+fn test_midi_controllers_drive_pipeline() {
+    use std::io::Write;
+    use steel_capture::midi_input::{parse_midi_map_spec, MidiInput, MidiMap};
+
+    let (reader, mut writer) = std::io::pipe().unwrap();
+    let (tx, rx) = unbounded();
+    // Default map, plus pedal A on the sustain pedal CC and the bar on the
+    // bend wheel (opt-in)
+    let mut map = MidiMap::default();
+    for spec in ["pedal:A=cc64", "bar=bend"] {
+        map.entries.push(parse_midi_map_spec(spec).unwrap());
+    }
+    let input = MidiInput::new(reader, tx, SessionClock::new())
+        .with_map(map)
+        .with_rate(500);
+    let handle = thread::spawn(move || input.run());
+
+    // Bar at fret 5 (bend 5/15 of the way up), pick string 3, then pedal A down
+    let bend = (16383.0 * 5.0 / 15.0) as u16;
+    writer
+        .write_all(&[0xE0, (bend & 0x7F) as u8, (bend >> 7) as u8, 0xB0, 7, 100])
+        .unwrap();
+    thread::sleep(Duration::from_millis(30));
+    writer.write_all(&[0x90, 62, 100]).unwrap();
+    thread::sleep(Duration::from_millis(30));
+    writer.write_all(&[0xB0, 64, 127]).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    drop(writer);
+    handle.join().unwrap();
+
+    let events: Vec<InputEvent> = rx.try_iter().collect();
+    assert!(events.len() > 20, "{} frames", events.len());
+    let frames = run_pipeline(events, false);
+    let last = frames.last().unwrap();
+    let bar = last.bar_position.expect("bar from pitch-bend");
+    // Same sensor-only inference tolerance as simulated hall readings
+    assert!((bar - 5.0).abs() < 1.0, "bar {}", bar);
+    assert!(last.string_active[2]);
+    assert_eq!(last.pedals[0], 1.0);
+    assert!((last.volume - 100.0 / 127.0).abs() < 1e-6);
+
+    // Pitches follow the pedal and bar like sensor input: A raises string 5
+    let engine = CopedantEngine::new(buddy_emmons_e9());
+    let mut pedal_a = SensorFrame::at_rest(0);
+    pedal_a.pedals[0] = 1.0;
+    let expected = engine.compute_pitches(&pedal_a, Some(bar))[4];
+    let unpedaled = engine.compute_pitches(&SensorFrame::at_rest(0), Some(bar))[4];
+    assert!((last.string_pitches_hz[4] - expected).abs() < 0.01);
+    assert!(expected > unpedaled * 1.1);
+    // One attack, when the note arrived
+    let attacks: usize = frames.iter().filter(|f| f.attacks[2]).count();
+    assert_eq!(attacks, 1);
+}
+
+#[test]
+fn test_midi_input_with_wav_audio_detects_strings() {
+    use std::io::Write;
+    use steel_capture::midi_input::MidiInput;
+    use steel_capture::wav_player::WavPlayer;
+
+    // A grip at fret 5 recorded to WAV; MIDI only places the bar
+    // and opens the volume pedal, no notes
+    let path = std::env::temp_dir().join(format!("midi_wav_{}.wav", std::process::id()));
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = hound::WavWriter::create(&path, spec).unwrap();
+    for event in make_events(5.0, &[2, 3, 4], [0.0; 3], [0.0; 5], 0.8, 600, 48000) {
+        if let InputEvent::Audio(chunk) = event {
+            for s in chunk.samples {
+                wav.write_sample((s * 32767.0) as i16).unwrap();
+            }
+        }
+    }
+    wav.finalize().unwrap();
+
+    // Both inputs on one channel and clock, as `--midi-input - --audio-file`
+    let clock = SessionClock::new();
+    let (input_tx, input_rx) = bounded::<InputEvent>(4096);
+    let (frame_tx, frame_rx) = unbounded::<CaptureFrame>();
+    let coord = thread::spawn(move || {
+        Coordinator::new(input_rx, vec![frame_tx], None, buddy_emmons_e9())
+            .with_audio_detection(true)
+            .run();
+    });
+    let (reader, mut writer) = std::io::pipe().unwrap();
+    let midi = MidiInput::new(reader, input_tx.clone(), clock.clone());
+    let midi = thread::spawn(move || midi.run());
+    let bar = (5.0 / 15.0 * 127.0f32).round() as u8;
+    writer.write_all(&[0xB0, 28, bar, 0xB0, 7, 127]).unwrap();
+    WavPlayer::new(path.clone(), input_tx, clock).run();
+    std::fs::remove_file(&path).ok();
+    drop(writer);
+    midi.join().unwrap();
+    coord.join().unwrap();
+
+    let frames: Vec<CaptureFrame> = frame_rx.try_iter().collect();
+    let last = frames.last().unwrap();
+    let fret = last.bar_position.expect("bar from the MIDI pedal");
+    // Same sensor-only inference tolerance as simulated hall readings
+    assert!((fret - 5.0).abs() < 1.0, "bar {}", fret);
+    // The strings come from the WAV: at least 2 of the 3 picked strings
+    // (as in the simulator detection test), each with an attack
+    let heard: Vec<usize> = [2, 3, 4]
+        .into_iter()
+        .filter(|&si| last.string_active[si] && frames.iter().any(|f| f.attacks[si]))
+        .collect();
+    assert!(heard.len() >= 2, "{:?}", last.string_active);
+    assert!(
+        last.string_active
+            .iter()
+            .enumerate()
+            .all(|(si, &on)| !on || [2, 3, 4].contains(&si)),
+        "{:?}",
+        last.string_active
+    );
+}
+
+// ─── Piped PCM audio ───────────────────────────────────────────────────────
+
+#[test]
+fn test_audio_pipe_feeds_string_detection() {
+    use std::io::Cursor;
+    use steel_capture::audio_pipe::AudioPipe;
+
+    // Plucked grip rendered to stereo s16, as `arecord -c 2` would deliver it
+    let rendered = make_steel_events(5.0, &[2, 3, 4], 0.8, 300, 48000);
+    let mut pcm = Vec::new();
+    let mut sensors = Vec::new();
+    for event in rendered {
+        match event {
+            InputEvent::Audio(chunk) => {
+                for s in chunk.samples {
+                    let v = ((s * 32767.0) as i16).to_le_bytes();
+                    pcm.extend_from_slice(&v);
+                    pcm.extend_from_slice(&v);
+                }
+            }
+            sensor => sensors.push(sensor),
+        }
+    }
+
+    let (tx, rx) = unbounded();
+    AudioPipe::new(Cursor::new(pcm), tx, SessionClock::new())
+        .with_channels(2)
+        .run();
+    let chunks: Vec<InputEvent> = rx.try_iter().collect();
+    let total: usize = chunks
+        .iter()
+        .map(|e| match e {
+            InputEvent::Audio(c) => c.samples.len(),
+            _ => 0,
+        })
+        .sum();
+    assert_eq!(total, 300 * 48);
+
+    // Session timestamps line the audio up with 1 kHz sensor ticks
+    let mut events: Vec<(u64, InputEvent)> = sensors
+        .into_iter()
+        .chain(chunks)
+        .map(|e| {
+            let ts = match &e {
+                InputEvent::Sensor(s) => s.timestamp_us,
+                InputEvent::Audio(c) => c.timestamp_us,
+                _ => 0,
+            };
+            (ts, e)
+        })
+        .collect();
+    events.sort_by_key(|(ts, _)| *ts);
+    let events: Vec<InputEvent> = events.into_iter().map(|(_, e)| e).collect();
+    let active = detect_with_known_bar(&events, 5.0);
+    for si in [2, 3, 4] {
+        assert!(active[si], "string {} not detected: {:?}", si + 1, active);
+    }
+}
+
+#[test]
+fn test_hex_pickup_wav_per_string_detection() {
+    use steel_capture::string_detector::StringChannels;
+    use steel_capture::wav_player::WavPlayer;
+
+    // 4-channel recording: strings 3 and 4 ring on their own channels,
+    // channel 3 picks up bleed from string 3, channel 4 is quiet
+    let engine = CopedantEngine::new(buddy_emmons_e9());
+    let open = engine.effective_open_pitches(&SensorFrame::at_rest(0));
+    let s3 = sine(midi_to_hz(open[2] + 5.0), 48000, 300);
+    let s4 = sine(midi_to_hz(open[3] + 5.0), 48000, 300);
+    let path = std::env::temp_dir().join(format!("hex_{}.wav", std::process::id()));
+    let spec = hound::WavSpec {
+        channels: 4,
+        sample_rate: 48000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..s3.len() {
+        for s in [s3[i] * 0.5, s4[i] * 0.5, s3[i] * 0.05, 0.0] {
+            writer.write_sample((s * 32767.0) as i16).unwrap();
+        }
+    }
+    writer.finalize().unwrap();
+
+    let (tx, _rx) = unbounded();
+    let chunks = WavPlayer::new(path.clone(), tx, SessionClock::new())
+        .chunks()
+        .unwrap();
+    std::fs::remove_file(&path).ok();
+    assert!(chunks.iter().all(|c| c.channels.len() == 4));
+    assert_eq!(
+        chunks.iter().map(|c| c.samples.len()).sum::<usize>(),
+        s3.len()
+    );
+
+    let map: StringChannels = "-,-,1,2,3,4".parse().unwrap();
+    let detect = |bar: Option<f32>| {
+        let mut det = StringDetector::new().with_string_channels(map);
+        let sensor = SensorFrame::at_rest(0);
+        let mut active = [false; 10];
+        for chunk in &chunks {
+            det.push_audio(chunk);
+            active = det.detect(&sensor, bar, &engine).0;
+        }
+        active
+    };
+
+    // No bar: each string's own channel level is enough
+    let active = detect(None);
+    assert!(active[2] && active[3], "{:?}", active);
+    assert!(!active[5], "{:?}", active);
+
+    // Known bar: bleed at the wrong pitch is rejected
+    let active = detect(Some(5.0));
+    assert!(active[2] && active[3], "{:?}", active);
+    assert!(!active[4] && !active[5], "{:?}", active);
+    // Unmapped strings fall back to the mono mix
+    assert!(!active[0] && !active[1], "{:?}", active);
+}
+
+// ─── MPE output ────────────────────────────────────────────────────────────
+
+#[test]
+fn test_mpe_output_file_from_pipeline() {
+    use steel_capture::midi_input::{MidiMessage, MidiParser};
+    use steel_capture::mpe_output::MpeOutput;
+
+    // Strings 3 and 5 ring at fret 3; pedal A raises string 5 mid-phrase
+    let events: Vec<InputEvent> = (0..40u64)
+        .map(|t| {
+            let pedals = if t >= 20 { [1.0, 0.0, 0.0] } else { [0.0; 3] };
+            InputEvent::Sensor(sensor_with_bar_and_strings(
+                t * 1000,
+                3.0,
+                &[2, 4],
+                pedals,
+                [0.0; 5],
+                0.8,
+            ))
+        })
+        .collect();
+    let frames = run_pipeline(events, false);
+
+    let path = std::env::temp_dir().join(format!("mpe_{}.mid", std::process::id()));
+    let (tx, rx) = unbounded();
+    for f in frames {
+        tx.send(f).unwrap();
+    }
+    drop(tx);
+    MpeOutput::new(rx, path.clone()).run();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    let mut parser = MidiParser::new();
+    let notes: Vec<MidiMessage> = bytes
+        .iter()
+        .filter_map(|&b| parser.push(b))
+        .filter(|m| matches!(m, MidiMessage::NoteOn { .. } | MidiMessage::NoteOff { .. }))
+        .collect();
+    let engine = CopedantEngine::new(buddy_emmons_e9());
+    let open = engine.effective_open_pitches(&SensorFrame::at_rest(0));
+    let (s3, s5) = ((open[2] + 3.0) as u8, (open[4] + 3.0) as u8);
+    assert_eq!(
+        notes,
+        [
+            MidiMessage::NoteOn {
+                channel: 3,
+                note: s3,
+                velocity: 100
+            },
+            MidiMessage::NoteOn {
+                channel: 5,
+                note: s5,
+                velocity: 100
+            },
+            // Pedal A re-articulates string 5 a whole tone up
+            MidiMessage::NoteOff {
+                channel: 5,
+                note: s5
+            },
+            MidiMessage::NoteOn {
+                channel: 5,
+                note: s5 + 2,
+                velocity: 100
+            },
+            // End of stream
+            MidiMessage::NoteOff {
+                channel: 3,
+                note: s3
+            },
+            MidiMessage::NoteOff {
+                channel: 5,
+                note: s5 + 2
+            },
+        ]
+    );
+}
+
+/// Split an SMF into its track bodies' channel messages, in order.
+fn smf_track_messages(smf: &[u8]) -> Vec<Vec<Vec<u8>>> {
+    assert_eq!(&smf[..4], b"MThd");
+    let mut pos = 14;
+    let mut tracks = Vec::new();
+    while pos < smf.len() {
+        assert_eq!(&smf[pos..pos + 4], b"MTrk");
+        let len = u32::from_be_bytes(smf[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let body = &smf[pos + 8..pos + 8 + len];
+        pos += 8 + len;
+        let (mut i, mut messages) = (0, Vec::new());
+        while i < body.len() {
+            while body[i] & 0x80 != 0 {
+                i += 1;
+            }
+            i += 1;
+            let len = match body[i] {
+                0xFF => 3 + body[i + 2] as usize,
+                s if matches!(s & 0xF0, 0xC0 | 0xD0) => 2,
+                _ => 3,
+            };
+            if body[i] != 0xFF {
+                messages.push(body[i..i + len].to_vec());
+            }
+            i += len;
+        }
+        tracks.push(messages);
+    }
+    tracks
+}
+
+#[test]
+fn test_smf_export_of_bar_slide_session() {
+    use steel_capture::smf_export::SmfExport;
+
+    let jsonl = generate_jsonl_bar_slide(3.0, 8.0, &[2, 3], 200);
+    let mut reader = SessionReader::open(std::io::Cursor::new(jsonl)).unwrap();
+    let frames = std::iter::from_fn(|| reader.next_frame().map(Result::unwrap));
+    let smf = SmfExport::new().export("slide", frames);
+    let tracks = smf_track_messages(&smf);
+    assert_eq!(tracks.len(), 20, "conductor + 10 strings + 9 controls");
+
+    // String 3 holds one note on channel 3 and bends up as the bar slides
+    let s3 = &tracks[3];
+    let note_ons: Vec<&Vec<u8>> = s3.iter().filter(|m| m[0] == 0x92).collect();
+    assert_eq!(
+        note_ons.len(),
+        1,
+        "one attack, no re-articulation: {:?}",
+        s3
+    );
+    let bends: Vec<u16> = s3
+        .iter()
+        .filter(|m| m[0] == 0xE2)
+        .map(|m| m[1] as u16 | (m[2] as u16) << 7)
+        .collect();
+    assert!(bends.len() > 10, "bar movement should produce bends");
+    let (first, last) = (bends[0], *bends.last().unwrap());
+    // 5 frets of 48 semitones ≈ 853 steps
+    assert!(
+        last > first + 600,
+        "bend should rise with the bar: {} → {}",
+        first,
+        last
+    );
+
+    // Untouched strings stay silent; volume sits at 0.7 on channel 16
+    assert!(tracks[1].iter().all(|m| m[0] & 0xF0 != 0x90));
+    assert_eq!(tracks[11], [vec![0xBF, 7, 89]]);
+}
+
+#[test]
+fn test_musicxml_export_from_pipeline_notes() {
+    use steel_capture::musicxml_export::MusicXmlExport;
+    use steel_capture::note_events::note_events;
+
+    // Strings 3 and 5 at fret 3 for a second; pedal A comes in at half time
+    let events: Vec<InputEvent> = (0..100u64)
+        .map(|t| {
+            let pedals = if t >= 50 { [1.0, 0.0, 0.0] } else { [0.0; 3] };
+            InputEvent::Sensor(sensor_with_bar_and_strings(
+                t * 10_000,
+                3.0,
+                &[2, 4],
+                pedals,
+                [0.0; 5],
+                0.8,
+            ))
+        })
+        .collect();
+    let notes = note_events(run_pipeline(events, false));
+    let summary: Vec<_> = notes
+        .iter()
+        .map(|n| (n.string, n.fret, n.changers()))
+        .collect();
+    assert_eq!(
+        summary,
+        [(2, 3, vec![]), (4, 3, vec![]), (4, 3, vec!["A"])],
+        "pedal A re-articulates string 5"
+    );
+    assert_eq!(notes[2].midi, notes[1].midi + 2);
+
+    let open = buddy_emmons_e9().open_strings;
+    let xml = MusicXmlExport::new(open).export("pedal A", &notes);
+    // One 4/4 bar at 120 BPM; string 5 re-picked with A under the tab
+    assert_eq!(xml.matches("<measure number=").count(), 1);
+    assert_eq!(xml.matches("<words>A</words>").count(), 1);
+    assert_eq!(
+        xml.matches("<technical><string>5</string><fret>3</fret></technical>")
+            .count(),
+        2
+    );
+    assert!(xml.contains("<work-title>pedal A</work-title>"));
+}
+
+#[test]
+fn test_live_tab_matches_offline_export() {
+    use steel_capture::note_events::note_events;
+    use steel_capture::text_tab::{render, TabOutput, TabRenderer};
+
+    // Strings 3+4 at fret 5, pedal B (string 3) at 1 s, then a slide to 7
+    let events: Vec<InputEvent> = (0..300u64)
+        .map(|t| {
+            let pedals = if t >= 100 { [0.0, 1.0, 0.0] } else { [0.0; 3] };
+            let fret = if t < 200 { 5.0 } else { 7.0 };
+            InputEvent::Sensor(sensor_with_bar_and_strings(
+                t * 10_000,
+                fret,
+                &[2, 3],
+                pedals,
+                [0.0; 5],
+                0.8,
+            ))
+        })
+        .collect();
+    let frames = run_pipeline(events, false);
+
+    let path = std::env::temp_dir().join(format!("tab_{}.txt", std::process::id()));
+    let (tx, rx) = unbounded();
+    for f in frames.clone() {
+        tx.send(f).unwrap();
+    }
+    drop(tx);
+    let renderer = || TabRenderer::new().with_column_ms(250).with_width(8);
+    TabOutput::new(rx, path.clone())
+        .with_renderer(renderer())
+        .run();
+    let live = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    let offline = render(&note_events(frames), renderer());
+    assert_eq!(live, offline);
+    let lines: Vec<&str> = live.lines().collect();
+    assert_eq!(lines[0], "0.00s");
+    assert_eq!(lines[3], " 3|-5-------5B-------|", "{}", live);
+    assert_eq!(lines[4], " 4|-5----------------|", "{}", live);
+    // Both strings slide to 7 at 2 s, with pedal B still down
+    assert!(live.contains("2.00s\n 1|-----|\n 2|-----|\n 3|-/7B-|\n 4|-/7B-|\n"));
+}
+
+#[test]
+fn test_tab_plays_through_pipeline_and_round_trips() {
+    use steel_capture::note_events::note_events;
+    use steel_capture::tab_import::Tab;
+    use steel_capture::text_tab::{render, TabRenderer};
+
+    // Grip with pedal A, slide to 12, re-pick with B, release B under
+    // string 3 (string 4 doesn't change), open strings
+    let text = "\
+ 3|-10A---/12A---12B---h12---0-|
+ 4|-10A---/12A---12B---h12---0-|
+ 5|-10A---/12A------------------|
+";
+    let tab = Tab::parse(text).unwrap();
+    let steps: Vec<u32> = tab.columns.iter().map(|c| c.step).collect();
+    assert_eq!(steps, [0, 2, 4, 6, 8]);
+
+    let (input_tx, input_rx) = unbounded::<InputEvent>();
+    let mut sim = Simulator::new(SessionClock::new(), input_tx, buddy_emmons_e9(), 1000)
+        .with_offline()
+        .with_suppress_audio()
+        .with_tab(tab);
+    sim.play("tab");
+    drop(sim);
+    let frames = run_pipeline(input_rx.iter().collect(), false);
+
+    let played = render(&note_events(frames), TabRenderer::new());
+    let lines: Vec<&str> = played.lines().collect();
+    assert_eq!(lines[3], " 3|-10A---/12A---12B---12---0-|", "{}", played);
+    assert_eq!(lines[4], " 4|-10A---/12A---12B--------0-|", "{}", played);
+    assert_eq!(lines[5], " 5|-10A---/12A----------------|", "{}", played);
+    assert_eq!(lines[6], format!(" 6|{}|", "-".repeat(27)), "{}", played);
+
+    // The export reads back in the same columns
+    let again = Tab::parse(&played).unwrap();
+    let steps: Vec<u32> = again.columns.iter().map(|c| c.step).collect();
+    assert_eq!(steps, [0, 2, 4, 6, 8]);
+}
+
+#[test]
+fn test_gone_country_intro_tab_file() {
+    use steel_capture::tab_import::{Articulation, Tab};
+
+    // Pickup, G at 10 with pedal A hammered in and out, slides to D at 5
+    // and C at 3 with pedals A and B; the interlude re-picks instead
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tab/gone_country_intro.txt");
+    let tab = Tab::load(&path).unwrap();
+    assert_eq!(tab.columns.len(), 22);
+    let count = |a| {
+        tab.columns
+            .iter()
+            .filter(|c| c.notes[0].articulation == a)
+            .count()
+    };
+    assert_eq!(
+        (
+            count(Articulation::Picked),
+            count(Articulation::Slide),
+            count(Articulation::HammerOn)
+        ),
+        (7, 2, 13)
+    );
+
+    let (input_tx, _input_rx) = unbounded::<InputEvent>();
+    let (truth_tx, truth_rx) = unbounded::<GroundTruth>();
+    let mut sim = Simulator::new(SessionClock::new(), input_tx, buddy_emmons_e9(), 1000)
+        .with_offline()
+        .with_suppress_audio()
+        .with_ground_truth(truth_tx)
+        .with_tab(tab.clone());
+    sim.play("tab");
+    drop(sim);
+    // One record per 1 ms tick
+    let truth: Vec<GroundTruth> = truth_rx.iter().collect();
+
+    let mut prev = 0;
+    for column in &tab.columns {
+        let at = 400 + column.step as usize * 125;
+        // Hammered changers travel from the column on; sample once they're in
+        let t = &truth[at + 100];
+        let strings: Vec<usize> = column.notes.iter().map(|n| n.string).collect();
+        let ringing: Vec<usize> = (0..10).filter(|&s| t.string_active[s]).collect();
+        let pedals = t.pedals.map(|p| p > 0.5);
+        let state = (t.bar_fret.map(|f| f.round() as u8), pedals, ringing);
+        let want = (Some(column.fret()), column.notes[0].pedals, strings.clone());
+        assert_eq!(state, want, "step {}", column.step);
+
+        match column.notes[0].articulation {
+            // Picks land on the column
+            Articulation::Picked => {
+                let picked = strings.iter().all(|&s| truth[at].attacks[s]);
+                assert!(picked, "step {}", column.step);
+            }
+            // Slides and hammer-ons move under strings that keep ringing
+            _ => {
+                let held = truth[prev..at]
+                    .iter()
+                    .all(|t| strings.iter().all(|&s| t.string_active[s]));
+                assert!(held, "step {}", column.step);
+            }
+        }
+        if column.notes[0].articulation == Articulation::Slide {
+            let from = truth[prev].bar_fret.unwrap();
+            let passes = truth[prev..at].iter().any(|t| {
+                let f = t.bar_fret.unwrap();
+                f < from - 0.5 && f > column.fret() as f32 + 0.5
+            });
+            assert!(passes, "step {}", column.step);
+        }
+        prev = at;
+    }
+}
+
+#[test]
+fn test_csv_export_of_pipeline_session() {
+    use steel_capture::csv_export::{CsvExport, CsvSummary};
+
+    // 200 ms of a pedal-A grip at fret 5, with a corrupted line in it
+    let jsonl = generate_jsonl(Some(5.0), &[2, 3, 4], [1.0, 0.0, 0.0], [0.0; 5], 0.7, 200);
+    let mut lines: Vec<&str> = jsonl.lines().collect();
+    lines.insert(50, "{\"t\": oops");
+    let jsonl = lines.join("\n");
+
+    let mut reader = SessionReader::open(std::io::Cursor::new(jsonl)).unwrap();
+    let export = CsvExport::new()
+        .with_columns(vec![
+            "timestamp_us".into(),
+            "pedal_A".into(),
+            "string3_*".into(),
+        ])
+        .with_range(0.0, Some(0.1))
+        .with_rate(100.0);
+    let mut out = Vec::new();
+    let summary = export.write(&mut reader, &mut out).unwrap();
+    assert_eq!(
+        summary,
+        CsvSummary {
+            rows: 10,
+            skipped: 1
+        }
+    );
+
+    let csv = String::from_utf8(out).unwrap();
+    let rows: Vec<Vec<&str>> = csv.lines().map(|l| l.split(',').collect()).collect();
+    assert_eq!(
+        rows[0],
+        [
+            "timestamp_us",
+            "pedal_A",
+            "string3_hz",
+            "string3_active",
+            "string3_attack",
+            "string3_amplitude"
+        ]
+    );
+    let expected_hz = midi_to_hz(buddy_emmons_e9().open_strings[2] + 5.0);
+    for (k, row) in rows[1..].iter().enumerate() {
+        assert_eq!(row[0], (k * 10_000).to_string());
+        assert_eq!(row[1], "1.0");
+        let hz: f64 = row[2].parse().unwrap();
+        // Within a semitone: the bar estimate is fused, not exactly fret 5
+        assert!(
+            (hz / expected_hz - 1.0).abs() < 0.03,
+            "{} vs {}",
+            hz,
+            expected_hz
+        );
+        assert_eq!(row[3], "1");
+        // The pick on the first frame, and nothing after
+        assert_eq!(row[4], if k == 0 { "1" } else { "0" });
+    }
+}